@@ -0,0 +1,56 @@
+//! 不同索引类型下多线程写入的性能对比
+//!
+//! 运行: cargo bench --bench index_bench
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bitcask_rs::db::Engine;
+use bitcask_rs::options::{IndexType, Options};
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// 每次迭代写入的key数量
+const KEYS_PER_ITER: usize = 1000;
+
+fn bench_multi_thread_put(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_thread_put");
+    group.throughput(Throughput::Elements(KEYS_PER_ITER as u64));
+    for (name, index_type) in [
+        ("btree", IndexType::BTree),
+        ("sharded_btree", IndexType::ShardedBTree),
+        ("skiplist", IndexType::SkipList),
+    ] {
+        for threads in [1, 4, 8] {
+            let opts = Options {
+                dir_path: PathBuf::from(format!("/tmp/bitcask-rs-bench-{}-{}", name, threads)),
+                data_file_size: 256 * 1024 * 1024,
+                index_type,
+                ..Default::default()
+            };
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            let counter = AtomicUsize::new(0);
+            group.bench_with_input(BenchmarkId::new(name, threads), &threads, |b, &threads| {
+                b.iter(|| {
+                    std::thread::scope(|s| {
+                        for _ in 0..threads {
+                            s.spawn(|| {
+                                for _ in 0..KEYS_PER_ITER / threads {
+                                    let n = counter.fetch_add(1, Ordering::Relaxed);
+                                    let key = Bytes::from(format!("bitcask-rs-key-{:09}", n));
+                                    engine.put(key, Bytes::from_static(b"value")).unwrap();
+                                }
+                            });
+                        }
+                    });
+                });
+            });
+            std::mem::drop(engine);
+            std::fs::remove_dir_all(opts.dir_path).expect("failed to remove bench dir");
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_multi_thread_put);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+//! 根据src/lib.rs生成C头文件到OUT_DIR，不修改源码目录。
+//! 提交的include/bitcask.h由测试检查与生成的头文件一致
+
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let crate_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("failed to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_src(crate_dir.join("src/lib.rs"))
+        .with_config(config)
+        .generate()
+        .expect("failed to generate C header")
+        .write_to_file(out_dir.join("bitcask.h"));
+}
@@ -0,0 +1,161 @@
+#ifndef BITCASK_H
+#define BITCASK_H
+
+/* 由cbindgen根据src/lib.rs生成，不要手动修改 */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * 函数调用结果
+ */
+typedef enum BitcaskStatus {
+  BITCASK_STATUS_OK = 0,
+  /**
+   * key不存在，或者迭代结束
+   */
+  BITCASK_STATUS_NOT_FOUND = 1,
+  /**
+   * 参数为空指针或者不合法
+   */
+  BITCASK_STATUS_INVALID_ARGUMENT = 2,
+  /**
+   * 其他错误
+   */
+  BITCASK_STATUS_ERROR = 3,
+} BitcaskStatus;
+
+/**
+ * 数据库句柄
+ */
+typedef struct BitcaskEngine BitcaskEngine;
+
+/**
+ * 迭代器句柄，关闭数据库后读取返回错误，仍需使用bitcask_iter_free释放
+ */
+typedef struct BitcaskIterator BitcaskIterator;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * 打开数据库，dir_path为数据库目录，其他配置使用默认值
+ *
+ * # Safety
+ *
+ * dir_path必须是以'\0'结尾的字符串，out_engine必须可写
+ */
+enum BitcaskStatus bitcask_open(const char *dir_path, struct BitcaskEngine **out_engine);
+
+/**
+ * 关闭数据库并释放句柄，持久化数据出错时返回Error，句柄同样被释放
+ *
+ * # Safety
+ *
+ * engine必须是bitcask_open返回的句柄或者空指针，释放后不能再使用
+ */
+enum BitcaskStatus bitcask_close(struct BitcaskEngine *engine);
+
+/**
+ * 写入数据
+ *
+ * # Safety
+ *
+ * engine必须是有效的句柄，key和value必须指向对应长度的可读内存
+ */
+enum BitcaskStatus bitcask_put(const struct BitcaskEngine *engine,
+                               const uint8_t *key,
+                               uintptr_t key_len,
+                               const uint8_t *value,
+                               uintptr_t value_len);
+
+/**
+ * 读取数据，key不存在时返回NotFound，读取到的value使用bitcask_free_buf释放
+ *
+ * # Safety
+ *
+ * engine必须是有效的句柄，key必须指向对应长度的可读内存，out_value和out_value_len必须可写
+ */
+enum BitcaskStatus bitcask_get(const struct BitcaskEngine *engine,
+                               const uint8_t *key,
+                               uintptr_t key_len,
+                               uint8_t **out_value,
+                               uintptr_t *out_value_len);
+
+/**
+ * 删除数据，key不存在时同样返回Ok
+ *
+ * # Safety
+ *
+ * engine必须是有效的句柄，key必须指向对应长度的可读内存
+ */
+enum BitcaskStatus bitcask_delete(const struct BitcaskEngine *engine,
+                                  const uint8_t *key,
+                                  uintptr_t key_len);
+
+/**
+ * merge数据文件，清理无效数据
+ *
+ * # Safety
+ *
+ * engine必须是有效的句柄
+ */
+enum BitcaskStatus bitcask_merge(const struct BitcaskEngine *engine);
+
+/**
+ * 创建按key顺序遍历以prefix开头的数据的迭代器，prefix_len为0时遍历所有数据
+ *
+ * # Safety
+ *
+ * engine必须是有效的句柄，prefix必须指向对应长度的可读内存，out_iter必须可写
+ */
+enum BitcaskStatus bitcask_iter_new(const struct BitcaskEngine *engine,
+                                    const uint8_t *prefix,
+                                    uintptr_t prefix_len,
+                                    struct BitcaskIterator **out_iter);
+
+/**
+ * 获取下一条数据，迭代结束时返回NotFound，读取到的key和value使用bitcask_free_buf释放
+ *
+ * # Safety
+ *
+ * iter必须是bitcask_iter_new返回的句柄，输出参数必须可写
+ */
+enum BitcaskStatus bitcask_iter_next(struct BitcaskIterator *iter,
+                                     uint8_t **out_key,
+                                     uintptr_t *out_key_len,
+                                     uint8_t **out_value,
+                                     uintptr_t *out_value_len);
+
+/**
+ * 释放迭代器
+ *
+ * # Safety
+ *
+ * iter必须是bitcask_iter_new返回的句柄或者空指针，释放后不能再使用
+ */
+void bitcask_iter_free(struct BitcaskIterator *iter);
+
+/**
+ * 释放bitcask_get和bitcask_iter_next返回的数据
+ *
+ * # Safety
+ *
+ * buf和len必须是上述函数返回的值或者空指针，释放后不能再使用
+ */
+void bitcask_free_buf(uint8_t *buf, uintptr_t len);
+
+/**
+ * 当前线程最近一次调用的错误信息，没有错误时返回空指针。
+ * 返回的字符串在当前线程下一次调用之前有效，不需要释放
+ */
+const char *bitcask_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BITCASK_H */
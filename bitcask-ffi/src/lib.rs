@@ -0,0 +1,444 @@
+//! C语言接口，编译为动态库供C/C++/Go等语言使用，头文件为include/bitcask.h
+//!
+//! 函数返回BitcaskStatus，出错时通过bitcask_last_error获取错误信息。
+//! 返回给调用方的key和value由Rust分配，需要使用bitcask_free_buf释放
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+
+use bitcask_rs::db::Engine;
+use bitcask_rs::error::Error;
+use bitcask_rs::iterator::Scan;
+use bitcask_rs::options::{IteratorOptions, Options};
+use bytes::Bytes;
+
+/// 函数调用结果
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitcaskStatus {
+    Ok = 0,
+    /// key不存在，或者迭代结束
+    NotFound = 1,
+    /// 参数为空指针或者不合法
+    InvalidArgument = 2,
+    /// 其他错误
+    Error = 3,
+}
+
+/// 数据库句柄
+pub struct BitcaskEngine {
+    engine: Engine,
+}
+
+/// 迭代器句柄，关闭数据库后读取返回错误，仍需使用bitcask_iter_free释放
+pub struct BitcaskIterator {
+    scan: Scan,
+}
+
+thread_local! {
+    /// 当前线程最近一次调用的错误信息
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+struct FfiError {
+    status: BitcaskStatus,
+    message: String,
+}
+
+impl FfiError {
+    fn invalid_argument(message: &str) -> Self {
+        Self {
+            status: BitcaskStatus::InvalidArgument,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<Error> for FfiError {
+    fn from(e: Error) -> Self {
+        let status = match e {
+            Error::KeyNotFound => BitcaskStatus::NotFound,
+            Error::KeyIsEmpty | Error::KeyTooLarge | Error::ValueTooLarge => {
+                BitcaskStatus::InvalidArgument
+            }
+            _ => BitcaskStatus::Error,
+        };
+        Self {
+            status,
+            message: e.to_string(),
+        }
+    }
+}
+
+/// 执行f并转换为状态码，记录错误信息，panic不会跨越FFI边界
+fn ffi_call(f: impl FnOnce() -> Result<(), FfiError>) -> BitcaskStatus {
+    let (status, message) = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => (BitcaskStatus::Ok, None),
+        Ok(Err(e)) => (e.status, Some(e.message)),
+        Err(_) => (BitcaskStatus::Error, Some("bitcask panicked".to_string())),
+    };
+    LAST_ERROR.with(|last_error| {
+        *last_error.borrow_mut() = message.map(|message| {
+            CString::new(message.replace('\0', " ")).expect("nul bytes are replaced")
+        });
+    });
+    status
+}
+
+/// 调用方传入的字节数组，长度为0时可以为空指针
+unsafe fn slice_from_raw<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], FfiError> {
+    match (ptr.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(FfiError::invalid_argument("buffer is null")),
+        (false, _) => Ok(std::slice::from_raw_parts(ptr, len)),
+    }
+}
+
+unsafe fn engine_from_raw<'a>(engine: *const BitcaskEngine) -> Result<&'a Engine, FfiError> {
+    engine
+        .as_ref()
+        .map(|engine| &engine.engine)
+        .ok_or_else(|| FfiError::invalid_argument("engine is null"))
+}
+
+/// 将数据复制到Rust分配的内存中交给调用方
+unsafe fn write_buf(data: &[u8], out: *mut *mut u8, out_len: *mut usize) {
+    let buf = data.to_vec().into_boxed_slice();
+    *out_len = buf.len();
+    *out = Box::into_raw(buf) as *mut u8;
+}
+
+/// 打开数据库，dir_path为数据库目录，其他配置使用默认值
+///
+/// # Safety
+///
+/// dir_path必须是以'\0'结尾的字符串，out_engine必须可写
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_open(
+    dir_path: *const c_char,
+    out_engine: *mut *mut BitcaskEngine,
+) -> BitcaskStatus {
+    ffi_call(|| {
+        if dir_path.is_null() || out_engine.is_null() {
+            return Err(FfiError::invalid_argument("argument is null"));
+        }
+        let dir_path = CStr::from_ptr(dir_path)
+            .to_str()
+            .map_err(|_| FfiError::invalid_argument("dir_path is not valid UTF-8"))?;
+        let opts = Options::builder().dir_path(dir_path).build()?;
+        let engine = Engine::open(opts)?;
+        *out_engine = Box::into_raw(Box::new(BitcaskEngine { engine }));
+        Ok(())
+    })
+}
+
+/// 关闭数据库并释放句柄，持久化数据出错时返回Error，句柄同样被释放
+///
+/// # Safety
+///
+/// engine必须是bitcask_open返回的句柄或者空指针，释放后不能再使用
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_close(engine: *mut BitcaskEngine) -> BitcaskStatus {
+    if engine.is_null() {
+        return BitcaskStatus::Ok;
+    }
+    ffi_call(|| {
+        let engine = Box::from_raw(engine);
+        engine.engine.close()?;
+        Ok(())
+    })
+}
+
+/// 写入数据
+///
+/// # Safety
+///
+/// engine必须是有效的句柄，key和value必须指向对应长度的可读内存
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_put(
+    engine: *const BitcaskEngine,
+    key: *const u8,
+    key_len: usize,
+    value: *const u8,
+    value_len: usize,
+) -> BitcaskStatus {
+    ffi_call(|| {
+        let engine = engine_from_raw(engine)?;
+        let key = slice_from_raw(key, key_len)?;
+        let value = slice_from_raw(value, value_len)?;
+        engine.put(Bytes::copy_from_slice(key), Bytes::copy_from_slice(value))?;
+        Ok(())
+    })
+}
+
+/// 读取数据，key不存在时返回NotFound，读取到的value使用bitcask_free_buf释放
+///
+/// # Safety
+///
+/// engine必须是有效的句柄，key必须指向对应长度的可读内存，out_value和out_value_len必须可写
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_get(
+    engine: *const BitcaskEngine,
+    key: *const u8,
+    key_len: usize,
+    out_value: *mut *mut u8,
+    out_value_len: *mut usize,
+) -> BitcaskStatus {
+    ffi_call(|| {
+        let engine = engine_from_raw(engine)?;
+        let key = slice_from_raw(key, key_len)?;
+        if out_value.is_null() || out_value_len.is_null() {
+            return Err(FfiError::invalid_argument("output is null"));
+        }
+        let value = engine.get(Bytes::copy_from_slice(key))?;
+        write_buf(&value, out_value, out_value_len);
+        Ok(())
+    })
+}
+
+/// 删除数据，key不存在时同样返回Ok
+///
+/// # Safety
+///
+/// engine必须是有效的句柄，key必须指向对应长度的可读内存
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_delete(
+    engine: *const BitcaskEngine,
+    key: *const u8,
+    key_len: usize,
+) -> BitcaskStatus {
+    ffi_call(|| {
+        let engine = engine_from_raw(engine)?;
+        let key = slice_from_raw(key, key_len)?;
+        engine.delete(Bytes::copy_from_slice(key))?;
+        Ok(())
+    })
+}
+
+/// merge数据文件，清理无效数据
+///
+/// # Safety
+///
+/// engine必须是有效的句柄
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_merge(engine: *const BitcaskEngine) -> BitcaskStatus {
+    ffi_call(|| {
+        engine_from_raw(engine)?.merge()?;
+        Ok(())
+    })
+}
+
+/// 创建按key顺序遍历以prefix开头的数据的迭代器，prefix_len为0时遍历所有数据
+///
+/// # Safety
+///
+/// engine必须是有效的句柄，prefix必须指向对应长度的可读内存，out_iter必须可写
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_iter_new(
+    engine: *const BitcaskEngine,
+    prefix: *const u8,
+    prefix_len: usize,
+    out_iter: *mut *mut BitcaskIterator,
+) -> BitcaskStatus {
+    ffi_call(|| {
+        let engine = engine_from_raw(engine)?;
+        let prefix = slice_from_raw(prefix, prefix_len)?;
+        if out_iter.is_null() {
+            return Err(FfiError::invalid_argument("output is null"));
+        }
+        let scan = engine.scan(IteratorOptions {
+            prefix: prefix.to_vec(),
+            ..Default::default()
+        });
+        *out_iter = Box::into_raw(Box::new(BitcaskIterator { scan }));
+        Ok(())
+    })
+}
+
+/// 获取下一条数据，迭代结束时返回NotFound，读取到的key和value使用bitcask_free_buf释放
+///
+/// # Safety
+///
+/// iter必须是bitcask_iter_new返回的句柄，输出参数必须可写
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_iter_next(
+    iter: *mut BitcaskIterator,
+    out_key: *mut *mut u8,
+    out_key_len: *mut usize,
+    out_value: *mut *mut u8,
+    out_value_len: *mut usize,
+) -> BitcaskStatus {
+    ffi_call(|| {
+        let Some(iter) = iter.as_mut() else {
+            return Err(FfiError::invalid_argument("iterator is null"));
+        };
+        if out_key.is_null()
+            || out_key_len.is_null()
+            || out_value.is_null()
+            || out_value_len.is_null()
+        {
+            return Err(FfiError::invalid_argument("output is null"));
+        }
+        let Some(entry) = iter.scan.next() else {
+            return Err(FfiError {
+                status: BitcaskStatus::NotFound,
+                message: "iteration finished".to_string(),
+            });
+        };
+        let (key, value) = entry?;
+        write_buf(&key, out_key, out_key_len);
+        write_buf(&value, out_value, out_value_len);
+        Ok(())
+    })
+}
+
+/// 释放迭代器
+///
+/// # Safety
+///
+/// iter必须是bitcask_iter_new返回的句柄或者空指针，释放后不能再使用
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_iter_free(iter: *mut BitcaskIterator) {
+    if !iter.is_null() {
+        ffi_call(|| {
+            drop(Box::from_raw(iter));
+            Ok(())
+        });
+    }
+}
+
+/// 释放bitcask_get和bitcask_iter_next返回的数据
+///
+/// # Safety
+///
+/// buf和len必须是上述函数返回的值或者空指针，释放后不能再使用
+#[no_mangle]
+pub unsafe extern "C" fn bitcask_free_buf(buf: *mut u8, len: usize) {
+    if !buf.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(buf, len)));
+    }
+}
+
+/// 当前线程最近一次调用的错误信息，没有错误时返回空指针。
+/// 返回的字符串在当前线程下一次调用之前有效，不需要释放
+#[no_mangle]
+pub extern "C" fn bitcask_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    unsafe fn take_buf(buf: *mut u8, len: usize) -> Vec<u8> {
+        let data = std::slice::from_raw_parts(buf, len).to_vec();
+        bitcask_free_buf(buf, len);
+        data
+    }
+
+    #[test]
+    fn test_ffi() {
+        let dir_path = "/tmp/bitcask-rs-ffi";
+        let _ = std::fs::remove_dir_all(dir_path);
+        let c_dir_path = CString::new(dir_path).unwrap();
+        unsafe {
+            let mut engine = ptr::null_mut();
+            assert_eq!(
+                bitcask_open(c_dir_path.as_ptr(), &mut engine),
+                BitcaskStatus::Ok
+            );
+            assert!(bitcask_last_error().is_null());
+
+            for (key, value) in [("a1", "1"), ("a2", "2"), ("b1", "3")] {
+                assert_eq!(
+                    bitcask_put(engine, key.as_ptr(), key.len(), value.as_ptr(), value.len()),
+                    BitcaskStatus::Ok
+                );
+            }
+            let (mut value, mut value_len) = (ptr::null_mut(), 0);
+            assert_eq!(
+                bitcask_get(engine, b"a2".as_ptr(), 2, &mut value, &mut value_len),
+                BitcaskStatus::Ok
+            );
+            assert_eq!(take_buf(value, value_len), b"2");
+
+            assert_eq!(bitcask_delete(engine, b"a2".as_ptr(), 2), BitcaskStatus::Ok);
+            assert_eq!(
+                bitcask_get(engine, b"a2".as_ptr(), 2, &mut value, &mut value_len),
+                BitcaskStatus::NotFound
+            );
+            assert!(!bitcask_last_error().is_null());
+            assert_eq!(
+                bitcask_put(engine, ptr::null(), 0, b"v".as_ptr(), 1),
+                BitcaskStatus::InvalidArgument
+            );
+            assert_eq!(
+                bitcask_put(ptr::null(), b"k".as_ptr(), 1, b"v".as_ptr(), 1),
+                BitcaskStatus::InvalidArgument
+            );
+            assert_eq!(bitcask_merge(engine), BitcaskStatus::Ok);
+
+            let mut iter = ptr::null_mut();
+            assert_eq!(
+                bitcask_iter_new(engine, b"a".as_ptr(), 1, &mut iter),
+                BitcaskStatus::Ok
+            );
+            let mut entries = vec![];
+            let (mut key, mut key_len) = (ptr::null_mut(), 0);
+            while bitcask_iter_next(iter, &mut key, &mut key_len, &mut value, &mut value_len)
+                == BitcaskStatus::Ok
+            {
+                entries.push((take_buf(key, key_len), take_buf(value, value_len)));
+            }
+            assert_eq!(entries, vec![(b"a1".to_vec(), b"1".to_vec())]);
+            bitcask_iter_free(iter);
+
+            // 关闭数据库后迭代器读取返回错误
+            assert_eq!(
+                bitcask_iter_new(engine, b"a".as_ptr(), 1, &mut iter),
+                BitcaskStatus::Ok
+            );
+            assert_eq!(bitcask_close(engine), BitcaskStatus::Ok);
+            assert_eq!(
+                bitcask_iter_next(iter, &mut key, &mut key_len, &mut value, &mut value_len),
+                BitcaskStatus::Error
+            );
+            bitcask_iter_free(iter);
+
+            // 关闭后可以再次打开
+            let mut engine = ptr::null_mut();
+            assert_eq!(
+                bitcask_open(c_dir_path.as_ptr(), &mut engine),
+                BitcaskStatus::Ok
+            );
+            assert_eq!(
+                bitcask_get(engine, b"b1".as_ptr(), 2, &mut value, &mut value_len),
+                BitcaskStatus::Ok
+            );
+            assert_eq!(take_buf(value, value_len), b"3");
+            assert_eq!(bitcask_close(engine), BitcaskStatus::Ok);
+            assert_eq!(bitcask_close(ptr::null_mut()), BitcaskStatus::Ok);
+        }
+
+        std::fs::remove_dir_all(dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_header_up_to_date() {
+        // 修改接口后需要用build.rs生成的头文件更新include/bitcask.h
+        assert!(
+            include_str!("../include/bitcask.h")
+                == include_str!(concat!(env!("OUT_DIR"), "/bitcask.h")),
+            "include/bitcask.h is outdated, update it from {}",
+            concat!(env!("OUT_DIR"), "/bitcask.h")
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! 异步接口，需要开启`tokio` feature
+//!
+//! 读写数据文件和持久化都是同步IO，放到tokio的阻塞线程池中执行，不阻塞异步运行时的工作线程
+
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::batch::{CommitInfo, WriteBatch};
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::options::{Options, WriteOptions};
+
+/// 异步数据库接口，克隆的句柄共享同一个数据库
+#[derive(Clone)]
+pub struct AsyncEngine {
+    engine: Engine,
+}
+
+impl From<Engine> for AsyncEngine {
+    fn from(engine: Engine) -> Self {
+        Self { engine }
+    }
+}
+
+impl AsyncEngine {
+    /// 打开数据库，加载数据文件和构建索引在阻塞线程池中执行
+    pub async fn open(opts: Options) -> Result<Self> {
+        let engine = spawn_blocking(move || Engine::open(opts)).await?;
+        Ok(Self { engine })
+    }
+
+    /// 同步接口，只访问内存的操作可以直接调用
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub async fn put(&self, key: Bytes, value: Bytes) -> Result<()> {
+        self.run_blocking(move |engine| engine.put(key, value))
+            .await
+    }
+
+    pub async fn put_with_ttl(&self, key: Bytes, value: Bytes, ttl: Duration) -> Result<()> {
+        self.run_blocking(move |engine| engine.put_with_ttl(key, value, ttl))
+            .await
+    }
+
+    pub async fn get(&self, key: Bytes) -> Result<Bytes> {
+        self.run_blocking(move |engine| engine.get(key)).await
+    }
+
+    pub async fn delete(&self, key: Bytes) -> Result<()> {
+        self.run_blocking(move |engine| engine.delete(key)).await
+    }
+
+    /// 创建批量写操作，暂存数据不读写文件，提交时调用commit
+    pub fn new_write_batch(&self, opts: WriteOptions) -> Result<WriteBatch> {
+        self.engine.new_write_batch(opts)
+    }
+
+    /// 提交批量写操作
+    pub async fn commit(&self, batch: WriteBatch) -> Result<CommitInfo> {
+        spawn_blocking(move || batch.commit()).await
+    }
+
+    pub async fn sync(&self) -> Result<()> {
+        self.run_blocking(|engine| engine.sync()).await
+    }
+
+    pub async fn merge(&self) -> Result<()> {
+        self.run_blocking(|engine| engine.merge()).await
+    }
+
+    /// 关闭数据库，所有克隆的句柄都会被关闭
+    pub async fn close(&self) -> Result<()> {
+        self.run_blocking(|engine| engine.close()).await
+    }
+
+    /// 在阻塞线程池中使用数据库的句柄执行f
+    async fn run_blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Engine) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let engine = self.engine.clone();
+        spawn_blocking(move || f(&engine)).await
+    }
+}
+
+/// 在阻塞线程池中执行f，f中的panic传递给调用方
+async fn spawn_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(res) => res,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        // 运行时关闭时还没有开始执行的任务被取消
+        Err(_) => Err(Error::TaskCancelled),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_engine() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-async");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        let engine = AsyncEngine::open(opts.clone()).await.unwrap();
+
+        let tasks = (0..4)
+            .map(|t| {
+                let engine = engine.clone();
+                tokio::spawn(async move {
+                    for i in t * 100..(t + 1) * 100 {
+                        engine
+                            .put(get_test_key(i), get_test_value(i))
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(engine.engine().len(), 400);
+        assert_eq!(
+            engine.get(get_test_key(0)).await.unwrap(),
+            get_test_value(0)
+        );
+
+        engine.delete(get_test_key(0)).await.unwrap();
+        assert!(matches!(
+            engine.get(get_test_key(0)).await.err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        let batch = engine.new_write_batch(WriteOptions::default()).unwrap();
+        batch.put(get_test_key(400), get_test_value(400)).unwrap();
+        batch.delete(get_test_key(1)).unwrap();
+        let info = engine.commit(batch).await.unwrap();
+        assert!(info.seq_num > 0);
+        assert_eq!(
+            engine.get(get_test_key(400)).await.unwrap(),
+            get_test_value(400)
+        );
+        assert!(engine.get(get_test_key(1)).await.is_err());
+
+        engine.merge().await.unwrap();
+        engine.close().await.unwrap();
+        drop(engine);
+
+        let engine = AsyncEngine::open(opts.clone()).await.unwrap();
+        assert_eq!(engine.engine().len(), 399);
+        drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
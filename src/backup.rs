@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use log::warn;
+
+use crate::cdc::LogCursor;
+use crate::data::data_file::{get_data_file_full_path, HINT_FILE_NAME};
+use crate::db::{read_dir, Engine};
+use crate::dictionary::DICT_FILE_PREFIX;
+use crate::error::{Error, Result};
+use crate::options::BackupOptions;
+
+impl Engine {
+    /// 备份数据库到指定目录，备份的目录可以直接作为数据库打开
+    ///
+    /// 备份期间不允许merge，只在确定备份范围时短暂阻塞写操作，
+    /// 旧的数据文件不会再被修改，活跃文件只拷贝到确定备份范围时的写入位置
+    pub fn backup(&self, dir_path: impl AsRef<Path>, opts: BackupOptions) -> Result<()> {
+        self.backup_with_cursor(dir_path, opts).map(|_| ())
+    }
+
+    /// 备份数据库，返回备份范围的结束位置，之后写入的数据可以从该位置通过read_log_since读取
+    pub(crate) fn backup_with_cursor(
+        &self,
+        dir_path: impl AsRef<Path>,
+        opts: BackupOptions,
+    ) -> Result<LogCursor> {
+        self.check_closed()?;
+        let dir_path = dir_path.as_ref();
+        if self.is_in_memory() {
+            return Err(Error::BackupNotSupported);
+        }
+        if dir_path == self.options.dir_path.as_path() {
+            return Err(Error::InvalidBackupDir);
+        }
+        if let Err(source) = std::fs::create_dir_all(dir_path) {
+            return Err(Error::FailedToCreateBackupDir {
+                path: dir_path.to_path_buf(),
+                source,
+            });
+        }
+
+        // 备份期间merge会删除旧的数据文件
+        let _merging_guard = self.merging_lock.lock();
+
+        // 确定备份范围
+        let (mut older_file_ids, active_file_id, active_offset) = {
+            let _rotate_guard = self.rotate_lock.write();
+            let active_file = self.active_file.read();
+            active_file.sync()?;
+            let older_file_ids = self.older_files.read().keys().copied().collect::<Vec<_>>();
+            (
+                older_file_ids,
+                active_file.get_file_id(),
+                active_file.get_write_offset(),
+            )
+        };
+        older_file_ids.sort();
+
+        let src_path = &self.options.dir_path;
+        for file_id in older_file_ids {
+            let src = get_data_file_full_path(self.data_file_dir(file_id), file_id);
+            let dst = get_data_file_full_path(dir_path, file_id);
+            if opts.hard_link {
+                remove_backup_file(&dst)?;
+                // 不在同一个文件系统时无法创建硬链接，退化为拷贝
+                match std::fs::hard_link(&src, &dst) {
+                    Ok(_) => continue,
+                    Err(e) => warn!("failed to hard link data file, copy it instead: {}", e),
+                }
+            }
+            copy_file(&src, &dst, None)?;
+        }
+        copy_file(
+            &get_data_file_full_path(self.data_file_dir(active_file_id), active_file_id),
+            &get_data_file_full_path(dir_path, active_file_id),
+            Some(active_offset),
+        )?;
+        // hint文件只会在merge时被替换
+        let hint_file_path = src_path.join(HINT_FILE_NAME);
+        if hint_file_path.is_file() {
+            copy_file(&hint_file_path, &dir_path.join(HINT_FILE_NAME), None)?;
+        }
+        // 读取使用字典压缩的数据需要zstd字典，字典写入后不会被修改
+        for entry in read_dir(src_path)? {
+            let file_name = entry.file_name();
+            if file_name.to_string_lossy().starts_with(DICT_FILE_PREFIX) {
+                copy_file(&entry.path(), &dir_path.join(&file_name), None)?;
+            }
+        }
+        self.save_seq_num(dir_path)?;
+        Ok(LogCursor {
+            file_id: active_file_id,
+            offset: active_offset,
+        })
+    }
+}
+
+/// 删除备份目录中已存在的文件，它可能是指向数据库文件的硬链接，不能直接覆盖
+fn remove_backup_file(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    std::fs::remove_file(path).map_err(|source| Error::FailedToRemoveBackupFile {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// 拷贝文件，len不为空时只拷贝文件开头的len个字节
+fn copy_file(src: &Path, dst: &Path, len: Option<u64>) -> Result<()> {
+    remove_backup_file(dst)?;
+    let copy = || -> std::io::Result<()> {
+        let src_file = File::open(src)?;
+        let mut dst_file = File::create(dst)?;
+        match len {
+            Some(len) => std::io::copy(&mut src_file.take(len), &mut dst_file)?,
+            None => std::io::copy(&mut &src_file, &mut dst_file)?,
+        };
+        dst_file.flush()?;
+        dst_file.sync_all()
+    };
+    copy().map_err(|source| Error::FailedToCopyFile {
+        from: src.to_path_buf(),
+        to: dst.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+
+    use crate::{
+        db::FILE_LOCK_NAME,
+        options::Options,
+        util::rand_kv::{get_test_key, get_test_value},
+    };
+
+    use super::*;
+
+    fn backup_and_check(name: &str, hard_link: bool) {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from(format!("/tmp/bitcask-rs-{}", name));
+        opts.data_file_size = 32 * 1024;
+        let backup_path = PathBuf::from(format!("/tmp/bitcask-rs-{}-dst", name));
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..5000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..1000 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        engine.merge().unwrap();
+        engine
+            .put(get_test_key(1), Bytes::from("new value"))
+            .unwrap();
+
+        let backup_res = engine.backup(&backup_path, BackupOptions { hard_link });
+        assert!(backup_res.is_ok());
+        assert!(!backup_path.join(FILE_LOCK_NAME).exists());
+
+        // 备份后的写入不影响备份的数据
+        engine.put(get_test_key(2), get_test_value(2)).unwrap();
+        engine.delete(get_test_key(4999)).unwrap();
+
+        let mut backup_opts = opts.clone();
+        backup_opts.dir_path = backup_path.clone();
+        let backup_engine = Engine::open(backup_opts).expect("failed to open backup");
+        assert_eq!(backup_engine.list_keys().unwrap().len(), 4001);
+        assert_eq!(
+            backup_engine.get(get_test_key(1)).unwrap(),
+            Bytes::from("new value")
+        );
+        assert!(matches!(
+            backup_engine.get(get_test_key(2)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        assert_eq!(
+            backup_engine.get(get_test_key(4999)).unwrap(),
+            get_test_value(4999)
+        );
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+        std::fs::remove_dir_all(backup_path).expect("failed to remove backup dir");
+    }
+
+    #[test]
+    fn test_backup() {
+        backup_and_check("backup-copy", false);
+    }
+
+    #[test]
+    fn test_backup_hard_link() {
+        backup_and_check("backup-hard-link", true);
+    }
+
+    #[test]
+    fn test_backup_invalid_dir() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-backup-invalid");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let backup_res = engine.backup(&opts.dir_path, BackupOptions::default());
+        assert!(matches!(backup_res.err().unwrap(), Error::InvalidBackupDir));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -1,129 +1,303 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 use bytes::{Bytes, BytesMut};
 use parking_lot::RwLock;
 
-use crate::data::log_record::{LogRecord, LogRecordPos, LogRecordType};
+use crate::cdc::LogCursor;
+use crate::data::log_record::{max_log_record_header_size, now_millis, LogRecord, LogRecordType};
 use crate::db::Engine;
 use crate::error::{Error, Result};
 use crate::options::WriteOptions;
+use crate::telemetry;
 
-const TXN_FINISH_KEY: &'static [u8] = b"txn-finish";
+const TXN_FINISH_KEY: &[u8] = b"txn-finish";
 pub(crate) const NON_TRANSACTION_SEQ_NUM: usize = 0;
 
-/// 批量写操作，保证原子性
-pub struct WriteBatch<'a> {
+/// 批量写操作，保证原子性，持有数据库的句柄
+pub struct WriteBatch {
     pending_writes: Arc<RwLock<HashMap<Vec<u8>, LogRecord>>>,
-    engine: &'a Engine,
+    /// 暂存的数据编码后的大小，修改pending_writes时持有其写锁更新
+    pending_bytes: AtomicUsize,
+    engine: Engine,
     opts: WriteOptions,
 }
 
-impl<'a> WriteBatch<'a> {
+/// 批量写操作的提交结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitInfo {
+    /// 分配的事务编号，空batch不写入数据，事务编号为0
+    pub seq_num: usize,
+    /// 提交之后的位置，与read_log_since返回的该次提交的LogEntry::next相同
+    pub next: LogCursor,
+}
+
+impl WriteBatch {
     /// 写入数据
     pub fn put(&self, key: Bytes, value: Bytes) -> Result<()> {
+        self.put_with_expire_at(key, value, 0)
+    }
+
+    /// 写入数据，expire_at为过期时间的unix毫秒时间戳，0表示永不过期
+    pub(crate) fn put_with_expire_at(
+        &self,
+        key: Bytes,
+        value: Bytes,
+        expire_at: u64,
+    ) -> Result<()> {
         if key.is_empty() {
             return Err(Error::KeyIsEmpty);
         }
+        self.engine.check_kv_size(&key, value.len())?;
         // 暂存数据
         let log_record = LogRecord {
             key: key.to_vec(),
             value: value.to_vec(),
             record_type: crate::data::log_record::LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at,
         };
         // 写入batch
         let mut pending_writes = self.pending_writes.write();
-        pending_writes.insert(key.to_vec(), log_record);
-        Ok(())
+        self.stage(&mut pending_writes, log_record)
     }
 
     /// 删除数据
+    ///
+    /// 总是暂存一条删除记录，替换batch中同一个key未提交的写入，与暂存时key是否存在无关。
+    /// 提交后key一定不存在，包括暂存之后、提交之前被其他写操作写入的key；
+    /// 之后再次put同一个key会替换这条删除记录
     pub fn delete(&self, key: Bytes) -> Result<()> {
         if key.is_empty() {
             return Err(Error::KeyIsEmpty);
         }
         let mut pending_writes = self.pending_writes.write();
-        if self.engine.index.get(key.to_vec()).is_none() {
-            // 如果key不在索引中，但在batch中,需要从batch中删掉
-            if pending_writes.contains_key(key.as_ref()) {
-                pending_writes.remove(key.as_ref());
-            }
-            return Ok(());
-        }
         // 暂存数据
         let log_record = LogRecord {
             key: key.to_vec(),
             value: Default::default(),
             record_type: crate::data::log_record::LogRecordType::DELETE,
+            timestamp: 0,
+            expire_at: 0,
         };
         // 写入batch
-        pending_writes.insert(key.to_vec(), log_record);
+        self.stage(&mut pending_writes, log_record)
+    }
+
+    /// 暂存数据，替换同一个key已暂存的数据，超出max_batch_bytes时返回BatchTooLarge
+    fn stage(
+        &self,
+        pending_writes: &mut HashMap<Vec<u8>, LogRecord>,
+        log_record: LogRecord,
+    ) -> Result<()> {
+        let old_size = pending_writes.get(&log_record.key).map_or(0, encoded_size);
+        let size = self.pending_bytes.load(Ordering::SeqCst) - old_size + encoded_size(&log_record);
+        if size > self.opts.max_batch_bytes {
+            return Err(Error::BatchTooLarge);
+        }
+        self.pending_bytes.store(size, Ordering::SeqCst);
+        pending_writes.insert(log_record.key.clone(), log_record);
         Ok(())
     }
 
-    /// 提交批量写操作，将数据写入文件并更新内存索引
-    pub fn commit(&self) -> Result<()> {
+    /// 暂存的数据编码后的大小（估计的上限）
+    pub fn size_in_bytes(&self) -> usize {
+        self.pending_bytes.load(Ordering::SeqCst)
+    }
+
+    /// 读取数据，优先读取batch中未提交的写入和删除，否则从数据库中读取
+    pub fn get(&self, key: Bytes) -> Result<Bytes> {
+        if key.is_empty() {
+            return Err(Error::KeyIsEmpty);
+        }
+        if let Some(res) = self.get_pending(&key) {
+            return res;
+        }
+        self.engine.get(key)
+    }
+
+    /// 读取batch中未提交的写入和删除，key不在batch中时返回None
+    pub(crate) fn get_pending(&self, key: &[u8]) -> Option<Result<Bytes>> {
+        let pending_writes = self.pending_writes.read();
+        let rec = pending_writes.get(key)?;
+        Some(match rec.record_type {
+            LogRecordType::DELETE => Err(Error::KeyNotFound),
+            _ => Ok(rec.value.clone().into()),
+        })
+    }
+
+    /// 丢弃所有未提交的数据，batch可以继续使用
+    pub fn rollback(&self) {
+        let mut pending_writes = self.pending_writes.write();
+        pending_writes.clear();
+        self.pending_bytes.store(0, Ordering::SeqCst);
+    }
+
+    /// 未提交的数据条数
+    pub fn len(&self) -> usize {
+        self.pending_writes.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending_writes.read().is_empty()
+    }
+
+    /// 提交批量写操作，将数据写入文件并更新内存索引，返回分配的事务编号和提交之后的位置
+    pub fn commit(&self) -> Result<CommitInfo> {
+        telemetry::timed("batch_commit", || self.commit_with_check(None))
+    }
+
+    /// 提交批量写操作，check不为空时在写入前调用，返回错误时不写入任何数据
+    ///
+    /// 调用check时持有rotate_lock的写锁，期间没有其他写操作
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(records, seq_num))
+    )]
+    pub(crate) fn commit_with_check(
+        &self,
+        check: Option<&dyn Fn() -> Result<()>>,
+    ) -> Result<CommitInfo> {
+        let start = Instant::now();
+        self.engine.check_closed()?;
         let mut pending_writes = self.pending_writes.write();
         if pending_writes.is_empty() {
-            return Ok(());
+            if let Some(check) = check {
+                check()?;
+            }
+            return Ok(CommitInfo {
+                seq_num: NON_TRANSACTION_SEQ_NUM,
+                next: LogCursor::default(),
+            });
         }
         if pending_writes.len() > self.opts.max_batch_size {
             return Err(Error::BatchTooLarge);
         }
+        self.engine.evict_if_needed()?;
         // 加锁保证事务串行化
         let _lock = self.engine.batch_commit_lock.lock();
+        let _rotate_read_guard;
+        let _rotate_write_guard;
+        match check {
+            Some(check) => {
+                _rotate_write_guard = self.engine.rotate_lock.write();
+                check()?;
+            }
+            None => _rotate_read_guard = self.engine.rotate_lock.read(),
+        }
+        // 写入前检查，保证超出索引内存上限或者配额时不会写入任何数据
+        if pending_writes
+            .values()
+            .any(|rec| rec.record_type == LogRecordType::NORMAL)
+        {
+            self.engine.check_db_size()?;
+        }
+        for rec in pending_writes.values() {
+            if rec.record_type == LogRecordType::NORMAL {
+                self.engine.check_index_memory(&rec.key)?;
+            }
+        }
+        pending_writes
+            .values()
+            .filter(|rec| rec.record_type == LogRecordType::NORMAL)
+            .for_each(|rec| self.engine.add_to_bloom_filter(&rec.key));
         // 获取全局事务编号
         let seq_num = self
             .engine
             .seq_num
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-
-        let mut positions = HashMap::with_capacity(pending_writes.len());
-        for (_, rec) in pending_writes.iter() {
-            let log_record = LogRecord {
+        telemetry::record_field("records", pending_writes.len() as u64);
+        telemetry::record_field("seq_num", seq_num as u64);
+
+        // 同一批次的数据使用相同的写入时间
+        let timestamp = now_millis();
+        let mut log_records = pending_writes
+            .values()
+            .map(|rec| LogRecord {
                 key: log_record_key_with_seq_num(&rec.key, seq_num),
                 value: rec.value.clone(),
                 record_type: rec.record_type,
-            };
-            let pos = self.engine.append_log_record(&log_record)?;
-            positions.insert(rec.key.clone(), pos);
-        }
-        // 写入最后一条标识事务完成的数据
-        let finish_record = LogRecord {
+                timestamp,
+                expire_at: rec.expire_at,
+            })
+            .collect::<Vec<_>>();
+        // 最后一条标识事务完成的数据
+        log_records.push(LogRecord {
             key: log_record_key_with_seq_num(TXN_FINISH_KEY, seq_num),
             value: Default::default(),
             record_type: LogRecordType::TXNFINISHED,
-        };
-        self.engine.append_log_record(&finish_record)?;
+            timestamp,
+            expire_at: 0,
+        });
+        // 所有数据一起写入
+        let mut positions = self.engine.append_log_records(&log_records)?;
+        let finish_pos = positions.pop().unwrap();
+        let first_pos = positions.first().copied();
+        let positions = pending_writes
+            .keys()
+            .cloned()
+            .zip(positions)
+            .collect::<HashMap<_, _>>();
         // 持久化批量写入
         if self.opts.sync_writes {
             self.engine.sync()?;
         }
-        // 更新内存索引
-        pending_writes
-            .iter()
-            .map(|(key, rec)| {
+        // 标识事务完成的记录不再需要
+        self.engine.add_reclaim_size(&finish_pos);
+        // 更新内存索引，同一批次的数据一起更新二级索引并通知订阅者
+        let mut updates = Vec::with_capacity(pending_writes.len());
+        for (key, rec) in pending_writes.iter() {
+            let pos = positions.get(key).unwrap();
+            let old_pos = match rec.record_type {
                 // 正常的记录,更新内存索引
-                if rec.record_type == LogRecordType::NORMAL {
-                    let pos = positions.get(key).unwrap();
-                    self.engine.index.put(rec.key.clone(), *pos);
-                } else if rec.record_type == LogRecordType::DELETE {
-                    self.engine.index.delete(rec.key.clone());
+                LogRecordType::NORMAL => {
+                    updates.push((key.as_slice(), Some(rec.value.as_slice())));
+                    self.engine.index.put(rec.key.clone(), *pos)
                 }
-            })
-            .count();
+                // 删除记录本身也是无效数据，只通知实际被删除的key
+                LogRecordType::DELETE => {
+                    self.engine.add_reclaim_size(pos);
+                    let old_pos = self.engine.index.delete(rec.key.clone());
+                    if old_pos.is_some() {
+                        updates.push((key.as_slice(), None));
+                    }
+                    old_pos
+                }
+                _ => None,
+            };
+            if let Some(old_pos) = old_pos {
+                self.engine.add_reclaim_size(&old_pos);
+            }
+        }
+        self.engine.after_commit(&updates);
+        let (key_size, value_size) = pending_writes.values().fold((0, 0), |(k, v), rec| {
+            (k + rec.key.len(), v + rec.value.len())
+        });
+        self.engine
+            .check_slow_op("commit", start, key_size, value_size, first_pos);
         // 清空batch
         pending_writes.clear();
-        Ok(())
+        self.pending_bytes.store(0, Ordering::SeqCst);
+        Ok(CommitInfo {
+            seq_num,
+            next: LogCursor {
+                file_id: finish_pos.file_id,
+                offset: finish_pos.offset + finish_pos.size as u64,
+            },
+        })
     }
 }
 
 impl Engine {
     /// 创建一个批量写操作
     pub fn new_write_batch(&self, opts: WriteOptions) -> Result<WriteBatch> {
+        self.check_closed()?;
         Ok(WriteBatch {
             pending_writes: Arc::new(RwLock::new(HashMap::new())),
-            engine: self,
+            pending_bytes: AtomicUsize::new(0),
+            engine: self.clone(),
             opts,
         })
     }
@@ -137,6 +311,14 @@ pub(crate) fn log_record_key_with_seq_num(key: &[u8], seq_num: usize) -> Vec<u8>
     encoded_key.into()
 }
 
+/// 暂存的数据写入文件后的最大大小：header + 事务编号 + key + value
+fn encoded_size(log_record: &LogRecord) -> usize {
+    max_log_record_header_size()
+        + prost::length_delimiter_len(usize::MAX)
+        + log_record.key.len()
+        + log_record.value.len()
+}
+
 /// 解析key，返回key和事务编号
 pub(crate) fn parse_log_record_key(key: &[u8]) -> Result<(Vec<u8>, usize)> {
     let mut buf = BytesMut::from(key);
@@ -168,7 +350,7 @@ mod tests {
         wb.put(get_test_key(1), get_test_value(11)).unwrap();
         wb.put(get_test_key(2), get_test_value(22)).unwrap();
         let get_res = engine.get(get_test_key(1));
-        assert_eq!(get_res.err().unwrap(), Error::KeyNotFound);
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
 
         // 提交
         let commit_res = wb.commit();
@@ -222,6 +404,250 @@ mod tests {
         std::fs::remove_dir_all(opts.dir_path.clone()).unwrap();
     }
 
+    #[test]
+    fn test_write_batch_get() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-get");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+        engine.put(get_test_key(2), get_test_value(2)).unwrap();
+
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        // 未修改的数据从数据库中读取
+        assert_eq!(wb.get(get_test_key(1)).unwrap(), get_test_value(1));
+        assert!(matches!(
+            wb.get(get_test_key(3)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        assert!(matches!(
+            wb.get(Bytes::new()).err().unwrap(),
+            Error::KeyIsEmpty
+        ));
+
+        // 读取未提交的写入和删除
+        wb.put(get_test_key(1), get_test_value(11)).unwrap();
+        wb.put(get_test_key(3), get_test_value(33)).unwrap();
+        wb.delete(get_test_key(2)).unwrap();
+        assert_eq!(wb.get(get_test_key(1)).unwrap(), get_test_value(11));
+        assert_eq!(wb.get(get_test_key(3)).unwrap(), get_test_value(33));
+        assert!(matches!(
+            wb.get(get_test_key(2)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(1));
+        assert_eq!(engine.get(get_test_key(2)).unwrap(), get_test_value(2));
+
+        // 删除只在batch中的数据
+        wb.delete(get_test_key(3)).unwrap();
+        assert!(matches!(
+            wb.get(get_test_key(3)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        wb.commit().unwrap();
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(11));
+        assert!(matches!(
+            engine.get(get_test_key(2)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).unwrap();
+    }
+
+    #[test]
+    fn test_write_batch_rollback() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-rollback");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        assert!(wb.is_empty());
+        wb.put(get_test_key(1), get_test_value(1)).unwrap();
+        wb.put(get_test_key(2), get_test_value(2)).unwrap();
+        wb.put(get_test_key(1), get_test_value(11)).unwrap();
+        assert_eq!(wb.len(), 2);
+
+        // 回滚后提交不会写入任何数据
+        wb.rollback();
+        assert!(wb.is_empty());
+        wb.commit().unwrap();
+        assert!(matches!(
+            engine.get(get_test_key(1)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        // 回滚后可以继续使用
+        wb.put(get_test_key(3), get_test_value(3)).unwrap();
+        assert_eq!(wb.len(), 1);
+        wb.commit().unwrap();
+        assert!(wb.is_empty());
+        assert_eq!(engine.get(get_test_key(3)).unwrap(), get_test_value(3));
+        assert_eq!(engine.list_keys().unwrap().len(), 1);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).unwrap();
+    }
+
+    #[test]
+    fn test_write_batch_max_bytes() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-max-bytes");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.put(get_test_key(0), get_test_value(0)).unwrap();
+
+        let record_size = |value_len: usize| {
+            max_log_record_header_size()
+                + prost::length_delimiter_len(usize::MAX)
+                + get_test_key(0).len()
+                + value_len
+        };
+        let wb_opts = WriteOptions {
+            max_batch_bytes: record_size(1000) * 2,
+            ..Default::default()
+        };
+        let wb = engine.new_write_batch(wb_opts).unwrap();
+        let value = Bytes::from(vec![b'a'; 1000]);
+        wb.put(get_test_key(1), value.clone()).unwrap();
+        assert_eq!(wb.size_in_bytes(), record_size(1000));
+        // 覆盖同一个key时替换原来的大小
+        wb.put(get_test_key(1), value.clone()).unwrap();
+        wb.put(get_test_key(2), value.clone()).unwrap();
+        assert_eq!(wb.size_in_bytes(), record_size(1000) * 2);
+        assert!(matches!(
+            wb.put(get_test_key(3), value.clone()),
+            Err(Error::BatchTooLarge)
+        ));
+        assert_eq!(wb.len(), 2);
+
+        // 删除未提交的数据时替换为删除记录
+        engine.delete(get_test_key(0)).unwrap();
+        wb.delete(get_test_key(2)).unwrap();
+        assert_eq!(wb.size_in_bytes(), record_size(1000) + record_size(0));
+        let small_value = Bytes::from(vec![b'a'; 100]);
+        wb.put(get_test_key(3), small_value.clone()).unwrap();
+        wb.commit().unwrap();
+        assert_eq!(wb.size_in_bytes(), 0);
+        assert_eq!(engine.get(get_test_key(3)).unwrap(), small_value);
+
+        wb.put(get_test_key(4), value).unwrap();
+        wb.rollback();
+        assert_eq!(wb.size_in_bytes(), 0);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).unwrap();
+    }
+
+    #[test]
+    fn test_write_batch_delete() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-delete");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // 暂存时不存在的key在提交前被写入，提交后仍被删除
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.delete(get_test_key(1)).unwrap();
+        assert_eq!(wb.len(), 1);
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+        wb.commit().unwrap();
+        assert!(!engine.contains_key(&get_test_key(1)));
+
+        // 删除替换未提交的写入，提交前被其他写操作写入的key也被删除
+        wb.put(get_test_key(2), get_test_value(2)).unwrap();
+        wb.delete(get_test_key(2)).unwrap();
+        assert_eq!(wb.len(), 1);
+        assert!(matches!(wb.get(get_test_key(2)), Err(Error::KeyNotFound)));
+        let other = engine.new_write_batch(WriteOptions::default()).unwrap();
+        other.put(get_test_key(2), get_test_value(2)).unwrap();
+        other.commit().unwrap();
+        wb.commit().unwrap();
+        assert!(!engine.contains_key(&get_test_key(2)));
+
+        // 删除之后再写入，以最后一次操作为准
+        engine.put(get_test_key(3), get_test_value(3)).unwrap();
+        wb.delete(get_test_key(3)).unwrap();
+        wb.put(get_test_key(3), get_test_value(33)).unwrap();
+        wb.delete(get_test_key(4)).unwrap();
+        wb.commit().unwrap();
+        assert_eq!(engine.get(get_test_key(3)).unwrap(), get_test_value(33));
+
+        // 重启后删除记录重放的结果相同，merge后也不会恢复被删除的key
+        engine.put(get_test_key(4), get_test_value(4)).unwrap();
+        wb.delete(get_test_key(4)).unwrap();
+        wb.commit().unwrap();
+        engine.close().unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 1);
+        assert_eq!(engine.get(get_test_key(3)).unwrap(), get_test_value(33));
+        engine.merge().unwrap();
+        engine.close().unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap(), vec![get_test_key(3)]);
+        drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).unwrap();
+    }
+
+    #[test]
+    fn test_write_batch_commit_info() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-commit-info");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        let empty = wb.commit().unwrap();
+        assert_eq!(empty.seq_num, NON_TRANSACTION_SEQ_NUM);
+
+        wb.put(get_test_key(1), get_test_value(1)).unwrap();
+        let first = wb.commit().unwrap();
+        engine.put(get_test_key(2), get_test_value(2)).unwrap();
+        wb.put(get_test_key(3), get_test_value(3)).unwrap();
+        wb.delete(get_test_key(1)).unwrap();
+        let second = wb.commit().unwrap();
+        assert!(second.seq_num > first.seq_num);
+
+        // 提交之后的位置与CDC中该次提交的位置相同
+        let (entries, cursor) = engine
+            .read_log_since(crate::cdc::LogCursor::default(), 10)
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].next, first.next);
+        assert_eq!(entries[2].next, second.next);
+        assert_eq!(cursor, second.next);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).unwrap();
+    }
+
+    #[test]
+    fn test_write_batch_across_data_files() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-batch-across-files");
+        opts.data_file_size = 32 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.put(get_test_key(0), get_test_value(0)).unwrap();
+
+        // 一个批次的数据写入多个数据文件
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        for i in 0..2000 {
+            wb.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        wb.commit().unwrap();
+        assert!(engine.stat().unwrap().data_file_num > 1);
+        for i in 0..2000 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+
+        // 重启后事务完整
+        engine.close().unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 2000);
+        assert_eq!(
+            engine.get(get_test_key(1999)).unwrap(),
+            get_test_value(1999)
+        );
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).unwrap();
+    }
+
     #[test]
     fn test_write_batch_three() {
         let mut opts = Options::default();
@@ -0,0 +1,377 @@
+//! 命令行工具，直接操作数据库目录
+//!
+//! 用法: bitcask-cli --dir <path> <command> [args]
+//!
+//! dump命令直接读取单个数据文件，repair命令修复数据库目录，都不需要打开数据库
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bitcask_rs::data::dump;
+use bitcask_rs::db::Engine;
+use bitcask_rs::options::{BackupOptions, IteratorOptions, Options};
+use bytes::Bytes;
+
+const USAGE: &str = "usage: bitcask-cli --dir <path> <command> [args]
+
+commands:
+    get <key>
+    put <key> <value> [--ttl <seconds>]
+    delete <key>
+    scan [--prefix <prefix>]
+    stat
+    merge
+    backup <dir> [--hard-link]
+    fsck
+    repair
+    dump <data file>";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Get {
+        key: String,
+    },
+    Put {
+        key: String,
+        value: String,
+        ttl: Option<u64>,
+    },
+    Delete {
+        key: String,
+    },
+    Scan {
+        prefix: String,
+    },
+    Stat,
+    Merge,
+    Backup {
+        dir: PathBuf,
+        hard_link: bool,
+    },
+    Fsck,
+    Repair,
+    Dump {
+        path: PathBuf,
+    },
+}
+
+fn main() {
+    env_logger::init();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (dir, cmd) = match parse_args(&args) {
+        Ok(res) => res,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Command::Dump { path } = &cmd {
+        if let Err(e) = run_dump(path, &mut std::io::stdout()) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if cmd == Command::Repair {
+        if let Err(e) = run_repair(dir.as_ref().unwrap(), &mut std::io::stdout()) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let opts = Options {
+        dir_path: dir.unwrap(),
+        ..Default::default()
+    };
+    let engine = match Engine::open(opts) {
+        Ok(engine) => engine,
+        Err(e) => {
+            eprintln!("failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = run(&engine, cmd, &mut std::io::stdout()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// 解析命令行参数，返回数据库目录和要执行的命令，只有dump命令可以不指定数据库目录
+fn parse_args(args: &[String]) -> Result<(Option<PathBuf>, Command), String> {
+    let mut dir = None;
+    let mut rest = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dir" => dir = Some(PathBuf::from(iter.next().ok_or("missing value for --dir")?)),
+            _ => rest.push(arg.as_str()),
+        }
+    }
+    let cmd = match rest.as_slice() {
+        ["get", key] => Command::Get {
+            key: key.to_string(),
+        },
+        ["put", key, value] => Command::Put {
+            key: key.to_string(),
+            value: value.to_string(),
+            ttl: None,
+        },
+        ["put", key, value, "--ttl", ttl] => Command::Put {
+            key: key.to_string(),
+            value: value.to_string(),
+            ttl: Some(ttl.parse().map_err(|_| format!("invalid ttl: {}", ttl))?),
+        },
+        ["delete", key] => Command::Delete {
+            key: key.to_string(),
+        },
+        ["scan"] => Command::Scan {
+            prefix: String::new(),
+        },
+        ["scan", "--prefix", prefix] => Command::Scan {
+            prefix: prefix.to_string(),
+        },
+        ["stat"] => Command::Stat,
+        ["merge"] => Command::Merge,
+        ["backup", dir] => Command::Backup {
+            dir: PathBuf::from(dir),
+            hard_link: false,
+        },
+        ["backup", dir, "--hard-link"] => Command::Backup {
+            dir: PathBuf::from(dir),
+            hard_link: true,
+        },
+        ["fsck"] => Command::Fsck,
+        ["repair"] => Command::Repair,
+        ["dump", path] => Command::Dump {
+            path: PathBuf::from(path),
+        },
+        [] => return Err("missing command".to_string()),
+        _ => return Err(format!("invalid command: {}", rest.join(" "))),
+    };
+    if dir.is_none() && !matches!(cmd, Command::Dump { .. }) {
+        return Err("missing --dir".to_string());
+    }
+    Ok((dir, cmd))
+}
+
+/// 执行命令，结果输出到out
+fn run(engine: &Engine, cmd: Command, out: &mut impl Write) -> Result<(), String> {
+    let write_err = |e: std::io::Error| e.to_string();
+    match cmd {
+        Command::Get { key } => {
+            let value = engine.get(Bytes::from(key)).map_err(|e| e.to_string())?;
+            writeln!(out, "{}", String::from_utf8_lossy(&value)).map_err(write_err)?;
+        }
+        Command::Put { key, value, ttl } => {
+            let (key, value) = (Bytes::from(key), Bytes::from(value));
+            match ttl {
+                Some(ttl) => engine.put_with_ttl(key, value, Duration::from_secs(ttl)),
+                None => engine.put(key, value),
+            }
+            .map_err(|e| e.to_string())?;
+        }
+        Command::Delete { key } => {
+            engine.delete(Bytes::from(key)).map_err(|e| e.to_string())?;
+        }
+        Command::Scan { prefix } => {
+            let iter = engine.iter(IteratorOptions {
+                prefix: prefix.into_bytes(),
+                ..Default::default()
+            });
+            while let Some((key, value)) = iter.next() {
+                writeln!(
+                    out,
+                    "{}\t{}",
+                    String::from_utf8_lossy(&key),
+                    String::from_utf8_lossy(&value)
+                )
+                .map_err(write_err)?;
+            }
+        }
+        Command::Stat => {
+            let stat = engine.stat().map_err(|e| e.to_string())?;
+            writeln!(out, "key_num: {}", stat.key_num).map_err(write_err)?;
+            writeln!(out, "data_file_num: {}", stat.data_file_num).map_err(write_err)?;
+            writeln!(out, "reclaimable_size: {}", stat.reclaimable_size).map_err(write_err)?;
+            writeln!(out, "disk_size: {}", stat.disk_size).map_err(write_err)?;
+        }
+        Command::Merge => engine.merge().map_err(|e| e.to_string())?,
+        Command::Backup { dir, hard_link } => engine
+            .backup(dir, BackupOptions { hard_link })
+            .map_err(|e| e.to_string())?,
+        Command::Fsck => {
+            let report = engine.verify().map_err(|e| e.to_string())?;
+            writeln!(out, "files: {}", report.files).map_err(write_err)?;
+            writeln!(out, "records: {}", report.records).map_err(write_err)?;
+            writeln!(out, "index_entries: {}", report.index_entries).map_err(write_err)?;
+            for corruption in report.corruptions.iter() {
+                writeln!(out, "{}", corruption).map_err(write_err)?;
+            }
+            // 发现损坏时以非0状态退出
+            if !report.is_ok() {
+                return Err(format!("found {} corruptions", report.corruptions.len()));
+            }
+        }
+        // 修复时数据库不能处于打开状态
+        Command::Repair => return Err("repair requires the database to be closed".to_string()),
+        Command::Dump { path } => run_dump(&path, out)?,
+    }
+    Ok(())
+}
+
+/// 修复数据库目录中损坏的数据文件，输出修复的结果
+fn run_repair(dir: &Path, out: &mut impl Write) -> Result<(), String> {
+    let write_err = |e: std::io::Error| e.to_string();
+    let report = Engine::repair(dir).map_err(|e| e.to_string())?;
+    let file_ids = report
+        .repaired_files
+        .iter()
+        .map(|file_id| file_id.to_string())
+        .collect::<Vec<_>>();
+    writeln!(out, "repaired_files: {}", file_ids.join(",")).map_err(write_err)?;
+    writeln!(out, "salvaged_records: {}", report.salvaged_records).map_err(write_err)?;
+    writeln!(out, "discarded_bytes: {}", report.discarded_bytes).map_err(write_err)?;
+    if let Some(quarantine_dir) = report.quarantine_dir {
+        writeln!(out, "quarantine_dir: {}", quarantine_dir.display()).map_err(write_err)?;
+    }
+    Ok(())
+}
+
+/// 逐条输出数据文件中的记录：位置、类型、事务编号、key、大小以及crc是否正确
+fn run_dump(path: &Path, out: &mut impl Write) -> Result<(), String> {
+    let write_err = |e: std::io::Error| e.to_string();
+    for res in dump(path) {
+        let (offset, summary) = res.map_err(|e| e.to_string())?;
+        let record_type = summary
+            .record_type
+            .map_or("UNKNOWN".to_string(), |t| format!("{:?}", t));
+        let seq_num = summary
+            .seq_num
+            .map_or("-".to_string(), |seq_num| seq_num.to_string());
+        writeln!(
+            out,
+            "{}\t{}\tseq={}\tkey={}\tsize={}\tcrc={}",
+            offset,
+            record_type,
+            seq_num,
+            String::from_utf8_lossy(&summary.key),
+            summary.size,
+            if summary.crc_valid { "ok" } else { "mismatch" }
+        )
+        .map_err(write_err)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse_args(&args("--dir /tmp/db get k")).unwrap(),
+            (
+                Some(PathBuf::from("/tmp/db")),
+                Command::Get {
+                    key: "k".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            parse_args(&args("put k v --ttl 10 --dir /tmp/db"))
+                .unwrap()
+                .1,
+            Command::Put {
+                key: "k".to_string(),
+                value: "v".to_string(),
+                ttl: Some(10)
+            }
+        );
+        assert_eq!(
+            parse_args(&args("--dir /tmp/db backup /tmp/bak --hard-link"))
+                .unwrap()
+                .1,
+            Command::Backup {
+                dir: PathBuf::from("/tmp/bak"),
+                hard_link: true
+            }
+        );
+        assert_eq!(
+            parse_args(&args("dump /tmp/db/000000000.data")).unwrap(),
+            (
+                None,
+                Command::Dump {
+                    path: PathBuf::from("/tmp/db/000000000.data")
+                }
+            )
+        );
+        assert_eq!(
+            parse_args(&args("--dir /tmp/db repair")).unwrap().1,
+            Command::Repair
+        );
+        assert!(parse_args(&args("repair")).is_err());
+        assert!(parse_args(&args("get k")).is_err());
+        assert!(parse_args(&args("--dir /tmp/db")).is_err());
+        assert!(parse_args(&args("--dir /tmp/db get")).is_err());
+        assert!(parse_args(&args("--dir /tmp/db put k v --ttl x")).is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let opts = Options {
+            dir_path: PathBuf::from("/tmp/bitcask-rs-cli"),
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let exec = |cmd: &str| {
+            let (_, cmd) = parse_args(&args(&format!("--dir {:?} {}", opts.dir_path, cmd)))?;
+            let mut out = vec![];
+            run(&engine, cmd, &mut out)?;
+            Ok::<_, String>(String::from_utf8(out).unwrap())
+        };
+
+        assert!(exec("get name").is_err());
+        assert_eq!(exec("put name bitcask").unwrap(), "");
+        assert_eq!(exec("put nick rs --ttl 100").unwrap(), "");
+        assert_eq!(exec("put age 1").unwrap(), "");
+        let dump = exec(&format!(
+            "dump {}",
+            opts.dir_path.join("000000000.data").display()
+        ))
+        .unwrap();
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("8\tNORMAL\tseq=0\tkey=name\tsize="));
+        assert!(lines.iter().all(|line| line.ends_with("crc=ok")));
+        assert_eq!(exec("get name").unwrap(), "bitcask\n");
+        assert_eq!(
+            exec("scan --prefix n").unwrap(),
+            "name\tbitcask\nnick\trs\n"
+        );
+        assert_eq!(exec("delete name").unwrap(), "");
+        assert_eq!(exec("scan").unwrap(), "age\t1\nnick\trs\n");
+        assert!(exec("stat").unwrap().starts_with("key_num: 2\n"));
+        assert_eq!(exec("merge").unwrap(), "");
+        assert_eq!(
+            exec("fsck").unwrap(),
+            "files: 2\nrecords: 2\nindex_entries: 2\n"
+        );
+        assert!(exec("repair").is_err());
+        drop(engine);
+        let mut out = vec![];
+        run_repair(&opts.dir_path, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "repaired_files: \nsalvaged_records: 0\ndiscarded_bytes: 0\n"
+        );
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
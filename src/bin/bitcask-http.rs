@@ -0,0 +1,43 @@
+//! HTTP服务端，需要开启`http` feature
+//!
+//! 用法: bitcask-http [--dir <path>] [--addr <host:port>]
+
+use std::path::PathBuf;
+
+use bitcask_rs::db::Engine;
+use bitcask_rs::options::Options;
+use log::info;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let mut opts = Options::default();
+    let mut addr = DEFAULT_ADDR.to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--dir", Some(dir)) => opts.dir_path = PathBuf::from(dir),
+            ("--addr", Some(a)) => addr = a,
+            _ => {
+                eprintln!("usage: bitcask-http [--dir <path>] [--addr <host:port>]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let engine = match Engine::open(opts) {
+        Ok(engine) => engine,
+        Err(e) => {
+            eprintln!("failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("bitcask-http listening on {}", addr);
+    if let Err(e) = bitcask_rs::http::serve(engine, &addr).await {
+        eprintln!("http server error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,159 @@
+//! 离线merge工具，数据库没有被其他进程打开时merge数据库目录，可以由cron定时执行
+//!
+//! 用法: bitcask-merge --dir <path> [--rate-limit <bytes per second>]
+//!
+//! 数据库正在被使用时直接退出，不会与在线的数据库同时修改数据文件
+
+use std::path::PathBuf;
+
+use bitcask_rs::db::{Engine, Stat};
+use bitcask_rs::error::Error;
+use bitcask_rs::options::Options;
+
+const USAGE: &str = "usage: bitcask-merge --dir <path> [--rate-limit <bytes per second>]";
+
+/// 数据库正在被其他进程使用时的退出码
+const EXIT_IN_USE: i32 = 3;
+
+fn main() {
+    env_logger::init();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let opts = match parse_args(&args) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    match run_merge(opts) {
+        Ok((before, after)) => {
+            println!(
+                "data_file_num: {} -> {}",
+                before.data_file_num, after.data_file_num
+            );
+            println!("disk_size: {} -> {}", before.disk_size, after.disk_size);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            let code = match e {
+                Error::DatabaseIsInUse => EXIT_IN_USE,
+                _ => 1,
+            };
+            std::process::exit(code);
+        }
+    }
+}
+
+/// 解析命令行参数，返回打开数据库的配置项
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut dir = None;
+    let mut rate_limit = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dir" => dir = Some(PathBuf::from(iter.next().ok_or("missing value for --dir")?)),
+            "--rate-limit" => {
+                let value = iter.next().ok_or("missing value for --rate-limit")?;
+                rate_limit = Some(
+                    value
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|limit| *limit > 0)
+                        .ok_or(format!("invalid rate limit: {}", value))?,
+                );
+            }
+            _ => return Err(format!("invalid argument: {}", arg)),
+        }
+    }
+    Ok(Options {
+        dir_path: dir.ok_or("missing --dir")?,
+        merge_rate_limit_bytes_per_sec: rate_limit,
+        ..Default::default()
+    })
+}
+
+/// 以离线merge模式打开数据库并merge，返回merge前后的统计信息
+fn run_merge(opts: Options) -> Result<(Stat, Stat), Error> {
+    let engine = Engine::open_for_merge(opts)?;
+    let before = engine.stat()?;
+    engine.merge()?;
+    let after = engine.stat()?;
+    engine.close()?;
+    Ok((before, after))
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcask_rs::options::IOType;
+    use bytes::Bytes;
+
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let opts = parse_args(&args("--dir /tmp/db --rate-limit 1024")).unwrap();
+        assert_eq!(opts.dir_path, PathBuf::from("/tmp/db"));
+        assert_eq!(opts.merge_rate_limit_bytes_per_sec, Some(1024));
+        let opts = parse_args(&args("--dir /tmp/db")).unwrap();
+        assert_eq!(opts.merge_rate_limit_bytes_per_sec, None);
+        assert!(parse_args(&args("")).is_err());
+        assert!(parse_args(&args("--dir")).is_err());
+        assert!(parse_args(&args("--dir /tmp/db merge")).is_err());
+        assert!(parse_args(&args("--dir /tmp/db --rate-limit 0")).is_err());
+        assert!(parse_args(&args("--dir /tmp/db --rate-limit x")).is_err());
+    }
+
+    #[test]
+    fn test_run_merge() {
+        let opts = Options {
+            dir_path: PathBuf::from("/tmp/bitcask-rs-merge-tool"),
+            ..Default::default()
+        };
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        // 数据库不存在时不创建
+        assert!(matches!(
+            run_merge(opts.clone()),
+            Err(Error::DatabaseNotFound { .. })
+        ));
+        assert!(!opts.dir_path.exists());
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..100 {
+            engine
+                .put(Bytes::from(format!("key-{}", i)), Bytes::from("value"))
+                .unwrap();
+        }
+        for i in 0..50 {
+            engine.delete(Bytes::from(format!("key-{}", i))).unwrap();
+        }
+        // 数据库正在被使用时不能merge
+        assert!(matches!(
+            run_merge(opts.clone()),
+            Err(Error::DatabaseIsInUse)
+        ));
+        drop(engine);
+
+        let (before, after) = run_merge(opts.clone()).unwrap();
+        assert!(before.reclaimable_size > 0);
+        assert_eq!(after.reclaimable_size, 0);
+        assert!(after.disk_size < before.disk_size);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 50);
+        drop(engine);
+
+        // 内存数据库没有可以离线merge的目录
+        let mem_opts = Options {
+            dir_path: PathBuf::from("/tmp/bitcask-rs-merge-tool-mem"),
+            io_type: IOType::Memory,
+            ..Default::default()
+        };
+        assert!(run_merge(mem_opts).is_err());
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
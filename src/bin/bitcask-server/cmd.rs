@@ -0,0 +1,412 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::time::Duration;
+
+use bitcask_rs::db::Engine;
+use bitcask_rs::error::Error;
+use bitcask_rs::options::IteratorOptions;
+use bytes::Bytes;
+use parking_lot::Mutex;
+
+use crate::resp::Reply;
+
+/// SCAN默认每次返回的key数量
+const DEFAULT_SCAN_COUNT: usize = 10;
+/// 最多保留的SCAN游标数量，超出后最早的游标失效
+const MAX_SCAN_CURSORS: usize = 4096;
+
+/// SCAN返回的游标，所有连接共享
+///
+/// 每个游标对应上一页的最后一个key，下一页从其之后继续遍历。游标编号递增，0表示开始或结束
+#[derive(Default)]
+pub struct ScanCursors {
+    inner: Mutex<CursorTable>,
+}
+
+#[derive(Default)]
+struct CursorTable {
+    last_id: u64,
+    keys: BTreeMap<u64, Bytes>,
+}
+
+impl ScanCursors {
+    fn get(&self, cursor: u64) -> Option<Bytes> {
+        self.inner.lock().keys.get(&cursor).cloned()
+    }
+
+    /// 记录下一页的起始位置，返回新的游标
+    fn insert(&self, last_key: Bytes) -> u64 {
+        let mut inner = self.inner.lock();
+        inner.last_id += 1;
+        let cursor = inner.last_id;
+        inner.keys.insert(cursor, last_key);
+        if inner.keys.len() > MAX_SCAN_CURSORS {
+            inner.keys.pop_first();
+        }
+        cursor
+    }
+}
+
+/// 执行一条命令
+pub fn execute(engine: &Engine, cursors: &ScanCursors, args: &[Vec<u8>]) -> Reply {
+    let name = match args.first() {
+        Some(name) => String::from_utf8_lossy(name).to_lowercase(),
+        None => return Reply::error("ERR empty command"),
+    };
+    let args = &args[1..];
+    let res = match name.as_str() {
+        "ping" => ping(args),
+        "get" => get(engine, args),
+        "set" => set(engine, args),
+        "del" => del(engine, args),
+        "exists" => exists(engine, args),
+        "keys" => keys(engine, args),
+        "scan" => scan(engine, cursors, args),
+        "ttl" => ttl(engine, args),
+        // redis-cli连接时会发送COMMAND
+        "command" => Ok(Reply::Array(vec![])),
+        _ => Err(format!("ERR unknown command '{}'", name)),
+    };
+    res.unwrap_or_else(Reply::Error)
+}
+
+type CmdResult = std::result::Result<Reply, String>;
+
+fn wrong_args(name: &str) -> String {
+    format!("ERR wrong number of arguments for '{}' command", name)
+}
+
+fn engine_err(e: Error) -> String {
+    format!("ERR {}", e)
+}
+
+fn ping(args: &[Vec<u8>]) -> CmdResult {
+    match args {
+        [] => Ok(Reply::Simple("PONG".to_string())),
+        [msg] => Ok(Reply::Bulk(Some(Bytes::from(msg.clone())))),
+        _ => Err(wrong_args("ping")),
+    }
+}
+
+fn get(engine: &Engine, args: &[Vec<u8>]) -> CmdResult {
+    let [key] = args else {
+        return Err(wrong_args("get"));
+    };
+    match engine.get(Bytes::from(key.clone())) {
+        Ok(value) => Ok(Reply::Bulk(Some(value))),
+        Err(Error::KeyNotFound) => Ok(Reply::Bulk(None)),
+        Err(e) => Err(engine_err(e)),
+    }
+}
+
+/// SET key value [EX seconds | PX milliseconds]
+fn set(engine: &Engine, args: &[Vec<u8>]) -> CmdResult {
+    let (key, value, ttl) = match args {
+        [key, value] => (key, value, None),
+        [key, value, unit, n] => {
+            let n = parse_int(n)?;
+            let ttl = match unit.to_ascii_lowercase().as_slice() {
+                b"ex" => Duration::from_secs(n),
+                b"px" => Duration::from_millis(n),
+                _ => return Err("ERR syntax error".to_string()),
+            };
+            (key, value, Some(ttl))
+        }
+        _ => return Err("ERR syntax error".to_string()),
+    };
+    let key = Bytes::from(key.clone());
+    let value = Bytes::from(value.clone());
+    let res = match ttl {
+        Some(ttl) => engine.put_with_ttl(key, value, ttl),
+        None => engine.put(key, value),
+    };
+    res.map(|_| Reply::ok()).map_err(engine_err)
+}
+
+fn del(engine: &Engine, args: &[Vec<u8>]) -> CmdResult {
+    if args.is_empty() {
+        return Err(wrong_args("del"));
+    }
+    let mut count = 0;
+    for key in args {
+        let key = Bytes::from(key.clone());
+        match engine.get(key.clone()) {
+            Ok(_) => {}
+            Err(Error::KeyNotFound) => continue,
+            Err(e) => return Err(engine_err(e)),
+        }
+        engine.delete(key).map_err(engine_err)?;
+        count += 1;
+    }
+    Ok(Reply::Integer(count))
+}
+
+fn exists(engine: &Engine, args: &[Vec<u8>]) -> CmdResult {
+    if args.is_empty() {
+        return Err(wrong_args("exists"));
+    }
+    let mut count = 0;
+    for key in args {
+        match engine.get(Bytes::from(key.clone())) {
+            Ok(_) => count += 1,
+            Err(Error::KeyNotFound) => {}
+            Err(e) => return Err(engine_err(e)),
+        }
+    }
+    Ok(Reply::Integer(count))
+}
+
+fn keys(engine: &Engine, args: &[Vec<u8>]) -> CmdResult {
+    let [pattern] = args else {
+        return Err(wrong_args("keys"));
+    };
+    let mut keys = Vec::new();
+    for entry in engine.scan(pattern_options(pattern, Bound::Unbounded)) {
+        let (key, _) = entry.map_err(engine_err)?;
+        if glob_match(pattern, &key) {
+            keys.push(Reply::Bulk(Some(key)));
+        }
+    }
+    Ok(Reply::Array(keys))
+}
+
+/// SCAN cursor [MATCH pattern] [COUNT count]，从游标对应的key之后遍历，匹配到count个key时停止
+fn scan(engine: &Engine, cursors: &ScanCursors, args: &[Vec<u8>]) -> CmdResult {
+    let Some((cursor, options)) = args.split_first() else {
+        return Err(wrong_args("scan"));
+    };
+    let cursor = parse_int(cursor)?;
+    let mut pattern = None;
+    let mut count = DEFAULT_SCAN_COUNT;
+    for option in options.chunks(2) {
+        match option {
+            [name, value] if name.eq_ignore_ascii_case(b"match") => pattern = Some(value),
+            [name, value] if name.eq_ignore_ascii_case(b"count") => {
+                count = parse_int(value)? as usize;
+                if count == 0 {
+                    return Err("ERR syntax error".to_string());
+                }
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        }
+    }
+
+    let lower_bound = match cursor {
+        0 => Bound::Unbounded,
+        cursor => match cursors.get(cursor) {
+            Some(last_key) => Bound::Excluded(last_key.to_vec()),
+            None => return Err("ERR invalid cursor".to_string()),
+        },
+    };
+    let options = match pattern {
+        Some(pattern) => pattern_options(pattern, lower_bound),
+        None => IteratorOptions {
+            lower_bound,
+            keys_only: true,
+            ..Default::default()
+        },
+    };
+    let mut keys: Vec<Bytes> = Vec::new();
+    let mut next_cursor = 0;
+    for entry in engine.scan(options) {
+        let (key, _) = entry.map_err(engine_err)?;
+        if keys.len() == count {
+            // 还有更多的key，下一页从本页的最后一个key之后开始
+            next_cursor = cursors.insert(keys[count - 1].clone());
+            break;
+        }
+        if pattern.is_none_or(|pattern| glob_match(pattern, &key)) {
+            keys.push(key);
+        }
+    }
+    Ok(Reply::Array(vec![
+        Reply::Bulk(Some(Bytes::from(next_cursor.to_string()))),
+        Reply::Array(keys.into_iter().map(|key| Reply::Bulk(Some(key))).collect()),
+    ]))
+}
+
+/// 只遍历以模式中第一个通配符之前的部分开头的key，不读取value
+fn pattern_options(pattern: &[u8], lower_bound: Bound<Vec<u8>>) -> IteratorOptions {
+    let prefix_len = pattern
+        .iter()
+        .position(|c| matches!(c, b'*' | b'?' | b'\\'))
+        .unwrap_or(pattern.len());
+    IteratorOptions {
+        prefix: pattern[..prefix_len].to_vec(),
+        lower_bound,
+        keys_only: true,
+        ..Default::default()
+    }
+}
+
+/// key不存在时返回-2，永不过期时返回-1
+fn ttl(engine: &Engine, args: &[Vec<u8>]) -> CmdResult {
+    let [key] = args else {
+        return Err(wrong_args("ttl"));
+    };
+    match engine.ttl(Bytes::from(key.clone())) {
+        Ok(Some(ttl)) => Ok(Reply::Integer(((ttl.as_millis() + 500) / 1000) as i64)),
+        Ok(None) => Ok(Reply::Integer(-1)),
+        Err(Error::KeyNotFound) => Ok(Reply::Integer(-2)),
+        Err(e) => Err(engine_err(e)),
+    }
+}
+
+fn parse_int(buf: &[u8]) -> std::result::Result<u64, String> {
+    std::str::from_utf8(buf)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| "ERR value is not an integer or out of range".to_string())
+}
+
+/// glob风格的匹配，支持*、?和\转义
+fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // 最近一次*的位置，以及此时匹配到的字符位置
+    let mut star = None;
+    while i < s.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, i));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => {
+                p += 1;
+                i += 1;
+                continue;
+            }
+            Some(b'\\') if pattern.get(p + 1) == Some(&s[i]) => {
+                p += 2;
+                i += 1;
+                continue;
+            }
+            Some(c) if *c != b'\\' && *c == s[i] => {
+                p += 1;
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        // 匹配失败，回退到上一个*多匹配一个字符
+        match star {
+            Some((star_p, star_i)) => {
+                star = Some((star_p, star_i + 1));
+                p = star_p + 1;
+                i = star_i + 1;
+            }
+            None => return false,
+        }
+    }
+    pattern[p.min(pattern.len())..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bitcask_rs::options::Options;
+
+    use super::*;
+
+    fn run(engine: &Engine, cursors: &ScanCursors, cmd: &str) -> Reply {
+        let args = cmd
+            .split_whitespace()
+            .map(|arg| arg.as_bytes().to_vec())
+            .collect::<Vec<_>>();
+        execute(engine, cursors, &args)
+    }
+
+    fn bulk(s: &str) -> Reply {
+        Reply::Bulk(Some(Bytes::from(s.to_string())))
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"*", b"abc"));
+        assert!(glob_match(b"a*", b"abc"));
+        assert!(glob_match(b"*c", b"abc"));
+        assert!(glob_match(b"a?c", b"abc"));
+        assert!(glob_match(b"a*b*c", b"axxbyyc"));
+        assert!(glob_match(b"a\\*", b"a*"));
+        assert!(!glob_match(b"a\\*", b"ab"));
+        assert!(!glob_match(b"a?c", b"ac"));
+        assert!(!glob_match(b"a*d", b"abc"));
+        assert!(!glob_match(b"", b"a"));
+    }
+
+    #[test]
+    fn test_execute() {
+        let opts = Options {
+            dir_path: PathBuf::from("/tmp/bitcask-rs-server-cmd"),
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let cursors = ScanCursors::default();
+
+        assert_eq!(
+            run(&engine, &cursors, "PING"),
+            Reply::Simple("PONG".to_string())
+        );
+        assert_eq!(run(&engine, &cursors, "GET k1"), Reply::Bulk(None));
+        assert_eq!(run(&engine, &cursors, "SET k1 v1"), Reply::ok());
+        assert_eq!(run(&engine, &cursors, "get k1"), bulk("v1"));
+        assert_eq!(run(&engine, &cursors, "SET k2 v2 EX 100"), Reply::ok());
+        assert_eq!(run(&engine, &cursors, "SET k3 v3"), Reply::ok());
+        assert_eq!(run(&engine, &cursors, "SET a1 v4"), Reply::ok());
+
+        assert_eq!(run(&engine, &cursors, "TTL k1"), Reply::Integer(-1));
+        assert_eq!(run(&engine, &cursors, "TTL k2"), Reply::Integer(100));
+        assert_eq!(run(&engine, &cursors, "TTL nokey"), Reply::Integer(-2));
+
+        assert_eq!(
+            run(&engine, &cursors, "EXISTS k1 k2 nokey"),
+            Reply::Integer(2)
+        );
+        assert_eq!(
+            run(&engine, &cursors, "KEYS k*"),
+            Reply::Array(vec![bulk("k1"), bulk("k2"), bulk("k3")])
+        );
+        assert_eq!(
+            run(&engine, &cursors, "SCAN 0 COUNT 2"),
+            Reply::Array(vec![bulk("1"), Reply::Array(vec![bulk("a1"), bulk("k1")])])
+        );
+        assert_eq!(
+            run(&engine, &cursors, "SCAN 1 MATCH k* COUNT 2"),
+            Reply::Array(vec![bulk("0"), Reply::Array(vec![bulk("k2"), bulk("k3")])])
+        );
+        assert_eq!(
+            run(&engine, &cursors, "SCAN 0 MATCH k* COUNT 1"),
+            Reply::Array(vec![bulk("2"), Reply::Array(vec![bulk("k1")])])
+        );
+        assert_eq!(
+            run(&engine, &cursors, "SCAN 2 MATCH k* COUNT 5"),
+            Reply::Array(vec![bulk("0"), Reply::Array(vec![bulk("k2"), bulk("k3")])])
+        );
+        assert!(matches!(
+            run(&engine, &cursors, "SCAN 100"),
+            Reply::Error(_)
+        ));
+
+        assert_eq!(run(&engine, &cursors, "DEL k1 k2 nokey"), Reply::Integer(2));
+        assert_eq!(run(&engine, &cursors, "EXISTS k1 k2"), Reply::Integer(0));
+
+        // 错误的命令
+        assert!(matches!(run(&engine, &cursors, "GET"), Reply::Error(_)));
+        assert!(matches!(
+            run(&engine, &cursors, "SET k v EX x"),
+            Reply::Error(_)
+        ));
+        assert!(matches!(
+            run(&engine, &cursors, "SET k v KEEPTTL"),
+            Reply::Error(_)
+        ));
+        assert!(matches!(
+            run(&engine, &cursors, "HGET k f"),
+            Reply::Error(_)
+        ));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,95 @@
+//! 兼容Redis协议的服务端，支持GET/SET/DEL/EXISTS/KEYS/SCAN/TTL命令
+//!
+//! 用法: bitcask-server [--dir <path>] [--addr <host:port>]
+
+mod cmd;
+mod resp;
+
+use std::io::{BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use bitcask_rs::db::Engine;
+use bitcask_rs::options::Options;
+use log::{error, info, warn};
+
+use cmd::ScanCursors;
+use resp::Reply;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:6380";
+
+fn main() {
+    env_logger::init();
+
+    let mut opts = Options::default();
+    let mut addr = DEFAULT_ADDR.to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--dir", Some(dir)) => opts.dir_path = PathBuf::from(dir),
+            ("--addr", Some(a)) => addr = a,
+            _ => {
+                eprintln!("usage: bitcask-server [--dir <path>] [--addr <host:port>]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let engine = match Engine::open(opts) {
+        Ok(engine) => engine,
+        Err(e) => {
+            eprintln!("failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let listener = match TcpListener::bind(&addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("failed to listen on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+    info!("bitcask-server listening on {}", addr);
+
+    let cursors = Arc::new(ScanCursors::default());
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let engine = engine.clone();
+                let cursors = cursors.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_connection(&engine, &cursors, stream) {
+                        warn!("connection closed with error: {}", e);
+                    }
+                });
+            }
+            Err(e) => error!("failed to accept connection: {}", e),
+        }
+    }
+}
+
+/// 处理一个客户端连接，逐条读取命令并回复
+fn handle_connection(
+    engine: &Engine,
+    cursors: &ScanCursors,
+    stream: TcpStream,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut buf = Vec::new();
+    while let Some(args) = resp::read_command(&mut reader)? {
+        if args.is_empty() {
+            continue;
+        }
+        buf.clear();
+        if args[0].eq_ignore_ascii_case(b"quit") {
+            Reply::ok().encode(&mut buf);
+            writer.write_all(&buf)?;
+            break;
+        }
+        cmd::execute(engine, cursors, &args).encode(&mut buf);
+        writer.write_all(&buf)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use std::io::{BufRead, Error, ErrorKind, Read, Result};
+
+use bytes::Bytes;
+
+/// 一条命令最多的参数数量
+const MAX_ARGS: usize = 1024 * 1024;
+/// 单个参数的最大长度，与Redis相同
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+/// inline命令和长度行的最大长度，与Redis相同
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// RESP协议的回复
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reply {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    /// None表示nil
+    Bulk(Option<Bytes>),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    pub fn ok() -> Self {
+        Reply::Simple("OK".to_string())
+    }
+
+    pub fn error(msg: impl Into<String>) -> Self {
+        Reply::Error(msg.into())
+    }
+
+    /// 编码回复
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Reply::Simple(s) => {
+                buf.push(b'+');
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Reply::Error(s) => {
+                buf.push(b'-');
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Reply::Integer(n) => {
+                buf.push(b':');
+                buf.extend_from_slice(n.to_string().as_bytes());
+            }
+            Reply::Bulk(None) => buf.extend_from_slice(b"$-1"),
+            Reply::Bulk(Some(data)) => {
+                buf.push(b'$');
+                buf.extend_from_slice(data.len().to_string().as_bytes());
+                buf.extend_from_slice(b"\r\n");
+                buf.extend_from_slice(data);
+            }
+            Reply::Array(items) => {
+                buf.push(b'*');
+                buf.extend_from_slice(items.len().to_string().as_bytes());
+                buf.extend_from_slice(b"\r\n");
+                for item in items {
+                    item.encode(buf);
+                }
+                return;
+            }
+        }
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+/// 读取一条命令，支持RESP数组和inline命令，连接关闭时返回None
+pub fn read_command(reader: &mut impl BufRead) -> Result<Option<Vec<Vec<u8>>>> {
+    let line = match read_line(reader)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+    if line.first() != Some(&b'*') {
+        // inline命令，以空白分隔
+        let args = line
+            .split(|c| c.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| arg.to_vec())
+            .collect();
+        return Ok(Some(args));
+    }
+
+    // 长度来自客户端，超过上限时拒绝，也不按长度预先分配内存
+    let count = parse_len(&line[1..])?;
+    if count > MAX_ARGS {
+        return Err(Error::new(ErrorKind::InvalidData, "too many arguments"));
+    }
+    let mut args = vec![];
+    for _ in 0..count {
+        let line = read_line(reader)?.ok_or_else(unexpected_eof)?;
+        if line.first() != Some(&b'$') {
+            return Err(Error::new(ErrorKind::InvalidData, "expected bulk string"));
+        }
+        let len = parse_len(&line[1..])?;
+        if len > MAX_BULK_LEN {
+            return Err(Error::new(ErrorKind::InvalidData, "bulk string too large"));
+        }
+        // 数据后面跟着\r\n
+        let mut arg = vec![];
+        reader.take(len as u64 + 2).read_to_end(&mut arg)?;
+        if arg.len() != len + 2 {
+            return Err(unexpected_eof());
+        }
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Ok(Some(args))
+}
+
+/// 读取一行，去掉结尾的\r\n，超过MAX_LINE_LEN时返回错误
+fn read_line(reader: &mut impl BufRead) -> Result<Option<Vec<u8>>> {
+    let mut line = vec![];
+    let n = reader
+        .take(MAX_LINE_LEN as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if n > MAX_LINE_LEN {
+        return Err(Error::new(ErrorKind::InvalidData, "line too long"));
+    }
+    while line.last().is_some_and(|c| *c == b'\n' || *c == b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+fn parse_len(buf: &[u8]) -> Result<usize> {
+    std::str::from_utf8(buf)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid length"))
+}
+
+fn unexpected_eof() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "unexpected eof")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_command() {
+        let mut input: &[u8] = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nva\r\nl\r\nPING\r\n";
+        let cmd = read_command(&mut input).unwrap().unwrap();
+        assert_eq!(
+            cmd,
+            vec![b"SET".to_vec(), b"key".to_vec(), b"va\r\nl".to_vec()]
+        );
+
+        // inline命令
+        let cmd = read_command(&mut input).unwrap().unwrap();
+        assert_eq!(cmd, vec![b"PING".to_vec()]);
+
+        // 连接关闭
+        assert!(read_command(&mut input).unwrap().is_none());
+
+        // 数据不完整
+        let mut input: &[u8] = b"*2\r\n$3\r\nGET\r\n";
+        assert!(read_command(&mut input).is_err());
+        let mut input: &[u8] = b"*x\r\n";
+        assert!(read_command(&mut input).is_err());
+
+        // 参数数量和长度超过上限
+        for input in [
+            &b"*1000000000000\r\n"[..],
+            b"*1\r\n$18446744073709551615\r\n",
+            b"*1\r\n$536870913\r\n",
+            &[b'a'; MAX_LINE_LEN + 1],
+        ] {
+            let mut input = input;
+            assert_eq!(
+                read_command(&mut input).err().unwrap().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+    }
+
+    #[test]
+    fn test_reply_encode() {
+        let mut buf = vec![];
+        Reply::ok().encode(&mut buf);
+        Reply::error("ERR oops").encode(&mut buf);
+        Reply::Integer(-2).encode(&mut buf);
+        Reply::Bulk(None).encode(&mut buf);
+        Reply::Array(vec![
+            Reply::Bulk(Some(Bytes::from("a"))),
+            Reply::Array(vec![]),
+        ])
+        .encode(&mut buf);
+        assert_eq!(
+            buf,
+            b"+OK\r\n-ERR oops\r\n:-2\r\n$-1\r\n*2\r\n$1\r\na\r\n*0\r\n".to_vec()
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// 布隆过滤器最少的key数量，避免数据很少时误判率过高
+const MIN_BLOOM_FILTER_KEYS: usize = 1024;
+
+/// 布隆过滤器，用于快速判断key一定不存在
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    /// 哈希函数的个数
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// 根据预计的key数量和每个key占用的位数创建布隆过滤器
+    pub(crate) fn new(num_keys: usize, bits_per_key: usize) -> Self {
+        let num_bits = num_keys.max(MIN_BLOOM_FILTER_KEYS) * bits_per_key;
+        // 误判率最低时哈希函数的个数为 bits_per_key * ln2
+        let num_hashes = ((bits_per_key as f64 * std::f64::consts::LN_2) as u32).clamp(1, 30);
+        Self {
+            bits: vec![0; num_bits.div_ceil(64)],
+            num_hashes,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: &[u8]) {
+        let num_bits = self.bits.len() as u64 * 64;
+        for bit in bit_positions(key, self.num_hashes, num_bits) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// 返回false时key一定不存在，返回true时key可能存在
+    pub(crate) fn may_contain(&self, key: &[u8]) -> bool {
+        let num_bits = self.bits.len() as u64 * 64;
+        bit_positions(key, self.num_hashes, num_bits)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+}
+
+/// 使用两个哈希值模拟多个哈希函数
+fn bit_positions(key: &[u8], num_hashes: u32, num_bits: u64) -> impl Iterator<Item = u64> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let hash = hasher.finish();
+    let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+    (0..num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::rand_kv::get_test_key;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut bloom = BloomFilter::new(10000, 10);
+        for i in 0..10000 {
+            bloom.insert(&get_test_key(i));
+        }
+        // 插入过的key一定可能存在
+        assert!((0..10000).all(|i| bloom.may_contain(&get_test_key(i))));
+
+        // 每个key占10位时误判率约为1%
+        let false_positives = (10000..20000)
+            .filter(|i| bloom.may_contain(&get_test_key(*i)))
+            .count();
+        assert!(
+            false_positives < 300,
+            "false positives: {}",
+            false_positives
+        );
+
+        let bloom = BloomFilter::new(0, 10);
+        assert!(!bloom.may_contain(b"key"));
+    }
+}
@@ -0,0 +1,212 @@
+use std::ops::Bound;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::options::IteratorOptions;
+
+/// 命名空间，同一个数据库中的不同bucket使用独立的key空间
+///
+/// bucket中的key在数据库中存储为 bucket名称 + '\0' + key，
+/// 直接通过Engine写入的key不应以这种形式开头
+pub struct Bucket {
+    engine: Engine,
+    /// bucket中所有key的前缀
+    prefix: Vec<u8>,
+}
+
+/// bucket的统计信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BucketStat {
+    /// key的数量
+    pub key_num: usize,
+    /// 有效数据占据的磁盘空间大小
+    pub data_size: u64,
+}
+
+impl Engine {
+    /// 获取名称为name的bucket，名称不能为空，也不能包含'\0'
+    pub fn bucket(&self, name: impl AsRef<[u8]>) -> Result<Bucket> {
+        let name = name.as_ref();
+        if name.is_empty() || name.contains(&0) {
+            return Err(Error::InvalidBucketName);
+        }
+        let mut prefix = name.to_vec();
+        prefix.push(0);
+        Ok(Bucket {
+            engine: self.clone(),
+            prefix,
+        })
+    }
+}
+
+impl Bucket {
+    /// bucket的名称
+    pub fn name(&self) -> &[u8] {
+        &self.prefix[..self.prefix.len() - 1]
+    }
+
+    pub fn put(&self, key: Bytes, value: Bytes) -> Result<()> {
+        self.engine.put(self.full_key(&key)?, value)
+    }
+
+    pub fn put_with_ttl(&self, key: Bytes, value: Bytes, ttl: Duration) -> Result<()> {
+        self.engine.put_with_ttl(self.full_key(&key)?, value, ttl)
+    }
+
+    pub fn get(&self, key: Bytes) -> Result<Bytes> {
+        self.engine.get(self.full_key(&key)?)
+    }
+
+    pub fn delete(&self, key: Bytes) -> Result<()> {
+        self.engine.delete(self.full_key(&key)?)
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.full_key(key)
+            .is_ok_and(|key| self.engine.contains_key(&key))
+    }
+
+    /// bucket中的所有key
+    pub fn list_keys(&self) -> Result<Vec<Bytes>> {
+        let (keys, _) = self.engine.list_keys_with(&self.prefix, usize::MAX, None)?;
+        Ok(keys.into_iter().map(|key| self.strip_prefix(key)).collect())
+    }
+
+    /// 按顺序遍历bucket中的数据，options中的前缀和上下界都是bucket中的key，返回的key不包含bucket前缀
+    pub fn scan(&self, options: IteratorOptions) -> impl Iterator<Item = Result<(Bytes, Bytes)>> {
+        let with_prefix =
+            |bound: Bound<Vec<u8>>| bound.map(|key| [self.prefix.clone(), key].concat());
+        let options = IteratorOptions {
+            prefix: [self.prefix.clone(), options.prefix].concat(),
+            lower_bound: with_prefix(options.lower_bound),
+            upper_bound: with_prefix(options.upper_bound),
+            ..options
+        };
+        let prefix_len = self.prefix.len();
+        self.engine
+            .scan(options)
+            .map(move |entry| entry.map(|(key, value)| (key.slice(prefix_len..), value)))
+    }
+
+    /// 删除bucket中所有以prefix开头的key，返回删除的数量
+    pub fn delete_prefix(&self, prefix: &[u8]) -> Result<usize> {
+        self.engine
+            .delete_prefix(&[self.prefix.as_slice(), prefix].concat())
+    }
+
+    /// 删除bucket中的所有数据
+    pub fn clear(&self) -> Result<usize> {
+        self.delete_prefix(&[])
+    }
+
+    /// 获取bucket的统计信息，只遍历内存索引
+    pub fn stat(&self) -> Result<BucketStat> {
+        self.engine.check_closed()?;
+        let mut index_iter = self.engine.index.iterator(IteratorOptions {
+            prefix: self.prefix.clone(),
+            ..Default::default()
+        });
+        let mut stat = BucketStat {
+            key_num: 0,
+            data_size: 0,
+        };
+        while let Some((_, pos)) = index_iter.next() {
+            stat.key_num += 1;
+            stat.data_size += pos.size as u64;
+        }
+        Ok(stat)
+    }
+
+    /// 加上bucket前缀，key不能为空
+    fn full_key(&self, key: &[u8]) -> Result<Bytes> {
+        if key.is_empty() {
+            return Err(Error::KeyIsEmpty);
+        }
+        Ok([self.prefix.as_slice(), key].concat().into())
+    }
+
+    fn strip_prefix(&self, key: Bytes) -> Bytes {
+        key.slice(self.prefix.len()..)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::options::Options;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-bucket");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(matches!(engine.bucket(""), Err(Error::InvalidBucketName)));
+        assert!(matches!(
+            engine.bucket("a\0b"),
+            Err(Error::InvalidBucketName)
+        ));
+
+        let users = engine.bucket("users").unwrap();
+        let orders = engine.bucket("orders").unwrap();
+        assert_eq!(users.name(), b"users");
+        for i in 0..10 {
+            users.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        orders.put(get_test_key(1), get_test_value(100)).unwrap();
+        engine.put(get_test_key(1), get_test_value(200)).unwrap();
+        assert!(matches!(
+            users.put(Bytes::new(), get_test_value(1)),
+            Err(Error::KeyIsEmpty)
+        ));
+
+        // 不同bucket中相同的key互不影响
+        assert_eq!(users.get(get_test_key(1)).unwrap(), get_test_value(1));
+        assert_eq!(orders.get(get_test_key(1)).unwrap(), get_test_value(100));
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(200));
+        assert!(!orders.contains_key(&get_test_key(2)));
+        orders.delete(get_test_key(1)).unwrap();
+        assert!(users.contains_key(&get_test_key(1)));
+        assert_eq!(users.list_keys().unwrap().len(), 10);
+        assert!(orders.list_keys().unwrap().is_empty());
+
+        // 迭代器只返回bucket中的数据，key不包含bucket前缀
+        let entries = users
+            .scan(IteratorOptions {
+                lower_bound: Bound::Included(get_test_key(3).to_vec()),
+                upper_bound: Bound::Excluded(get_test_key(6).to_vec()),
+                ..Default::default()
+            })
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            (3..6)
+                .map(|i| (get_test_key(i), get_test_value(i)))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(users.scan(IteratorOptions::default()).count(), 10);
+
+        let stat = users.stat().unwrap();
+        assert_eq!(stat.key_num, 10);
+        assert!(stat.data_size > 0);
+        assert_eq!(orders.stat().unwrap().key_num, 0);
+
+        // 按前缀删除只影响当前bucket
+        orders.put(get_test_key(1), get_test_value(100)).unwrap();
+        assert_eq!(users.delete_prefix(&get_test_key(1)).unwrap(), 1);
+        assert!(!users.contains_key(&get_test_key(1)));
+        assert!(orders.contains_key(&get_test_key(1)));
+        assert_eq!(users.clear().unwrap(), 9);
+        assert_eq!(users.stat().unwrap().key_num, 0);
+        assert_eq!(engine.len(), 2);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
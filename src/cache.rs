@@ -0,0 +1,194 @@
+use lru::LruCache;
+
+use crate::data::log_record::{LogRecord, LogRecordPos};
+
+/// 每条缓存数据额外占用的内存（估计值）
+const ENTRY_OVERHEAD: usize = 64;
+
+/// 读缓存，按数据位置缓存解码后的log record，淘汰最近最少使用的数据
+///
+/// 数据文件中的记录写入后不会被修改，写入和删除数据后索引指向新的位置，
+/// 旧位置的缓存不会再被读取，之后被淘汰；merge后删除被清理的数据文件的缓存
+pub(crate) struct ValueCache {
+    entries: LruCache<LogRecordPos, LogRecord>,
+    /// 缓存的容量，单位为字节
+    capacity: usize,
+    /// 缓存的数据占用的内存大小
+    size: usize,
+}
+
+impl ValueCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            capacity,
+            size: 0,
+        }
+    }
+
+    pub(crate) fn get(&mut self, pos: &LogRecordPos) -> Option<LogRecord> {
+        self.entries.get(pos).cloned()
+    }
+
+    /// 缓存数据，超出容量时淘汰最近最少使用的数据，大于容量的数据不缓存
+    pub(crate) fn insert(&mut self, pos: LogRecordPos, log_record: LogRecord) {
+        let charge = entry_size(&log_record);
+        if charge > self.capacity {
+            return;
+        }
+        if let Some(old) = self.entries.put(pos, log_record) {
+            self.size -= entry_size(&old);
+        }
+        self.size += charge;
+        while self.size > self.capacity {
+            match self.entries.pop_lru() {
+                Some((_, evicted)) => self.size -= entry_size(&evicted),
+                None => break,
+            }
+        }
+    }
+
+    /// 删除数据文件中的所有缓存数据
+    pub(crate) fn remove_files(&mut self, file_ids: &[u32]) {
+        let positions = self
+            .entries
+            .iter()
+            .map(|(pos, _)| *pos)
+            .filter(|pos| file_ids.contains(&pos.file_id))
+            .collect::<Vec<_>>();
+        for pos in positions {
+            if let Some(removed) = self.entries.pop(&pos) {
+                self.size -= entry_size(&removed);
+            }
+        }
+    }
+
+    /// 清空缓存
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.size = 0;
+    }
+}
+
+fn entry_size(log_record: &LogRecord) -> usize {
+    log_record.key.len() + log_record.value.len() + ENTRY_OVERHEAD
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+
+    use crate::data::log_record::LogRecordType;
+    use crate::db::Engine;
+    use crate::error::Error;
+    use crate::options::Options;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    fn log_record(value_len: usize) -> LogRecord {
+        LogRecord {
+            key: b"key".to_vec(),
+            value: vec![0; value_len],
+            record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
+        }
+    }
+
+    fn pos(file_id: u32, offset: u64) -> LogRecordPos {
+        LogRecordPos {
+            file_id,
+            offset,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn test_value_cache() {
+        let entry_size = entry_size(&log_record(100));
+        let mut cache = ValueCache::new(entry_size * 3);
+        for i in 0..3 {
+            cache.insert(pos(0, i), log_record(100));
+        }
+        assert_eq!(cache.entries.len(), 3);
+
+        // 淘汰最近最少使用的数据
+        assert!(cache.get(&pos(0, 0)).is_some());
+        cache.insert(pos(1, 0), log_record(100));
+        assert!(cache.get(&pos(0, 1)).is_none());
+        assert!(cache.get(&pos(0, 0)).is_some());
+        assert_eq!(cache.size, entry_size * 3);
+
+        // 大于容量的数据不缓存
+        cache.insert(pos(1, 1), log_record(entry_size * 3));
+        assert!(cache.get(&pos(1, 1)).is_none());
+        assert_eq!(cache.entries.len(), 3);
+
+        cache.remove_files(&[0]);
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.size, entry_size);
+        cache.clear();
+        assert_eq!(cache.entries.len(), 0);
+        assert_eq!(cache.size, 0);
+    }
+
+    #[test]
+    fn test_engine_value_cache() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-value-cache");
+        opts.value_cache_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let value_cache = engine.value_cache.clone().unwrap();
+        for i in 0..100 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..100 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+        assert_eq!(value_cache.lock().entries.len(), 100);
+
+        // 写入和删除后读取新的数据
+        engine.put(get_test_key(0), get_test_value(1000)).unwrap();
+        engine.delete(get_test_key(1)).unwrap();
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), get_test_value(1000));
+        assert!(matches!(
+            engine.get(get_test_key(1)),
+            Err(Error::KeyNotFound)
+        ));
+
+        // 缓存之后过期的数据
+        engine
+            .put_with_ttl(
+                get_test_key(2),
+                get_test_value(2),
+                std::time::Duration::from_millis(50),
+            )
+            .unwrap();
+        assert_eq!(engine.get(get_test_key(2)).unwrap(), get_test_value(2));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(matches!(
+            engine.get(get_test_key(2)),
+            Err(Error::KeyNotFound)
+        ));
+
+        // merge后删除被清理的数据文件的缓存
+        engine.merge().unwrap();
+        assert_eq!(value_cache.lock().entries.len(), 0);
+        assert_eq!(engine.get(get_test_key(3)).unwrap(), get_test_value(3));
+        assert_eq!(value_cache.lock().entries.len(), 1);
+
+        // 超出容量后淘汰旧的数据
+        let large_value = Bytes::from(vec![b'a'; 16 * 1024]);
+        for i in 0..10 {
+            engine.put(get_test_key(i), large_value.clone()).unwrap();
+            engine.get(get_test_key(i)).unwrap();
+        }
+        assert!(value_cache.lock().size <= opts.value_cache_size);
+
+        drop(engine);
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
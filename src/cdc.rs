@@ -0,0 +1,238 @@
+use bytes::Bytes;
+
+use crate::batch::{parse_log_record_key, NON_TRANSACTION_SEQ_NUM};
+use crate::chunk::read_chunked_record;
+use crate::data::data_file::DATA_FILE_HEADER_SIZE;
+use crate::data::log_record::LogRecordType;
+use crate::db::Engine;
+use crate::error::{Error, Result};
+
+/// 数据文件中的读取位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LogCursor {
+    pub file_id: u32,
+    pub offset: u64,
+}
+
+/// 一次写操作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogOp {
+    /// expire_at为过期时间的unix毫秒时间戳，0表示永不过期
+    Put {
+        key: Bytes,
+        value: Bytes,
+        expire_at: u64,
+    },
+    Delete {
+        key: Bytes,
+    },
+}
+
+/// 一次提交的数据，事务批量写入的数据作为一个整体返回
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub ops: Vec<LogOp>,
+    /// 写入时间，unix毫秒时间戳
+    pub timestamp: u64,
+    /// 该提交之后的位置，从这里继续读取
+    pub next: LogCursor,
+}
+
+impl Engine {
+    /// 从cursor处按写入顺序读取已提交的数据，最多返回max_entries次提交，
+    /// 同时返回下一次读取的位置
+    ///
+    /// 没有提交的事务数据会被跳过，还没有写完的事务等提交后再返回。
+    /// cursor所在的数据文件被merge清理后返回LogCursorExpired，需要重新同步全部数据
+    pub fn read_log_since(
+        &self,
+        cursor: LogCursor,
+        max_entries: usize,
+    ) -> Result<(Vec<LogEntry>, LogCursor)> {
+        self.check_closed()?;
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        let active_file_id = active_file.get_file_id();
+        if cursor.file_id != active_file_id && !older_files.contains_key(&cursor.file_id) {
+            return Err(Error::LogCursorExpired);
+        }
+        let mut file_ids = older_files
+            .keys()
+            .copied()
+            .filter(|file_id| *file_id >= cursor.file_id)
+            .collect::<Vec<_>>();
+        file_ids.push(active_file_id);
+        file_ids.sort();
+
+        let mut entries = Vec::new();
+        let mut next = cursor;
+        // 正在读取的事务：事务编号，已读取的写操作
+        let mut transaction: Option<(usize, Vec<LogOp>)> = None;
+        for file_id in file_ids {
+            let data_file = match file_id == active_file_id {
+                true => &*active_file,
+                false => &older_files[&file_id],
+            };
+            let start = match file_id == cursor.file_id {
+                true => cursor.offset.max(DATA_FILE_HEADER_SIZE),
+                false => DATA_FILE_HEADER_SIZE,
+            };
+            let mut reader = data_file.reader(start);
+            while entries.len() < max_entries {
+                let log_record = match reader.next_record() {
+                    Ok(rc) => rc.record,
+                    Err(Error::ReadDataFileEOF) => break,
+                    Err(e) => return Err(e),
+                };
+                let (key, seq_num) = parse_log_record_key(&log_record.key)?;
+                let timestamp = log_record.timestamp;
+                let record_type = log_record.record_type;
+                let op = match record_type {
+                    LogRecordType::NORMAL | LogRecordType::CHUNKED => {
+                        let log_record = match record_type {
+                            LogRecordType::CHUNKED => {
+                                read_chunked_record(&active_file, &older_files, log_record)?
+                            }
+                            _ => log_record,
+                        };
+                        Some(LogOp::Put {
+                            key: key.into(),
+                            value: log_record.value.into(),
+                            expire_at: log_record.expire_at,
+                        })
+                    }
+                    // 返回合并后的完整value
+                    LogRecordType::OPERAND => {
+                        let log_record =
+                            self.resolve_operands(&active_file, &older_files, log_record)?;
+                        Some(LogOp::Put {
+                            key: key.into(),
+                            value: log_record.value.into(),
+                            expire_at: 0,
+                        })
+                    }
+                    LogRecordType::DELETE => Some(LogOp::Delete { key: key.into() }),
+                    // 分块随记录分块位置的记录一起返回
+                    _ => None,
+                };
+                let offset = reader.offset();
+
+                if seq_num == NON_TRANSACTION_SEQ_NUM {
+                    // 未完成的事务之后出现了其他数据，说明事务没有提交
+                    transaction = None;
+                    if let Some(op) = op {
+                        entries.push(LogEntry {
+                            ops: vec![op],
+                            timestamp,
+                            next: LogCursor { file_id, offset },
+                        });
+                    }
+                    next = LogCursor { file_id, offset };
+                } else if record_type == LogRecordType::TXNFINISHED {
+                    if let Some((_, ops)) = transaction
+                        .take()
+                        .filter(|(transaction_seq, _)| *transaction_seq == seq_num)
+                    {
+                        entries.push(LogEntry {
+                            ops,
+                            timestamp,
+                            next: LogCursor { file_id, offset },
+                        });
+                    }
+                    next = LogCursor { file_id, offset };
+                } else {
+                    match &mut transaction {
+                        Some((transaction_seq, ops)) if *transaction_seq == seq_num => {
+                            ops.extend(op)
+                        }
+                        _ => transaction = Some((seq_num, op.into_iter().collect())),
+                    }
+                }
+            }
+            if entries.len() >= max_entries {
+                break;
+            }
+        }
+        Ok((entries, next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_read_log_since() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-read-log-since");
+        opts.data_file_size = 4096;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..100 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        engine.delete(get_test_key(0)).unwrap();
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(100), get_test_value(100)).unwrap();
+        wb.delete(get_test_key(1)).unwrap();
+        wb.commit().unwrap();
+        let large_value: Bytes = (0..10000).map(|i| (i % 251) as u8).collect();
+        engine.put(get_test_key(101), large_value.clone()).unwrap();
+        assert!(engine.stat().unwrap().data_file_num > 1);
+
+        // 分多次读取
+        let (entries, cursor) = engine.read_log_since(LogCursor::default(), 60).unwrap();
+        assert_eq!(entries.len(), 60);
+        assert_eq!(entries.last().unwrap().next, cursor);
+        assert_eq!(
+            entries[0].ops,
+            vec![LogOp::Put {
+                key: get_test_key(0),
+                value: get_test_value(0),
+                expire_at: 0,
+            }]
+        );
+        let (entries, cursor) = engine.read_log_since(cursor, 100).unwrap();
+        assert_eq!(entries.len(), 43);
+        assert_eq!(
+            entries[40].ops,
+            vec![LogOp::Delete {
+                key: get_test_key(0)
+            }]
+        );
+        // 事务中的数据一起返回
+        assert_eq!(entries[41].ops.len(), 2);
+        assert!(entries[41].ops.contains(&LogOp::Delete {
+            key: get_test_key(1)
+        }));
+        assert_eq!(
+            entries[42].ops,
+            vec![LogOp::Put {
+                key: get_test_key(101),
+                value: large_value,
+                expire_at: 0,
+            }]
+        );
+
+        // 没有新的数据
+        let (entries, next) = engine.read_log_since(cursor, 100).unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(next, cursor);
+        engine.put(get_test_key(102), get_test_value(102)).unwrap();
+        let (entries, _) = engine.read_log_since(cursor, 100).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        // merge后旧的位置失效
+        engine.merge().unwrap();
+        assert!(matches!(
+            engine.read_log_since(cursor, 100),
+            Err(Error::LogCursorExpired)
+        ));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,377 @@
+//! 索引检查点，保存完整的内存索引，重启时加载检查点后只需要加载之后写入的数据
+//!
+//! 检查点记录了创建时活跃数据文件的写入位置和所有旧数据文件的大小，
+//! 打开数据库时数据文件与检查点不一致（例如merge之后）则忽略检查点，从数据文件中加载索引
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+use bytes::{Buf, BufMut};
+use log::{error, warn};
+
+use crate::data::data_file::get_data_file_full_path;
+use crate::data::log_record::{decode_log_record_pos, LogRecordPos};
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::options::{IndexType, IteratorOptions};
+
+/// 索引检查点文件名
+pub(crate) const CHECKPOINT_FILE_NAME: &str = "index-checkpoint";
+const CHECKPOINT_MAGIC: &[u8; 4] = b"BCKP";
+const CHECKPOINT_VERSION: u8 = 2;
+
+/// 从检查点中加载的索引之后的数据的起始位置
+pub(crate) struct Checkpoint {
+    /// 创建检查点时的活跃数据文件ID
+    pub(crate) file_id: u32,
+    /// 创建检查点时活跃数据文件的写入位置
+    pub(crate) offset: u64,
+    /// 创建检查点时下一个可用的事务编号
+    pub(crate) seq_num: usize,
+}
+
+impl Engine {
+    /// 创建索引检查点，配置了Options::index_checkpoint时关闭数据库会自动创建
+    ///
+    /// 复制索引期间阻塞写操作，不允许merge。内存数据库和B+树索引不需要检查点，直接返回
+    pub fn checkpoint_index(&self) -> Result<()> {
+        self.check_closed()?;
+        self.write_checkpoint()
+    }
+
+    /// 是否使用索引检查点，B+树索引本身就会持久化
+    pub(crate) fn checkpoint_enabled(&self) -> bool {
+        self.options.index_checkpoint
+            && !self.is_in_memory()
+            && !matches!(self.options.index_type, IndexType::BPlusTree)
+    }
+
+    pub(crate) fn write_checkpoint(&self) -> Result<()> {
+        if !self.checkpoint_enabled() {
+            return Ok(());
+        }
+        // merge会修改旧的数据文件
+        let _merging_guard = self.merging_lock.lock();
+        let mut buf = Vec::new();
+        buf.put_slice(CHECKPOINT_MAGIC);
+        buf.put_u8(CHECKPOINT_VERSION);
+        let (mut older_file_ids, dead_sizes) = {
+            // 阻塞写操作，保证索引与写入位置一致
+            let _rotate_guard = self.rotate_lock.write();
+            let active_file = self.active_file.read();
+            // 检查点中的索引指向的数据必须已经持久化
+            active_file.sync()?;
+            buf.put_u64(self.seq_num.load(Ordering::SeqCst) as u64);
+            buf.put_u64(self.reclaim_size.load(Ordering::SeqCst) as u64);
+            buf.put_u32(active_file.get_file_id());
+            buf.put_u64(active_file.get_write_offset());
+            buf.put_u64(self.index.len() as u64);
+            let mut index_iter = self.index.iterator(IteratorOptions::default());
+            while let Some((key, pos)) = index_iter.next() {
+                let pos = pos.encode();
+                buf.put_u32(key.len() as u32);
+                buf.put_slice(key);
+                buf.put_u8(pos.len() as u8);
+                buf.put_slice(&pos);
+            }
+            (
+                self.older_files.read().keys().copied().collect::<Vec<_>>(),
+                self.file_counters.lock().dead_sizes(),
+            )
+        };
+        // 旧的数据文件不会再被修改
+        older_file_ids.sort();
+        buf.put_u32(older_file_ids.len() as u32);
+        for file_id in older_file_ids {
+            let path = get_data_file_full_path(self.data_file_dir(file_id), file_id);
+            let size = std::fs::metadata(&path)
+                .map_err(|source| Error::FailedToWriteCheckpoint {
+                    path: path.clone(),
+                    source,
+                })?
+                .len();
+            buf.put_u32(file_id);
+            buf.put_u64(size);
+        }
+        // 每个数据文件的无效数据大小
+        buf.put_u32(dead_sizes.len() as u32);
+        for (file_id, dead_size) in dead_sizes {
+            buf.put_u32(file_id);
+            buf.put_u64(dead_size);
+        }
+        buf.put_u32(crc32fast::hash(&buf));
+
+        // 先写临时文件再重命名，保证文件内容完整
+        let path = self.options.dir_path.join(CHECKPOINT_FILE_NAME);
+        let tmp_path = path.with_extension("tmp");
+        let write = || -> std::io::Result<()> {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&buf)?;
+            file.sync_all()?;
+            std::fs::rename(&tmp_path, &path)
+        };
+        write().map_err(|source| Error::FailedToWriteCheckpoint {
+            path: path.clone(),
+            source,
+        })
+    }
+
+    /// 从检查点中加载索引，检查点不存在、损坏或者与数据文件不一致时返回None
+    pub(crate) fn load_index_from_checkpoint(
+        &self,
+        file_ids: &[u32],
+    ) -> Result<Option<Checkpoint>> {
+        if !self.checkpoint_enabled() {
+            return Ok(None);
+        }
+        let path = self.options.dir_path.join(CHECKPOINT_FILE_NAME);
+        let buf = match std::fs::read(&path) {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(Error::FailedToReadCheckpoint { path, source }),
+        };
+        let Some((checkpoint, reclaim_size, entries, older_files, dead_sizes)) =
+            decode_checkpoint(&buf)
+        else {
+            warn!("ignore invalid index checkpoint {:?}", path);
+            return Ok(None);
+        };
+        if !self.match_data_files(&checkpoint, file_ids, &older_files) {
+            warn!("ignore stale index checkpoint {:?}", path);
+            return Ok(None);
+        }
+        for (key, pos) in entries {
+            self.index.put(key, pos);
+        }
+        self.reclaim_size.fetch_add(reclaim_size, Ordering::SeqCst);
+        let mut file_counters = self.file_counters.lock();
+        for (file_id, dead_size) in dead_sizes {
+            file_counters.set_dead_size(file_id, dead_size);
+        }
+        Ok(Some(checkpoint))
+    }
+
+    /// 检查数据文件是否与创建检查点时一致，之后只能追加写入或者创建新的数据文件
+    fn match_data_files(
+        &self,
+        checkpoint: &Checkpoint,
+        file_ids: &[u32],
+        older_files: &[(u32, u64)],
+    ) -> bool {
+        let file_size = |file_id| {
+            std::fs::metadata(get_data_file_full_path(
+                self.data_file_dir(file_id),
+                file_id,
+            ))
+            .map(|metadata| metadata.len())
+            .ok()
+        };
+        let file_ids = file_ids
+            .iter()
+            .copied()
+            .filter(|file_id| *file_id < checkpoint.file_id)
+            .collect::<Vec<_>>();
+        file_ids.len() == older_files.len()
+            && older_files
+                .iter()
+                .zip(file_ids)
+                .all(|((id, size), file_id)| *id == file_id && file_size(file_id) == Some(*size))
+            && file_size(checkpoint.file_id).is_some_and(|size| size >= checkpoint.offset)
+    }
+
+    /// 启动后台线程，每隔interval创建一次索引检查点
+    pub(crate) fn start_checkpoint_worker(&self, interval: Duration) {
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+        let engine = self.background_handle();
+        let handle = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                if let Err(e) = engine.write_checkpoint() {
+                    error!("background index checkpoint failed: {}", e);
+                }
+            }
+        });
+        *self.checkpoint_worker.lock() = Some((stop_tx, handle));
+    }
+}
+
+/// 删除索引检查点，merge和清空数据库之后检查点不再可用
+pub(crate) fn remove_checkpoint(dir_path: &Path) -> Result<()> {
+    let path = dir_path.join(CHECKPOINT_FILE_NAME);
+    match std::fs::remove_file(&path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(source) => Err(Error::FailedToWriteCheckpoint { path, source }),
+    }
+}
+
+type DecodedCheckpoint = (
+    Checkpoint,
+    usize,
+    Vec<(Vec<u8>, LogRecordPos)>,
+    Vec<(u32, u64)>,
+    Vec<(u32, u64)>,
+);
+
+/// 解码检查点，返回检查点位置、无效数据大小、索引、旧数据文件的大小以及每个数据文件的无效数据大小，
+/// 格式错误时返回None
+fn decode_checkpoint(buf: &[u8]) -> Option<DecodedCheckpoint> {
+    let (mut buf, crc) = buf.split_at_checked(buf.len().checked_sub(4)?)?;
+    if crc32fast::hash(buf) != (&crc[..]).get_u32() {
+        return None;
+    }
+    if buf.len() < 5 || &buf[..4] != CHECKPOINT_MAGIC || buf[4] != CHECKPOINT_VERSION {
+        return None;
+    }
+    buf.advance(5);
+    let seq_num = buf.try_get_u64().ok()? as usize;
+    let reclaim_size = buf.try_get_u64().ok()? as usize;
+    let file_id = buf.try_get_u32().ok()?;
+    let offset = buf.try_get_u64().ok()?;
+    let len = buf.try_get_u64().ok()? as usize;
+    let mut entries = Vec::with_capacity(len.min(buf.len()));
+    for _ in 0..len {
+        let key_len = buf.try_get_u32().ok()? as usize;
+        let key = buf.get(..key_len)?.to_vec();
+        buf.advance(key_len);
+        let pos_len = buf.try_get_u8().ok()? as usize;
+        let pos = decode_log_record_pos(buf.get(..pos_len)?.to_vec()).ok()?;
+        buf.advance(pos_len);
+        entries.push((key, pos));
+    }
+    let file_num = buf.try_get_u32().ok()? as usize;
+    let mut older_files = Vec::with_capacity(file_num.min(buf.len()));
+    for _ in 0..file_num {
+        older_files.push((buf.try_get_u32().ok()?, buf.try_get_u64().ok()?));
+    }
+    let dead_num = buf.try_get_u32().ok()? as usize;
+    let mut dead_sizes = Vec::with_capacity(dead_num.min(buf.len()));
+    for _ in 0..dead_num {
+        dead_sizes.push((buf.try_get_u32().ok()?, buf.try_get_u64().ok()?));
+    }
+    let checkpoint = Checkpoint {
+        file_id,
+        offset,
+        seq_num,
+    };
+    Some((checkpoint, reclaim_size, entries, older_files, dead_sizes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_engine_index_checkpoint() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-checkpoint");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        opts.index_checkpoint = true;
+        let checkpoint_path = opts.dir_path.join(CHECKPOINT_FILE_NAME);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..100 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        let stat = engine.stat().unwrap();
+        drop(engine);
+        assert!(checkpoint_path.is_file());
+
+        // 从检查点中加载索引
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(
+            engine.stat().unwrap().reclaimable_size,
+            stat.reclaimable_size
+        );
+        // 每个数据文件的无效数据大小同样从检查点中恢复
+        let dead_size = engine
+            .file_stats()
+            .unwrap()
+            .iter()
+            .map(|stat| stat.dead_size)
+            .sum::<u64>();
+        assert_eq!(dead_size, stat.reclaimable_size as u64);
+        assert_eq!(engine.len(), 900);
+        assert_eq!(engine.get(get_test_key(500)).unwrap(), get_test_value(500));
+
+        // 检查点之后写入的数据从数据文件中加载
+        engine.checkpoint_index().unwrap();
+        let batch = engine.new_write_batch(WriteOptions::default()).unwrap();
+        for i in 1000..1500 {
+            batch.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        batch.commit().unwrap();
+        engine.delete(get_test_key(500)).unwrap();
+        let seq_num = engine.seq_num.load(Ordering::SeqCst);
+        let stat = engine.stat().unwrap();
+        // 模拟崩溃，复制数据库目录，检查点停留在写入之前
+        let mut crash_opts = opts.clone();
+        crash_opts.dir_path = PathBuf::from("/tmp/bitcask-rs-checkpoint-crash");
+        let _ = std::fs::remove_dir_all(&crash_opts.dir_path);
+        std::fs::create_dir_all(&crash_opts.dir_path).unwrap();
+        for entry in std::fs::read_dir(&opts.dir_path).unwrap() {
+            let entry = entry.unwrap();
+            std::fs::copy(entry.path(), crash_opts.dir_path.join(entry.file_name())).unwrap();
+        }
+        let crashed = Engine::open(crash_opts.clone()).expect("failed to open engine");
+        assert_eq!(crashed.len(), 1399);
+        assert_eq!(crashed.seq_num.load(Ordering::SeqCst), seq_num);
+        assert_eq!(
+            crashed.stat().unwrap().reclaimable_size,
+            stat.reclaimable_size
+        );
+        assert!(crashed.get(get_test_key(500)).is_err());
+        assert_eq!(
+            crashed.get(get_test_key(1499)).unwrap(),
+            get_test_value(1499)
+        );
+        drop(crashed);
+        std::fs::remove_dir_all(crash_opts.dir_path).expect("failed to remove test dir");
+        drop(batch);
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 1399);
+
+        // merge后检查点失效
+        engine.merge().unwrap();
+        assert!(!checkpoint_path.exists());
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 1399);
+        drop(engine);
+
+        // 损坏的检查点被忽略
+        let mut buf = std::fs::read(&checkpoint_path).unwrap();
+        let n = buf.len() / 2;
+        buf[n] ^= 0xff;
+        std::fs::write(&checkpoint_path, buf).unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 1399);
+        assert_eq!(
+            engine.get(get_test_key(1200)).unwrap(),
+            get_test_value(1200)
+        );
+        drop(engine);
+
+        // 后台定时创建检查点
+        std::fs::remove_file(&checkpoint_path).unwrap();
+        opts.index_checkpoint_interval = Some(Duration::from_millis(20));
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(checkpoint_path.is_file());
+        drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
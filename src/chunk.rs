@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use bytes::Bytes;
+
+use crate::batch::{log_record_key_with_seq_num, NON_TRANSACTION_SEQ_NUM};
+use crate::data::cipher::Cipher;
+use crate::data::data_file::{DataFile, DATA_FILE_HEADER_SIZE};
+use crate::data::log_record::{
+    decode_chunk_positions, encode_chunk_positions, max_log_record_header_size, now_millis,
+    LogRecord, LogRecordPos, LogRecordType,
+};
+use crate::db::{read_log_record_from_files, Engine};
+use crate::error::{Error, Result};
+
+impl Engine {
+    /// 流式读取数据，分块存储的value每次只读取一个分块到内存中
+    pub fn get_reader(&self, key: Bytes) -> Result<impl Read> {
+        let head = self.get_head_record(&key)?;
+        ValueReader::new(self.clone(), key.to_vec(), head)
+    }
+
+    /// 从reader中流式写入长度为len的value，每次只读取一个分块到内存中，
+    /// 每个分块写入时单独计算CRC
+    ///
+    /// 写入期间持有rotate_lock的读锁，merge需要等待写入完成，
+    /// 避免还没有被索引的分块被merge清理
+    pub fn put_reader(&self, key: Bytes, mut reader: impl Read, len: u64) -> Result<()> {
+        self.check_closed()?;
+        if key.is_empty() {
+            return Err(Error::KeyIsEmpty);
+        }
+        let record_key = log_record_key_with_seq_num(&key, NON_TRANSACTION_SEQ_NUM);
+        let chunk_size = self.chunk_size(record_key.len());
+        // 不需要分块存储时直接写入
+        if chunk_size == 0 || len <= chunk_size as u64 {
+            let mut value = vec![0; len as usize];
+            reader
+                .read_exact(&mut value)
+                .map_err(|source| Error::FailedToReadValue { source })?;
+            return self.put(key, value.into());
+        }
+
+        self.check_kv_size(&key, usize::try_from(len).unwrap_or(usize::MAX))?;
+        let timestamp = now_millis();
+        self.evict_if_needed()?;
+        let _rotate_guard = self.rotate_lock.read();
+        self.check_db_size()?;
+        self.check_index_memory(&key)?;
+        let mut positions = Vec::new();
+        let mut remaining = len;
+        while remaining > 0 {
+            let mut value = vec![0; remaining.min(chunk_size as u64) as usize];
+            reader
+                .read_exact(&mut value)
+                .map_err(|source| Error::FailedToReadValue { source })?;
+            remaining -= value.len() as u64;
+            let chunk = LogRecord {
+                key: record_key.clone(),
+                value,
+                record_type: LogRecordType::CHUNK,
+                timestamp,
+                expire_at: 0,
+            };
+            positions.push(self.append_log_record(&chunk)?);
+        }
+        let head = LogRecord {
+            key: record_key,
+            value: encode_chunk_positions(&positions),
+            record_type: LogRecordType::CHUNKED,
+            timestamp,
+            expire_at: 0,
+        };
+        self.add_to_bloom_filter(&key);
+        let pos = self.append_log_record(&head)?;
+
+        // 更新内存索引，被覆盖的旧数据成为无效数据
+        if let Some(old_pos) = self.index.put(key.to_vec(), pos) {
+            self.add_reclaim_size(&old_pos);
+        }
+        // 二级索引和订阅者需要完整的value，重新读取
+        if self.has_secondary_indexes() || self.has_watchers() {
+            let value = self.get(key.clone())?;
+            self.after_commit(&[(&key, Some(&value))]);
+        } else {
+            self.after_commit(&[]);
+        }
+        Ok(())
+    }
+
+    /// 单个分块中value的最大长度
+    ///
+    /// 分块编码后不超过数据文件可用空间的一半，切换活跃文件时浪费的空间不会太多
+    pub(crate) fn chunk_size(&self, key_len: usize) -> usize {
+        let mut overhead = max_log_record_header_size() + key_len + std::mem::size_of::<u32>();
+        if self.cipher.is_some() {
+            overhead += Cipher::encrypted_len(0);
+        }
+        let available = self
+            .options
+            .data_file_size
+            .saturating_sub(DATA_FILE_HEADER_SIZE)
+            / 2;
+        (available as usize).saturating_sub(overhead)
+    }
+
+    /// value超过一个分块的长度时需要分块存储
+    pub(crate) fn should_chunk(&self, record: &LogRecord) -> bool {
+        let chunk_size = self.chunk_size(record.key.len());
+        record.record_type == LogRecordType::NORMAL
+            && chunk_size > 0
+            && record.value.len() > chunk_size
+    }
+
+    /// 将value分块写入活跃数据文件，返回记录各个分块位置的记录，
+    /// 写入该记录之前分块不会被索引，写入失败时留下的分块只是无效数据
+    pub(crate) fn write_chunks(
+        &self,
+        active_file: &mut DataFile,
+        record: &LogRecord,
+    ) -> Result<LogRecord> {
+        let positions = record
+            .value
+            .chunks(self.chunk_size(record.key.len()))
+            .map(|value| {
+                let chunk = LogRecord {
+                    key: record.key.clone(),
+                    value: value.to_vec(),
+                    record_type: LogRecordType::CHUNK,
+                    timestamp: record.timestamp,
+                    expire_at: record.expire_at,
+                };
+                self.append_to_active_file(active_file, &chunk)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(LogRecord {
+            key: record.key.clone(),
+            value: encode_chunk_positions(&positions),
+            record_type: LogRecordType::CHUNKED,
+            timestamp: record.timestamp,
+            expire_at: record.expire_at,
+        })
+    }
+}
+
+/// 读取分块存储的记录的所有分块
+pub(crate) fn read_chunks(
+    active_file: &DataFile,
+    older_files: &HashMap<u32, DataFile>,
+    head: &LogRecord,
+) -> Result<Vec<LogRecord>> {
+    decode_chunk_positions(&head.value)?
+        .iter()
+        .map(|pos| {
+            let chunk = read_log_record_from_files(active_file, older_files, pos)?;
+            if chunk.record_type != LogRecordType::CHUNK || chunk.key != head.key {
+                return Err(Error::InvalidLogRecord);
+            }
+            Ok(chunk)
+        })
+        .collect()
+}
+
+/// 读取所有分块并拼接为完整的记录
+pub(crate) fn read_chunked_record(
+    active_file: &DataFile,
+    older_files: &HashMap<u32, DataFile>,
+    head: LogRecord,
+) -> Result<LogRecord> {
+    let value = read_chunks(active_file, older_files, &head)?
+        .into_iter()
+        .map(|chunk| chunk.value)
+        .collect::<Vec<_>>()
+        .concat();
+    Ok(LogRecord {
+        value,
+        record_type: LogRecordType::NORMAL,
+        ..head
+    })
+}
+
+/// 按分块读取value的Read实现
+struct ValueReader {
+    engine: Engine,
+    key: Vec<u8>,
+    /// 数据文件中记录的key，包含事务编号，用于校验分块
+    record_key: Vec<u8>,
+    /// 写入时间，merge后重新查询索引时用于判断是否为同一条记录
+    timestamp: u64,
+    /// 各个分块的位置，没有分块存储时为空
+    positions: Vec<LogRecordPos>,
+    /// 下一个要读取的分块
+    next_chunk: usize,
+    /// 当前分块的数据
+    buf: Vec<u8>,
+    /// 当前分块中已读取的长度
+    offset: usize,
+}
+
+impl ValueReader {
+    fn new(engine: Engine, key: Vec<u8>, head: LogRecord) -> Result<Self> {
+        let (positions, buf) = match head.record_type {
+            LogRecordType::CHUNKED => (decode_chunk_positions(&head.value)?, vec![]),
+            _ => (vec![], head.value),
+        };
+        Ok(Self {
+            engine,
+            key,
+            record_key: head.key,
+            timestamp: head.timestamp,
+            positions,
+            next_chunk: 0,
+            buf,
+            offset: 0,
+        })
+    }
+
+    /// 读取下一个分块
+    fn read_next_chunk(&mut self) -> Result<Vec<u8>> {
+        self.engine.check_closed()?;
+        let chunk = match self.read_chunk() {
+            // 数据文件可能刚被merge清理，分块已迁移到新的位置，
+            // merge不改变分块的划分，重新查询索引后从同一个分块继续读取
+            Err(Error::DataFileNotFound) => {
+                let head = self.engine.get_head_record(&self.key)?;
+                let positions = match head.record_type {
+                    LogRecordType::CHUNKED => decode_chunk_positions(&head.value)?,
+                    _ => vec![],
+                };
+                if head.timestamp != self.timestamp || positions.len() != self.positions.len() {
+                    return Err(Error::DataFileNotFound);
+                }
+                self.record_key = head.key;
+                self.positions = positions;
+                self.read_chunk()?
+            }
+            res => res?,
+        };
+        self.next_chunk += 1;
+        Ok(chunk.value)
+    }
+
+    fn read_chunk(&self) -> Result<LogRecord> {
+        let active_file = self.engine.active_file.read();
+        let older_files = self.engine.older_files.read();
+        let pos = &self.positions[self.next_chunk];
+        let chunk = read_log_record_from_files(&active_file, &older_files, pos)?;
+        if chunk.record_type != LogRecordType::CHUNK || chunk.key != self.record_key {
+            return Err(Error::InvalidLogRecord);
+        }
+        Ok(chunk)
+    }
+}
+
+impl Read for ValueReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.offset == self.buf.len() {
+            if self.next_chunk == self.positions.len() {
+                return Ok(0);
+            }
+            self.buf = self.read_next_chunk().map_err(std::io::Error::other)?;
+            self.offset = 0;
+        }
+        let n_bytes = buf.len().min(self.buf.len() - self.offset);
+        buf[..n_bytes].copy_from_slice(&self.buf[self.offset..self.offset + n_bytes]);
+        self.offset += n_bytes;
+        Ok(n_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::options::Options;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_engine_get_reader() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-get-reader");
+        opts.data_file_size = 4096;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let value: Bytes = (0..20000).map(|i| (i * 7 % 251) as u8).collect();
+        engine.put(get_test_key(1), value.clone()).unwrap();
+        engine.put(get_test_key(2), get_test_value(2)).unwrap();
+
+        let mut buf = vec![];
+        engine
+            .get_reader(get_test_key(1))
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        assert_eq!(buf, value);
+        buf.clear();
+        engine
+            .get_reader(get_test_key(2))
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        assert_eq!(buf, get_test_value(2));
+        assert!(matches!(
+            engine.get_reader(get_test_key(3)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        // 读取过程中merge，分块迁移后继续读取
+        let mut reader = engine.get_reader(get_test_key(1)).unwrap();
+        let mut buf = vec![0; 5000];
+        reader.read_exact(&mut buf).unwrap();
+        engine.merge().unwrap();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, value);
+
+        // 读取过程中数据被覆盖并merge
+        let mut reader = engine.get_reader(get_test_key(1)).unwrap();
+        let mut buf = vec![0; 5000];
+        reader.read_exact(&mut buf).unwrap();
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+        engine.merge().unwrap();
+        assert!(reader.read_to_end(&mut buf).is_err());
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_put_reader() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-put-reader");
+        opts.data_file_size = 4096;
+        opts.max_value_size = Some(30000);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let value: Bytes = (0..20000).map(|i| (i * 7 % 251) as u8).collect();
+        engine
+            .put_reader(get_test_key(1), value.as_ref(), value.len() as u64)
+            .unwrap();
+        engine
+            .put_reader(get_test_key(2), get_test_value(2).as_ref(), 10)
+            .unwrap();
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), value);
+        assert_eq!(
+            engine.get(get_test_key(2)).unwrap(),
+            get_test_value(2)[..10]
+        );
+
+        // reader中的数据不足，已写入的分块不会被索引
+        assert!(matches!(
+            engine.put_reader(get_test_key(3), value.as_ref(), 25000),
+            Err(Error::FailedToReadValue { .. })
+        ));
+        assert!(matches!(
+            engine.get(get_test_key(3)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        assert!(matches!(
+            engine.put_reader(get_test_key(3), value.as_ref(), 30001),
+            Err(Error::ValueTooLarge)
+        ));
+
+        // 重启并merge后数据仍然有效
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 2);
+        engine.merge().unwrap();
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), value);
+        assert_eq!(
+            engine.get(get_test_key(2)).unwrap(),
+            get_test_value(2)[..10]
+        );
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,439 @@
+//! 部分数据文件的压缩
+//!
+//! 只把选定的旧数据文件中仍然有效的数据追加写入活跃数据文件，再删除这些文件。
+//! 相比merge整个数据库，每次阻塞写操作的时间更短，写放大也更小
+
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+
+use crate::batch::{log_record_key_with_seq_num, parse_log_record_key, NON_TRANSACTION_SEQ_NUM};
+use crate::checkpoint::remove_checkpoint;
+use crate::chunk::read_chunked_record;
+use crate::data::data_file::{get_data_file_full_path, DATA_FILE_HEADER_SIZE};
+use crate::data::log_record::{decode_chunk_positions, LogRecord, LogRecordPos, LogRecordType};
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::fio::{mem_io, Advice};
+
+/// 每批重写的记录数量，重写期间阻塞写操作
+const COMPACT_BATCH_SIZE: usize = 256;
+
+/// 需要重写的记录
+struct Candidate {
+    key: Vec<u8>,
+    /// 原来的位置，None表示删除记录
+    pos: Option<LogRecordPos>,
+    record: LogRecord,
+    /// 重写后成为无效数据的其他数据文件中的分块
+    dead_chunks: Vec<LogRecordPos>,
+}
+
+/// 数据文件首尾的记录是否与相邻的数据文件相关
+struct FileBoundary {
+    /// 第一条记录属于事务，事务可能从上一个数据文件开始
+    starts_in_transaction: bool,
+    /// 最后一条记录是分块，记录分块位置的记录在下一个数据文件中
+    ends_with_chunk: bool,
+}
+
+impl Engine {
+    /// 压缩指定的旧数据文件，将其中仍然有效的数据重写到活跃数据文件后删除这些文件
+    ///
+    /// 分批重写，每批短暂阻塞写操作。事务或者分块存储的数据跨越相邻的数据文件时，
+    /// 相邻的数据文件同样被压缩。不能压缩活跃数据文件，不能与merge同时进行
+    pub fn compact_files(&self, file_ids: &[u32]) -> Result<()> {
+        self.check_closed()?;
+        self.check_writable()?;
+        self.check_merge_paused()?;
+        let _merging_guard = match self.merging_lock.try_lock() {
+            Some(guard) => guard,
+            None => return Err(Error::MergeInProgress),
+        };
+        {
+            let active_file = self.active_file.read();
+            let older_files = self.older_files.read();
+            if let Some(file_id) = file_ids.iter().find(|file_id| {
+                **file_id == active_file.get_file_id() || !older_files.contains_key(file_id)
+            }) {
+                return Err(Error::InvalidCompactionFile(*file_id));
+            }
+        }
+        if file_ids.is_empty() {
+            return Ok(());
+        }
+        // 被压缩的数据文件会被删除，索引检查点不再可用
+        if !self.is_in_memory() {
+            remove_checkpoint(&self.options.dir_path)?;
+        }
+
+        let pending = file_ids.iter().copied().collect::<BTreeSet<_>>();
+        self.track_merge(pending.len(), || self.compact_pending_files(pending))?;
+        self.refresh_db_size();
+        Ok(())
+    }
+
+    /// 依次压缩待压缩的数据文件，以及与之相关的相邻数据文件
+    fn compact_pending_files(&self, mut pending: BTreeSet<u32>) -> Result<()> {
+        let mut compacted = BTreeSet::new();
+        while let Some(file_id) = pending.pop_first() {
+            if !compacted.insert(file_id) {
+                continue;
+            }
+            // 更早的数据文件都被压缩时，不再需要保留删除记录
+            let has_earlier_file = self
+                .older_files
+                .read()
+                .keys()
+                .any(|id| *id < file_id && !compacted.contains(id) && !pending.contains(id));
+            let boundary = self.compact_file(file_id, has_earlier_file)?;
+            if boundary.starts_in_transaction {
+                let previous = self
+                    .older_files
+                    .read()
+                    .keys()
+                    .copied()
+                    .filter(|id| *id < file_id)
+                    .max();
+                if let Some(previous) = previous.filter(|id| !compacted.contains(id)) {
+                    if pending.insert(previous) {
+                        self.merge_control.add_total_files(1);
+                    }
+                }
+            }
+            if boundary.ends_with_chunk {
+                let next = self.next_file_id(file_id)?;
+                if !compacted.contains(&next) && pending.insert(next) {
+                    self.merge_control.add_total_files(1);
+                }
+            }
+            self.merge_control.finish_file();
+        }
+
+        // 重写的数据持久化后再删除旧的数据文件
+        self.active_file.read().sync()?;
+        {
+            let mut older_files = self.older_files.write();
+            for file_id in compacted.iter() {
+                // 仍有快照在使用时保留旧的数据文件，快照全部释放后再删除
+                if let Some(data_file) = older_files.remove(file_id) {
+                    if self.retire_data_file(*file_id, data_file) {
+                        continue;
+                    }
+                }
+                let file_path = get_data_file_full_path(self.data_file_dir(*file_id), *file_id);
+                if self.is_in_memory() {
+                    mem_io::remove_file(file_path);
+                } else if let Err(source) = std::fs::remove_file(&file_path) {
+                    return Err(Error::FailedToRemoveDataFile {
+                        path: file_path,
+                        source,
+                    });
+                }
+            }
+        }
+        let compacted = compacted.into_iter().collect::<Vec<_>>();
+        if let Some(value_cache) = &self.value_cache {
+            value_cache.lock().remove_files(&compacted);
+        }
+        // 被删除的数据文件中的无效数据已被清理
+        let reclaimed_size = {
+            let mut file_counters = self.file_counters.lock();
+            compacted
+                .iter()
+                .map(|file_id| file_counters.remove(*file_id))
+                .sum::<u64>()
+        };
+        let _ = self
+            .reclaim_size
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |size| {
+                Some(size.saturating_sub(reclaimed_size as usize))
+            });
+        self.merge_control.set_reclaimed(reclaimed_size);
+        Ok(())
+    }
+
+    /// 重写数据文件中仍然有效的数据，has_earlier_file表示存在更早的没有被压缩的数据文件
+    fn compact_file(&self, file_id: u32, has_earlier_file: bool) -> Result<FileBoundary> {
+        let data_file = self.open_data_file(self.data_file_dir(file_id), file_id)?;
+        data_file.advise(Advice::Sequential);
+        let mut reader = data_file.reader(DATA_FILE_HEADER_SIZE);
+        let mut boundary = FileBoundary {
+            starts_in_transaction: false,
+            ends_with_chunk: false,
+        };
+        let mut first = true;
+        let mut candidates = Vec::new();
+        loop {
+            // 暂停时在记录之间等待恢复
+            self.wait_merge_resumed()?;
+            let offset = reader.offset();
+            let (log_record, size) = match reader.next_record() {
+                Ok(rc) => (rc.record, rc.size),
+                Err(Error::ReadDataFileEOF) => break,
+                Err(e) => return Err(e),
+            };
+            self.merge_control.add_processed(size as u64);
+            self.throttle_merge(size);
+            let pos = LogRecordPos {
+                file_id,
+                offset,
+                size: size as u32,
+            };
+            let (key, seq_num) = parse_log_record_key(&log_record.key)?;
+            if first {
+                boundary.starts_in_transaction = seq_num != NON_TRANSACTION_SEQ_NUM;
+                first = false;
+            }
+            boundary.ends_with_chunk = log_record.record_type == LogRecordType::CHUNK;
+            if let Some(candidate) =
+                self.compaction_candidate(key, pos, log_record, has_earlier_file)?
+            {
+                candidates.push(candidate);
+            }
+            if candidates.len() >= COMPACT_BATCH_SIZE {
+                self.rewrite_candidates(std::mem::take(&mut candidates))?;
+            }
+        }
+        self.rewrite_candidates(candidates)?;
+        Ok(boundary)
+    }
+
+    /// 判断记录是否需要重写，需要时读取完整的value
+    fn compaction_candidate(
+        &self,
+        key: Vec<u8>,
+        pos: LogRecordPos,
+        log_record: LogRecord,
+        has_earlier_file: bool,
+    ) -> Result<Option<Candidate>> {
+        let current_pos = self.index.get(key.clone());
+        match log_record.record_type {
+            LogRecordType::NORMAL | LogRecordType::CHUNKED | LogRecordType::OPERAND
+                if current_pos == Some(pos) =>
+            {
+                // 没有更早的数据文件时直接清理已过期的数据，否则保留以覆盖更早的数据
+                if log_record.is_expired() && !has_earlier_file {
+                    if self.index.delete_if(key.clone(), pos) {
+                        self.update_secondary_indexes(&[(&key, None)]);
+                    }
+                    return Ok(None);
+                }
+                let active_file = self.active_file.read();
+                let older_files = self.older_files.read();
+                let mut dead_chunks = Vec::new();
+                let record = match log_record.record_type {
+                    LogRecordType::OPERAND => {
+                        self.resolve_operands(&active_file, &older_files, log_record)?
+                    }
+                    LogRecordType::CHUNKED => {
+                        // 其他数据文件中的分块重写后成为无效数据
+                        dead_chunks = decode_chunk_positions(&log_record.value)?
+                            .into_iter()
+                            .filter(|chunk_pos| chunk_pos.file_id != pos.file_id)
+                            .collect();
+                        read_chunked_record(&active_file, &older_files, log_record)?
+                    }
+                    _ => log_record,
+                };
+                Ok(Some(Candidate {
+                    key: key.clone(),
+                    pos: Some(pos),
+                    record: LogRecord {
+                        // 已提交的事务数据不再需要事务编号
+                        key: log_record_key_with_seq_num(&key, NON_TRANSACTION_SEQ_NUM),
+                        ..record
+                    },
+                    dead_chunks,
+                }))
+            }
+            // 删除记录可能覆盖了更早的数据文件中的数据，key仍然不存在时需要保留
+            LogRecordType::DELETE if has_earlier_file && current_pos.is_none() => {
+                Ok(Some(Candidate {
+                    key: key.clone(),
+                    pos: None,
+                    record: LogRecord {
+                        key: log_record_key_with_seq_num(&key, NON_TRANSACTION_SEQ_NUM),
+                        ..log_record
+                    },
+                    dead_chunks: Vec::new(),
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// 阻塞写操作，将仍然有效的记录追加写入活跃数据文件并更新索引
+    fn rewrite_candidates(&self, candidates: Vec<Candidate>) -> Result<()> {
+        if candidates.is_empty() {
+            return Ok(());
+        }
+        // 在阻塞写操作前等待merge限额
+        self.throttle_merge(
+            candidates
+                .iter()
+                .map(|candidate| candidate.record.key.len() + candidate.record.value.len())
+                .sum(),
+        );
+        let _rotate_guard = self.rotate_lock.write();
+        // 读取记录后key可能被重新写入或删除
+        let candidates = candidates
+            .into_iter()
+            .filter(|candidate| {
+                let current_pos = self.index.get(candidate.key.clone());
+                match candidate.pos {
+                    Some(pos) => current_pos == Some(pos),
+                    None => current_pos.is_none(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let records = candidates
+            .iter()
+            .map(|candidate| candidate.record.clone())
+            .collect::<Vec<_>>();
+        let positions = self.append_log_records(&records)?;
+        for (candidate, new_pos) in candidates.into_iter().zip(positions) {
+            match candidate.pos {
+                Some(_) => {
+                    self.index.put(candidate.key, new_pos);
+                    for chunk_pos in candidate.dead_chunks.iter() {
+                        self.add_reclaim_size(chunk_pos);
+                    }
+                }
+                // 删除记录本身是无效数据
+                None => self.add_reclaim_size(&new_pos),
+            }
+        }
+        Ok(())
+    }
+
+    /// 下一个数据文件的ID，下一个数据文件是活跃数据文件时先切换新的活跃数据文件
+    fn next_file_id(&self, file_id: u32) -> Result<u32> {
+        let _rotate_guard = self.rotate_lock.write();
+        let mut active_file = self.active_file.write();
+        let next = self
+            .older_files
+            .read()
+            .keys()
+            .copied()
+            .filter(|id| *id > file_id)
+            .min();
+        match next {
+            Some(next) => Ok(next),
+            None => {
+                let next = active_file.get_file_id();
+                self.rotate_active_file(&mut active_file)?;
+                Ok(next)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_engine_compact_files() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-compact");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        // 删除第一个数据文件中的数据，删除记录写入之后的数据文件
+        let delete_file_id = engine.active_file_id();
+        for i in 0..50 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        for i in 50..100 {
+            engine.put(get_test_key(i), Bytes::from("new")).unwrap();
+        }
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        for i in 1000..1100 {
+            wb.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        wb.commit().unwrap();
+        for i in 100..700 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+
+        let active_file_id = engine.active_file_id();
+        assert!(matches!(
+            engine.compact_files(&[active_file_id]),
+            Err(Error::InvalidCompactionFile(_))
+        ));
+        assert!(matches!(
+            engine.compact_files(&[u32::MAX]),
+            Err(Error::InvalidCompactionFile(_))
+        ));
+
+        let check = |engine: &Engine| {
+            assert_eq!(engine.len(), 1050);
+            for i in 0..50 {
+                assert!(engine.get(get_test_key(i)).is_err());
+            }
+            for i in 50..100 {
+                assert_eq!(engine.get(get_test_key(i)).unwrap(), Bytes::from("new"));
+            }
+            for i in 100..1100 {
+                assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+            }
+        };
+
+        // 更早的数据文件中还有被删除的数据，删除记录被保留，重启后数据不会复活
+        assert!(engine.data_file_ids()[0] < delete_file_id);
+        assert!(engine.active_file_id() > delete_file_id);
+        engine.compact_files(&[delete_file_id]).unwrap();
+        check(&engine);
+        drop(wb);
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        check(&engine);
+
+        // 压缩第一个数据文件，其中大部分数据已无效
+        let stats = engine.file_stats().unwrap();
+        let first = stats[0];
+        assert!(first.dead_size > 0);
+        let reclaimable_size = engine.stat().unwrap().reclaimable_size;
+        engine.compact_files(&[first.file_id]).unwrap();
+        assert!(!engine.data_file_ids().contains(&first.file_id));
+        assert_eq!(
+            engine.stat().unwrap().reclaimable_size,
+            reclaimable_size - first.dead_size as usize
+        );
+        check(&engine);
+
+        // 压缩所有旧的数据文件，不再需要保留删除记录
+        let file_ids = engine.data_file_ids();
+        engine
+            .compact_files(&file_ids[..file_ids.len() - 1])
+            .unwrap();
+        assert!(engine.data_file_ids()[0] > file_ids[file_ids.len() - 2]);
+        check(&engine);
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        check(&engine);
+
+        // merge之后压缩hint文件覆盖的数据文件
+        engine.merge().unwrap();
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let file_ids = engine.data_file_ids();
+        engine.compact_files(&file_ids[..1]).unwrap();
+        check(&engine);
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        check(&engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
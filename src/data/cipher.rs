@@ -0,0 +1,117 @@
+//! value加密，需要开启`encryption` feature
+//!
+//! 使用ChaCha20-Poly1305，每条数据使用随机的nonce，
+//! 加密后的value为 nonce + 密文 + tag，header和key作为附加数据参与认证
+
+#[cfg(feature = "encryption")]
+use crate::error::Error;
+use crate::error::Result;
+
+/// nonce的大小
+const NONCE_SIZE: usize = 12;
+/// 认证tag的大小
+const TAG_SIZE: usize = 16;
+
+#[cfg(feature = "encryption")]
+pub struct Cipher {
+    aead: chacha20poly1305::ChaCha20Poly1305,
+}
+
+/// 没有开启`encryption` feature时无法构造
+#[cfg(not(feature = "encryption"))]
+pub enum Cipher {}
+
+impl Cipher {
+    /// 加密后的value长度
+    pub fn encrypted_len(len: usize) -> usize {
+        NONCE_SIZE + len + TAG_SIZE
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl Cipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        use chacha20poly1305::KeyInit;
+        Self {
+            aead: chacha20poly1305::ChaCha20Poly1305::new(key.into()),
+        }
+    }
+
+    /// 加密value，aad为需要认证但不加密的数据
+    pub fn encrypt(&self, aad: &[u8], value: &[u8]) -> Result<Vec<u8>> {
+        use chacha20poly1305::aead::{Aead, AeadCore, OsRng, Payload};
+        let nonce = chacha20poly1305::ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .aead
+            .encrypt(&nonce, Payload { msg: value, aad })
+            .map_err(|_| Error::FailedToEncryptValue)?;
+        let mut buf = Vec::with_capacity(Self::encrypted_len(value.len()));
+        buf.extend_from_slice(&nonce);
+        buf.extend_from_slice(&ciphertext);
+        Ok(buf)
+    }
+
+    /// 解密value，密钥错误或者数据被篡改时返回错误
+    pub fn decrypt(&self, aad: &[u8], value: &[u8]) -> Result<Vec<u8>> {
+        use chacha20poly1305::aead::{Aead, Payload};
+        if value.len() < NONCE_SIZE + TAG_SIZE {
+            return Err(Error::FailedToDecryptValue);
+        }
+        let (nonce, ciphertext) = value.split_at(NONCE_SIZE);
+        self.aead
+            .decrypt(
+                nonce.into(),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| Error::FailedToDecryptValue)
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+impl Cipher {
+    pub fn encrypt(&self, _aad: &[u8], _value: &[u8]) -> Result<Vec<u8>> {
+        match *self {}
+    }
+
+    pub fn decrypt(&self, _aad: &[u8], _value: &[u8]) -> Result<Vec<u8>> {
+        match *self {}
+    }
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cipher() {
+        let cipher = Cipher::new(&[1; 32]);
+        let encrypted = cipher.encrypt(b"header", b"bitcask-rs").unwrap();
+        assert_eq!(encrypted.len(), Cipher::encrypted_len(10));
+        assert_eq!(
+            cipher.decrypt(b"header", &encrypted).unwrap(),
+            b"bitcask-rs"
+        );
+        // 每次加密使用不同的nonce
+        assert_ne!(cipher.encrypt(b"header", b"bitcask-rs").unwrap(), encrypted);
+
+        // 附加数据被篡改、密钥错误或者数据不完整
+        assert!(matches!(
+            cipher.decrypt(b"header2", &encrypted).err().unwrap(),
+            Error::FailedToDecryptValue
+        ));
+        assert!(matches!(
+            Cipher::new(&[2; 32])
+                .decrypt(b"header", &encrypted)
+                .err()
+                .unwrap(),
+            Error::FailedToDecryptValue
+        ));
+        assert!(matches!(
+            cipher.decrypt(b"header", &encrypted[..20]).err().unwrap(),
+            Error::FailedToDecryptValue
+        ));
+    }
+}
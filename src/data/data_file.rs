@@ -1,20 +1,45 @@
 use std::{
+    collections::BTreeMap,
     path::{Path, PathBuf},
     sync::Arc,
 };
 
 use crate::{
     data::log_record::max_log_record_header_size,
+    db::read_dir,
     error::{Error, Result},
-    fio::new_io_manager,
+    fio::{new_io_manager, Advice},
+    options::{ChecksumType, IOType},
 };
-use bytes::{Buf, BytesMut};
-use parking_lot::RwLock;
-use prost::{decode_length_delimiter, length_delimiter_len};
+use bytes::{Buf, BufMut, BytesMut};
+use log::error;
+use parking_lot::{Mutex, RwLock};
+use prost::decode_length_delimiter;
+use prost::encoding::decode_varint;
 
-use super::log_record::{LogRecord, ReadLogRecord};
+use super::cipher::Cipher;
+use super::log_record::{
+    compute_checksum, decode_record_type, decompress, LogRecord, LogRecordPos, LogRecordType,
+    ReadLogRecord,
+};
+use crate::dictionary::Dictionaries;
 
 pub const DATA_FILE_SUFFIX: &str = ".data";
+pub const HINT_FILE_NAME: &str = "hint-index";
+pub const SEQ_NUM_FILE_NAME: &str = "seq-num";
+
+/// 顺序读取时每块数据的大小
+const READ_AHEAD_CHUNK_SIZE: usize = 64 * 1024;
+/// 顺序读取时一次批量读取的块数
+const READ_AHEAD_CHUNKS: usize = 4;
+
+/// 数据文件头的魔数
+const DATA_FILE_MAGIC: &[u8; 4] = b"BCRS";
+/// 当前的数据文件格式版本，版本1的数据记录包含写入时间戳
+pub const DATA_FILE_VERSION: u16 = 1;
+/// 数据文件头的大小: magic(4) + version(2) + flags(2)，数据记录从文件头之后开始，
+/// flags的低2位为数据记录的校验算法
+pub const DATA_FILE_HEADER_SIZE: u64 = 8;
 
 pub struct DataFile {
     /// 文件ID
@@ -23,19 +48,119 @@ pub struct DataFile {
     write_offset: Arc<RwLock<u64>>,
     /// IO管理器
     io_manager: Box<dyn crate::fio::IOManager>,
+    /// 文件路径，用于错误信息
+    path: PathBuf,
+    /// 读取加密的数据时用于解密
+    cipher: Option<Arc<Cipher>>,
+    /// 写缓冲区，其中的数据位于文件的[write_offset - len, write_offset)，还没有写入文件
+    write_buffer: Mutex<Vec<u8>>,
+    /// 写缓冲区的大小，0表示不使用写缓冲区
+    write_buffer_size: usize,
+    /// 数据记录的校验算法
+    checksum: ChecksumType,
+    /// 读取使用字典压缩的数据时用于解压
+    dictionaries: Option<Arc<Dictionaries>>,
 }
 
 impl DataFile {
-    pub fn new(dir_path: impl AsRef<Path>, file_id: u32) -> Result<Self> {
+    pub fn new(dir_path: impl AsRef<Path>, file_id: u32, io_type: IOType) -> Result<Self> {
+        Self::new_with_checksum(dir_path, file_id, io_type, ChecksumType::Crc32)
+    }
+
+    /// 打开数据文件，新创建的文件使用checksum校验数据记录，已存在的文件使用文件头中记录的校验算法
+    pub fn new_with_checksum(
+        dir_path: impl AsRef<Path>,
+        file_id: u32,
+        io_type: IOType,
+        checksum: ChecksumType,
+    ) -> Result<Self> {
         let file_path = get_data_file_full_path(&dir_path, file_id);
-        let io_manager = new_io_manager(file_path)?;
+        // mmap不支持写入，文件头统一用标准IO写入和校验，内存文件不经过文件系统
+        let header_io_type = match io_type {
+            IOType::Memory => IOType::Memory,
+            _ => IOType::StandardFIO,
+        };
+        let io_manager = new_io_manager(&file_path, header_io_type)?;
+        let mut header_buf = [0; DATA_FILE_HEADER_SIZE as usize];
+        let checksum = match io_manager.read(&mut header_buf, 0)? {
+            // 新创建的文件，写入文件头
+            0 => {
+                io_manager.write(&encode_data_file_header(checksum))?;
+                checksum
+            }
+            n if n < header_buf.len() => return Err(Error::InvalidDataFileHeader),
+            _ => check_data_file_header(&header_buf)?,
+        };
+        let io_manager = match io_type {
+            IOType::StandardFIO | IOType::Memory => io_manager,
+            _ => new_io_manager(&file_path, io_type)?,
+        };
         Ok(Self {
             file_id: Arc::new(RwLock::new(file_id)),
+            write_offset: Arc::new(RwLock::new(DATA_FILE_HEADER_SIZE)),
+            io_manager,
+            path: file_path,
+            cipher: None,
+            write_buffer: Mutex::new(Vec::new()),
+            write_buffer_size: 0,
+            checksum,
+            dictionaries: None,
+        })
+    }
+
+    /// 创建hint索引文件
+    pub fn new_hint_file(dir_path: impl AsRef<Path>) -> Result<Self> {
+        let file_path = dir_path.as_ref().join(HINT_FILE_NAME);
+        let io_manager = new_io_manager(&file_path, IOType::StandardFIO)?;
+        Ok(Self {
+            file_id: Arc::new(RwLock::new(0)),
             write_offset: Arc::new(RwLock::new(0)),
-            io_manager: Box::new(io_manager),
+            io_manager,
+            path: file_path,
+            cipher: None,
+            write_buffer: Mutex::new(Vec::new()),
+            write_buffer_size: 0,
+            checksum: ChecksumType::Crc32,
+            dictionaries: None,
         })
     }
 
+    /// 创建事务编号文件
+    pub fn new_seq_num_file(dir_path: impl AsRef<Path>) -> Result<Self> {
+        let file_path = dir_path.as_ref().join(SEQ_NUM_FILE_NAME);
+        let io_manager = new_io_manager(&file_path, IOType::StandardFIO)?;
+        Ok(Self {
+            file_id: Arc::new(RwLock::new(0)),
+            write_offset: Arc::new(RwLock::new(0)),
+            io_manager,
+            path: file_path,
+            cipher: None,
+            write_buffer: Mutex::new(Vec::new()),
+            write_buffer_size: 0,
+            checksum: ChecksumType::Crc32,
+            dictionaries: None,
+        })
+    }
+
+    /// 设置解密数据使用的cipher
+    pub fn with_cipher(mut self, cipher: Option<Arc<Cipher>>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// 设置解压数据使用的zstd字典
+    pub(crate) fn with_dictionaries(mut self, dictionaries: Option<Arc<Dictionaries>>) -> Self {
+        self.dictionaries = dictionaries;
+        self
+    }
+
+    /// 设置写缓冲区的大小，小数据的写入先合并到缓冲区中，
+    /// 缓冲区满了、sync或者关闭文件时再写入文件
+    pub fn with_write_buffer(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = write_buffer_size;
+        self
+    }
+
     pub fn get_write_offset(&self) -> u64 {
         *self.write_offset.read()
     }
@@ -44,77 +169,581 @@ impl DataFile {
         *self.file_id.read()
     }
 
+    /// 数据记录的校验算法
+    pub fn checksum(&self) -> ChecksumType {
+        self.checksum
+    }
+
     pub fn write(&self, buf: &[u8]) -> Result<usize> {
-        let n_bytes = self.io_manager.write(buf)?;
-        // 更新写入偏移量
+        if self.write_buffer_size == 0 {
+            let n_bytes = self.io_manager.write(buf)?;
+            // 更新写入偏移量
+            *self.write_offset.write() += n_bytes as u64;
+            return Ok(n_bytes);
+        }
+        let mut write_buffer = self.write_buffer.lock();
+        if write_buffer.len() + buf.len() > self.write_buffer_size {
+            self.flush_buffer(&mut write_buffer)?;
+        }
+        // 大于缓冲区的数据直接写入文件
+        if buf.len() >= self.write_buffer_size {
+            let n_bytes = self.io_manager.write(buf)?;
+            *self.write_offset.write() += n_bytes as u64;
+            return Ok(n_bytes);
+        }
+        write_buffer.extend_from_slice(buf);
+        *self.write_offset.write() += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    /// 依次写入多段数据，标准文件IO下只需要一次writev系统调用
+    pub fn write_vectored(&self, bufs: &[&[u8]]) -> Result<usize> {
+        let len = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+        if self.write_buffer_size == 0 {
+            let n_bytes = self.io_manager.write_vectored(bufs)?;
+            *self.write_offset.write() += n_bytes as u64;
+            return Ok(n_bytes);
+        }
+        let mut write_buffer = self.write_buffer.lock();
+        if write_buffer.len() + len <= self.write_buffer_size {
+            bufs.iter()
+                .for_each(|buf| write_buffer.extend_from_slice(buf));
+            *self.write_offset.write() += len as u64;
+            return Ok(len);
+        }
+        self.flush_buffer(&mut write_buffer)?;
+        let n_bytes = self.io_manager.write_vectored(bufs)?;
         *self.write_offset.write() += n_bytes as u64;
         Ok(n_bytes)
     }
 
+    /// 将写缓冲区中的数据写入文件
+    pub fn flush(&self) -> Result<()> {
+        self.flush_buffer(&mut self.write_buffer.lock())
+    }
+
+    fn flush_buffer(&self, write_buffer: &mut Vec<u8>) -> Result<()> {
+        if write_buffer.is_empty() {
+            return Ok(());
+        }
+        if self.io_manager.write(write_buffer)? < write_buffer.len() {
+            return Err(Error::FailedToWriteToDataFile {
+                path: self.path.clone(),
+                source: std::io::ErrorKind::WriteZero.into(),
+            });
+        }
+        write_buffer.clear();
+        Ok(())
+    }
+
+    /// 从offset处读取数据，包括写缓冲区中还没有写入文件的数据
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        if self.write_buffer_size == 0 {
+            return self.io_manager.read(buf, offset);
+        }
+        let write_buffer = self.write_buffer.lock();
+        let buffer_start = self.get_write_offset() - write_buffer.len() as u64;
+        // 缓冲区之前的数据已经写入文件，不会再改变，不需要持有缓冲区的锁
+        if write_buffer.is_empty() || offset + buf.len() as u64 <= buffer_start {
+            drop(write_buffer);
+            return self.io_manager.read(buf, offset);
+        }
+        let file_len = (buffer_start.saturating_sub(offset) as usize).min(buf.len());
+        if file_len > 0 {
+            let n_bytes = self.io_manager.read(&mut buf[..file_len], offset)?;
+            if n_bytes < file_len {
+                return Ok(n_bytes);
+            }
+        }
+        let start = (offset.max(buffer_start) - buffer_start) as usize;
+        if start >= write_buffer.len() {
+            return Ok(file_len);
+        }
+        let n_bytes = (buf.len() - file_len).min(write_buffer.len() - start);
+        buf[file_len..file_len + n_bytes].copy_from_slice(&write_buffer[start..start + n_bytes]);
+        Ok(file_len + n_bytes)
+    }
+
+    /// 批量读取数据，包括写缓冲区中还没有写入文件的数据
+    fn read_batch(&self, reqs: &mut [(u64, &mut [u8])]) -> Result<Vec<usize>> {
+        if self.write_buffer_size == 0 {
+            return self.io_manager.read_batch(reqs);
+        }
+        reqs.iter_mut()
+            .map(|(offset, buf)| self.read_at(buf, *offset))
+            .collect()
+    }
+
+    /// 重新读取并校验数据文件头
+    pub fn check_header(&self) -> Result<()> {
+        let mut buf = [0; DATA_FILE_HEADER_SIZE as usize];
+        if self.read_at(&mut buf, 0)? < buf.len() {
+            return Err(Error::InvalidDataFileHeader);
+        }
+        check_data_file_header(&buf).map(|_| ())
+    }
+
     /// 从offset处读取log record
     pub fn read_log_record(&self, offset: u64) -> Result<ReadLogRecord> {
         // log record 的结构
         // 1 byte for log record type
+        // var bytes for timestamp
+        // var bytes for expire at
         // var bytes for key length
         // var bytes for value length
         // key
         // value
         // 4 bytes for crc
 
-        // 读取header
+        // 不知道数据的大小，先按最大的header大小读取header
         let mut header_buf = BytesMut::zeroed(max_log_record_header_size());
-        self.io_manager.read(header_buf.as_mut(), offset)?;
-        // 解析header, 获取record type, key length, value length
-        let record_type = header_buf.get_u8();
-        let key_len = decode_length_delimiter(&mut header_buf).unwrap();
-        let value_len = decode_length_delimiter(&mut header_buf).unwrap();
-        // 如果key length和value length都为0, 则表示文件结束
-        if key_len == 0 && value_len == 0 {
-            return Err(Error::ReadDataFileEOF);
-        }
-        // 计算实际的header大小(编码后)
-        let actual_header_size =
-            length_delimiter_len(key_len) + length_delimiter_len(value_len) + 1;
+        self.read_at(header_buf.as_mut(), offset)?;
+        let header = decode_header(&header_buf)?;
         // 读取key, value
-        let mut kv_buf = BytesMut::zeroed(key_len + value_len + 4);
-        self.io_manager
-            .read(&mut kv_buf, offset + actual_header_size as u64)?;
-        // 构造log record
-        let log_record = LogRecord {
-            key: kv_buf.get(..key_len).unwrap().into(),
-            value: kv_buf.get(key_len..kv_buf.len() - 4).unwrap().into(),
-            record_type: record_type.into(),
-        };
-        // 读取crc
-        kv_buf.advance(key_len + value_len);
-        let crc = kv_buf.get_u32();
-        // 验证crc
-        if crc != log_record.get_crc() {
+        let mut kv_buf = BytesMut::zeroed(header.key_len + header.value_len + 4);
+        let n_bytes = self.read_at(&mut kv_buf, offset + header.size as u64)?;
+        // 数据超出文件末尾，说明写入不完整
+        if n_bytes < kv_buf.len() {
+            return Err(Error::InvalidLogRecord);
+        }
+        self.decode_log_record(&header, &header_buf[..header.size], &kv_buf)
+    }
+
+    /// 从offset处读取大小已知的log record，只需要一次IO，用于根据索引中的位置信息读取数据。
+    /// size为0表示大小未知，先读取header再读取key和value
+    pub fn read_log_record_with_size(&self, offset: u64, size: u32) -> Result<ReadLogRecord> {
+        if size == 0 {
+            return self.read_log_record(offset);
+        }
+        let mut buf = BytesMut::zeroed(size as usize);
+        let n_bytes = self.read_at(&mut buf, offset)?;
+        if n_bytes < buf.len() {
+            return Err(Error::InvalidLogRecord);
+        }
+        let header = decode_header(&buf)?;
+        // 位置信息中的大小与数据不符
+        if header.size + header.key_len + header.value_len + 4 != buf.len() {
+            return Err(Error::InvalidLogRecord);
+        }
+        let (header_buf, kv_buf) = buf.split_at(header.size);
+        self.decode_log_record(&header, header_buf, kv_buf)
+    }
+
+    /// 校验crc，解密、解压value，kv_buf为key + value + crc
+    fn decode_log_record(
+        &self,
+        header: &LogRecordHeader,
+        header_buf: &[u8],
+        kv_buf: &[u8],
+    ) -> Result<ReadLogRecord> {
+        let (record_type, compression, encrypted) = decode_record_type(header.record_type)?;
+        let (key_len, value_len) = (header.key_len, header.value_len);
+        // 验证crc，crc根据磁盘上的数据计算，value可能是压缩过的
+        let crc = (&kv_buf[key_len + value_len..]).get_u32();
+        if !self.verify_checksum(header_buf, &kv_buf[..key_len + value_len], crc) {
             return Err(Error::InvalidLogRecordCRC);
         }
+        // 构造log record
+        let mut value = kv_buf[key_len..key_len + value_len].to_vec();
+        if encrypted {
+            let cipher = self.cipher.as_ref().ok_or(Error::MissingEncryptionKey)?;
+            // header和key是加密时的附加数据
+            let mut aad = header_buf.to_vec();
+            aad.extend_from_slice(&kv_buf[..key_len]);
+            value = cipher.decrypt(&aad, &value)?;
+        }
         Ok(ReadLogRecord {
-            record: log_record,
-            size: actual_header_size + key_len + value_len + 4,
+            record: LogRecord {
+                key: kv_buf[..key_len].to_vec(),
+                value: decompress(compression, value, self.dictionaries.as_deref())?,
+                record_type,
+                timestamp: header.timestamp,
+                expire_at: header.expire_at,
+            },
+            size: header.size + key_len + value_len + 4,
         })
     }
+
+    /// 校验header和key、value的校验值，不校验时总是通过
+    fn verify_checksum(&self, header_buf: &[u8], kv_buf: &[u8], crc: u32) -> bool {
+        match self.checksum {
+            ChecksumType::Crc32 => {
+                let mut hasher = crc32fast::Hasher::new();
+                hasher.update(header_buf);
+                hasher.update(kv_buf);
+                crc == hasher.finalize()
+            }
+            ChecksumType::Xxh3 => {
+                let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+                hasher.update(header_buf);
+                hasher.update(kv_buf);
+                crc == hasher.digest() as u32
+            }
+            ChecksumType::None => true,
+        }
+    }
+
+    /// 从offset处开始顺序读取log record
+    pub fn reader(&self, offset: u64) -> LogRecordReader<'_> {
+        LogRecordReader {
+            data_file: self,
+            buf: Vec::new(),
+            buf_offset: offset,
+            offset,
+            eof: false,
+        }
+    }
+
+    /// 写入hint索引，key为实际的key，value为数据的位置信息
+    pub fn write_hint_record(&self, key: Vec<u8>, pos: LogRecordPos) -> Result<()> {
+        let hint_record = LogRecord {
+            key,
+            value: pos.encode(),
+            record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
+        };
+        self.write(&hint_record.encode())?;
+        Ok(())
+    }
+
     pub fn set_write_offset(&self, offset: u64) {
         *self.write_offset.write() = offset;
     }
 
+    /// 写缓冲区中的数据写入文件后再持久化
     pub fn sync(&self) -> Result<()> {
+        self.flush()?;
         self.io_manager.sync()
     }
+
+    /// 为数据文件预分配len字节的磁盘空间，不改变文件大小
+    pub fn preallocate(&self, len: u64) -> Result<()> {
+        self.io_manager.preallocate(len)
+    }
+
+    /// 提示内核之后如何读取数据文件
+    pub(crate) fn advise(&self, advice: Advice) {
+        self.io_manager.advise(advice);
+    }
+
+    /// 切换数据文件的IO类型
+    pub fn set_io_manager(&mut self, io_type: IOType) -> Result<()> {
+        self.io_manager = new_io_manager(&self.path, io_type)?;
+        Ok(())
+    }
+}
+
+impl Drop for DataFile {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            error!("failed to flush data file: {}", e);
+        }
+    }
+}
+
+/// 编码数据文件头，flags中记录校验算法，其余位保留为0
+pub(crate) fn encode_data_file_header(checksum: ChecksumType) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(DATA_FILE_HEADER_SIZE as usize);
+    buf.extend_from_slice(DATA_FILE_MAGIC);
+    buf.put_u16(DATA_FILE_VERSION);
+    buf.put_u16(checksum as u16);
+    buf
+}
+
+/// 校验数据文件头的魔数和版本，返回数据记录的校验算法
+pub(crate) fn check_data_file_header(mut buf: &[u8]) -> Result<ChecksumType> {
+    if &buf[..DATA_FILE_MAGIC.len()] != DATA_FILE_MAGIC {
+        return Err(Error::InvalidDataFileHeader);
+    }
+    buf.advance(DATA_FILE_MAGIC.len());
+    let version = buf.get_u16();
+    if version != DATA_FILE_VERSION {
+        return Err(Error::UnsupportedDataFileVersion(version));
+    }
+    // 旧的数据文件flags为0，使用crc32
+    match buf.get_u16() {
+        0 => Ok(ChecksumType::Crc32),
+        1 => Ok(ChecksumType::Xxh3),
+        2 => Ok(ChecksumType::None),
+        flags => Err(Error::UnsupportedChecksumType(flags)),
+    }
 }
 
-fn get_data_file_full_path(dir_path: impl AsRef<Path>, file_id: u32) -> PathBuf {
+pub(crate) fn get_data_file_full_path(dir_path: impl AsRef<Path>, file_id: u32) -> PathBuf {
     dir_path
         .as_ref()
         .join(format!("{:09}{}", file_id, DATA_FILE_SUFFIX))
 }
 
+/// 数据文件所在的目录，已存在的数据文件可能位于任意一个数据目录中，
+/// 新的数据文件按ID轮流放在各个数据目录中
+pub(crate) fn find_data_file_dir(data_dirs: &[PathBuf], file_id: u32) -> PathBuf {
+    data_dirs
+        .iter()
+        .find(|dir_path| get_data_file_full_path(dir_path, file_id).is_file())
+        .unwrap_or(&data_dirs[file_id as usize % data_dirs.len()])
+        .clone()
+}
+
+/// 查找所有数据目录中的数据文件，返回按ID从小到大排序的文件ID及其所在的目录
+pub(crate) fn find_data_files(data_dirs: &[PathBuf]) -> Result<Vec<(u32, PathBuf)>> {
+    let mut data_files = BTreeMap::new();
+    for dir_path in data_dirs {
+        for entry in read_dir(dir_path)? {
+            let file_os_name = entry.file_name();
+            let Some(id) = file_os_name
+                .to_str()
+                .and_then(|file_name| file_name.strip_suffix(DATA_FILE_SUFFIX))
+            else {
+                continue;
+            };
+            let id = id.parse::<u32>().map_err(|_| Error::FailedToParseFileId)?;
+            // 同一个数据文件不能出现在多个目录中
+            if data_files.insert(id, dir_path.clone()).is_some() {
+                return Err(Error::DuplicateDataFile(id));
+            }
+        }
+    }
+    Ok(data_files.into_iter().collect())
+}
+
+/// 顺序读取数据文件中的log record，每次批量读取多块数据后在内存中解码，
+/// 用于启动时加载索引和merge，减少IO次数
+pub struct LogRecordReader<'a> {
+    data_file: &'a DataFile,
+    /// 预读的数据
+    buf: Vec<u8>,
+    /// buf在文件中的起始位置
+    buf_offset: u64,
+    /// 下一条数据的位置
+    offset: u64,
+    /// 预读时是否已经读取到了文件末尾
+    eof: bool,
+}
+
+impl LogRecordReader<'_> {
+    /// 下一条数据的位置，读取出错时不会前进
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// 读取下一条log record，读取到文件末尾时返回ReadDataFileEOF
+    pub fn next_record(&mut self) -> Result<ReadLogRecord> {
+        // 文件末尾不足最大header大小时补0，与read_log_record一致
+        let mut header_buf = vec![0; max_log_record_header_size()];
+        let available = self.fill(header_buf.len())?;
+        let start = (self.offset - self.buf_offset) as usize;
+        header_buf[..available].copy_from_slice(&self.buf[start..start + available]);
+        let header = decode_header(&header_buf)?;
+
+        let record_size = header.size + header.key_len + header.value_len + 4;
+        // 数据超出文件末尾，说明写入不完整
+        if self.fill(record_size)? < record_size {
+            return Err(Error::InvalidLogRecord);
+        }
+        let start = (self.offset - self.buf_offset) as usize;
+        let (header_buf, kv_buf) = self.buf[start..start + record_size].split_at(header.size);
+        let read_log_record = self
+            .data_file
+            .decode_log_record(&header, header_buf, kv_buf)?;
+        self.offset += record_size as u64;
+        Ok(read_log_record)
+    }
+
+    /// 保证预读的数据中从offset开始至少有len个字节，返回实际可用的字节数（不超过len），
+    /// 只有到达文件末尾时才会小于len
+    fn fill(&mut self, len: usize) -> Result<usize> {
+        let start = (self.offset - self.buf_offset) as usize;
+        let available = self.buf.len() - start;
+        if available >= len || self.eof {
+            return Ok(available.min(len));
+        }
+        // 从offset开始重新预读
+        let mut buf = vec![0; len.max(READ_AHEAD_CHUNK_SIZE * READ_AHEAD_CHUNKS)];
+        let mut reqs = buf
+            .chunks_mut(READ_AHEAD_CHUNK_SIZE)
+            .enumerate()
+            .map(|(i, chunk)| (self.offset + (i * READ_AHEAD_CHUNK_SIZE) as u64, chunk))
+            .collect::<Vec<_>>();
+        let n_bytes = self.data_file.read_batch(&mut reqs)?;
+        // 第一个没有读满的块之后的数据都无效
+        let mut total = 0;
+        for (n, (_, chunk)) in n_bytes.iter().zip(reqs.iter()) {
+            total += n;
+            if *n < chunk.len() {
+                self.eof = true;
+                break;
+            }
+        }
+        buf.truncate(total);
+        self.buf = buf;
+        self.buf_offset = self.offset;
+        Ok(total.min(len))
+    }
+}
+
+/// 数据文件中一条记录的概要信息，用于排查数据文件的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecordSummary {
+    /// 记录类型，无法识别时为None
+    pub record_type: Option<LogRecordType>,
+    /// 事务编号，0表示非事务写入的数据，无法解码时为None
+    pub seq_num: Option<usize>,
+    /// 去掉事务编号后的key
+    pub key: Vec<u8>,
+    pub timestamp: u64,
+    pub expire_at: u64,
+    /// value在磁盘上的大小，可能经过压缩和加密
+    pub value_size: usize,
+    /// 记录在磁盘上占据的大小
+    pub size: usize,
+    /// crc校验是否通过
+    pub crc_valid: bool,
+}
+
+/// 逐条解码数据文件中的记录，返回每条记录的位置和概要信息，不需要打开数据库
+///
+/// crc校验失败的记录仍会返回并继续读取，header无法解码或者记录不完整时返回错误并停止
+pub fn dump(path: impl AsRef<Path>) -> impl Iterator<Item = Result<(u64, LogRecordSummary)>> {
+    let mut state = Some(open_for_dump(path.as_ref()).map(|f| (f, DATA_FILE_HEADER_SIZE)));
+    std::iter::from_fn(move || {
+        let (data_file, offset) = match state.as_mut()? {
+            Ok(state) => state,
+            // 打开文件失败时只返回一次错误
+            Err(_) => return state.take().and_then(|state| state.err()).map(Err),
+        };
+        match data_file.read_summary(*offset) {
+            Ok(Some((summary, size))) => {
+                let item = (*offset, summary);
+                *offset += size as u64;
+                Some(Ok(item))
+            }
+            Ok(None) => {
+                state = None;
+                None
+            }
+            Err(e) => {
+                state = None;
+                Some(Err(e))
+            }
+        }
+    })
+}
+
+/// 打开已存在的数据文件，文件名需要是数据文件ID
+fn open_for_dump(path: &Path) -> Result<DataFile> {
+    if !path.is_file() {
+        return Err(Error::DataFileNotFound);
+    }
+    let file_id = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(DATA_FILE_SUFFIX))
+        .and_then(|id| id.parse::<u32>().ok())
+        .ok_or(Error::FailedToParseFileId)?;
+    DataFile::new(path.parent().unwrap(), file_id, IOType::StandardFIO)
+}
+
+impl DataFile {
+    /// 读取offset处记录的概要信息和记录大小，到达文件末尾时返回None
+    fn read_summary(&self, offset: u64) -> Result<Option<(LogRecordSummary, usize)>> {
+        let mut header_buf = BytesMut::zeroed(max_log_record_header_size());
+        if self.read_at(header_buf.as_mut(), offset)? == 0 {
+            return Ok(None);
+        }
+        let header = match decode_header(&header_buf) {
+            Ok(header) => header,
+            Err(Error::ReadDataFileEOF) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut kv_buf = BytesMut::zeroed(header.key_len + header.value_len + 4);
+        if self.read_at(&mut kv_buf, offset + header.size as u64)? < kv_buf.len() {
+            return Err(Error::InvalidLogRecord);
+        }
+        // crc根据磁盘上的数据计算，不需要解密和解压value
+        let (kv, crc_buf) = kv_buf.split_at(header.key_len + header.value_len);
+        let crc_valid =
+            self.verify_checksum(&header_buf[..header.size], kv, (&crc_buf[..]).get_u32());
+        let mut key = &kv[..header.key_len];
+        let seq_num = decode_length_delimiter(&mut key).ok();
+        let size = header.size + kv_buf.len();
+        let summary = LogRecordSummary {
+            record_type: decode_record_type(header.record_type)
+                .ok()
+                .map(|(record_type, _, _)| record_type),
+            seq_num,
+            key: key.to_vec(),
+            timestamp: header.timestamp,
+            expire_at: header.expire_at,
+            value_size: header.value_len,
+            size,
+            crc_valid,
+        };
+        Ok(Some((summary, size)))
+    }
+}
+
+/// 判断buf开头是否为一条完整且crc正确的记录，是则返回记录的大小，
+/// 不校验时只能判断header是否可以解码
+pub(crate) fn check_record(buf: &[u8], checksum: ChecksumType) -> Option<usize> {
+    // 文件末尾不足最大header大小时补0
+    let mut header_buf = vec![0; max_log_record_header_size()];
+    let len = header_buf.len().min(buf.len());
+    header_buf[..len].copy_from_slice(&buf[..len]);
+    let header = decode_header(&header_buf).ok()?;
+    decode_record_type(header.record_type).ok()?;
+    let size = header.size + header.key_len + header.value_len + 4;
+    if buf.len() < size {
+        return None;
+    }
+    let crc = (&buf[size - 4..size]).get_u32();
+    let valid =
+        checksum == ChecksumType::None || crc == compute_checksum(checksum, &buf[..size - 4]);
+    valid.then_some(size)
+}
+
+/// 解码后的log record header
+struct LogRecordHeader {
+    record_type: u8,
+    timestamp: u64,
+    expire_at: u64,
+    key_len: usize,
+    value_len: usize,
+    /// header编码后的大小
+    size: usize,
+}
+
+/// 解析header, 获取record type, timestamp, expire at, key length, value length
+fn decode_header(buf: &[u8]) -> Result<LogRecordHeader> {
+    let mut header = buf;
+    if !header.has_remaining() {
+        return Err(Error::InvalidLogRecord);
+    }
+    let record_type = header.get_u8();
+    let timestamp = decode_varint(&mut header).map_err(|_| Error::InvalidLogRecord)?;
+    let expire_at = decode_varint(&mut header).map_err(|_| Error::InvalidLogRecord)?;
+    let key_len = decode_length_delimiter(&mut header).map_err(|_| Error::InvalidLogRecord)?;
+    let value_len = decode_length_delimiter(&mut header).map_err(|_| Error::InvalidLogRecord)?;
+    // 如果key length和value length都为0, 则表示文件结束
+    if key_len == 0 && value_len == 0 {
+        return Err(Error::ReadDataFileEOF);
+    }
+    Ok(LogRecordHeader {
+        record_type,
+        timestamp,
+        expire_at,
+        key_len,
+        value_len,
+        // 计算实际的header大小(编码后)
+        size: buf.len() - header.len(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::data::log_record::LogRecordType;
+    use crate::data::log_record::decode_log_record_pos;
+    use crate::options::CompressionType;
 
     use super::*;
 
@@ -122,44 +751,44 @@ mod tests {
     fn test_new_data_file() {
         let dir_path = std::env::temp_dir();
         // println!("dir_path: {}", dir_path.display());
-        let data_file = DataFile::new(&dir_path, 0).unwrap();
+        let data_file = DataFile::new(&dir_path, 0, IOType::StandardFIO).unwrap();
         assert_eq!(data_file.get_file_id(), 0);
-        assert_eq!(data_file.get_write_offset(), 0);
+        assert_eq!(data_file.get_write_offset(), DATA_FILE_HEADER_SIZE);
 
-        let data_file = DataFile::new(&dir_path, 0).unwrap();
+        let data_file = DataFile::new(&dir_path, 0, IOType::StandardFIO).unwrap();
         assert_eq!(data_file.get_file_id(), 0);
-        assert_eq!(data_file.get_write_offset(), 0);
+        assert_eq!(data_file.get_write_offset(), DATA_FILE_HEADER_SIZE);
 
-        let data_file = DataFile::new(&dir_path, 1).unwrap();
+        let data_file = DataFile::new(&dir_path, 1, IOType::StandardFIO).unwrap();
         assert_eq!(data_file.get_file_id(), 1);
-        assert_eq!(data_file.get_write_offset(), 0);
+        assert_eq!(data_file.get_write_offset(), DATA_FILE_HEADER_SIZE);
     }
 
     #[test]
     fn test_data_file_write() {
         let dir_path = std::env::temp_dir();
-        let data_file = DataFile::new(&dir_path, 0).unwrap();
+        let data_file = DataFile::new(&dir_path, 0, IOType::StandardFIO).unwrap();
         let n_bytes = data_file.write(b"hello").unwrap();
         assert_eq!(n_bytes, 5);
-        assert_eq!(data_file.get_write_offset(), 5);
+        assert_eq!(data_file.get_write_offset(), DATA_FILE_HEADER_SIZE + 5);
 
         let n_bytes = data_file.write(b"world").unwrap();
         assert_eq!(n_bytes, 5);
-        assert_eq!(data_file.get_write_offset(), 10);
+        assert_eq!(data_file.get_write_offset(), DATA_FILE_HEADER_SIZE + 10);
 
         let n_bytes = data_file.write(b"111").unwrap();
         assert_eq!(n_bytes, 3);
-        assert_eq!(data_file.get_write_offset(), 13);
+        assert_eq!(data_file.get_write_offset(), DATA_FILE_HEADER_SIZE + 13);
 
         let n_bytes = data_file.write(b"").unwrap();
         assert_eq!(n_bytes, 0);
-        assert_eq!(data_file.get_write_offset(), 13);
+        assert_eq!(data_file.get_write_offset(), DATA_FILE_HEADER_SIZE + 13);
     }
 
     #[test]
     fn test_data_file_sync() {
         let dir_path = std::env::temp_dir();
-        let data_file = DataFile::new(&dir_path, 3).unwrap();
+        let data_file = DataFile::new(&dir_path, 3, IOType::StandardFIO).unwrap();
         data_file.write(b"222").unwrap();
         assert!(data_file.sync().is_ok());
     }
@@ -168,21 +797,23 @@ mod tests {
     fn test_data_file_read_log_record() {
         let dir_path = std::env::temp_dir();
         println!("dir_path: {}", dir_path.display());
-        let data_file = DataFile::new(&dir_path, 4).unwrap();
+        let data_file = DataFile::new(&dir_path, 4, IOType::StandardFIO).unwrap();
         assert_eq!(data_file.get_file_id(), 4);
 
         let log_record = LogRecord {
             key: b"name".to_vec(),
             value: b"bitcask-rs-kv".to_vec(),
             record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
         };
         let write_res = data_file.write(&log_record.encode());
         assert!(write_res.is_ok());
-        // 24
+        // 25
         // println!("first write_res: {}", write_res.unwrap());
 
         // 从起始位置读取
-        let read_res = data_file.read_log_record(0);
+        let read_res = data_file.read_log_record(DATA_FILE_HEADER_SIZE);
         assert!(read_res.is_ok());
         let read_res = read_res.unwrap().record;
         assert_eq!(log_record, read_res);
@@ -192,12 +823,14 @@ mod tests {
             key: b"name".to_vec(),
             value: b"new-value".to_vec(),
             record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
         };
         let write_res = data_file.write(&log_record.encode());
         assert!(write_res.is_ok());
-        // 20
+        // 21
         // println!("second write_res: {}", write_res.unwrap());
-        let read_res = data_file.read_log_record(24);
+        let read_res = data_file.read_log_record(DATA_FILE_HEADER_SIZE + 26);
         assert!(read_res.is_ok());
         let read_res = read_res.unwrap().record;
         assert_eq!(log_record, read_res);
@@ -207,14 +840,405 @@ mod tests {
             key: b"name".to_vec(),
             value: Default::default(),
             record_type: LogRecordType::DELETE,
+            timestamp: 0,
+            expire_at: 0,
         };
         let write_res = data_file.write(&log_record.encode());
         assert!(write_res.is_ok());
-        // 11
+        // 12
         // println!("delete write_res: {}", write_res.unwrap());
-        let read_res = data_file.read_log_record(44);
+        let read_res = data_file.read_log_record(DATA_FILE_HEADER_SIZE + 48);
         assert!(read_res.is_ok());
         let read_res = read_res.unwrap().record;
         assert_eq!(log_record, read_res);
+
+        std::fs::remove_file(dir_path.join("000000004.data")).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_read_log_record_with_size() {
+        let dir_path = std::env::temp_dir().join("bitcask-rs-read-with-size");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let data_file = DataFile::new(&dir_path, 0, IOType::StandardFIO).unwrap();
+
+        let log_record = LogRecord {
+            key: b"name".to_vec(),
+            value: b"bitcask-rs-kv".to_vec(),
+            record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
+        };
+        let size = data_file.write(&log_record.encode()).unwrap() as u32;
+        data_file.write(&log_record.encode()).unwrap();
+
+        // 与不知道大小时读取的结果相同
+        let read_res = data_file
+            .read_log_record_with_size(DATA_FILE_HEADER_SIZE, size)
+            .unwrap();
+        assert_eq!(read_res.record, log_record);
+        assert_eq!(read_res.size, size as usize);
+        let read_res = data_file
+            .read_log_record_with_size(DATA_FILE_HEADER_SIZE + size as u64, size)
+            .unwrap();
+        assert_eq!(read_res.record, log_record);
+
+        // 大小未知时读取两次
+        let read_res = data_file
+            .read_log_record_with_size(DATA_FILE_HEADER_SIZE, 0)
+            .unwrap();
+        assert_eq!(read_res.record, log_record);
+        assert_eq!(read_res.size, size as usize);
+
+        // 大小与数据不符
+        assert!(matches!(
+            data_file
+                .read_log_record_with_size(DATA_FILE_HEADER_SIZE, size - 1)
+                .err()
+                .unwrap(),
+            Error::InvalidLogRecord
+        ));
+        assert!(matches!(
+            data_file
+                .read_log_record_with_size(DATA_FILE_HEADER_SIZE + size as u64, size + 1)
+                .err()
+                .unwrap(),
+            Error::InvalidLogRecord
+        ));
+
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_reader() {
+        let dir_path = std::env::temp_dir().join("bitcask-rs-data-file-reader");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let data_file = DataFile::new(&dir_path, 0, IOType::StandardFIO).unwrap();
+
+        // 数据跨越多次预读，最后一条数据大于一次预读的大小
+        let mut records = (0..20000)
+            .map(|i| LogRecord {
+                key: format!("key-{}", i).into_bytes(),
+                value: format!("value-{}", i).into_bytes(),
+                record_type: LogRecordType::NORMAL,
+                timestamp: i,
+                expire_at: 0,
+            })
+            .collect::<Vec<_>>();
+        records.push(LogRecord {
+            key: b"large".to_vec(),
+            value: vec![1; READ_AHEAD_CHUNK_SIZE * READ_AHEAD_CHUNKS * 2],
+            record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
+        });
+        for record in records.iter() {
+            data_file.write(&record.encode()).unwrap();
+        }
+
+        let mut reader = data_file.reader(DATA_FILE_HEADER_SIZE);
+        for record in records.iter() {
+            let offset = reader.offset();
+            let read_res = reader.next_record().unwrap();
+            assert_eq!(&read_res.record, record);
+            assert_eq!(reader.offset(), offset + read_res.size as u64);
+        }
+        assert!(matches!(
+            reader.next_record().err().unwrap(),
+            Error::ReadDataFileEOF
+        ));
+        assert_eq!(reader.offset(), data_file.get_write_offset());
+
+        // 末尾的数据不完整
+        data_file.write(&records[0].encode()[..10]).unwrap();
+        let mut reader = data_file.reader(data_file.get_write_offset() - 10);
+        assert!(matches!(
+            reader.next_record().err().unwrap(),
+            Error::InvalidLogRecord
+        ));
+
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_zero_filled_tail() {
+        let dir_path = std::env::temp_dir().join("bitcask-rs-data-file-zero-tail");
+        let _ = std::fs::remove_dir_all(&dir_path);
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let data_file = DataFile::new(&dir_path, 0, IOType::StandardFIO).unwrap();
+        let record = LogRecord {
+            key: b"key".to_vec(),
+            value: b"value".to_vec(),
+            record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
+        };
+        data_file.write(&record.encode()).unwrap();
+        let end = data_file.get_write_offset();
+        // 预分配或者崩溃后文件末尾可能是填充的0，读取到0时视为文件末尾
+        data_file.write(&[0; 4096]).unwrap();
+
+        let mut reader = data_file.reader(DATA_FILE_HEADER_SIZE);
+        assert_eq!(reader.next_record().unwrap().record, record);
+        assert!(matches!(
+            reader.next_record().err().unwrap(),
+            Error::ReadDataFileEOF
+        ));
+        assert_eq!(reader.offset(), end);
+        assert!(matches!(
+            data_file.read_log_record(end).err().unwrap(),
+            Error::ReadDataFileEOF
+        ));
+
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_write_buffer() {
+        let dir_path = std::env::temp_dir().join("bitcask-rs-write-buffer");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let data_file = DataFile::new(&dir_path, 0, IOType::StandardFIO)
+            .unwrap()
+            .with_write_buffer(64);
+        let file_path = get_data_file_full_path(&dir_path, 0);
+
+        let log_record = LogRecord {
+            key: b"name".to_vec(),
+            value: b"bitcask-rs-kv".to_vec(),
+            record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
+        };
+        let encoded = log_record.encode();
+        let size = data_file.write(&encoded).unwrap() as u64;
+        data_file.write(&encoded).unwrap();
+        // 数据还在缓冲区中，可以读取
+        assert_eq!(
+            std::fs::metadata(&file_path).unwrap().len(),
+            DATA_FILE_HEADER_SIZE
+        );
+        assert_eq!(
+            data_file.get_write_offset(),
+            DATA_FILE_HEADER_SIZE + size * 2
+        );
+        let read_res = data_file.read_log_record(DATA_FILE_HEADER_SIZE + size);
+        assert_eq!(read_res.unwrap().record, log_record);
+
+        // 缓冲区满了时写入文件，读取的数据跨越文件和缓冲区
+        data_file.write(&encoded).unwrap();
+        assert_eq!(
+            std::fs::metadata(&file_path).unwrap().len(),
+            DATA_FILE_HEADER_SIZE + size * 2
+        );
+        let mut reader = data_file.reader(DATA_FILE_HEADER_SIZE);
+        for _ in 0..3 {
+            assert_eq!(reader.next_record().unwrap().record, log_record);
+        }
+        assert!(matches!(
+            reader.next_record().err().unwrap(),
+            Error::ReadDataFileEOF
+        ));
+
+        // sync时写入文件
+        data_file.sync().unwrap();
+        assert_eq!(
+            std::fs::metadata(&file_path).unwrap().len(),
+            data_file.get_write_offset()
+        );
+
+        // 关闭时写入文件
+        data_file.write(&encoded).unwrap();
+        std::mem::drop(data_file);
+        assert_eq!(
+            std::fs::metadata(&file_path).unwrap().len(),
+            DATA_FILE_HEADER_SIZE + size * 4
+        );
+
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_header() {
+        let dir_path = std::env::temp_dir().join("bitcask-rs-data-file-header");
+        std::fs::create_dir_all(&dir_path).unwrap();
+
+        // 新文件写入文件头，重新打开时校验通过
+        DataFile::new(&dir_path, 0, IOType::StandardFIO).unwrap();
+        let file_path = get_data_file_full_path(&dir_path, 0);
+        assert_eq!(
+            std::fs::read(&file_path).unwrap(),
+            encode_data_file_header(ChecksumType::Crc32)
+        );
+        let data_file = DataFile::new(&dir_path, 0, IOType::MemoryMap).unwrap();
+        assert_eq!(data_file.get_write_offset(), DATA_FILE_HEADER_SIZE);
+
+        // 不是数据文件
+        std::fs::write(get_data_file_full_path(&dir_path, 1), b"not a data file").unwrap();
+        assert!(matches!(
+            DataFile::new(&dir_path, 1, IOType::StandardFIO)
+                .err()
+                .unwrap(),
+            Error::InvalidDataFileHeader
+        ));
+        std::fs::write(get_data_file_full_path(&dir_path, 2), b"BCRS").unwrap();
+        assert!(matches!(
+            DataFile::new(&dir_path, 2, IOType::StandardFIO)
+                .err()
+                .unwrap(),
+            Error::InvalidDataFileHeader
+        ));
+
+        // 未知的版本
+        std::fs::write(
+            get_data_file_full_path(&dir_path, 3),
+            [b'B', b'C', b'R', b'S', 0, 2, 0, 0],
+        )
+        .unwrap();
+        assert!(matches!(
+            DataFile::new(&dir_path, 3, IOType::StandardFIO)
+                .err()
+                .unwrap(),
+            Error::UnsupportedDataFileVersion(2)
+        ));
+        // 未知的校验算法
+        std::fs::write(
+            get_data_file_full_path(&dir_path, 4),
+            [b'B', b'C', b'R', b'S', 0, 1, 0, 3],
+        )
+        .unwrap();
+        assert!(matches!(
+            DataFile::new(&dir_path, 4, IOType::StandardFIO)
+                .err()
+                .unwrap(),
+            Error::UnsupportedChecksumType(3)
+        ));
+
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_checksum() {
+        let dir_path = std::env::temp_dir().join("bitcask-rs-data-file-checksum");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let log_record = LogRecord {
+            key: b"hello".to_vec(),
+            value: b"world".to_vec(),
+            record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
+        };
+
+        for (file_id, checksum) in [ChecksumType::Xxh3, ChecksumType::None]
+            .into_iter()
+            .enumerate()
+        {
+            let file_id = file_id as u32;
+            let data_file =
+                DataFile::new_with_checksum(&dir_path, file_id, IOType::StandardFIO, checksum)
+                    .unwrap();
+            let encoded = log_record
+                .encode_with(CompressionType::None, None, checksum, None)
+                .unwrap();
+            assert_eq!(encoded.len(), log_record.encode().len());
+            data_file.write(&encoded).unwrap();
+            // 损坏value
+            let mut corrupted = encoded.clone();
+            corrupted[encoded.len() - 5] ^= 0xff;
+            data_file.write(&corrupted).unwrap();
+            assert_eq!(check_record(&encoded, checksum), Some(encoded.len()));
+
+            // 已存在的文件使用文件头中记录的校验算法
+            let data_file = DataFile::new(&dir_path, file_id, IOType::StandardFIO).unwrap();
+            assert_eq!(data_file.checksum(), checksum);
+            let read_log_record = data_file.read_log_record(DATA_FILE_HEADER_SIZE).unwrap();
+            assert_eq!(read_log_record.record, log_record);
+            let res = data_file.read_log_record(DATA_FILE_HEADER_SIZE + encoded.len() as u64);
+            match checksum {
+                ChecksumType::None => assert!(res.is_ok()),
+                _ => assert!(matches!(res, Err(Error::InvalidLogRecordCRC))),
+            }
+        }
+        // crc32编码的数据无法通过xxh3校验
+        assert_eq!(check_record(&log_record.encode(), ChecksumType::Xxh3), None);
+
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_write_hint_record() {
+        let dir_path = std::env::temp_dir().join("bitcask-rs-hint-file");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let hint_file = DataFile::new_hint_file(&dir_path).unwrap();
+
+        let pos = LogRecordPos {
+            file_id: 10,
+            offset: 1024,
+            size: 10,
+        };
+        hint_file.write_hint_record(b"name".to_vec(), pos).unwrap();
+        let read_res = hint_file.read_log_record(0).unwrap().record;
+        assert_eq!(read_res.key, b"name".to_vec());
+        assert_eq!(decode_log_record_pos(read_res.value).unwrap(), pos);
+
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_dump() {
+        let dir_path = std::path::PathBuf::from("/tmp/bitcask-rs-dump");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let data_file = DataFile::new(&dir_path, 3, IOType::StandardFIO).unwrap();
+        let mut offsets = vec![];
+        for (i, record_type) in [LogRecordType::NORMAL, LogRecordType::DELETE]
+            .into_iter()
+            .enumerate()
+        {
+            offsets.push(data_file.get_write_offset());
+            let log_record = LogRecord {
+                key: crate::batch::log_record_key_with_seq_num(format!("key-{}", i).as_bytes(), i),
+                value: b"value".to_vec(),
+                record_type,
+                timestamp: 100,
+                expire_at: 0,
+            };
+            data_file.write(&log_record.encode()).unwrap();
+        }
+        data_file.sync().unwrap();
+        let path = get_data_file_full_path(&dir_path, 3);
+
+        let summaries = dump(&path).collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].0, offsets[0]);
+        assert_eq!(summaries[1].0, offsets[1]);
+        let (_, summary) = &summaries[1];
+        assert_eq!(summary.record_type, Some(LogRecordType::DELETE));
+        assert_eq!(summary.seq_num, Some(1));
+        assert_eq!(summary.key, b"key-1");
+        assert_eq!(summary.timestamp, 100);
+        assert_eq!(summary.value_size, 5);
+        assert_eq!(
+            summary.size as u64,
+            data_file.get_write_offset() - offsets[1]
+        );
+        assert!(summary.crc_valid);
+
+        // crc校验失败的记录继续读取
+        let mut buf = std::fs::read(&path).unwrap();
+        buf[offsets[1] as usize - 1] ^= 0xff;
+        std::fs::write(&path, &buf).unwrap();
+        let summaries = dump(&path).collect::<Result<Vec<_>>>().unwrap();
+        assert!(!summaries[0].1.crc_valid);
+        assert!(summaries[1].1.crc_valid);
+
+        // 记录不完整时返回错误
+        std::fs::write(&path, &buf[..buf.len() - 1]).unwrap();
+        let res = dump(&path).collect::<Vec<_>>();
+        assert_eq!(res.len(), 2);
+        assert!(matches!(res[1], Err(Error::InvalidLogRecord)));
+
+        assert!(matches!(
+            dump(dir_path.join("000000004.data")).next(),
+            Some(Err(Error::DataFileNotFound))
+        ));
+        std::fs::remove_dir_all(dir_path).unwrap();
     }
 }
@@ -1,11 +1,73 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use bytes::{BufMut, BytesMut};
-use prost::{encode_length_delimiter, length_delimiter_len};
+use log::{error, warn};
+use prost::encoding::{encode_varint, encoded_len_varint};
+use prost::{decode_length_delimiter, encode_length_delimiter, length_delimiter_len};
+
+use crate::data::cipher::Cipher;
+use crate::dictionary::Dictionaries;
+use crate::error::{Error, Result};
+use crate::options::{ChecksumType, CompressionType};
+
+/// record_type中表示value已加密的标志位
+const ENCRYPTED_FLAG: u8 = 0x80;
 
 /// 数据位置索引信息，描述数据存储到了哪个位置
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct LogRecordPos {
     pub(crate) file_id: u32,
     pub(crate) offset: u64,
+    /// 数据在磁盘上占据的大小
+    pub(crate) size: u32,
+}
+
+impl LogRecordPos {
+    /// 编码位置信息，写入hint文件
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        encode_length_delimiter(self.file_id as usize, &mut buf).unwrap();
+        encode_length_delimiter(self.offset as usize, &mut buf).unwrap();
+        encode_length_delimiter(self.size as usize, &mut buf).unwrap();
+        buf.to_vec()
+    }
+}
+
+/// 解码hint文件中的位置信息
+pub fn decode_log_record_pos(pos: Vec<u8>) -> Result<LogRecordPos> {
+    let mut buf = BytesMut::from(pos.as_slice());
+    let file_id = decode_length_delimiter(&mut buf).map_err(|_| Error::InvalidLogRecord)?;
+    let offset = decode_length_delimiter(&mut buf).map_err(|_| Error::InvalidLogRecord)?;
+    // 旧版本的hint文件没有记录数据的大小，0表示大小未知
+    let size = decode_length_delimiter(&mut buf).unwrap_or(0);
+    Ok(LogRecordPos {
+        file_id: file_id as u32,
+        offset: offset as u64,
+        size: size as u32,
+    })
+}
+
+/// 编码分块存储的记录中各个分块的位置信息
+pub(crate) fn encode_chunk_positions(positions: &[LogRecordPos]) -> Vec<u8> {
+    positions.iter().flat_map(|pos| pos.encode()).collect()
+}
+
+/// 解码分块存储的记录中各个分块的位置信息
+pub(crate) fn decode_chunk_positions(value: &[u8]) -> Result<Vec<LogRecordPos>> {
+    let mut buf = value;
+    let mut positions = Vec::new();
+    while !buf.is_empty() {
+        let mut decode = || decode_length_delimiter(&mut buf).map_err(|_| Error::InvalidLogRecord);
+        let file_id = decode()?;
+        let offset = decode()?;
+        let size = decode()?;
+        positions.push(LogRecordPos {
+            file_id: file_id as u32,
+            offset: offset as u64,
+            size: size as u32,
+        });
+    }
+    Ok(positions)
 }
 
 /// log record 结构, 实际写入到数据文件的结构
@@ -14,45 +76,109 @@ pub struct LogRecord {
     pub(crate) key: Vec<u8>,
     pub(crate) value: Vec<u8>,
     pub(crate) record_type: LogRecordType,
+    /// 写入时间，unix毫秒时间戳
+    pub(crate) timestamp: u64,
+    /// 过期时间，unix毫秒时间戳，0表示永不过期
+    pub(crate) expire_at: u64,
 }
 
 impl LogRecord {
     /// 编码log record
+    /// ```text
+    ///  +---------------------------------------------------------------------------------+
+    ///  | record_type | timestamp  | expire_at  | key_len  | value_len | key | value | crc |
+    ///  +---------------------------------------------------------------------------------+
+    ///  | 1B          |var(max:10) |var(max:10) |var(max:5)| var(max:5)| var | var   | 4B  |
+    ///  +---------------------------------------------------------------------------------+
     /// ```
-    ///  +--------------------------------------------------------+
-    ///  | record_type | key_len  | value_len | key | value | crc |
-    ///  +--------------------------------------------------------+
-    ///  | 1B          |var(max:5)| var(max:5)| var | var   | 4B  |
-    ///  +--------------------------------------------------------+
-    /// ```
+    /// record_type的低4位为记录类型，4~6位为value使用的压缩算法，最高位表示value是否加密
     pub fn encode(&self) -> Vec<u8> {
-        let (encoded_buf, _) = self.encode_and_get_crc();
+        let (encoded_buf, _) = self.encode_and_get_crc(0, &self.value, ChecksumType::Crc32);
         encoded_buf
     }
 
+    /// 压缩、加密value后编码，压缩后没有变小时不压缩，checksum为写入的数据文件使用的校验算法，
+    /// dictionaries为使用字典压缩时的zstd字典
+    pub(crate) fn encode_with(
+        &self,
+        compression: CompressionType,
+        cipher: Option<&Cipher>,
+        checksum: ChecksumType,
+        dictionaries: Option<&Dictionaries>,
+    ) -> Result<Vec<u8>> {
+        if !matches!(
+            self.record_type,
+            LogRecordType::NORMAL | LogRecordType::CHUNK | LogRecordType::OPERAND
+        ) || self.value.is_empty()
+        {
+            return Ok(self.encode_and_get_crc(0, &self.value, checksum).0);
+        }
+        // 没有训练字典或者value较大时使用zstd
+        let compression = match (compression, dictionaries) {
+            (CompressionType::ZstdDict, Some(d)) if d.can_compress(self.value.len()) => compression,
+            (CompressionType::ZstdDict, _) => CompressionType::Zstd,
+            _ => compression,
+        };
+        let (compression, value) = match compression {
+            CompressionType::None => (CompressionType::None, None),
+            compression => match compress(compression, &self.value, dictionaries) {
+                Some(value) if value.len() < self.value.len() => (compression, Some(value)),
+                _ => (CompressionType::None, None),
+            },
+        };
+        let value = value.as_deref().unwrap_or(&self.value);
+        let flags = (compression as u8) << 4;
+        let cipher = match cipher {
+            Some(cipher) => cipher,
+            None => return Ok(self.encode_and_get_crc(flags, value, checksum).0),
+        };
+
+        // header和key作为附加数据参与认证
+        let flags = flags | ENCRYPTED_FLAG;
+        let mut buf = self.encode_header(flags, Cipher::encrypted_len(value.len()));
+        buf.put_slice(&self.key);
+        let value = cipher.encrypt(&buf, value)?;
+        buf.put_slice(&value);
+        buf.put_u32(compute_checksum(checksum, &buf));
+        Ok(buf.into())
+    }
+
+    #[cfg(test)]
     pub fn get_crc(&self) -> u32 {
-        let (_, crc) = self.encode_and_get_crc();
+        let (_, crc) = self.encode_and_get_crc(0, &self.value, ChecksumType::Crc32);
         crc
     }
 
-    fn encode_and_get_crc(&self) -> (Vec<u8>, u32) {
+    /// 编码header，flags为record_type的高4位
+    fn encode_header(&self, flags: u8, value_len: usize) -> BytesMut {
         let mut buf = BytesMut::with_capacity(self.encoded_length());
-
         // 写入record_type
-        buf.put_u8(self.record_type as u8);
+        buf.put_u8(self.record_type as u8 | flags);
+        // 写入时间戳
+        encode_varint(self.timestamp, &mut buf);
+        // 写入过期时间
+        encode_varint(self.expire_at, &mut buf);
         // 写入key长度
         encode_length_delimiter(self.key.len(), &mut buf).unwrap();
         // 写入value长度
-        encode_length_delimiter(self.value.len(), &mut buf).unwrap();
+        encode_length_delimiter(value_len, &mut buf).unwrap();
+        buf
+    }
+
+    fn encode_and_get_crc(
+        &self,
+        flags: u8,
+        value: &[u8],
+        checksum: ChecksumType,
+    ) -> (Vec<u8>, u32) {
+        let mut buf = self.encode_header(flags, value.len());
         // 写入key
         buf.put_slice(&self.key);
         // 写入value
-        buf.put_slice(&self.value);
+        buf.put_slice(value);
 
         // 计算crc
-        let mut hasher = crc32fast::Hasher::new();
-        hasher.update(&mut buf);
-        let crc = hasher.finalize();
+        let crc = compute_checksum(checksum, &buf);
         // println!("crc: {}", crc);
         // 写入crc
         buf.put_u32(crc);
@@ -62,14 +188,22 @@ impl LogRecord {
     /// 计算编码后的长度
     fn encoded_length(&self) -> usize {
         std::mem::size_of::<u8>()
+            + encoded_len_varint(self.timestamp)
+            + encoded_len_varint(self.expire_at)
             + length_delimiter_len(self.key.len())
             + length_delimiter_len(self.value.len())
             + self.key.len()
             + self.value.len()
             + std::mem::size_of::<u32>()
     }
+
+    /// 判断数据是否已过期
+    pub fn is_expired(&self) -> bool {
+        self.expire_at > 0 && self.expire_at <= now_millis()
+    }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LogRecordType {
     /// 正常的记录
@@ -78,19 +212,102 @@ pub enum LogRecordType {
     DELETE = 2,
     /// 事务完成的记录
     TXNFINISHED = 3,
+    /// 较大value的一个分块，不单独建立索引
+    CHUNK = 4,
+    /// 分块存储的记录，value为各个分块的位置信息
+    CHUNKED = 5,
+    /// merge_value写入的操作数，value包含上一条记录的位置
+    OPERAND = 6,
 }
 
-impl From<u8> for LogRecordType {
-    fn from(value: u8) -> Self {
+impl TryFrom<u8> for LogRecordType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
         match value {
-            1 => LogRecordType::NORMAL,
-            2 => LogRecordType::DELETE,
-            3 => LogRecordType::TXNFINISHED,
-            _ => unreachable!(),
+            1 => Ok(LogRecordType::NORMAL),
+            2 => Ok(LogRecordType::DELETE),
+            3 => Ok(LogRecordType::TXNFINISHED),
+            4 => Ok(LogRecordType::CHUNK),
+            5 => Ok(LogRecordType::CHUNKED),
+            6 => Ok(LogRecordType::OPERAND),
+            _ => Err(Error::InvalidLogRecord),
         }
     }
 }
 
+/// 解析record_type字节，返回记录类型、压缩算法以及value是否加密
+pub(crate) fn decode_record_type(value: u8) -> Result<(LogRecordType, CompressionType, bool)> {
+    let compression = match (value >> 4) & 0x07 {
+        0 => CompressionType::None,
+        1 => CompressionType::Lz4,
+        2 => CompressionType::Zstd,
+        3 => CompressionType::ZstdDict,
+        _ => return Err(Error::InvalidLogRecord),
+    };
+    Ok((
+        LogRecordType::try_from(value & 0x0f)?,
+        compression,
+        value & ENCRYPTED_FLAG != 0,
+    ))
+}
+
+/// 计算数据记录的校验值，不校验时为0
+pub(crate) fn compute_checksum(checksum: ChecksumType, buf: &[u8]) -> u32 {
+    match checksum {
+        ChecksumType::Crc32 => crc32fast::hash(buf),
+        ChecksumType::Xxh3 => xxhash_rust::xxh3::xxh3_64(buf) as u32,
+        ChecksumType::None => 0,
+    }
+}
+
+/// 压缩数据，失败时返回None
+fn compress(
+    compression: CompressionType,
+    value: &[u8],
+    dictionaries: Option<&Dictionaries>,
+) -> Option<Vec<u8>> {
+    match compression {
+        CompressionType::None => Some(value.to_vec()),
+        CompressionType::Lz4 => Some(lz4_flex::compress_prepend_size(value)),
+        CompressionType::Zstd => match zstd::bulk::compress(value, zstd::DEFAULT_COMPRESSION_LEVEL)
+        {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!("failed to compress value with zstd: {}", e);
+                None
+            }
+        },
+        CompressionType::ZstdDict => dictionaries?.compress(value),
+    }
+}
+
+/// 解压数据，使用字典压缩的数据需要数据帧中记录的字典
+pub(crate) fn decompress(
+    compression: CompressionType,
+    value: Vec<u8>,
+    dictionaries: Option<&Dictionaries>,
+) -> Result<Vec<u8>> {
+    let res = match compression {
+        CompressionType::None => return Ok(value),
+        CompressionType::Lz4 => {
+            lz4_flex::decompress_size_prepended(&value).map_err(|e| e.to_string())
+        }
+        CompressionType::Zstd => {
+            zstd::stream::decode_all(value.as_slice()).map_err(|e| e.to_string())
+        }
+        CompressionType::ZstdDict => {
+            return dictionaries
+                .ok_or(Error::FailedToDecompressValue)?
+                .decompress(&value)
+        }
+    };
+    res.map_err(|e| {
+        error!("failed to decompress value: {}", e);
+        Error::FailedToDecompressValue
+    })
+}
+
 /// 从数据文件中读取的log record，包含实际的log record和log record的大小
 #[derive(Debug)]
 pub struct ReadLogRecord {
@@ -107,17 +324,30 @@ pub struct TransactionRecord {
 /// 获取log record header的最大大小
 pub fn max_log_record_header_size() -> usize {
     // 1 byte for log record type
+    // var bytes for timestamp
+    // var bytes for expire at
     // var bytes for key length
     // var bytes for value length
     // key
     // value
     // 4 bytes for crc
-    std::mem::size_of::<u8>() + 2 * length_delimiter_len(u32::MAX as usize)
+    std::mem::size_of::<u8>()
+        + 2 * encoded_len_varint(u64::MAX)
+        + 2 * length_delimiter_len(u32::MAX as usize)
+}
+
+/// 当前的unix毫秒时间戳
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::data::data_file::DataFile;
+    use crate::data::data_file::{DataFile, DATA_FILE_HEADER_SIZE};
+    use crate::options::IOType;
 
     use super::*;
 
@@ -128,44 +358,171 @@ mod tests {
             key: b"hello".to_vec(),
             value: b"world".to_vec(),
             record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
         };
         let encoded = log_record.encode();
         println!("encoded: {:?}", encoded);
-        assert_eq!(log_record.get_crc(), 561450126);
+        assert_eq!(log_record.get_crc(), 2646428550);
         // value 为空
         let log_record = LogRecord {
             key: b"hello".to_vec(),
             value: vec![],
             record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
         };
         let encoded = log_record.encode();
         println!("encoded: {:?}", encoded);
-        assert_eq!(log_record.get_crc(), 193110475);
+        assert_eq!(log_record.get_crc(), 2923593076);
         // delete 记录
         let log_record = LogRecord {
             key: b"hello".to_vec(),
             value: b"world".to_vec(),
             record_type: LogRecordType::DELETE,
+            timestamp: 0,
+            expire_at: 0,
         };
         let encoded = log_record.encode();
         println!("encoded: {:?}", encoded);
-        assert_eq!(log_record.get_crc(), 2629656640);
+        assert_eq!(log_record.get_crc(), 2361401855);
+    }
+
+    #[test]
+    fn test_log_record_compression() {
+        let dir_path = std::env::temp_dir();
+        let data_file = DataFile::new(&dir_path, 502, IOType::StandardFIO).unwrap();
+        let mut offset = DATA_FILE_HEADER_SIZE;
+        for compression in [CompressionType::Lz4, CompressionType::Zstd] {
+            let log_record = LogRecord {
+                key: b"hello".to_vec(),
+                value: b"bitcask-rs".repeat(100),
+                record_type: LogRecordType::NORMAL,
+                timestamp: now_millis(),
+                expire_at: 0,
+            };
+            let encoded = log_record
+                .encode_with(compression, None, ChecksumType::Crc32, None)
+                .unwrap();
+            assert!(encoded.len() < log_record.encode().len());
+            assert_eq!(
+                decode_record_type(encoded[0]).unwrap(),
+                (LogRecordType::NORMAL, compression, false)
+            );
+            data_file.write(&encoded).unwrap();
+            let read_log_record = data_file.read_log_record(offset).unwrap();
+            assert_eq!(read_log_record.record, log_record);
+            assert_eq!(read_log_record.size, encoded.len());
+            offset += encoded.len() as u64;
+
+            // 压缩后没有变小或者不是正常记录时不压缩
+            let log_record = LogRecord {
+                key: b"hello".to_vec(),
+                value: b"world".to_vec(),
+                record_type: LogRecordType::NORMAL,
+                timestamp: 0,
+                expire_at: 0,
+            };
+            assert_eq!(
+                log_record
+                    .encode_with(compression, None, ChecksumType::Crc32, None)
+                    .unwrap(),
+                log_record.encode()
+            );
+            let log_record = LogRecord {
+                key: b"hello".to_vec(),
+                value: b"bitcask-rs".repeat(100),
+                record_type: LogRecordType::DELETE,
+                timestamp: 0,
+                expire_at: 0,
+            };
+            assert_eq!(
+                log_record
+                    .encode_with(compression, None, ChecksumType::Crc32, None)
+                    .unwrap(),
+                log_record.encode()
+            );
+        }
+        assert!(matches!(
+            decode_record_type(4 << 4 | 1).err().unwrap(),
+            Error::InvalidLogRecord
+        ));
+
+        std::fs::remove_file(dir_path.join("000000502.data")).unwrap();
+    }
+
+    #[test]
+    fn test_log_record_pos_encode() {
+        let pos = LogRecordPos {
+            file_id: 1,
+            offset: 100,
+            size: 10,
+        };
+        let encoded = pos.encode();
+        assert_eq!(decode_log_record_pos(encoded).unwrap(), pos);
+
+        let pos = LogRecordPos {
+            file_id: u32::MAX,
+            offset: 64 * 1024 * 1024 * 1024,
+            size: u32::MAX,
+        };
+        let encoded = pos.encode();
+        assert_eq!(decode_log_record_pos(encoded).unwrap(), pos);
+
+        let positions = vec![
+            LogRecordPos {
+                file_id: 1,
+                offset: 100,
+                size: 10,
+            },
+            pos,
+        ];
+        let encoded = encode_chunk_positions(&positions);
+        assert_eq!(decode_chunk_positions(&encoded).unwrap(), positions);
+        assert!(matches!(
+            decode_chunk_positions(&encoded[..encoded.len() - 1]),
+            Err(Error::InvalidLogRecord)
+        ));
+
+        // 旧版本的hint文件没有记录大小
+        let mut encoded = BytesMut::new();
+        encode_length_delimiter(1, &mut encoded).unwrap();
+        encode_length_delimiter(100, &mut encoded).unwrap();
+        assert_eq!(
+            decode_log_record_pos(encoded.to_vec()).unwrap(),
+            LogRecordPos {
+                file_id: 1,
+                offset: 100,
+                size: 0,
+            }
+        );
+        encoded.truncate(1);
+        assert!(matches!(
+            decode_log_record_pos(encoded.to_vec()),
+            Err(Error::InvalidLogRecord)
+        ));
+        assert!(matches!(
+            decode_log_record_pos(vec![0xff]),
+            Err(Error::InvalidLogRecord)
+        ));
     }
 
     #[test]
     fn test_data_file_read_log_record() {
         let dir_path = std::env::temp_dir();
-        let data_file = DataFile::new(&dir_path, 500).unwrap();
+        let data_file = DataFile::new(&dir_path, 500, IOType::StandardFIO).unwrap();
         assert_eq!(data_file.get_file_id(), 500);
 
         let log_record = LogRecord {
             key: b"hello".to_vec(),
             value: b"world".to_vec(),
             record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
         };
         let encoded = log_record.encode();
         data_file.write(&encoded).unwrap();
-        let read_log_record = data_file.read_log_record(0).unwrap();
+        let read_log_record = data_file.read_log_record(DATA_FILE_HEADER_SIZE).unwrap();
         assert_eq!(read_log_record.record, log_record);
         assert_eq!(read_log_record.size, encoded.len());
 
@@ -174,10 +531,14 @@ mod tests {
             key: b"hello".to_vec(),
             value: b"lyf".to_vec(),
             record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
         };
         let encoded = log_record.encode();
         data_file.write(&encoded).unwrap();
-        let read_log_record = data_file.read_log_record(17).unwrap();
+        let read_log_record = data_file
+            .read_log_record(DATA_FILE_HEADER_SIZE + 19)
+            .unwrap();
         assert_eq!(read_log_record.record, log_record);
         // println!("read_log_record: {:?}", read_log_record);
         assert_eq!(read_log_record.size, encoded.len());
@@ -187,11 +548,44 @@ mod tests {
             key: b"hello".to_vec(),
             value: b"world".to_vec(),
             record_type: LogRecordType::DELETE,
+            timestamp: 0,
+            expire_at: 0,
         };
         let encoded = log_record.encode();
         data_file.write(&encoded).unwrap();
-        let read_log_record = data_file.read_log_record(32).unwrap();
+        let read_log_record = data_file
+            .read_log_record(DATA_FILE_HEADER_SIZE + 36)
+            .unwrap();
         assert_eq!(read_log_record.record, log_record);
         assert_eq!(read_log_record.size, encoded.len());
+
+        std::fs::remove_file(dir_path.join("000000500.data")).unwrap();
+    }
+
+    #[test]
+    fn test_log_record_expire() {
+        let mut log_record = LogRecord {
+            key: b"hello".to_vec(),
+            value: b"world".to_vec(),
+            record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
+        };
+        assert!(!log_record.is_expired());
+
+        log_record.expire_at = now_millis() - 1;
+        assert!(log_record.is_expired());
+
+        log_record.expire_at = now_millis() + 60 * 1000;
+        assert!(!log_record.is_expired());
+
+        // 过期时间写入header后可以正确读取
+        let dir_path = std::env::temp_dir();
+        let data_file = DataFile::new(&dir_path, 501, IOType::StandardFIO).unwrap();
+        data_file.write(&log_record.encode()).unwrap();
+        let read_log_record = data_file.read_log_record(DATA_FILE_HEADER_SIZE).unwrap();
+        assert_eq!(read_log_record.record, log_record);
+
+        std::fs::remove_file(dir_path.join("000000501.data")).unwrap();
     }
 }
@@ -1,2 +1,6 @@
+pub(crate) mod cipher;
 pub(crate) mod data_file;
 pub(crate) mod log_record;
+
+pub use data_file::{dump, LogRecordSummary};
+pub use log_record::LogRecordType;
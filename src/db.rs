@@ -1,160 +1,910 @@
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::path::Path;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, Sender};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use bytes::Bytes;
-use log::warn;
+use fs2::FileExt;
+use log::{error, warn};
 use parking_lot::{Mutex, RwLock};
 
 use crate::batch::{log_record_key_with_seq_num, parse_log_record_key, NON_TRANSACTION_SEQ_NUM};
-use crate::data::data_file::DataFile;
-use crate::data::log_record::{LogRecord, LogRecordPos, LogRecordType, TransactionRecord};
+use crate::bloom::BloomFilter;
+use crate::cache::ValueCache;
+use crate::chunk;
+use crate::data::cipher::Cipher;
+use crate::data::data_file::{
+    find_data_file_dir, find_data_files, get_data_file_full_path, DataFile, DATA_FILE_HEADER_SIZE,
+    SEQ_NUM_FILE_NAME,
+};
+use crate::data::log_record::{
+    now_millis, LogRecord, LogRecordPos, LogRecordType, TransactionRecord,
+};
+use crate::destroy::finish_clear;
+use crate::dictionary::Dictionaries;
 use crate::error::{Error, Result};
+use crate::eviction::AccessTracker;
+use crate::file_stat::FileCounters;
+use crate::fio::{mem_io, Advice};
 use crate::index;
-use crate::options::Options;
+use crate::merge::finish_merge;
+use crate::merge_control::MergeControl;
+use crate::merge_operator::MergeOperator;
+use crate::open_progress::OpenProgressTracker;
+use crate::options::{check_options, EvictionPolicy, IOType, Options};
+use crate::rate_limiter::RateLimiter;
+use crate::secondary_index::SecondaryIndex;
+use crate::slow_op::SlowOpListener;
+use crate::snapshot::SnapshotFiles;
+use crate::telemetry;
+use crate::watch::Watcher;
 
 const INITIAL_FILE_ID: u32 = 0;
+/// 文件锁，保证同一时刻只有一个进程打开数据库目录
+pub(crate) const FILE_LOCK_NAME: &str = "flock";
+/// 事务编号文件中记录的key
+const SEQ_NUM_KEY: &[u8] = b"seq-num";
 
 /// 数据库接口
+///
+/// 克隆的句柄共享同一个数据库，可以在线程和异步任务间传递，最后一个句柄被drop时关闭数据库
+#[derive(Clone)]
 pub struct Engine {
-    options: Arc<Options>,
+    pub(crate) options: Arc<Options>,
     /// 活跃数据文件
-    active_file: Arc<RwLock<DataFile>>,
+    pub(crate) active_file: Arc<RwLock<DataFile>>,
     /// 旧数据文件
-    older_files: Arc<RwLock<HashMap<u32, DataFile>>>,
+    pub(crate) older_files: Arc<RwLock<HashMap<u32, DataFile>>>,
     /// 内存索引
-    pub(crate) index: Box<dyn index::Indexer>,
-    /// 数据库启动时，数据文件ID
-    file_ids: Vec<u32>,
+    pub(crate) index: Arc<dyn index::Indexer>,
     /// 批量写操作的锁
-    pub(crate) batch_commit_lock: Mutex<()>,
+    pub(crate) batch_commit_lock: Arc<Mutex<()>>,
     /// 全局事务编号
     pub(crate) seq_num: Arc<std::sync::atomic::AtomicUsize>,
+    /// 防止多个线程同时merge
+    pub(crate) merging_lock: Arc<Mutex<()>>,
+    /// 写操作在写入数据和更新索引期间持有读锁，merge转换活跃文件时持有写锁，
+    /// 保证被merge的文件中已写入的数据都已更新到内存索引
+    pub(crate) rotate_lock: Arc<RwLock<()>>,
+    /// 可以被merge清理的无效数据大小
+    pub(crate) reclaim_size: Arc<AtomicUsize>,
+    /// 数据文件的总大小（估计值），设置了max_db_size时统计
+    pub(crate) db_size: Arc<AtomicU64>,
+    /// 每个数据文件的记录数量和无效数据大小
+    pub(crate) file_counters: Arc<Mutex<FileCounters>>,
+    /// 数据库目录的文件锁，由用户持有的句柄共享，后台线程使用的句柄不持有文件锁
+    lock_file: Option<Arc<File>>,
+    /// 内存数据库目录的锁，内存数据库不使用文件锁
+    mem_dir_lock: Option<Arc<mem_io::DirLock>>,
+    /// merge的状态和暂停控制
+    pub(crate) merge_control: Arc<MergeControl>,
+    /// 后台merge线程
+    pub(crate) merge_worker: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// 设置了密钥时用于加密value
+    pub(crate) cipher: Option<Arc<Cipher>>,
+    /// 压缩小value使用的zstd字典
+    pub(crate) dictionaries: Arc<Dictionaries>,
+    /// 布隆过滤器，用于快速判断key不存在
+    bloom_filter: Option<Arc<RwLock<BloomFilter>>>,
+    /// 读缓存，缓存最近读取的数据
+    pub(crate) value_cache: Option<Arc<Mutex<ValueCache>>>,
+    /// 按LRU淘汰时记录key的读写顺序
+    pub(crate) access_tracker: Option<Arc<Mutex<AccessTracker>>>,
+    /// 写入限速
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    /// merge读写限速
+    pub(crate) merge_rate_limiter: Option<Arc<RateLimiter>>,
+    /// 后台定时持久化线程，以及通知其退出的channel
+    sync_worker: Arc<Mutex<Option<SyncWorker>>>,
+    /// 后台创建索引检查点的线程
+    pub(crate) checkpoint_worker: Arc<Mutex<Option<SyncWorker>>>,
+    /// 数据库是否已关闭，关闭后的操作返回DatabaseClosed
+    closed: Arc<AtomicBool>,
+    /// 磁盘空间不足后切换为只读模式，写操作返回ReadOnly
+    read_only: Arc<AtomicBool>,
+    /// 二级索引，索引名称 -> 索引
+    pub(crate) secondary_indexes: Arc<RwLock<HashMap<String, SecondaryIndex>>>,
+    /// 数据变更的订阅者
+    pub(crate) watchers: Arc<Mutex<Vec<Watcher>>>,
+    /// 提交次数，作为变更事件的seq
+    pub(crate) commit_seq: Arc<AtomicU64>,
+    /// 使用中的快照，以及merge后仍被快照使用的旧数据文件
+    pub(crate) snapshots: Arc<Mutex<SnapshotFiles>>,
+    /// 合并merge_value写入的操作数
+    pub(crate) merge_operator: Arc<RwLock<Option<Arc<MergeOperator>>>>,
+    /// 处理慢操作的回调
+    pub(crate) slow_op_listener: Arc<RwLock<Option<Arc<SlowOpListener>>>>,
+}
+
+/// 后台持久化线程，drop sender时线程退出
+type SyncWorker = (Sender<()>, JoinHandle<()>);
+
+/// 数据的元信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordMeta {
+    /// 最后修改时间
+    pub last_modified: SystemTime,
+    /// 过期时间，None表示永不过期
+    pub expire_at: Option<SystemTime>,
+    /// 数据所在的数据文件ID，分块存储和merge_value写入的数据为索引指向的最后一条记录
+    pub file_id: u32,
+    /// 数据在数据文件中的偏移
+    pub offset: u64,
+    /// 数据在磁盘上占据的大小
+    pub size: u32,
+}
+
+impl RecordMeta {
+    /// 剩余存活时间，None表示永不过期
+    pub fn ttl(&self) -> Option<Duration> {
+        self.expire_at.map(|expire_at| {
+            expire_at
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// 数据库统计信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "http", derive(serde::Serialize))]
+pub struct Stat {
+    /// key的数量
+    pub key_num: usize,
+    /// 数据文件的数量
+    pub data_file_num: usize,
+    /// 可以被merge清理的无效数据大小（估计值）
+    pub reclaimable_size: usize,
+    /// 索引占用的内存大小（估计值）
+    pub index_memory_usage: usize,
+    /// 数据库目录占据的磁盘空间大小
+    pub disk_size: u64,
 }
 
 impl Engine {
     /// 打开数据库
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(dir_path = %opts.dir_path.display()))
+    )]
     pub fn open(opts: Options) -> Result<Self> {
         // 校验配置项
         check_options(&opts)?;
-        // 判断数据库目录是否存在
+        let mut progress = OpenProgressTracker::new(&opts);
         let dir_path = opts.dir_path.clone();
+        // 内存数据库不读写文件系统，启动时没有需要加载的数据文件
+        if opts.io_type == IOType::Memory {
+            return Self::open_in_memory(opts);
+        }
+        // 判断数据库目录是否存在
         if !dir_path.exists() {
+            if !opts.create_if_missing {
+                return Err(Error::DatabaseNotFound { path: dir_path });
+            }
             // 创建数据库目录
-            if let Err(e) = std::fs::create_dir_all(&dir_path) {
-                warn!("Failed to create database directory: {}", e);
-                return Err(Error::FailedToCreateDbDir);
+            if let Err(source) = std::fs::create_dir_all(&dir_path) {
+                return Err(Error::FailedToCreateDbDir {
+                    path: dir_path,
+                    source,
+                });
+            }
+        }
+        // 创建额外的数据文件目录
+        for data_dir in opts.dir_paths.iter() {
+            if let Err(source) = std::fs::create_dir_all(data_dir) {
+                return Err(Error::FailedToCreateDbDir {
+                    path: data_dir.clone(),
+                    source,
+                });
             }
         }
-        // 加载目录中的数据文件
-        let mut data_files: Vec<DataFile> = load_data_files(&dir_path)?;
-        // ID大的数据文件越新
-        data_files.reverse();
+        // 获取文件锁，防止多个进程同时使用同一个数据库目录
+        let lock_file = lock_dir(&dir_path)?;
+        // 完成或者回滚上次中途崩溃的merge
+        let data_dirs = opts.data_dirs();
+        finish_merge(&dir_path, &data_dirs)?;
+        // 完成上次中途崩溃的清空操作
+        finish_clear(&dir_path, &data_dirs)?;
+        #[cfg(feature = "encryption")]
+        let cipher = opts.encryption_key.map(|key| Arc::new(Cipher::new(&key)));
+        #[cfg(not(feature = "encryption"))]
+        let cipher = None;
+        let dictionaries = Arc::new(Dictionaries::load(&dir_path)?);
+        // 加载所有数据目录中的数据文件
+        let data_files: Vec<DataFile> =
+            load_data_files(&data_dirs, opts.startup_io_type, &cipher, &dictionaries)?;
+        progress.check_timeout()?;
+        progress.set_total_files(data_files.len());
+        // 没有数据文件时视为新的数据库
+        if data_files.is_empty() && !opts.create_if_missing {
+            return Err(Error::DatabaseNotFound { path: dir_path });
+        }
+        if !data_files.is_empty() && opts.error_if_exists {
+            return Err(Error::DatabaseAlreadyExists { path: dir_path });
+        }
+        // 按ID从小到大的顺序加载索引
         let file_ids = data_files
             .iter()
             .map(|f| f.get_file_id())
             .collect::<Vec<_>>();
-        // 保存旧的数据文件
-        let mut older_files = HashMap::new();
-        if data_files.len() > 1 {
-            for _ in 0..data_files.len() - 1 {
-                let f = data_files.pop().unwrap();
-                older_files.insert(f.get_file_id(), f);
+        let (active_file, older_files) =
+            split_data_files(data_files, &opts, &cipher, &dictionaries)?;
+        let mut engine = Self::new(opts, active_file, older_files, cipher, dictionaries)?;
+        engine.lock_file = Some(Arc::new(lock_file));
+        engine
+            .load_state(&file_ids, &mut progress)
+            .inspect_err(|_| {
+                // 加载失败时不持久化只加载了一部分的索引
+                engine.closed.store(true, Ordering::SeqCst);
+            })?;
+        engine.refresh_db_size();
+        engine.reset_access_tracker()?;
+        if let Some(interval) = engine.options.sync_interval {
+            engine.start_sync_worker(interval);
+        }
+        if let Some(interval) = engine.options.index_checkpoint_interval {
+            if engine.checkpoint_enabled() {
+                engine.start_checkpoint_worker(interval);
             }
-        };
-        // 获取活跃数据文件
-        let active_file = match data_files.pop() {
-            Some(f) => f,
-            None => DataFile::new(&dir_path, INITIAL_FILE_ID)?,
-        };
-        let index_type = opts.index_type;
-        let engine = Self {
+        }
+        Ok(engine)
+    }
+
+    /// 打开内存数据库，数据只保存在内存中，关闭数据库后丢失
+    fn open_in_memory(opts: Options) -> Result<Self> {
+        // 内存数据库总是新的数据库
+        if !opts.create_if_missing {
+            return Err(Error::DatabaseNotFound {
+                path: opts.dir_path,
+            });
+        }
+        let mem_dir_lock = mem_io::DirLock::new(&opts.dir_path)?;
+        #[cfg(feature = "encryption")]
+        let cipher = opts.encryption_key.map(|key| Arc::new(Cipher::new(&key)));
+        #[cfg(not(feature = "encryption"))]
+        let cipher = None;
+        let dictionaries = Arc::new(Dictionaries::default());
+        let active_file = DataFile::new_with_checksum(
+            &opts.dir_path,
+            INITIAL_FILE_ID,
+            IOType::Memory,
+            opts.checksum,
+        )?
+        .with_cipher(cipher.clone())
+        .with_dictionaries(Some(dictionaries.clone()))
+        .with_write_buffer(opts.write_buffer_size);
+        let mut engine = Self::new(opts, active_file, HashMap::new(), cipher, dictionaries)?;
+        engine.mem_dir_lock = Some(Arc::new(mem_dir_lock));
+        if let Some(interval) = engine.options.sync_interval {
+            engine.start_sync_worker(interval);
+        }
+        Ok(engine)
+    }
+
+    /// 重新扫描数据库目录并重建索引等内存状态，例如外部工具将备份恢复到数据库目录之后
+    ///
+    /// 不需要重新创建Engine，已有的引用继续可用。期间阻塞写操作和merge，读操作可能暂时读不到数据，
+    /// 同样报告打开进度但不受open_timeout限制。订阅者不会收到通知，二级索引会重新建立。
+    /// 有快照在使用时返回SnapshotInUse，内存数据库直接返回。
+    /// 失败时内存状态可能不完整，应当关闭后重新打开数据库
+    pub fn reload(&self) -> Result<()> {
+        self.check_closed()?;
+        if self.is_in_memory() {
+            return Ok(());
+        }
+        {
+            let _merging_guard = self.merging_lock.lock();
+            let _rotate_guard = self.rotate_lock.write();
+            if self.snapshots.lock().count > 0 {
+                return Err(Error::SnapshotInUse);
+            }
+            let dir_path = &self.options.dir_path;
+            let mut progress = OpenProgressTracker::new(&self.options).without_timeout();
+            // 先持久化并关闭当前的数据文件，再重新读取目录
+            self.active_file.read().sync()?;
+            let mut active_file = self.active_file.write();
+            let mut older_files = self.older_files.write();
+            older_files.clear();
+            let data_dirs = self.options.data_dirs();
+            finish_merge(dir_path, &data_dirs)?;
+            finish_clear(dir_path, &data_dirs)?;
+            self.dictionaries.reload(dir_path)?;
+            let data_files = load_data_files(
+                &data_dirs,
+                self.options.startup_io_type,
+                &self.cipher,
+                &self.dictionaries,
+            )?;
+            progress.set_total_files(data_files.len());
+            let file_ids = data_files
+                .iter()
+                .map(|f| f.get_file_id())
+                .collect::<Vec<_>>();
+            let (new_active_file, new_older_files) =
+                split_data_files(data_files, &self.options, &self.cipher, &self.dictionaries)?;
+            *active_file = new_active_file;
+            *older_files = new_older_files;
+            drop(older_files);
+            drop(active_file);
+
+            self.index.clear()?;
+            if let Some(value_cache) = &self.value_cache {
+                value_cache.lock().clear();
+            }
+            self.reclaim_size.store(0, Ordering::SeqCst);
+            self.file_counters.lock().clear();
+            self.load_state(&file_ids, &mut progress)?;
+        }
+        self.refresh_db_size();
+        self.reset_access_tracker()?;
+        self.rebuild_secondary_indexes()
+    }
+
+    fn new(
+        opts: Options,
+        active_file: DataFile,
+        older_files: HashMap<u32, DataFile>,
+        cipher: Option<Arc<Cipher>>,
+        dictionaries: Arc<Dictionaries>,
+    ) -> Result<Self> {
+        let index = index::new_indexer(opts.index_type, &opts.dir_path)?;
+        let bloom_filter_bits_per_key = opts.bloom_filter_bits_per_key;
+        let value_cache_size = opts.value_cache_size;
+        let write_rate_limit = opts.write_rate_limit_bytes_per_sec;
+        let merge_rate_limit = opts.merge_rate_limit_bytes_per_sec;
+        let eviction_policy = opts.eviction_policy;
+        Ok(Self {
             options: Arc::new(opts),
             active_file: Arc::new(RwLock::new(active_file)),
             older_files: Arc::new(RwLock::new(older_files)),
-            index: Box::new(index::new_indexer(index_type)),
-            file_ids,
-            batch_commit_lock: Mutex::new(()),
+            index: Arc::from(index),
+            batch_commit_lock: Arc::new(Mutex::new(())),
             seq_num: Arc::new(std::sync::atomic::AtomicUsize::new(1)),
-        };
-        // 加载索引，并更新事务序列号
-        let seq_num = engine.load_index_from_data_files()?;
-        if seq_num > 0 {
-            engine
-                .seq_num
-                .store(seq_num, std::sync::atomic::Ordering::SeqCst);
-        }
-        Ok(engine)
+            merging_lock: Arc::new(Mutex::new(())),
+            rotate_lock: Arc::new(RwLock::new(())),
+            reclaim_size: Arc::new(AtomicUsize::new(0)),
+            db_size: Default::default(),
+            file_counters: Default::default(),
+            lock_file: None,
+            mem_dir_lock: None,
+            merge_control: Default::default(),
+            merge_worker: Arc::new(Mutex::new(None)),
+            cipher,
+            dictionaries,
+            bloom_filter: (bloom_filter_bits_per_key > 0)
+                .then(|| Arc::new(RwLock::new(BloomFilter::new(0, bloom_filter_bits_per_key)))),
+            value_cache: (value_cache_size > 0)
+                .then(|| Arc::new(Mutex::new(ValueCache::new(value_cache_size)))),
+            access_tracker: (eviction_policy == EvictionPolicy::Lru)
+                .then(|| Arc::new(Mutex::new(AccessTracker::new()))),
+            rate_limiter: write_rate_limit.map(|limit| Arc::new(RateLimiter::new(limit))),
+            merge_rate_limiter: merge_rate_limit.map(|limit| Arc::new(RateLimiter::new(limit))),
+            sync_worker: Arc::new(Mutex::new(None)),
+            checkpoint_worker: Arc::new(Mutex::new(None)),
+            closed: Arc::new(AtomicBool::new(false)),
+            read_only: Arc::new(AtomicBool::new(false)),
+            secondary_indexes: Default::default(),
+            watchers: Default::default(),
+            commit_seq: Default::default(),
+            snapshots: Default::default(),
+            merge_operator: Default::default(),
+            slow_op_listener: Default::default(),
+        })
     }
 
     /// 向数据库中写入数据, key不能为空
     pub fn put(&self, key: Bytes, value: Bytes) -> Result<()> {
+        telemetry::timed("put", || self.put_with_optional_ttl(key, value, None))
+    }
+
+    /// 向数据库中写入数据，并设置过期时间，过期后的数据视为不存在
+    pub fn put_with_ttl(&self, key: Bytes, value: Bytes, ttl: Duration) -> Result<()> {
+        telemetry::timed("put", || self.put_with_optional_ttl(key, value, Some(ttl)))
+    }
+
+    fn put_with_optional_ttl(&self, key: Bytes, value: Bytes, ttl: Option<Duration>) -> Result<()> {
+        let start = Instant::now();
+        self.check_closed()?;
         if key.is_empty() {
             return Err(Error::KeyIsEmpty);
         }
+        self.check_kv_size(&key, value.len())?;
+        self.evict_if_needed()?;
+        let _rotate_guard = self.rotate_lock.read();
+        let pos = self.put_locked(&key, &value, ttl)?;
+        self.check_slow_op("put", start, key.len(), value.len(), Some(pos));
+        Ok(())
+    }
+
+    /// 写入数据并更新索引，调用方需要持有rotate_lock
+    fn put_locked(&self, key: &[u8], value: &[u8], ttl: Option<Duration>) -> Result<LogRecordPos> {
+        // 过期时间从写入时间开始计算
+        let timestamp = now_millis();
+        let expire_at = ttl.map_or(0, |ttl| timestamp.saturating_add(ttl.as_millis() as u64));
 
         // 构造log record, 事务编号为0表示非事务写入的数据
         let record = LogRecord {
-            key: log_record_key_with_seq_num(&key, NON_TRANSACTION_SEQ_NUM),
+            key: log_record_key_with_seq_num(key, NON_TRANSACTION_SEQ_NUM),
             value: value.to_vec(),
             record_type: LogRecordType::NORMAL,
+            timestamp,
+            expire_at,
         };
+        self.check_db_size()?;
+        self.check_index_memory(key)?;
+        self.add_to_bloom_filter(key);
         // 追加写入活跃数据文件
         let pos = self.append_log_record(&record)?;
 
-        // 更新内存索引
-        if !self.index.put(key.to_vec(), pos) {
-            return Err(Error::FailedToUpdateIndex);
+        // 更新内存索引，被覆盖的旧数据成为无效数据
+        if let Some(old_pos) = self.index.put(key.to_vec(), pos) {
+            self.add_reclaim_size(&old_pos);
         }
-        Ok(())
+        self.after_commit(&[(key, Some(value))]);
+        Ok(pos)
     }
 
     /// 从数据库中读取数据
     pub fn get(&self, key: Bytes) -> Result<Bytes> {
+        telemetry::timed("get", || {
+            let start = Instant::now();
+            // 记录实际读取的位置，用于慢操作日志
+            let pos = Cell::new(None);
+            let log_record = self.lookup_log_record(&key, |p| {
+                pos.set(Some(*p));
+                self.get_log_record_by_position(p)
+            })?;
+            self.check_slow_op("get", start, key.len(), log_record.value.len(), pos.get());
+            self.record_access(&key);
+            Ok(log_record.value.into())
+        })
+    }
+
+    /// 读取数据及其元信息，包括写入时间、过期时间以及在数据文件中的位置
+    pub fn get_with_meta(&self, key: Bytes) -> Result<(Bytes, RecordMeta)> {
+        // 读取时可能重新查询索引，记录实际读取的位置
+        let pos = Cell::new(None);
+        let log_record = self.lookup_log_record(&key, |p| {
+            pos.set(Some(*p));
+            self.get_log_record_by_position(p)
+        })?;
+        let pos = pos.get().unwrap();
+        self.record_access(&key);
+        let meta = RecordMeta {
+            last_modified: UNIX_EPOCH + Duration::from_millis(log_record.timestamp),
+            expire_at: match log_record.expire_at {
+                0 => None,
+                expire_at => Some(UNIX_EPOCH + Duration::from_millis(expire_at)),
+            },
+            file_id: pos.file_id,
+            offset: pos.offset,
+            size: pos.size,
+        };
+        Ok((log_record.value.into(), meta))
+    }
+
+    /// 获取key的剩余存活时间，None表示永不过期
+    pub fn ttl(&self, key: Bytes) -> Result<Option<Duration>> {
+        let log_record = self.get_head_record(&key)?;
+        if log_record.expire_at == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Duration::from_millis(
+            log_record.expire_at.saturating_sub(now_millis()),
+        )))
+    }
+
+    /// 根据key读取有效的log record
+    pub(crate) fn get_log_record(&self, key: &[u8]) -> Result<LogRecord> {
+        self.lookup_log_record(key, |pos| self.get_log_record_by_position(pos))
+    }
+
+    /// 根据key读取有效的log record，分块存储的数据只读取记录分块位置的记录
+    pub(crate) fn get_head_record(&self, key: &[u8]) -> Result<LogRecord> {
+        self.lookup_log_record(key, |pos| {
+            let active_file = self.active_file.read();
+            let older_files = self.older_files.read();
+            let log_record = read_head_record_at(&active_file, &older_files, pos)?;
+            match log_record.record_type {
+                LogRecordType::OPERAND => {
+                    self.resolve_operands(&active_file, &older_files, log_record)
+                }
+                _ => Ok(log_record),
+            }
+        })
+    }
+
+    /// 查询内存索引，使用read读取位置信息对应的log record
+    fn lookup_log_record(
+        &self,
+        key: &[u8],
+        read: impl Fn(&LogRecordPos) -> Result<LogRecord>,
+    ) -> Result<LogRecord> {
+        self.check_closed()?;
         if key.is_empty() {
             return Err(Error::KeyIsEmpty);
         }
+        if !self.may_contain_key(key) {
+            return Err(Error::KeyNotFound);
+        }
         // 从内存索引中获取数据位置
-        if let Some(pos) = self.index.get(key.to_vec()) {
-            self.get_value_by_position(&pos)
-        } else {
-            Err(Error::KeyNotFound)
+        let pos = match self.index.get(key.to_vec()) {
+            Some(pos) => pos,
+            None => return Err(Error::KeyNotFound),
+        };
+        match read(&pos) {
+            // 数据文件可能刚被merge清理，数据已迁移到新的位置，重新查询索引
+            Err(Error::DataFileNotFound) => match self.index.get(key.to_vec()) {
+                Some(new_pos) if new_pos != pos => read(&new_pos),
+                Some(_) => Err(Error::DataFileNotFound),
+                None => Err(Error::KeyNotFound),
+            },
+            res => res,
         }
     }
 
+    /// 判断key是否存在，只查询内存索引，不读取数据文件
+    ///
+    /// 已过期但还没有被merge清理的key仍然视为存在
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        !key.is_empty() && self.may_contain_key(key) && self.index.get(key.to_vec()).is_some()
+    }
+
+    /// key的数量，与contains_key一样包含已过期但还没有被merge清理的key
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 关闭数据库，停止后台线程，持久化活跃数据文件和事务编号并释放文件锁
+    ///
+    /// 重复关闭直接返回，关闭后所有克隆的句柄的读写操作都返回DatabaseClosed
     pub fn close(&self) -> Result<()> {
-        self.active_file.read().sync()
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        // drop sender后后台持久化线程退出
+        let sync_worker = self.sync_worker.lock().take();
+        if let Some((stop_tx, handle)) = sync_worker {
+            drop(stop_tx);
+            if handle.join().is_err() {
+                error!("background sync thread panicked");
+            }
+        }
+        let checkpoint_worker = self.checkpoint_worker.lock().take();
+        if let Some((stop_tx, handle)) = checkpoint_worker {
+            drop(stop_tx);
+            if handle.join().is_err() {
+                error!("background checkpoint thread panicked");
+            }
+        }
+        // 唤醒暂停中的merge，关闭后merge直接返回
+        self.merge_control.wake();
+        let merge_worker = self.merge_worker.lock().take();
+        if let Some(handle) = merge_worker {
+            if handle.join().is_err() {
+                error!("background merge thread panicked");
+            }
+        }
+        self.active_file.read().sync()?;
+        self.write_checkpoint()?;
+        self.save_seq_num(&self.options.dir_path)?;
+        self.index.persist()?;
+        if let Some(lock_file) = &self.lock_file {
+            if let Err(source) = FileExt::unlock(lock_file.as_ref()) {
+                return Err(Error::FailedToUnlockDatabase {
+                    path: self.options.dir_path.join(FILE_LOCK_NAME),
+                    source,
+                });
+            }
+        }
+        Ok(())
     }
 
     pub fn sync(&self) -> Result<()> {
+        self.check_closed()?;
         self.active_file.read().sync()
     }
 
+    /// 检查key和value的长度是否超过配置的上限
+    pub(crate) fn check_kv_size(&self, key: &[u8], value_len: usize) -> Result<()> {
+        if self
+            .options
+            .max_key_size
+            .is_some_and(|max_key_size| key.len() > max_key_size)
+        {
+            return Err(Error::KeyTooLarge);
+        }
+        if self
+            .options
+            .max_value_size
+            .is_some_and(|max_value_size| value_len > max_value_size)
+        {
+            return Err(Error::ValueTooLarge);
+        }
+        Ok(())
+    }
+
+    /// 编码后的数据加上文件头不能超过数据文件的大小，否则每次写入都要切换新的活跃文件
+    fn check_record_size(&self, len: u64) -> Result<()> {
+        if DATA_FILE_HEADER_SIZE + len > self.options.data_file_size {
+            return Err(Error::RecordTooLarge);
+        }
+        Ok(())
+    }
+
+    /// 是否因为磁盘空间不足切换为了只读模式
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    /// 只读模式下返回ReadOnly
+    pub(crate) fn check_writable(&self) -> Result<()> {
+        match self.is_read_only() {
+            true => Err(Error::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    /// 磁盘空间不足导致写入失败时，按配置切换为只读模式
+    fn check_disk_full<T>(&self, res: Result<T>) -> Result<T> {
+        if let Err(e @ Error::DiskFull { .. }) = &res {
+            if self.options.read_only_on_disk_full && !self.read_only.swap(true, Ordering::SeqCst) {
+                error!("{}, database switched to read-only mode", e);
+            }
+        }
+        res
+    }
+
+    /// 数据库已关闭时返回DatabaseClosed
+    pub(crate) fn check_closed(&self) -> Result<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Error::DatabaseClosed);
+        }
+        Ok(())
+    }
+
+    /// 索引占用的内存达到上限时，拒绝写入新的key，覆盖已有的key不会增加内存
+    pub(crate) fn check_index_memory(&self, key: &[u8]) -> Result<()> {
+        match self.options.index_memory_limit {
+            Some(limit)
+                if self.index.memory_usage() >= limit && self.index.get(key.to_vec()).is_none() =>
+            {
+                Err(Error::IndexMemoryLimitExceeded)
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn get_value_by_position(&self, pos: &LogRecordPos) -> Result<Bytes> {
-        // 从数据文件中读取LogRecord数据
+        self.check_closed()?;
+        Ok(self.get_log_record_by_position(pos)?.value.into())
+    }
+
+    /// 根据位置信息读取有效的log record，已删除或过期的数据视为不存在
+    fn get_log_record_by_position(&self, pos: &LogRecordPos) -> Result<LogRecord> {
+        let Some(value_cache) = &self.value_cache else {
+            return self.read_log_record_by_position(pos);
+        };
+        if let Some(log_record) = value_cache.lock().get(pos) {
+            // 缓存的数据可能已经过期
+            return match log_record.is_expired() {
+                true => Err(Error::KeyNotFound),
+                false => Ok(log_record),
+            };
+        }
+        let log_record = self.read_log_record_by_position(pos)?;
+        value_cache.lock().insert(*pos, log_record.clone());
+        Ok(log_record)
+    }
+
+    /// 从数据文件中读取位置信息对应的有效log record，不使用读缓存
+    fn read_log_record_by_position(&self, pos: &LogRecordPos) -> Result<LogRecord> {
         let active_file = self.active_file.read();
         let older_files = self.older_files.read();
-        let log_record = match active_file.get_file_id() == pos.file_id {
-            true => active_file.read_log_record(pos.offset)?.record,
-            false => {
-                let older_file = older_files.get(&pos.file_id);
-                if older_file.is_none() {
-                    return Err(Error::DataFileNotFound);
+        match self.read_value_record_at(&active_file, &older_files, pos) {
+            // 快照中的数据所在的文件可能已被merge，从保留的旧数据文件中读取
+            Err(Error::DataFileNotFound) => {
+                let snapshots = self.snapshots.lock();
+                match snapshots.retired_files.contains_key(&pos.file_id) {
+                    true => self.read_value_record_at(&active_file, &snapshots.retired_files, pos),
+                    false => Err(Error::DataFileNotFound),
                 }
-                let older_file = older_file.unwrap();
-                older_file.read_log_record(pos.offset)?.record
             }
-        };
-        // 判断log record类型
+            res => res,
+        }
+    }
+
+    /// 读取位置信息对应的有效log record，合并merge_value写入的操作数
+    fn read_value_record_at(
+        &self,
+        active_file: &DataFile,
+        older_files: &HashMap<u32, DataFile>,
+        pos: &LogRecordPos,
+    ) -> Result<LogRecord> {
+        let log_record = read_log_record_at(active_file, older_files, pos)?;
         match log_record.record_type {
-            LogRecordType::NORMAL => Ok(log_record.value.into()),
-            LogRecordType::DELETE => Err(Error::KeyNotFound),
-            _ => unreachable!(),
+            LogRecordType::OPERAND => self.resolve_operands(active_file, older_files, log_record),
+            _ => Ok(log_record),
+        }
+    }
+
+    /// 批量读取数据，结果与keys一一对应
+    ///
+    /// 整个过程只获取一次数据文件的锁
+    pub fn multi_get(&self, keys: &[Bytes]) -> Vec<Result<Bytes>> {
+        if self.check_closed().is_err() {
+            return keys.iter().map(|_| Err(Error::DatabaseClosed)).collect();
+        }
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        keys.iter()
+            .map(|key| {
+                if key.is_empty() {
+                    return Err(Error::KeyIsEmpty);
+                }
+                if !self.may_contain_key(key) {
+                    return Err(Error::KeyNotFound);
+                }
+                let pos = self.index.get(key.to_vec()).ok_or(Error::KeyNotFound)?;
+                let log_record = self.read_value_record_at(&active_file, &older_files, &pos)?;
+                self.record_access(key);
+                Ok(log_record.value.into())
+            })
+            .collect()
+    }
+
+    /// 批量写入数据，只获取一次活跃数据文件的锁，开启sync_write时也只持久化一次
+    ///
+    /// 与WriteBatch不同，不保证原子性，中途出错时之前的数据已写入
+    pub fn multi_put(&self, pairs: &[(Bytes, Bytes)]) -> Result<()> {
+        self.check_closed()?;
+        if pairs.iter().any(|(key, _)| key.is_empty()) {
+            return Err(Error::KeyIsEmpty);
+        }
+        for (key, value) in pairs {
+            self.check_kv_size(key, value.len())?;
+        }
+        self.check_writable()?;
+        self.throttle_write(
+            pairs
+                .iter()
+                .map(|(key, value)| key.len() + value.len())
+                .sum(),
+        );
+        let timestamp = now_millis();
+        self.evict_if_needed()?;
+        let _rotate_guard = self.rotate_lock.read();
+        self.check_db_size()?;
+        let mut active_file = self.active_file.write();
+        for (key, value) in pairs {
+            let record = LogRecord {
+                key: log_record_key_with_seq_num(key, NON_TRANSACTION_SEQ_NUM),
+                value: value.to_vec(),
+                record_type: LogRecordType::NORMAL,
+                timestamp,
+                expire_at: 0,
+            };
+            self.check_index_memory(key)?;
+            self.add_to_bloom_filter(key);
+            let pos =
+                self.check_disk_full(self.append_to_active_file(&mut active_file, &record))?;
+            if let Some(old_pos) = self.index.put(key.to_vec(), pos) {
+                self.add_reclaim_size(&old_pos);
+            }
+        }
+        if self.options.sync_write {
+            self.check_disk_full(active_file.sync())?;
+        }
+        drop(active_file);
+        let updates = pairs
+            .iter()
+            .map(|(key, value)| (key.as_ref(), Some(value.as_ref())))
+            .collect::<Vec<_>>();
+        self.after_commit(&updates);
+        Ok(())
+    }
+
+    /// 比较并交换，只有key当前的值等于expected时才写入new，返回是否写入成功
+    ///
+    /// expected为None表示key不存在，new为None表示删除key。
+    /// 比较和写入期间持有rotate_lock的写锁，与所有写操作互斥
+    pub fn compare_and_swap(
+        &self,
+        key: Bytes,
+        expected: Option<Bytes>,
+        new: Option<Bytes>,
+    ) -> Result<bool> {
+        self.check_closed()?;
+        if key.is_empty() {
+            return Err(Error::KeyIsEmpty);
+        }
+        if let Some(value) = &new {
+            self.check_kv_size(&key, value.len())?;
+        }
+        self.evict_if_needed()?;
+        let _rotate_guard = self.rotate_lock.write();
+        let current = match self.get(key.clone()) {
+            Ok(value) => Some(value),
+            Err(Error::KeyNotFound) => None,
+            Err(e) => return Err(e),
+        };
+        if current != expected {
+            return Ok(false);
+        }
+        match new {
+            Some(value) => {
+                self.put_locked(&key, &value, None)?;
+            }
+            None if current.is_some() => self.delete_locked(&key)?,
+            None => {}
+        }
+        Ok(true)
+    }
+
+    /// 将key的值作为整数加上delta，返回相加后的值，key不存在时视为0
+    ///
+    /// 保留key原有的过期时间，读取和写入期间持有rotate_lock的写锁，与所有写操作互斥
+    pub fn incr(&self, key: Bytes, delta: i64) -> Result<i64> {
+        self.check_closed()?;
+        if key.is_empty() {
+            return Err(Error::KeyIsEmpty);
+        }
+        self.evict_if_needed()?;
+        let _rotate_guard = self.rotate_lock.write();
+        let (current, ttl) = match self.get_log_record(&key) {
+            Ok(log_record) => {
+                let current = std::str::from_utf8(&log_record.value)
+                    .ok()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .ok_or(Error::ValueIsNotInteger)?;
+                (current, remaining_ttl(log_record.expire_at))
+            }
+            Err(Error::KeyNotFound) => (0, None),
+            Err(e) => return Err(e),
+        };
+        let value = current.checked_add(delta).ok_or(Error::ValueIsNotInteger)?;
+        self.put_locked(&key, value.to_string().as_bytes(), ttl)?;
+        Ok(value)
+    }
+
+    /// 将suffix追加到key的值的末尾，返回追加后value的长度，key不存在时视为空值
+    ///
+    /// 保留key原有的过期时间，读取和写入期间持有rotate_lock的写锁，与所有写操作互斥
+    pub fn append(&self, key: Bytes, suffix: Bytes) -> Result<usize> {
+        self.check_closed()?;
+        if key.is_empty() {
+            return Err(Error::KeyIsEmpty);
         }
+        self.evict_if_needed()?;
+        let _rotate_guard = self.rotate_lock.write();
+        let (mut value, ttl) = match self.get_log_record(&key) {
+            Ok(log_record) => (log_record.value, remaining_ttl(log_record.expire_at)),
+            Err(Error::KeyNotFound) => (Vec::new(), None),
+            Err(e) => return Err(e),
+        };
+        value.extend_from_slice(&suffix);
+        let len = value.len();
+        self.check_kv_size(&key, len)?;
+        self.put_locked(&key, &value, ttl)?;
+        Ok(len)
     }
 
     /// 从数据库中删除数据
     pub fn delete(&self, key: Bytes) -> Result<()> {
+        telemetry::timed("delete", || self.delete_key(key))
+    }
+
+    fn delete_key(&self, key: Bytes) -> Result<()> {
+        self.check_closed()?;
         if key.is_empty() {
             return Err(Error::KeyIsEmpty);
         }
@@ -164,111 +914,361 @@ impl Engine {
         if pos.is_none() {
             return Ok(());
         }
+        let _rotate_guard = self.rotate_lock.read();
+        self.delete_locked(&key)
+    }
+
+    /// 写入删除记录并更新索引，调用方需要持有rotate_lock
+    fn delete_locked(&self, key: &[u8]) -> Result<()> {
         // 构造删除的log record, 事务编号为0表示非事务写入的数据
         let log_record = LogRecord {
-            key: log_record_key_with_seq_num(&key, NON_TRANSACTION_SEQ_NUM),
+            key: log_record_key_with_seq_num(key, NON_TRANSACTION_SEQ_NUM),
             value: Default::default(),
             record_type: LogRecordType::DELETE,
+            timestamp: now_millis(),
+            expire_at: 0,
         };
         let pos = self.append_log_record(&log_record)?;
+        // 删除记录本身也是无效数据
+        self.add_reclaim_size(&pos);
         // 更新内存索引
-        if !self.index.delete(key.to_vec()) {
-            return Err(Error::FailedToUpdateIndex);
+        match self.index.delete(key.to_vec()) {
+            Some(old_pos) => self.add_reclaim_size(&old_pos),
+            None => return Err(Error::FailedToUpdateIndex),
         }
+        self.after_commit(&[(key, None)]);
         Ok(())
     }
 
+    /// 删除所有以prefix开头的key，返回删除的数量
+    ///
+    /// 逐个写入删除记录，中途出错时已删除的key不会恢复
+    pub fn delete_prefix(&self, prefix: &[u8]) -> Result<usize> {
+        let (keys, _) = self.list_keys_with(prefix, usize::MAX, None)?;
+        for key in keys.iter() {
+            self.delete(key.clone())?;
+        }
+        Ok(keys.len())
+    }
+
+    /// 数据提交后更新二级索引并通知订阅者，value为None表示数据被删除
+    ///
+    /// 不能在持有活跃数据文件的锁时调用
+    pub(crate) fn after_commit(&self, updates: &[(&[u8], Option<&[u8]>)]) {
+        if let Some(tracker) = &self.access_tracker {
+            let mut tracker = tracker.lock();
+            for (key, value) in updates {
+                match value {
+                    Some(_) => tracker.touch(key),
+                    None => tracker.remove(key),
+                }
+            }
+        }
+        self.update_secondary_indexes(updates);
+        self.notify_watchers(updates);
+    }
+
     /// 追加写入活跃数据文件
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key_len = record.key.len(), file_id, offset)
+        )
+    )]
     pub(crate) fn append_log_record(&self, record: &LogRecord) -> Result<LogRecordPos> {
-        // 数据库目录
-        let dir_path = self.options.dir_path.clone();
-        // 编码输入数据
-        let encoded_data = record.encode();
-        let encoded_len = encoded_data.len() as u64;
+        self.check_writable()?;
+        self.throttle_write(record.key.len() + record.value.len());
         // 获取活跃数据文件
         let mut active_file = self.active_file.write();
-        // 如果活跃数据文件满了，则创建新的活跃数据文件
-        if active_file.get_write_offset() + encoded_len > self.options.data_file_size {
-            // 持久化当前活跃数据文件
-            active_file.sync()?;
+        let pos = self.check_disk_full(self.append_to_active_file(&mut active_file, record))?;
+        telemetry::record_pos(&pos);
 
-            // 将当前活跃数据文件移动到旧数据文件中
-            let current_file_id = active_file.get_file_id();
-            let mut older_files = self.older_files.write();
-            let old_file = DataFile::new(&dir_path, current_file_id)?;
-            older_files.insert(current_file_id, old_file);
+        // 根据配置决定是否持久化
+        if self.options.sync_write {
+            self.check_disk_full(active_file.sync())?;
+        }
+        Ok(pos)
+    }
 
-            // 创建新的活跃数据文件
-            let new_active_file = DataFile::new(&dir_path, current_file_id + 1)?;
-            *active_file = new_active_file;
+    /// 批量追加写入活跃数据文件，写入同一个数据文件的数据只使用一次writev，
+    /// 返回的位置信息与records一一对应
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(records = records.len(), file_id, offset)
+        )
+    )]
+    pub(crate) fn append_log_records(&self, records: &[LogRecord]) -> Result<Vec<LogRecordPos>> {
+        self.throttle_write(
+            records
+                .iter()
+                .map(|record| record.key.len() + record.value.len())
+                .sum(),
+        );
+        // 需要分块存储的数据在获取锁后写入分块，再编码记录分块位置的记录
+        let encoded_records = records
+            .iter()
+            .map(|record| match self.should_chunk(record) {
+                true => Ok(None),
+                false => self.encode_log_record(record).map(Some),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // 写入前检查所有数据，避免只写入一部分
+        for encoded_record in encoded_records.iter().flatten() {
+            self.check_record_size(encoded_record.len() as u64)?;
+        }
+        self.check_writable()?;
+        let mut active_file = self.active_file.write();
+        // 分块在记录分块位置的记录写入前不会被索引，写入失败时留下的分块只是无效数据
+        let mut chunked_records = Vec::with_capacity(records.len());
+        for (record, encoded_record) in records.iter().zip(encoded_records) {
+            let encoded_record = match encoded_record {
+                Some(encoded_record) => encoded_record,
+                None => {
+                    let head = self.check_disk_full(self.write_chunks(&mut active_file, record))?;
+                    let encoded_record = self.encode_log_record(&head)?;
+                    self.check_record_size(encoded_record.len() as u64)?;
+                    encoded_record
+                }
+            };
+            chunked_records.push(encoded_record);
+        }
+        let encoded_records = chunked_records;
+        let mut positions = Vec::with_capacity(records.len());
+        let mut start = 0;
+        while start < encoded_records.len() {
+            self.check_disk_full(self.rotate_active_file_if_full(
+                &mut active_file,
+                encoded_records[start].len() as u64,
+            ))?;
+            // 当前活跃数据文件能容纳的数据，至少写入一条
+            let file_id = active_file.get_file_id();
+            let mut offset = active_file.get_write_offset();
+            let mut end = start;
+            while end < encoded_records.len()
+                && (end == start
+                    || offset + encoded_records[end].len() as u64 <= self.options.data_file_size)
+            {
+                let size = encoded_records[end].len() as u32;
+                positions.push(LogRecordPos {
+                    file_id,
+                    offset,
+                    size,
+                });
+                offset += size as u64;
+                end += 1;
+            }
+            let bufs = encoded_records[start..end]
+                .iter()
+                .map(|buf| buf.as_slice())
+                .collect::<Vec<_>>();
+            self.check_disk_full(active_file.write_vectored(&bufs))?;
+            self.add_db_size(bufs.iter().map(|buf| buf.len() as u64).sum());
+            self.file_counters
+                .lock()
+                .add_records(file_id, (end - start) as u64, offset);
+            start = end;
         }
-        // 写入数据到活跃数据文件
-        let write_offset = active_file.get_write_offset();
-        active_file.write(&encoded_data)?;
 
-        // 根据配置决定是否持久化
         if self.options.sync_write {
-            active_file.sync()?;
+            self.check_disk_full(active_file.sync())?;
+        }
+        if let Some(pos) = positions.first() {
+            telemetry::record_pos(pos);
+        }
+        Ok(positions)
+    }
+
+    /// 在已持有活跃数据文件写锁的情况下追加写入，活跃数据文件满了时切换新的活跃数据文件
+    pub(crate) fn append_to_active_file(
+        &self,
+        active_file: &mut DataFile,
+        record: &LogRecord,
+    ) -> Result<LogRecordPos> {
+        // 较大的value先写入各个分块，再写入记录分块位置的记录
+        if self.should_chunk(record) {
+            let head = self.write_chunks(active_file, record)?;
+            return self.append_to_active_file(active_file, &head);
         }
+        // 编码输入数据
+        let encoded_data = self.encode_log_record(record)?;
+        let encoded_len = encoded_data.len() as u64;
+        self.check_record_size(encoded_len)?;
+        self.rotate_active_file_if_full(active_file, encoded_len)?;
+        // 写入数据到活跃数据文件
+        let write_offset = active_file.get_write_offset();
+        active_file.write(&encoded_data)?;
+        self.add_db_size(encoded_len);
+        self.file_counters.lock().add_records(
+            active_file.get_file_id(),
+            1,
+            active_file.get_write_offset(),
+        );
 
         // 返回活跃数据文件的内存索引信息
         Ok(LogRecordPos {
             file_id: active_file.get_file_id(),
             offset: write_offset,
+            size: encoded_len as u32,
         })
     }
 
+    /// 使用配置的压缩算法、密钥和校验算法编码数据
+    pub(crate) fn encode_log_record(&self, record: &LogRecord) -> Result<Vec<u8>> {
+        record.encode_with(
+            self.options.compression,
+            self.cipher.as_deref(),
+            self.options.checksum,
+            Some(&self.dictionaries),
+        )
+    }
+
+    /// 活跃数据文件写不下len字节的数据时，切换新的活跃数据文件
+    fn rotate_active_file_if_full(&self, active_file: &mut DataFile, len: u64) -> Result<()> {
+        // 如果活跃数据文件满了，则创建新的活跃数据文件
+        if active_file.get_write_offset() + len > self.options.data_file_size {
+            self.rotate_active_file(active_file)?;
+        }
+        Ok(())
+    }
+
+    /// 将活跃数据文件移动到旧数据文件中，并创建新的活跃数据文件
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(file_id = active_file.get_file_id()))
+    )]
+    pub(crate) fn rotate_active_file(&self, active_file: &mut DataFile) -> Result<()> {
+        // 持久化当前活跃数据文件
+        active_file.sync()?;
+
+        // 将当前活跃数据文件移动到旧数据文件中
+        let current_file_id = active_file.get_file_id();
+        let mut older_files = self.older_files.write();
+        let old_file = self.open_data_file(self.data_file_dir(current_file_id), current_file_id)?;
+        older_files.insert(current_file_id, old_file);
+
+        // 创建新的活跃数据文件
+        let new_file_id = current_file_id + 1;
+        let new_active_file =
+            self.open_active_file(self.data_file_dir(new_file_id), new_file_id)?;
+        *active_file = new_active_file;
+        self.add_db_size(DATA_FILE_HEADER_SIZE);
+
+        // 无效数据过多时，在后台merge
+        self.try_auto_merge();
+        Ok(())
+    }
+
+    /// 加载索引和事务编号，完成后切换为配置的IO类型，file_ids为按ID从小到大排序的数据文件ID
+    fn load_state(&self, file_ids: &[u32], progress: &mut OpenProgressTracker) -> Result<()> {
+        let seq_num = self.load_index(file_ids, progress)?;
+        // merge后的数据文件中不再保留事务编号，优先使用持久化的事务编号
+        let seq_num = load_seq_num(&self.options.dir_path)
+            .unwrap_or_default()
+            .max(seq_num + 1)
+            .max(self.seq_num.load(Ordering::SeqCst));
+        self.seq_num.store(seq_num, Ordering::SeqCst);
+        self.rebuild_bloom_filter()?;
+        // 加载完成后，数据文件切换为标准文件IO
+        if self.options.startup_io_type != self.options.io_type {
+            self.reset_io_type()?;
+        }
+        // 修改了校验算法时切换新的活跃数据文件，同一个数据文件中的数据使用相同的校验算法
+        let mut active_file = self.active_file.write();
+        if active_file.checksum() != self.options.checksum {
+            self.rotate_active_file(&mut active_file)?;
+        }
+        Ok(())
+    }
+
+    /// 加载索引，返回数据文件中最大的事务序列号，持久化的索引可以直接使用时不需要加载数据文件
+    fn load_index(&self, file_ids: &[u32], progress: &mut OpenProgressTracker) -> Result<usize> {
+        match self.index.try_reuse()? {
+            true => {
+                self.restore_write_offset()?;
+                Ok(NON_TRANSACTION_SEQ_NUM)
+            }
+            false => self.load_index_from_data_files(file_ids, progress),
+        }
+    }
+
     /// 从数据文件中加载索引
-    fn load_index_from_data_files(&self) -> Result<usize> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(files = file_ids.len()))
+    )]
+    fn load_index_from_data_files(
+        &self,
+        file_ids: &[u32],
+        progress: &mut OpenProgressTracker,
+    ) -> Result<usize> {
         let mut current_seq_num = NON_TRANSACTION_SEQ_NUM;
-        if self.file_ids.is_empty() {
+        if file_ids.is_empty() {
             return Ok(current_seq_num);
         }
+        // 优先从索引检查点中加载索引，否则merge后的数据文件直接从hint文件中加载索引，
+        // 之后只需要从start_file_id的start_offset处开始加载数据文件
+        let (start_file_id, start_offset) = match self.load_index_from_checkpoint(file_ids)? {
+            Some(checkpoint) => {
+                current_seq_num = checkpoint.seq_num.saturating_sub(1);
+                (checkpoint.file_id, checkpoint.offset)
+            }
+            None => match self.load_index_from_hint_file(progress)? {
+                Some(merged_file_id) => (merged_file_id + 1, DATA_FILE_HEADER_SIZE),
+                None => (INITIAL_FILE_ID, DATA_FILE_HEADER_SIZE),
+            },
+        };
+        let loaded_files = file_ids
+            .iter()
+            .filter(|file_id| **file_id < start_file_id)
+            .count();
+        if loaded_files > 0 {
+            progress.finish_files(loaded_files, self.index.len())?;
+        }
+        let start_offset = |file_id: u32| match file_id == start_file_id {
+            true => start_offset,
+            false => DATA_FILE_HEADER_SIZE,
+        };
+
         // 事务批量写入的数据，暂存到内存中
         // seq_num -> records
         let mut transaction_batch_records: HashMap<usize, Vec<TransactionRecord>> = HashMap::new();
-
-        let active_file = self.active_file.read();
-        let older_files = self.older_files.read();
-        // 遍历数据文件
-        for (i, file_id) in self.file_ids.iter().enumerate() {
-            let mut offset: u64 = 0;
-            // 遍历数据文件中的数据
-            loop {
-                let log_record_res = match *file_id == active_file.get_file_id() {
-                    true => active_file.read_log_record(offset),
-                    false => {
-                        let data_file = older_files.get(file_id).unwrap();
-                        data_file.read_log_record(offset)
-                    }
-                };
-                let (mut log_record, size) = match log_record_res {
-                    Ok(rc) => (rc.record, rc.size),
-                    Err(e) => {
-                        // 读取数据文件结束, 退出循环, 继续遍历下一个数据文件
-                        if e == Error::ReadDataFileEOF {
-                            break;
-                        }
-                        return Err(e);
+        // 按数据文件的顺序更新索引
+        let mut apply = |replay: FileReplay| {
+            current_seq_num = current_seq_num.max(replay.max_seq_num);
+            // 从检查点之后加载的数据文件在查询统计信息时再统计记录数量
+            if start_offset(replay.file_id) == DATA_FILE_HEADER_SIZE {
+                self.file_counters.lock().set_records(
+                    replay.file_id,
+                    replay.record_count,
+                    replay.end_offset,
+                );
+            }
+            for record in replay.records {
+                match record {
+                    // 非事务写入的数据，直接更新内存索引
+                    ReplayRecord::Record {
+                        key,
+                        record_type,
+                        pos,
+                    } => self.update_index(&key, record_type, pos),
+                    // 事务中提交的数据，暂存到内存中
+                    ReplayRecord::TxnRecord { seq_num, record } => {
+                        transaction_batch_records
+                            .entry(seq_num)
+                            .or_default()
+                            .push(record);
                     }
-                };
-                // 更新内存索引
-                let pos = LogRecordPos {
-                    file_id: *file_id,
-                    offset,
-                };
-                // 解析key，返回key和事务编号
-                let (key, seq_num) = parse_log_record_key(&log_record.key).unwrap();
-                // 非事务写入的数据，直接更新内存索引
-                if seq_num == NON_TRANSACTION_SEQ_NUM {
-                    self.update_index(&key, log_record.record_type, pos);
-                } else {
-                    // 事务批量写入的数据，暂存到内存中
-
                     // 表示一个事务的结束
-                    if log_record.record_type == LogRecordType::TXNFINISHED {
-                        // 当前事务的所有数据
-                        let records = transaction_batch_records.get(&seq_num).unwrap();
+                    ReplayRecord::TxnFinished { seq_num, pos } => {
+                        // 当前事务的所有数据，修复后的数据文件中事务的数据可能已经丢失
+                        let records = transaction_batch_records
+                            .remove(&seq_num)
+                            .unwrap_or_default();
                         // 更新内存索引
                         records.iter().for_each(|trans_record| {
                             self.update_index(
@@ -277,87 +1277,653 @@ impl Engine {
                                 trans_record.pos,
                             );
                         });
-                        // 移除当前事务的所有数据
-                        transaction_batch_records.remove(&seq_num);
-                    } else {
-                        // 事务中提交的数据，更新key
-                        log_record.key = key;
-                        // 暂存到内存中
-                        transaction_batch_records
-                            .entry(seq_num)
-                            .or_insert(Vec::new())
-                            .push(TransactionRecord {
-                                record: log_record,
-                                pos,
-                            });
+                        // 标识事务完成的记录不再需要
+                        self.add_reclaim_size(&pos);
                     }
                 }
+            }
+        };
 
-                // 更新事务序列号
-                if seq_num > current_seq_num {
-                    current_seq_num = seq_num;
-                }
-
-                // 更新偏移量
-                offset += size as u64;
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        // 旧的数据文件不会再被修改，并行读取，再按ID从小到大的顺序更新索引
+        let older_file_ids = file_ids
+            .iter()
+            .filter(|file_id| **file_id != active_file.get_file_id() && **file_id >= start_file_id)
+            .collect::<Vec<_>>();
+        let threads = match self.options.startup_threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        for file_ids in older_file_ids.chunks(threads) {
+            let replays = std::thread::scope(|s| {
+                let handles = file_ids
+                    .iter()
+                    .map(|file_id| {
+                        let data_file = older_files.get(file_id).unwrap();
+                        let offset = start_offset(**file_id);
+                        s.spawn(move || replay_data_file(data_file, offset, false))
+                    })
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("index loading thread panicked"))
+                    .collect::<Vec<_>>()
+            });
+            for replay in replays {
+                let replay = replay?;
+                progress.add_replayed(replay.bytes);
+                apply(replay);
+                progress.finish_files(1, self.index.len())?;
             }
-            // 最后一个数据文件处理完了，更新活跃数据文件的偏移量
-            if i == self.file_ids.len() - 1 {
-                active_file.set_write_offset(offset);
+        }
+
+        // 最后加载活跃数据文件，崩溃时活跃文件末尾的数据可能没有写完整，截断后继续启动
+        if active_file.get_file_id() >= start_file_id {
+            let file_id = active_file.get_file_id();
+            let replay = replay_data_file(&active_file, start_offset(file_id), true)?;
+            if replay.partial_write {
+                warn!(
+                    "truncate data file {} at offset {} due to a partial write",
+                    file_id, replay.end_offset
+                );
+                truncate_data_file(self.data_file_dir(file_id), file_id, replay.end_offset)?;
             }
+            let end_offset = replay.end_offset;
+            progress.add_replayed(replay.bytes);
+            apply(replay);
+            // 更新活跃数据文件的偏移量
+            active_file.set_write_offset(end_offset);
+            progress.finish_files(1, self.index.len())?;
         }
+        // 未完成的事务中的数据都是无效数据
+        transaction_batch_records
+            .values()
+            .flatten()
+            .for_each(|trans_record| self.add_reclaim_size(&trans_record.pos));
         Ok(current_seq_num)
     }
 
+    /// 不加载数据文件时，活跃数据文件从文件末尾开始写入
+    fn restore_write_offset(&self) -> Result<()> {
+        let active_file = self.active_file.read();
+        let file_id = active_file.get_file_id();
+        let file_path = get_data_file_full_path(self.data_file_dir(file_id), file_id);
+        match std::fs::metadata(&file_path) {
+            Ok(metadata) => active_file.set_write_offset(metadata.len()),
+            Err(source) => {
+                return Err(Error::FailedToOpenDataFile {
+                    path: file_path,
+                    source,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// 启动完成后将所有数据文件的IO类型切换为配置的IO类型
+    fn reset_io_type(&self) -> Result<()> {
+        let io_type = self.options.io_type;
+        self.active_file.write().set_io_manager(io_type)?;
+        for data_file in self.older_files.write().values_mut() {
+            data_file.set_io_manager(io_type)?;
+        }
+        Ok(())
+    }
+
     fn update_index(&self, key: &[u8], record_type: LogRecordType, pos: LogRecordPos) {
-        match record_type {
-            LogRecordType::NORMAL => {
+        let old_pos = match record_type {
+            LogRecordType::NORMAL | LogRecordType::CHUNKED => self.index.put(key.to_vec(), pos),
+            // 操作数之前的记录仍然有效
+            LogRecordType::OPERAND => {
                 self.index.put(key.to_vec(), pos);
+                None
             }
-            // 删除数据
+            // 删除数据，删除记录本身也是无效数据
             LogRecordType::DELETE => {
-                self.index.delete(key.to_vec());
+                self.add_reclaim_size(&pos);
+                self.index.delete(key.to_vec())
+            }
+            _ => None,
+        };
+        if let Some(old_pos) = old_pos {
+            self.add_reclaim_size(&old_pos);
+        }
+    }
+
+    /// 启动后台线程，每隔interval持久化一次活跃数据文件
+    fn start_sync_worker(&self, interval: Duration) {
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+        let engine = self.background_handle();
+        let handle = std::thread::spawn(move || {
+            // 超时表示需要持久化，收到消息或者sender被drop时退出
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                if let Err(e) = engine.active_file.read().sync() {
+                    error!("background sync failed: {}", e);
+                }
+            }
+        });
+        *self.sync_worker.lock() = Some((stop_tx, handle));
+    }
+
+    /// 后台线程使用的句柄，与当前句柄共享所有数据
+    pub(crate) fn background_handle(&self) -> Engine {
+        Engine {
+            options: self.options.clone(),
+            active_file: self.active_file.clone(),
+            older_files: self.older_files.clone(),
+            index: self.index.clone(),
+            batch_commit_lock: self.batch_commit_lock.clone(),
+            seq_num: self.seq_num.clone(),
+            merging_lock: self.merging_lock.clone(),
+            rotate_lock: self.rotate_lock.clone(),
+            reclaim_size: self.reclaim_size.clone(),
+            db_size: self.db_size.clone(),
+            file_counters: self.file_counters.clone(),
+            lock_file: None,
+            mem_dir_lock: None,
+            merge_control: self.merge_control.clone(),
+            merge_worker: self.merge_worker.clone(),
+            cipher: self.cipher.clone(),
+            dictionaries: self.dictionaries.clone(),
+            bloom_filter: self.bloom_filter.clone(),
+            value_cache: self.value_cache.clone(),
+            access_tracker: self.access_tracker.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            merge_rate_limiter: self.merge_rate_limiter.clone(),
+            sync_worker: self.sync_worker.clone(),
+            checkpoint_worker: self.checkpoint_worker.clone(),
+            closed: self.closed.clone(),
+            read_only: self.read_only.clone(),
+            secondary_indexes: self.secondary_indexes.clone(),
+            watchers: self.watchers.clone(),
+            commit_seq: self.commit_seq.clone(),
+            snapshots: self.snapshots.clone(),
+            merge_operator: self.merge_operator.clone(),
+            slow_op_listener: self.slow_op_listener.clone(),
+        }
+    }
+
+    /// 以配置的IO类型打开数据文件，读取加密的数据时使用数据库的密钥解密
+    pub(crate) fn open_data_file(
+        &self,
+        dir_path: impl AsRef<Path>,
+        file_id: u32,
+    ) -> Result<DataFile> {
+        Ok(DataFile::new_with_checksum(
+            dir_path,
+            file_id,
+            self.options.io_type,
+            self.options.checksum,
+        )?
+        .with_cipher(self.cipher.clone())
+        .with_dictionaries(Some(self.dictionaries.clone())))
+    }
+
+    /// 打开新的活跃数据文件，只有活跃数据文件使用写缓冲区
+    pub(crate) fn open_active_file(
+        &self,
+        dir_path: impl AsRef<Path>,
+        file_id: u32,
+    ) -> Result<DataFile> {
+        let data_file = self
+            .open_data_file(dir_path, file_id)?
+            .with_write_buffer(self.options.write_buffer_size);
+        // 预分配失败时不影响写入
+        if self.options.preallocate_data_files {
+            if let Err(e) = data_file.preallocate(self.options.data_file_size) {
+                warn!("{}", e);
+            }
+        }
+        Ok(data_file)
+    }
+
+    /// 持久化下一个可用的事务编号
+    pub(crate) fn save_seq_num(&self, dir_path: impl AsRef<Path>) -> Result<()> {
+        // 内存数据库关闭后数据丢失，不需要持久化
+        if self.is_in_memory() {
+            return Ok(());
+        }
+        let file_path = dir_path.as_ref().join(SEQ_NUM_FILE_NAME);
+        if file_path.is_file() {
+            if let Err(source) = std::fs::remove_file(&file_path) {
+                return Err(Error::FailedToRemoveSeqNumFile {
+                    path: file_path,
+                    source,
+                });
             }
-            _ => {}
         }
+        let seq_num_file = DataFile::new_seq_num_file(dir_path)?;
+        let record = LogRecord {
+            key: SEQ_NUM_KEY.to_vec(),
+            value: self.seq_num.load(Ordering::SeqCst).to_string().into_bytes(),
+            record_type: LogRecordType::NORMAL,
+            timestamp: now_millis(),
+            expire_at: 0,
+        };
+        seq_num_file.write(&record.encode())?;
+        seq_num_file.sync()
+    }
+
+    /// 写入数据前将key加入布隆过滤器，保证读取时不会误判为不存在
+    pub(crate) fn add_to_bloom_filter(&self, key: &[u8]) {
+        if let Some(bloom_filter) = &self.bloom_filter {
+            bloom_filter.write().insert(key);
+        }
+    }
+
+    /// 布隆过滤器判断key是否可能存在，未开启布隆过滤器时总是返回true
+    pub(crate) fn may_contain_key(&self, key: &[u8]) -> bool {
+        self.bloom_filter
+            .as_ref()
+            .is_none_or(|bloom_filter| bloom_filter.read().may_contain(key))
+    }
+
+    /// 根据内存索引重建布隆过滤器，清理已删除的key，调用时不能有并发的写操作
+    pub(crate) fn rebuild_bloom_filter(&self) -> Result<()> {
+        let Some(bloom_filter) = &self.bloom_filter else {
+            return Ok(());
+        };
+        let keys = self.index.list_keys()?;
+        // 预留空间给之后写入的key
+        let mut new_bloom_filter =
+            BloomFilter::new(keys.len() * 2, self.options.bloom_filter_bits_per_key);
+        keys.iter().for_each(|key| new_bloom_filter.insert(key));
+        *bloom_filter.write() = new_bloom_filter;
+        Ok(())
+    }
+
+    /// 开启写入限速时等待写入bytes字节的限额，不能在持有活跃数据文件的锁时调用
+    pub(crate) fn throttle_write(&self, bytes: usize) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(bytes);
+        }
+    }
+
+    /// 开启merge限速时等待merge读取或写入bytes字节的限额，不能在持有锁时调用
+    pub(crate) fn throttle_merge(&self, bytes: usize) {
+        if let Some(rate_limiter) = &self.merge_rate_limiter {
+            rate_limiter.acquire(bytes);
+        }
+    }
+
+    /// pos指向的数据成为无效数据，累加无效数据的大小
+    pub(crate) fn add_reclaim_size(&self, pos: &LogRecordPos) {
+        self.reclaim_size
+            .fetch_add(pos.size as usize, Ordering::SeqCst);
+        self.file_counters.lock().add_dead_size(pos);
+    }
+
+    /// 获取数据库统计信息
+    pub fn stat(&self) -> Result<Stat> {
+        self.check_closed()?;
+        let key_num = self.index.len();
+        let data_file_num = self.older_files.read().len() + 1;
+        let stat = Stat {
+            key_num,
+            data_file_num,
+            reclaimable_size: self.reclaim_size.load(Ordering::SeqCst),
+            index_memory_usage: self.index.memory_usage(),
+            disk_size: self.disk_size()?,
+        };
+        telemetry::record_stat(&stat);
+        Ok(stat)
+    }
+
+    /// 数据库目录
+    pub fn dir_path(&self) -> &Path {
+        &self.options.dir_path
+    }
+
+    /// 打开数据库时使用的配置项
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// 所有数据文件的ID，按从小到大排序，包含活跃数据文件
+    pub fn data_file_ids(&self) -> Vec<u32> {
+        let active_file = self.active_file.read();
+        let mut file_ids = self.older_files.read().keys().copied().collect::<Vec<_>>();
+        file_ids.push(active_file.get_file_id());
+        file_ids.sort();
+        file_ids
+    }
+
+    /// 活跃数据文件的ID
+    pub fn active_file_id(&self) -> u32 {
+        self.active_file.read().get_file_id()
+    }
+
+    /// 活跃数据文件的写入位置
+    pub fn active_file_offset(&self) -> u64 {
+        self.active_file.read().get_write_offset()
+    }
+
+    /// 是否为内存数据库
+    pub(crate) fn is_in_memory(&self) -> bool {
+        self.options.io_type == IOType::Memory
+    }
+
+    /// 数据库目录和数据文件目录占据的磁盘空间大小，内存数据库为数据文件占用的内存大小
+    pub(crate) fn disk_size(&self) -> Result<u64> {
+        match self.is_in_memory() {
+            true => Ok(mem_io::dir_size(&self.options.dir_path)),
+            false => self.options.data_dirs().iter().map(dir_disk_size).sum(),
+        }
+    }
+
+    /// 数据文件所在的目录，内存数据库只使用数据库目录
+    pub(crate) fn data_file_dir(&self, file_id: u32) -> PathBuf {
+        match self.is_in_memory() || self.options.dir_paths.is_empty() {
+            true => self.options.dir_path.clone(),
+            false => find_data_file_dir(&self.options.data_dirs(), file_id),
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        // 后台线程使用的句柄不负责关闭数据库，用户持有的句柄中最后一个被drop时关闭
+        let lock_file = self.lock_file.take().and_then(Arc::into_inner);
+        let mem_dir_lock = self.mem_dir_lock.take().and_then(Arc::into_inner);
+        if lock_file.is_none() && mem_dir_lock.is_none() {
+            return;
+        }
+        // 关闭时释放文件锁，内存数据库目录的锁在关闭后释放
+        self.lock_file = lock_file.map(Arc::new);
+        if let Err(e) = self.close() {
+            error!("failed to close database: {}", e);
+        }
+    }
+}
+
+/// 过期时间对应的剩余存活时间，0表示永不过期
+fn remaining_ttl(expire_at: u64) -> Option<Duration> {
+    match expire_at {
+        0 => None,
+        expire_at => Some(Duration::from_millis(
+            expire_at.saturating_sub(now_millis()),
+        )),
+    }
+}
+
+/// 从数据文件中读取位置信息对应的有效log record，已删除或过期的数据视为不存在，
+/// 不合并merge_value写入的操作数
+fn read_log_record_at(
+    active_file: &DataFile,
+    older_files: &HashMap<u32, DataFile>,
+    pos: &LogRecordPos,
+) -> Result<LogRecord> {
+    let log_record = read_head_record_at(active_file, older_files, pos)?;
+    match log_record.record_type {
+        LogRecordType::CHUNKED => chunk::read_chunked_record(active_file, older_files, log_record),
+        _ => Ok(log_record),
+    }
+}
+
+/// 从数据文件中读取位置信息对应的有效log record，分块存储的数据不读取分块
+fn read_head_record_at(
+    active_file: &DataFile,
+    older_files: &HashMap<u32, DataFile>,
+    pos: &LogRecordPos,
+) -> Result<LogRecord> {
+    let log_record = read_log_record_from_files(active_file, older_files, pos)?;
+    // 过期的数据视为不存在
+    if log_record.is_expired() {
+        return Err(Error::KeyNotFound);
+    }
+    // 判断log record类型
+    match log_record.record_type {
+        LogRecordType::NORMAL | LogRecordType::CHUNKED | LogRecordType::OPERAND => Ok(log_record),
+        LogRecordType::DELETE => Err(Error::KeyNotFound),
+        _ => unreachable!(),
+    }
+}
+
+/// 获取数据库目录的文件锁，已被其他进程持有时返回DatabaseIsInUse
+pub(crate) fn lock_dir(dir_path: &Path) -> Result<File> {
+    let lock_file_path = dir_path.join(FILE_LOCK_NAME);
+    let lock_file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_file_path)
+        .map_err(|source| Error::FailedToOpenLockFile {
+            path: lock_file_path,
+            source,
+        })?;
+    if lock_file.try_lock_exclusive().is_err() {
+        return Err(Error::DatabaseIsInUse);
+    }
+    Ok(lock_file)
+}
+
+/// 加载索引时从数据文件中读取的一条记录
+enum ReplayRecord {
+    /// 非事务写入的数据
+    Record {
+        key: Vec<u8>,
+        record_type: LogRecordType,
+        pos: LogRecordPos,
+    },
+    /// 事务中写入的数据，事务结束时才更新索引
+    TxnRecord {
+        seq_num: usize,
+        record: TransactionRecord,
+    },
+    /// 标识事务完成的记录
+    TxnFinished { seq_num: usize, pos: LogRecordPos },
+}
+
+/// 读取一个数据文件的结果
+struct FileReplay {
+    file_id: u32,
+    /// 按顺序排列的记录，分块不建立索引，不包含在内
+    records: Vec<ReplayRecord>,
+    /// 读取的记录的总大小
+    bytes: u64,
+    /// 读取的记录数量，包含分块
+    record_count: u64,
+    /// 最大的事务编号
+    max_seq_num: usize,
+    /// 读取结束的偏移
+    end_offset: u64,
+    /// 文件末尾是否有没写完整的数据
+    partial_write: bool,
+}
+
+/// 从offset开始读取数据文件中的记录，allow_partial_write为true时遇到没写完整的数据停止读取
+fn replay_data_file(
+    data_file: &DataFile,
+    offset: u64,
+    allow_partial_write: bool,
+) -> Result<FileReplay> {
+    let file_id = data_file.get_file_id();
+    let mut replay = FileReplay {
+        file_id,
+        records: Vec::new(),
+        bytes: 0,
+        record_count: 0,
+        max_seq_num: NON_TRANSACTION_SEQ_NUM,
+        end_offset: offset,
+        partial_write: false,
+    };
+    // 从头到尾读取整个文件，提前读取到page cache
+    data_file.advise(Advice::Sequential);
+    data_file.advise(Advice::WillNeed);
+    let mut reader = data_file.reader(offset);
+    loop {
+        let offset = reader.offset();
+        let (mut log_record, size) = match reader.next_record() {
+            Ok(rc) => (rc.record, rc.size),
+            // 读取数据文件结束
+            Err(Error::ReadDataFileEOF) => break,
+            Err(Error::InvalidLogRecordCRC | Error::InvalidLogRecord) if allow_partial_write => {
+                replay.partial_write = true;
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        replay.bytes += size as u64;
+        replay.record_count += 1;
+        let pos = LogRecordPos {
+            file_id,
+            offset,
+            size: size as u32,
+        };
+        // 解析key，返回key和事务编号
+        let (key, seq_num) = parse_log_record_key(&log_record.key).unwrap();
+        replay.max_seq_num = replay.max_seq_num.max(seq_num);
+        let record = match (seq_num, log_record.record_type) {
+            (NON_TRANSACTION_SEQ_NUM, record_type) => ReplayRecord::Record {
+                key,
+                record_type,
+                pos,
+            },
+            (seq_num, LogRecordType::TXNFINISHED) => ReplayRecord::TxnFinished { seq_num, pos },
+            // 分块不建立索引，无需暂存
+            (_, LogRecordType::CHUNK) => continue,
+            (seq_num, _) => {
+                // 更新索引只需要key和记录类型
+                log_record.key = key;
+                log_record.value = Vec::new();
+                ReplayRecord::TxnRecord {
+                    seq_num,
+                    record: TransactionRecord {
+                        record: log_record,
+                        pos,
+                    },
+                }
+            }
+        };
+        replay.records.push(record);
+    }
+    replay.end_offset = reader.offset();
+    Ok(replay)
+}
+
+/// 从数据文件中读取位置信息对应的log record
+pub(crate) fn read_log_record_from_files(
+    active_file: &DataFile,
+    older_files: &HashMap<u32, DataFile>,
+    pos: &LogRecordPos,
+) -> Result<LogRecord> {
+    let data_file = match active_file.get_file_id() == pos.file_id {
+        true => active_file,
+        false => match older_files.get(&pos.file_id) {
+            Some(older_file) => older_file,
+            None => return Err(Error::DataFileNotFound),
+        },
+    };
+    Ok(data_file
+        .read_log_record_with_size(pos.offset, pos.size)?
+        .record)
+}
+
+/// 读取持久化的事务编号，文件不存在或者损坏时返回None
+fn load_seq_num(dir_path: impl AsRef<Path>) -> Option<usize> {
+    if !dir_path.as_ref().join(SEQ_NUM_FILE_NAME).is_file() {
+        return None;
     }
+    let record = match DataFile::new_seq_num_file(&dir_path).and_then(|f| f.read_log_record(0)) {
+        Ok(rc) => rc.record,
+        Err(e) => {
+            warn!("failed to read seq num file: {}", e);
+            return None;
+        }
+    };
+    String::from_utf8(record.value).ok()?.parse().ok()
 }
 
-/// 校验配置项
-fn check_options(opts: &Options) -> Result<()> {
-    if opts.dir_path.to_str().is_none() || opts.dir_path.to_str().unwrap().is_empty() {
-        return Err(Error::InvalidDbDir);
+/// 截断数据文件，丢弃offset之后的数据
+fn truncate_data_file(dir_path: impl AsRef<Path>, file_id: u32, offset: u64) -> Result<()> {
+    let file_path = get_data_file_full_path(dir_path, file_id);
+    File::options()
+        .write(true)
+        .open(&file_path)
+        .and_then(|file| file.set_len(offset))
+        .map_err(|source| Error::FailedToTruncateDataFile {
+            path: file_path,
+            offset,
+            source,
+        })
+}
+
+/// 目录中所有文件的大小之和
+pub(crate) fn dir_disk_size(dir_path: impl AsRef<Path>) -> Result<u64> {
+    let dir_path = dir_path.as_ref();
+    let mut size = 0;
+    for entry in read_dir(dir_path)? {
+        let metadata = entry
+            .metadata()
+            .map_err(|source| Error::FailedToReadDirEntry {
+                path: dir_path.to_path_buf(),
+                source,
+            })?;
+        if metadata.is_file() {
+            size += metadata.len();
+        }
     }
-    if opts.data_file_size <= 0 {
-        return Err(Error::InvalidDataFileSize);
+    Ok(size)
+}
+
+/// 读取目录中的所有条目
+pub(crate) fn read_dir(dir_path: &Path) -> Result<Vec<std::fs::DirEntry>> {
+    let read_dir_err = |source| Error::FailedToReadDir {
+        path: dir_path.to_path_buf(),
+        source,
+    };
+    std::fs::read_dir(dir_path)
+        .map_err(read_dir_err)?
+        .map(|entry| {
+            entry.map_err(|source| Error::FailedToReadDirEntry {
+                path: dir_path.to_path_buf(),
+                source,
+            })
+        })
+        .collect()
+}
+
+/// 将按ID从小到大排序的数据文件分为活跃数据文件和旧数据文件，没有数据文件时创建新的活跃数据文件
+fn split_data_files(
+    mut data_files: Vec<DataFile>,
+    opts: &Options,
+    cipher: &Option<Arc<Cipher>>,
+    dictionaries: &Arc<Dictionaries>,
+) -> Result<(DataFile, HashMap<u32, DataFile>)> {
+    // ID最大的数据文件为活跃数据文件
+    let active_file = match data_files.pop() {
+        Some(f) => f,
+        None => DataFile::new_with_checksum(
+            &opts.dir_path,
+            INITIAL_FILE_ID,
+            opts.startup_io_type,
+            opts.checksum,
+        )?
+        .with_cipher(cipher.clone())
+        .with_dictionaries(Some(dictionaries.clone())),
     }
-    Ok(())
+    .with_write_buffer(opts.write_buffer_size);
+    let older_files = data_files
+        .into_iter()
+        .map(|f| (f.get_file_id(), f))
+        .collect();
+    Ok((active_file, older_files))
 }
 
-/// 加载目录中的数据文件
-fn load_data_files(dir_path: impl AsRef<Path>) -> Result<Vec<DataFile>> {
-    let mut file_ids = Vec::new();
+/// 加载所有数据目录中的数据文件，按ID从小到大排序
+fn load_data_files(
+    data_dirs: &[PathBuf],
+    io_type: IOType,
+    cipher: &Option<Arc<Cipher>>,
+    dictionaries: &Arc<Dictionaries>,
+) -> Result<Vec<DataFile>> {
     let mut data_files = Vec::new();
-    for entry in std::fs::read_dir(dir_path.as_ref()).map_err(|_| Error::FailedToReadDir)? {
-        let entry = entry.map_err(|_| Error::FailedToReadDirEntry)?;
-        let file_os_name = entry.file_name();
-        let file_name = file_os_name.to_str().unwrap();
-        // 判断文件名是否是以.data结尾
-        if file_name.ends_with(crate::data::data_file::DATA_FILE_SUFFIX) {
-            let (id, _) = file_name.split_once(".").unwrap();
-            let id = id.parse::<u32>().map_err(|_| Error::FailedToParseFileId)?;
-            file_ids.push(id);
-        }
-    }
-    if file_ids.is_empty() {
-        return Ok(data_files);
-    }
-
-    file_ids.sort();
-    // 根据file_ids加载数据文件
-    for id in file_ids.iter() {
-        let data_file =
-            DataFile::new(dir_path.as_ref(), *id).map_err(|_| Error::FailedToCreateDataFile)?;
+    for (id, dir_path) in find_data_files(data_dirs)? {
+        let data_file = DataFile::new(&dir_path, id, io_type)?
+            .with_cipher(cipher.clone())
+            .with_dictionaries(Some(dictionaries.clone()));
         data_files.push(data_file);
     }
     Ok(data_files)
@@ -365,9 +1931,15 @@ fn load_data_files(dir_path: impl AsRef<Path>) -> Result<Vec<DataFile>> {
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
     use std::path::PathBuf;
 
+    use crate::index::bptree::BPTREE_INDEX_FILE_NAME;
+    use crate::options::{
+        BackupOptions, ChecksumType, CompressionType, IndexType, IteratorOptions, WriteOptions,
+    };
     use crate::util::rand_kv::{get_test_key, get_test_value};
+    use crate::watch::Event;
 
     use super::*;
 
@@ -383,7 +1955,7 @@ mod tests {
         assert!(put_res.is_ok());
         let get_res = engine.get(get_test_key(11));
         assert!(get_res.is_ok());
-        assert!(get_res.unwrap().len() > 0);
+        assert!(!get_res.unwrap().is_empty());
 
         // 重复put key相同的数据
         let put_res = engine.put(get_test_key(22), get_test_value(22));
@@ -396,7 +1968,7 @@ mod tests {
 
         // put空key
         let put_res = engine.put(Bytes::new(), get_test_value(123));
-        assert!(put_res.err().unwrap() == Error::KeyIsEmpty);
+        assert!(matches!(put_res.err().unwrap(), Error::KeyIsEmpty));
 
         // put空value
         let put_res = engine.put(get_test_key(33), Bytes::new());
@@ -437,11 +2009,11 @@ mod tests {
         assert!(put_res.is_ok());
         let get_res = engine.get(get_test_key(111));
         assert!(get_res.is_ok());
-        assert!(get_res.unwrap().len() > 0);
+        assert!(!get_res.unwrap().is_empty());
 
         // 读一个不存在的key
         let get_res = engine.get(Bytes::from("not_exist_key"));
-        assert!(get_res.err().unwrap() == Error::KeyNotFound);
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
 
         // 值被重复put后再读取
         let put_res = engine.put(get_test_key(222), get_test_value(222));
@@ -458,7 +2030,7 @@ mod tests {
         let delete_res = engine.delete(get_test_key(333));
         assert!(delete_res.is_ok());
         let get_res = engine.get(get_test_key(333));
-        assert!(get_res.err().unwrap() == Error::KeyNotFound);
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
 
         // 转换为旧的数据文件，从旧的数据文件获取value
         for i in 500..=1000000 {
@@ -477,7 +2049,7 @@ mod tests {
         let get_res = engine.get(get_test_key(222));
         assert_eq!(get_res.unwrap(), Bytes::from("a new value"));
         let get_res = engine.get(get_test_key(333));
-        assert!(get_res.err().unwrap() == Error::KeyNotFound);
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
         let get_res = engine.get(get_test_key(555));
         assert_eq!(get_res.unwrap(), get_test_value(555));
 
@@ -498,7 +2070,7 @@ mod tests {
         let delete_res = engine.delete(get_test_key(11));
         assert!(delete_res.is_ok());
         let get_res = engine.get(get_test_key(11));
-        assert!(get_res.err().unwrap() == Error::KeyNotFound);
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
 
         // 删除一个不存在的key
         let delete_res = engine.delete(get_test_key(22));
@@ -506,7 +2078,7 @@ mod tests {
 
         // 删除空key
         let delete_res = engine.delete(Bytes::new());
-        assert!(delete_res.err().unwrap() == Error::KeyIsEmpty);
+        assert!(matches!(delete_res.err().unwrap(), Error::KeyIsEmpty));
 
         // 删除后再次put
         let put_res = engine.put(get_test_key(33), get_test_value(33));
@@ -522,7 +2094,7 @@ mod tests {
         std::mem::drop(engine);
         let engine = Engine::open(opts.clone()).expect("failed to open engine");
         let get_res = engine.get(get_test_key(11));
-        assert!(get_res.err().unwrap() == Error::KeyNotFound);
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
         let get_res = engine.get(get_test_key(33));
         assert_eq!(get_res.unwrap(), Bytes::from("a new value"));
 
@@ -530,6 +2102,34 @@ mod tests {
         std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
     }
 
+    #[test]
+    fn test_engine_delete_prefix() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-delete-prefix");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..30 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        // 前缀bitcask-rs-key-00000001匹配10~19
+        assert_eq!(
+            engine.delete_prefix(b"bitcask-rs-key-00000001").unwrap(),
+            10
+        );
+        assert_eq!(engine.len(), 20);
+        assert!(!engine.contains_key(&get_test_key(15)));
+        assert!(engine.contains_key(&get_test_key(20)));
+        assert_eq!(engine.delete_prefix(b"not-exist").unwrap(), 0);
+        assert_eq!(engine.delete_prefix(b"").unwrap(), 20);
+        assert!(engine.is_empty());
+        std::mem::drop(engine);
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(engine.is_empty());
+        std::mem::drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
     #[test]
     fn test_engine_close() {
         let mut opts = Options::default();
@@ -541,6 +2141,48 @@ mod tests {
         assert!(put_res.is_ok());
         let close_res = engine.close();
         assert!(close_res.is_ok());
+
+        // 重复关闭直接返回，关闭后的操作返回DatabaseClosed
+        assert!(engine.close().is_ok());
+        assert!(matches!(
+            engine
+                .put(get_test_key(12), get_test_value(12))
+                .err()
+                .unwrap(),
+            Error::DatabaseClosed
+        ));
+        assert!(matches!(
+            engine.get(get_test_key(11)).err().unwrap(),
+            Error::DatabaseClosed
+        ));
+        assert!(matches!(
+            engine.delete(get_test_key(11)).err().unwrap(),
+            Error::DatabaseClosed
+        ));
+        assert!(matches!(
+            engine.sync().err().unwrap(),
+            Error::DatabaseClosed
+        ));
+        assert!(matches!(
+            engine.merge().err().unwrap(),
+            Error::DatabaseClosed
+        ));
+        assert!(engine.new_write_batch(WriteOptions::default()).is_err());
+
+        // 文件锁已释放，可以再次打开
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine2.get(get_test_key(11)).unwrap(), get_test_value(11));
+        std::mem::drop(engine2);
+        std::mem::drop(engine);
+
+        // drop时自动关闭数据库
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.put(get_test_key(12), get_test_value(12)).unwrap();
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 2);
+        std::mem::drop(engine);
+
         std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
     }
 
@@ -557,4 +2199,1784 @@ mod tests {
         assert!(sync_res.is_ok());
         std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
     }
+
+    #[test]
+    fn test_engine_skiplist_index() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-skiplist");
+        opts.data_file_size = 64 * 1024;
+        opts.index_type = IndexType::SkipList;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..2000 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+        for i in 0..500 {
+            let delete_res = engine.delete(get_test_key(i));
+            assert!(delete_res.is_ok());
+        }
+        let get_res = engine.get(get_test_key(100));
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
+        let get_res = engine.get(get_test_key(1000));
+        assert_eq!(get_res.unwrap(), get_test_value(1000));
+
+        // 重启数据库
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 1500);
+        let get_res = engine.get(get_test_key(1999));
+        assert_eq!(get_res.unwrap(), get_test_value(1999));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_mmap_at_startup() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-mmap-startup");
+        opts.data_file_size = 64 * 1024;
+        opts.startup_io_type = IOType::MemoryMap;
+
+        // 空目录
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..2000 {
+            let put_res = engine.put(get_test_key(i), get_test_value(i));
+            assert!(put_res.is_ok());
+        }
+        for i in 0..500 {
+            let delete_res = engine.delete(get_test_key(i));
+            assert!(delete_res.is_ok());
+        }
+
+        // 使用mmap加载数据后，可以继续写入
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 1500);
+        let get_res = engine.get(get_test_key(1000));
+        assert_eq!(get_res.unwrap(), get_test_value(1000));
+        let put_res = engine.put(get_test_key(1), get_test_value(1));
+        assert!(put_res.is_ok());
+        let get_res = engine.get(get_test_key(1));
+        assert_eq!(get_res.unwrap(), get_test_value(1));
+
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 1501);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_write_buffer() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-write-buffer");
+        opts.data_file_size = 64 * 1024;
+        opts.write_buffer_size = 4096;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..2000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..500 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        // 缓冲区中的数据可以读取
+        assert_eq!(
+            engine.get(get_test_key(1999)).unwrap(),
+            get_test_value(1999)
+        );
+        assert_eq!(engine.scan(IteratorOptions::default()).count(), 1500);
+
+        // 关闭时写入文件
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 1500);
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+        engine.merge().unwrap();
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(1));
+        assert_eq!(
+            engine.get(get_test_key(1999)).unwrap(),
+            get_test_value(1999)
+        );
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_engine_direct_io() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-direct-io");
+        opts.data_file_size = 64 * 1024;
+        opts.io_type = IOType::DirectIO;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..2000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..500 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        assert_eq!(
+            engine.get(get_test_key(1000)).unwrap(),
+            get_test_value(1000)
+        );
+
+        // 重启后文件大小与写入的数据一致，可以继续写入
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 1500);
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+        engine.merge().unwrap();
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(1));
+        assert_eq!(
+            engine.get(get_test_key(1999)).unwrap(),
+            get_test_value(1999)
+        );
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_memory_io() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-memory-io");
+        opts.data_file_size = 64 * 1024;
+        opts.io_type = IOType::Memory;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        // 同一目录同时只能被一个数据库使用
+        assert!(matches!(
+            Engine::open(opts.clone()),
+            Err(Error::DatabaseIsInUse)
+        ));
+        for i in 0..2000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..500 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        let stat = engine.stat().unwrap();
+        assert!(stat.data_file_num > 1);
+        assert!(stat.disk_size > 0);
+
+        engine.merge().unwrap();
+        assert_eq!(engine.list_keys().unwrap().len(), 1500);
+        assert!(engine.stat().unwrap().disk_size < stat.disk_size);
+        assert_eq!(
+            engine.get(get_test_key(1000)).unwrap(),
+            get_test_value(1000)
+        );
+        assert!(matches!(
+            engine.backup("/tmp/bitcask-rs-memory-io-backup", BackupOptions::default()),
+            Err(Error::BackupNotSupported)
+        ));
+        // 不读写文件系统
+        assert!(!opts.dir_path.exists());
+
+        // 关闭后数据丢失
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(engine.is_empty());
+        assert_eq!(engine.stat().unwrap().disk_size, DATA_FILE_HEADER_SIZE);
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn test_engine_io_uring() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-io-uring");
+        opts.data_file_size = 64 * 1024;
+        opts.io_type = IOType::IoUring;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..2000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..500 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        engine.sync().unwrap();
+        assert_eq!(
+            engine.get(get_test_key(1000)).unwrap(),
+            get_test_value(1000)
+        );
+
+        // 重启后使用io_uring加载数据
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 1500);
+        engine.merge().unwrap();
+        assert_eq!(
+            engine.get(get_test_key(1999)).unwrap(),
+            get_test_value(1999)
+        );
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_put_with_ttl() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-put-with-ttl");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let put_res = engine.put_with_ttl(get_test_key(1), get_test_value(1), Duration::ZERO);
+        assert!(put_res.is_ok());
+        let put_res =
+            engine.put_with_ttl(get_test_key(2), get_test_value(2), Duration::from_secs(60));
+        assert!(put_res.is_ok());
+        let put_res = engine.put(get_test_key(3), get_test_value(3));
+        assert!(put_res.is_ok());
+
+        // 过期的数据读取不到
+        let get_res = engine.get(get_test_key(1));
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
+        let get_res = engine.get(get_test_key(2));
+        assert_eq!(get_res.unwrap(), get_test_value(2));
+
+        // 重新写入后不再过期
+        let put_res = engine.put(get_test_key(1), get_test_value(11));
+        assert!(put_res.is_ok());
+        let get_res = engine.get(get_test_key(1));
+        assert_eq!(get_res.unwrap(), get_test_value(11));
+
+        // 重启后过期时间仍然有效
+        let put_res = engine.put_with_ttl(
+            get_test_key(4),
+            get_test_value(4),
+            Duration::from_millis(200),
+        );
+        assert!(put_res.is_ok());
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let get_res = engine.get(get_test_key(2));
+        assert_eq!(get_res.unwrap(), get_test_value(2));
+        std::thread::sleep(Duration::from_millis(300));
+        let get_res = engine.get(get_test_key(4));
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
+
+        // 迭代器跳过过期的数据
+        let iter = engine.iter(Default::default());
+        let mut keys = vec![];
+        while let Some((key, _)) = iter.next() {
+            keys.push(key);
+        }
+        assert_eq!(
+            keys,
+            vec![get_test_key(1), get_test_key(2), get_test_key(3)]
+        );
+
+        // merge清理过期的数据
+        let merge_res = engine.merge();
+        assert!(merge_res.is_ok());
+        assert_eq!(engine.list_keys().unwrap().len(), 3);
+        let get_res = engine.get(get_test_key(4));
+        assert!(matches!(get_res.err().unwrap(), Error::KeyNotFound));
+        let get_res = engine.get(get_test_key(2));
+        assert_eq!(get_res.unwrap(), get_test_value(2));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_stat() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-stat");
+        opts.data_file_size = 32 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let stat = engine.stat().unwrap();
+        assert_eq!(stat.key_num, 0);
+        assert_eq!(stat.data_file_num, 1);
+        assert_eq!(stat.reclaimable_size, 0);
+
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        let stat = engine.stat().unwrap();
+        assert_eq!(stat.key_num, 1000);
+        assert!(stat.data_file_num > 1);
+        assert_eq!(stat.reclaimable_size, 0);
+        assert!(stat.disk_size > 0);
+
+        // 覆盖写和删除产生无效数据
+        for i in 0..100 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 100..200 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        let stat = engine.stat().unwrap();
+        assert_eq!(stat.key_num, 900);
+        assert!(stat.reclaimable_size > 0);
+        let reclaimable_size = stat.reclaimable_size;
+
+        // 重启后无效数据大小保持不变
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let stat = engine.stat().unwrap();
+        assert_eq!(stat.key_num, 900);
+        assert_eq!(stat.reclaimable_size, reclaimable_size);
+
+        // merge后无效数据被清理
+        engine.merge().unwrap();
+        let stat = engine.stat().unwrap();
+        assert_eq!(stat.key_num, 900);
+        assert_eq!(stat.reclaimable_size, 0);
+        assert!(stat.disk_size > 0);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_index_memory_limit() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-index-memory-limit");
+        opts.data_file_size = 64 * 1024 * 1024;
+        opts.index_memory_limit = Some(4096);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let mut key_num = 0;
+        loop {
+            match engine.put(get_test_key(key_num), get_test_value(key_num)) {
+                Ok(()) => key_num += 1,
+                Err(e) => {
+                    assert!(matches!(e, Error::IndexMemoryLimitExceeded));
+                    break;
+                }
+            }
+        }
+        let stat = engine.stat().unwrap();
+        assert_eq!(stat.key_num, key_num);
+        assert!(stat.index_memory_usage >= 4096);
+
+        // 覆盖和删除已有的key不受影响
+        engine.put(get_test_key(0), get_test_value(1)).unwrap();
+        assert!(matches!(
+            engine
+                .multi_put(&[(get_test_key(key_num), get_test_value(key_num))])
+                .err()
+                .unwrap(),
+            Error::IndexMemoryLimitExceeded
+        ));
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(1), get_test_value(11)).unwrap();
+        wb.put(get_test_key(key_num), get_test_value(key_num))
+            .unwrap();
+        assert!(matches!(
+            wb.commit().err().unwrap(),
+            Error::IndexMemoryLimitExceeded
+        ));
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(1));
+
+        // 删除后可以继续写入新的key
+        engine.delete(get_test_key(0)).unwrap();
+        assert!(engine.stat().unwrap().index_memory_usage < 4096);
+        engine
+            .put(get_test_key(key_num), get_test_value(key_num))
+            .unwrap();
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_max_kv_size() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-max-kv-size");
+        opts.data_file_size = 4096;
+        opts.max_key_size = Some(32);
+        opts.max_value_size = Some(8192);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        assert!(matches!(
+            engine.put(Bytes::from(vec![b'k'; 33]), get_test_value(1)),
+            Err(Error::KeyTooLarge)
+        ));
+        assert!(matches!(
+            engine.put(get_test_key(1), Bytes::from(vec![b'v'; 8193])),
+            Err(Error::ValueTooLarge)
+        ));
+        assert!(matches!(
+            engine.multi_put(&[
+                (get_test_key(1), get_test_value(1)),
+                (Bytes::from(vec![b'k'; 33]), get_test_value(2)),
+            ]),
+            Err(Error::KeyTooLarge)
+        ));
+        assert!(engine.is_empty());
+
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        assert!(matches!(
+            wb.put(Bytes::from(vec![b'k'; 33]), get_test_value(1)),
+            Err(Error::KeyTooLarge)
+        ));
+        assert!(engine.is_empty());
+
+        // 超过数据文件大小的value分块存储
+        engine
+            .put(get_test_key(1), Bytes::from(vec![b'v'; 4096]))
+            .unwrap();
+        assert_eq!(engine.get(get_test_key(1)).unwrap().len(), 4096);
+        wb.put(get_test_key(2), Bytes::from(vec![b'v'; 8192]))
+            .unwrap();
+        wb.commit().unwrap();
+        assert_eq!(engine.get(get_test_key(2)).unwrap().len(), 8192);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_large_value() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-large-value");
+        opts.data_file_size = 4096;
+        let large_value = |n: usize| -> Bytes { (0..n).map(|i| (i * 7 % 251) as u8).collect() };
+        // 分别测试不压缩，以及压缩并加密的分块
+        for compressed in [false, true] {
+            if compressed {
+                opts.compression = CompressionType::Lz4;
+                #[cfg(feature = "encryption")]
+                {
+                    opts.encryption_key = Some([7; 32]);
+                }
+            }
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            engine.put(get_test_key(1), large_value(20000)).unwrap();
+            engine.put(get_test_key(2), get_test_value(2)).unwrap();
+            engine.put(get_test_key(3), large_value(10000)).unwrap();
+            assert!(engine.stat().unwrap().data_file_num > 1);
+            assert_eq!(engine.get(get_test_key(1)).unwrap(), large_value(20000));
+            let values = engine.multi_get(&[get_test_key(3), get_test_key(2)]);
+            assert_eq!(values[0].as_ref().unwrap(), &large_value(10000));
+            assert_eq!(values[1].as_ref().unwrap(), &get_test_value(2));
+
+            // 覆盖、删除和批量写入
+            engine.put(get_test_key(1), large_value(15000)).unwrap();
+            engine.delete(get_test_key(3)).unwrap();
+            let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+            wb.put(get_test_key(4), large_value(9000)).unwrap();
+            wb.put(get_test_key(5), get_test_value(5)).unwrap();
+            wb.commit().unwrap();
+            assert_eq!(engine.get(get_test_key(1)).unwrap(), large_value(15000));
+            assert!(matches!(
+                engine.get(get_test_key(3)),
+                Err(Error::KeyNotFound)
+            ));
+            assert_eq!(engine.get(get_test_key(4)).unwrap(), large_value(9000));
+            std::mem::drop(wb);
+            std::mem::drop(engine);
+
+            // 重启后重新加载
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            assert_eq!(engine.len(), 4);
+            assert_eq!(engine.get(get_test_key(1)).unwrap(), large_value(15000));
+            assert_eq!(engine.get(get_test_key(4)).unwrap(), large_value(9000));
+
+            // merge后分块随记录一起迁移
+            let disk_size = engine.stat().unwrap().disk_size;
+            engine.merge().unwrap();
+            assert!(engine.stat().unwrap().disk_size < disk_size);
+            assert_eq!(engine.get(get_test_key(1)).unwrap(), large_value(15000));
+            assert_eq!(engine.get(get_test_key(4)).unwrap(), large_value(9000));
+            std::mem::drop(engine);
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            assert_eq!(engine.len(), 4);
+            assert_eq!(engine.get(get_test_key(1)).unwrap(), large_value(15000));
+            assert_eq!(engine.get(get_test_key(2)).unwrap(), get_test_value(2));
+            assert_eq!(engine.get(get_test_key(4)).unwrap(), large_value(9000));
+            std::mem::drop(engine);
+
+            std::fs::remove_dir_all(&opts.dir_path).expect("failed to remove test dir");
+        }
+
+        // key太大，无法分块存储
+        opts.compression = CompressionType::None;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(matches!(
+            engine.put(Bytes::from(vec![b'k'; 2048]), large_value(4096)),
+            Err(Error::RecordTooLarge)
+        ));
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(1), large_value(8192)).unwrap();
+        wb.put(Bytes::from(vec![b'k'; 2048]), large_value(4096))
+            .unwrap();
+        assert!(matches!(wb.commit(), Err(Error::RecordTooLarge)));
+        assert!(engine.is_empty());
+        std::mem::drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_read_only_on_disk_full() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-read-only-on-disk-full");
+        opts.read_only_on_disk_full = true;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+
+        // 模拟写入时磁盘空间不足
+        let disk_full = || -> Result<()> {
+            Err(Error::DiskFull {
+                path: opts.dir_path.clone(),
+                source: std::io::ErrorKind::StorageFull.into(),
+            })
+        };
+        assert!(matches!(
+            engine.check_disk_full(disk_full()),
+            Err(Error::DiskFull { .. })
+        ));
+        assert!(engine.is_read_only());
+        assert!(matches!(
+            engine.put(get_test_key(2), get_test_value(2)),
+            Err(Error::ReadOnly)
+        ));
+        assert!(matches!(
+            engine.delete(get_test_key(1)),
+            Err(Error::ReadOnly)
+        ));
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(3), get_test_value(3)).unwrap();
+        assert!(matches!(wb.commit(), Err(Error::ReadOnly)));
+        // 读操作不受影响
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(1));
+
+        // 重新打开后恢复写入
+        std::mem::drop(wb);
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(!engine.is_read_only());
+        engine.put(get_test_key(2), get_test_value(2)).unwrap();
+
+        // 没有开启时不切换为只读模式
+        std::mem::drop(engine);
+        opts.read_only_on_disk_full = false;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(engine.check_disk_full(disk_full()).is_err());
+        assert!(!engine.is_read_only());
+        engine.put(get_test_key(3), get_test_value(3)).unwrap();
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_file_lock() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-file-lock");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // 目录已被占用
+        let open_res = Engine::open(opts.clone());
+        assert!(matches!(open_res.err().unwrap(), Error::DatabaseIsInUse));
+
+        // 关闭后可以重新打开
+        engine.close().unwrap();
+        let engine2 = Engine::open(opts.clone()).expect("failed to open engine");
+        std::mem::drop(engine);
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::DatabaseIsInUse
+        ));
+
+        std::mem::drop(engine2);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        std::mem::drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_open_mode() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-open-mode");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+
+        // 数据库不存在时不创建
+        opts.create_if_missing = false;
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::DatabaseNotFound { .. }
+        ));
+        assert!(!opts.dir_path.exists());
+        // 空目录也视为数据库不存在
+        std::fs::create_dir_all(&opts.dir_path).unwrap();
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::DatabaseNotFound { .. }
+        ));
+
+        // 初始化新的数据库
+        opts.create_if_missing = true;
+        opts.error_if_exists = true;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.put(get_test_key(0), get_test_value(0)).unwrap();
+        drop(engine);
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::DatabaseAlreadyExists { .. }
+        ));
+
+        // 只打开已存在的数据库
+        opts.create_if_missing = false;
+        opts.error_if_exists = false;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), get_test_value(0));
+        drop(engine);
+
+        // 内存数据库总是新的数据库
+        let mut mem_opts = opts.clone();
+        mem_opts.io_type = IOType::Memory;
+        assert!(matches!(
+            Engine::open(mem_opts.clone()).err().unwrap(),
+            Error::DatabaseNotFound { .. }
+        ));
+        mem_opts.create_if_missing = true;
+        mem_opts.error_if_exists = true;
+        Engine::open(mem_opts).expect("failed to open engine");
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_ttl() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-ttl");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+        assert_eq!(engine.ttl(get_test_key(1)).unwrap(), None);
+
+        engine
+            .put_with_ttl(get_test_key(2), get_test_value(2), Duration::from_secs(60))
+            .unwrap();
+        let ttl = engine.ttl(get_test_key(2)).unwrap().unwrap();
+        assert!(ttl > Duration::from_secs(50) && ttl <= Duration::from_secs(60));
+
+        engine
+            .put_with_ttl(get_test_key(3), get_test_value(3), Duration::ZERO)
+            .unwrap();
+        assert!(matches!(
+            engine.ttl(get_test_key(3)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        assert!(matches!(
+            engine.ttl(get_test_key(4)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_startup_threads() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-startup-threads");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..3000 {
+            engine
+                .put(get_test_key(i % 1000), get_test_value(i))
+                .unwrap();
+            if i % 7 == 0 {
+                engine.delete(get_test_key(i % 500)).unwrap();
+            }
+            if i % 100 == 0 {
+                let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+                wb.put(get_test_key(i % 1000 + 1), get_test_value(i))
+                    .unwrap();
+                wb.delete(get_test_key(i % 1000 + 2)).unwrap();
+                wb.commit().unwrap();
+            }
+        }
+        let expected = engine
+            .scan(IteratorOptions::default())
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        drop(engine);
+
+        // 单线程和多线程加载的结果一致
+        let mut stats = Vec::new();
+        for startup_threads in [1, 4] {
+            opts.startup_threads = startup_threads;
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            let entries = engine
+                .scan(IteratorOptions::default())
+                .collect::<Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(entries, expected);
+            stats.push((
+                engine.stat().unwrap(),
+                engine.seq_num.load(Ordering::SeqCst),
+            ));
+        }
+        assert!(stats[0].0.data_file_num > 4);
+        assert_eq!(stats[0], stats[1]);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_recover_partial_write() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-partial-write");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..100 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        std::mem::drop(engine);
+
+        // 模拟最后一条数据只写入了一部分
+        let file_path = get_data_file_full_path(&opts.dir_path, INITIAL_FILE_ID);
+        let file_size = std::fs::metadata(&file_path).unwrap().len();
+        let file = File::options().write(true).open(&file_path).unwrap();
+        file.set_len(file_size - 10).unwrap();
+        std::mem::drop(file);
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 99);
+        assert!(matches!(
+            engine.get(get_test_key(99)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        assert!(std::fs::metadata(&file_path).unwrap().len() < file_size - 10);
+
+        // 截断后可以继续写入
+        engine.put(get_test_key(99), get_test_value(99)).unwrap();
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 100);
+        assert_eq!(engine.get(get_test_key(99)).unwrap(), get_test_value(99));
+
+        // 写入损坏的数据
+        std::mem::drop(engine);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&file_path)
+            .unwrap()
+            .write_all(&[1, 0, 3, 3, b'a', b'b'])
+            .unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 100);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_seq_num() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-seq-num");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.seq_num.load(Ordering::SeqCst), 1);
+        for i in 0..3 {
+            let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+            wb.put(get_test_key(i), get_test_value(i)).unwrap();
+            wb.commit().unwrap();
+        }
+        assert_eq!(engine.seq_num.load(Ordering::SeqCst), 4);
+        std::mem::drop(engine);
+
+        // 重启后恢复事务编号
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.seq_num.load(Ordering::SeqCst), 4);
+
+        // merge后数据文件中不再有事务编号
+        engine.merge().unwrap();
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.seq_num.load(Ordering::SeqCst), 4);
+        assert_eq!(engine.list_keys().unwrap().len(), 3);
+
+        // 事务编号文件丢失时，从数据文件中恢复
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(3), get_test_value(3)).unwrap();
+        wb.commit().unwrap();
+        std::mem::drop(wb);
+        std::mem::drop(engine);
+        std::fs::remove_file(opts.dir_path.join(SEQ_NUM_FILE_NAME)).unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.seq_num.load(Ordering::SeqCst), 5);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_compare_and_swap() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-cas");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let key = get_test_key(1);
+
+        // key不存在
+        assert!(!engine
+            .compare_and_swap(
+                key.clone(),
+                Some(get_test_value(1)),
+                Some(get_test_value(2))
+            )
+            .unwrap());
+        assert!(engine
+            .compare_and_swap(key.clone(), None, Some(get_test_value(1)))
+            .unwrap());
+        assert_eq!(engine.get(key.clone()).unwrap(), get_test_value(1));
+
+        // 值不匹配
+        assert!(!engine
+            .compare_and_swap(key.clone(), None, Some(get_test_value(2)))
+            .unwrap());
+        assert!(!engine
+            .compare_and_swap(key.clone(), Some(get_test_value(3)), None)
+            .unwrap());
+        assert_eq!(engine.get(key.clone()).unwrap(), get_test_value(1));
+
+        // 值匹配时更新和删除
+        assert!(engine
+            .compare_and_swap(
+                key.clone(),
+                Some(get_test_value(1)),
+                Some(get_test_value(2))
+            )
+            .unwrap());
+        assert_eq!(engine.get(key.clone()).unwrap(), get_test_value(2));
+        assert!(engine
+            .compare_and_swap(key.clone(), Some(get_test_value(2)), None)
+            .unwrap());
+        assert!(matches!(
+            engine.get(key.clone()).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        assert!(engine.compare_and_swap(key.clone(), None, None).unwrap());
+        assert!(matches!(
+            engine
+                .compare_and_swap(Bytes::new(), None, None)
+                .err()
+                .unwrap(),
+            Error::KeyIsEmpty
+        ));
+
+        // 多线程并发自增计数器
+        let engine = Arc::new(engine);
+        let counter = Bytes::from("counter");
+        engine.put(counter.clone(), Bytes::from("0")).unwrap();
+        let handles = (0..4)
+            .map(|_| {
+                let engine = engine.clone();
+                let counter = counter.clone();
+                std::thread::spawn(move || {
+                    let mut n = 0;
+                    while n < 50 {
+                        let current = engine.get(counter.clone()).unwrap();
+                        let next = std::str::from_utf8(&current)
+                            .unwrap()
+                            .parse::<u32>()
+                            .unwrap()
+                            + 1;
+                        if engine
+                            .compare_and_swap(
+                                counter.clone(),
+                                Some(current),
+                                Some(Bytes::from(next.to_string())),
+                            )
+                            .unwrap()
+                        {
+                            n += 1;
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        assert_eq!(engine.get(counter).unwrap(), "200");
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    /// 另一个线程不断写入key时执行op，按提交顺序返回key被写入的所有值
+    fn run_with_concurrent_puts(
+        engine: &Engine,
+        key: &Bytes,
+        put_value: impl Fn(usize) -> Bytes + Send + 'static,
+        mut op: impl FnMut(usize),
+    ) -> Vec<Bytes> {
+        let receiver = engine.watch_prefix(key);
+        engine.put(key.clone(), put_value(0)).unwrap();
+        let writer = {
+            let engine = engine.clone();
+            let key = key.clone();
+            std::thread::spawn(move || {
+                for i in 1..=2000 {
+                    engine.put(key.clone(), put_value(i)).unwrap();
+                }
+            })
+        };
+        for i in 0..2000 {
+            op(i);
+        }
+        writer.join().unwrap();
+        receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Put { value, .. } => Some(value),
+                Event::Delete { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_engine_compare_and_swap_with_concurrent_puts() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-cas-concurrent");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let key = Bytes::from("key");
+        let mut swapped = vec![];
+        let values = run_with_concurrent_puts(
+            &engine,
+            &key,
+            |i| Bytes::from(format!("put-{}", i)),
+            |i| {
+                let current = engine.get(key.clone()).unwrap();
+                let new = Bytes::from(format!("cas-{}", i));
+                if engine
+                    .compare_and_swap(key.clone(), Some(current.clone()), Some(new.clone()))
+                    .unwrap()
+                {
+                    swapped.push((current, new));
+                }
+            },
+        );
+        // 写入时key的值仍然是expected，没有覆盖并发写入的值
+        assert!(!swapped.is_empty());
+        for (expected, new) in swapped {
+            let i = values.iter().position(|value| *value == new).unwrap();
+            assert_eq!(values[i - 1], expected);
+        }
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_incr() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-incr");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // key不存在时从0开始
+        assert_eq!(engine.incr("counter".into(), 1).unwrap(), 1);
+        assert_eq!(engine.incr("counter".into(), 10).unwrap(), 11);
+        assert_eq!(engine.incr("counter".into(), -20).unwrap(), -9);
+        assert_eq!(engine.get("counter".into()).unwrap(), "-9");
+
+        // 不是整数或者溢出
+        engine.put("name".into(), "bitcask".into()).unwrap();
+        assert!(matches!(
+            engine.incr("name".into(), 1).err().unwrap(),
+            Error::ValueIsNotInteger
+        ));
+        engine
+            .put("max".into(), i64::MAX.to_string().into())
+            .unwrap();
+        assert!(matches!(
+            engine.incr("max".into(), 1).err().unwrap(),
+            Error::ValueIsNotInteger
+        ));
+        assert_eq!(engine.get("max".into()).unwrap(), i64::MAX.to_string());
+        assert!(matches!(
+            engine.incr(Bytes::new(), 1).err().unwrap(),
+            Error::KeyIsEmpty
+        ));
+
+        // 保留过期时间
+        engine
+            .put_with_ttl("ttl".into(), "1".into(), Duration::from_secs(100))
+            .unwrap();
+        assert_eq!(engine.incr("ttl".into(), 1).unwrap(), 2);
+        assert!(engine.ttl("ttl".into()).unwrap().unwrap() > Duration::from_secs(90));
+
+        // 多线程并发自增
+        let engine = Arc::new(engine);
+        let handles = (0..4)
+            .map(|_| {
+                let engine = engine.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        engine.incr("concurrent".into(), 1).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        assert_eq!(engine.get("concurrent".into()).unwrap(), "200");
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_incr_with_concurrent_puts() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-incr-concurrent");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let key = Bytes::from("counter");
+        let mut results = vec![];
+        let values = run_with_concurrent_puts(
+            &engine,
+            &key,
+            |i| Bytes::from((i * 1_000_000).to_string()),
+            |_| results.push(engine.incr(key.clone(), 1).unwrap()),
+        );
+        // 每次incr都基于提交顺序中的上一个值，没有覆盖并发写入的值
+        for result in results {
+            let i = values
+                .iter()
+                .position(|value| *value == result.to_string())
+                .unwrap();
+            assert_eq!(values[i - 1], (result - 1).to_string());
+        }
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_append() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-append");
+        opts.max_value_size = Some(16);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // key不存在时视为空值
+        assert_eq!(engine.append("log".into(), "a".into()).unwrap(), 1);
+        assert_eq!(engine.append("log".into(), "bc".into()).unwrap(), 3);
+        assert_eq!(engine.get("log".into()).unwrap(), "abc");
+        assert!(matches!(
+            engine.append(Bytes::new(), "a".into()).err().unwrap(),
+            Error::KeyIsEmpty
+        ));
+
+        // 超出value的最大长度时不写入
+        assert!(matches!(
+            engine.append("log".into(), vec![b'a'; 14].into()),
+            Err(Error::ValueTooLarge)
+        ));
+        assert_eq!(engine.get("log".into()).unwrap(), "abc");
+
+        // 保留过期时间
+        engine
+            .put_with_ttl("ttl".into(), "a".into(), Duration::from_secs(100))
+            .unwrap();
+        assert_eq!(engine.append("ttl".into(), "b".into()).unwrap(), 2);
+        assert!(engine.ttl("ttl".into()).unwrap().unwrap() > Duration::from_secs(90));
+
+        // 多线程并发追加
+        let engine = Arc::new(engine);
+        let handles = (0..4)
+            .map(|_| {
+                let engine = engine.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..4 {
+                        engine.append("concurrent".into(), "a".into()).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        assert_eq!(engine.get("concurrent".into()).unwrap().len(), 16);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_append_with_concurrent_puts() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-append-concurrent");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let key = Bytes::from("log");
+        let values = run_with_concurrent_puts(
+            &engine,
+            &key,
+            |i| Bytes::from(format!("put-{}", i)),
+            |_| {
+                engine.append(key.clone(), Bytes::from("a")).unwrap();
+            },
+        );
+        // 每次append都追加到提交顺序中的上一个值之后，没有覆盖并发写入的值
+        for i in 1..values.len() {
+            if values[i].ends_with(b"a") {
+                assert_eq!(values[i], [&values[i - 1][..], b"a"].concat());
+            }
+        }
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_multi_get_put() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-multi-get-put");
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let pairs = (0..1000)
+            .map(|i| (get_test_key(i), get_test_value(i)))
+            .collect::<Vec<_>>();
+        engine.multi_put(&pairs).unwrap();
+        // 写入过程中切换了活跃数据文件
+        assert!(!engine.older_files.read().is_empty());
+        engine.multi_put(&pairs[..10]).unwrap();
+        assert_eq!(engine.stat().unwrap().key_num, 1000);
+        assert!(engine.stat().unwrap().reclaimable_size > 0);
+
+        engine.delete(get_test_key(1)).unwrap();
+        let keys = vec![
+            get_test_key(0),
+            get_test_key(1),
+            get_test_key(999),
+            Bytes::new(),
+        ];
+        let values = engine.multi_get(&keys);
+        assert_eq!(values[0].as_ref().unwrap(), &get_test_value(0));
+        assert!(matches!(
+            values[1].as_ref().err().unwrap(),
+            Error::KeyNotFound
+        ));
+        assert_eq!(values[2].as_ref().unwrap(), &get_test_value(999));
+        assert!(matches!(
+            values[3].as_ref().err().unwrap(),
+            Error::KeyIsEmpty
+        ));
+
+        // key为空时不写入任何数据
+        assert!(matches!(
+            engine
+                .multi_put(&[
+                    (get_test_key(1), get_test_value(1)),
+                    (Bytes::new(), get_test_value(2))
+                ])
+                .err()
+                .unwrap(),
+            Error::KeyIsEmpty
+        ));
+        assert!(matches!(
+            engine.get(get_test_key(1)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        // 重启后数据仍然有效
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 999);
+        assert_eq!(engine.get(get_test_key(500)).unwrap(), get_test_value(500));
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_compression() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-compression");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let value = |i: usize| Bytes::from(get_test_value(i).repeat(20));
+
+        // 不同的压缩算法写入的数据混合在一起也可以读取
+        let compressions = [
+            CompressionType::Zstd,
+            CompressionType::Lz4,
+            CompressionType::None,
+        ];
+        for (n, compression) in compressions.into_iter().enumerate() {
+            opts.compression = compression;
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            for i in n * 100..(n + 1) * 100 {
+                engine.put(get_test_key(i), value(i)).unwrap();
+            }
+            for i in 0..(n + 1) * 100 {
+                assert_eq!(engine.get(get_test_key(i)).unwrap(), value(i));
+            }
+        }
+        let disk_size = dir_disk_size(&opts.dir_path).unwrap();
+
+        // merge时使用当前配置的压缩算法重写数据
+        opts.compression = CompressionType::Zstd;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.merge().unwrap();
+        assert!(dir_disk_size(&opts.dir_path).unwrap() < disk_size);
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..300 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), value(i));
+        }
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_checksum() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-checksum");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+
+        // 不同的校验算法写入的数据文件混合在一起也可以读取
+        let checksums = [ChecksumType::Xxh3, ChecksumType::None, ChecksumType::Crc32];
+        for (n, checksum) in checksums.into_iter().enumerate() {
+            opts.checksum = checksum;
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            // 修改校验算法后切换新的活跃数据文件
+            assert_eq!(engine.active_file.read().get_file_id(), n as u32);
+            assert_eq!(engine.active_file.read().checksum(), checksum);
+            for i in n * 100..(n + 1) * 100 {
+                engine.put(get_test_key(i), get_test_value(i)).unwrap();
+            }
+            for i in 0..(n + 1) * 100 {
+                assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+            }
+        }
+
+        // merge时使用当前配置的校验算法重写数据
+        opts.checksum = ChecksumType::Xxh3;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.merge().unwrap();
+        assert!(engine.verify().unwrap().is_ok());
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(engine
+            .older_files
+            .read()
+            .values()
+            .all(|f| f.checksum() == ChecksumType::Xxh3));
+        for i in 0..300 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_engine_encryption() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-encryption");
+        opts.data_file_size = 64 * 1024 * 1024;
+        opts.compression = CompressionType::Lz4;
+        opts.encryption_key = Some([7; 32]);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..100 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        engine.delete(get_test_key(0)).unwrap();
+        std::mem::drop(engine);
+
+        // 数据文件中没有明文的value
+        let data = std::fs::read(get_data_file_full_path(&opts.dir_path, INITIAL_FILE_ID)).unwrap();
+        assert!(!data
+            .windows(get_test_value(1).len())
+            .any(|w| w == get_test_value(1)));
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 99);
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(1));
+        engine.merge().unwrap();
+        assert_eq!(engine.get(get_test_key(99)).unwrap(), get_test_value(99));
+        std::mem::drop(engine);
+
+        // 缺少密钥或者密钥错误
+        let mut wrong_opts = opts.clone();
+        wrong_opts.encryption_key = None;
+        wrong_opts.compression = CompressionType::None;
+        let engine = Engine::open(wrong_opts.clone()).expect("failed to open engine");
+        assert!(matches!(
+            engine.get(get_test_key(1)).err().unwrap(),
+            Error::MissingEncryptionKey
+        ));
+        std::mem::drop(engine);
+        wrong_opts.encryption_key = Some([8; 32]);
+        let engine = Engine::open(wrong_opts).expect("failed to open engine");
+        assert!(matches!(
+            engine.get(get_test_key(1)).err().unwrap(),
+            Error::FailedToDecryptValue
+        ));
+        std::mem::drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_bloom_filter() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-bloom-filter");
+        opts.data_file_size = 64 * 1024 * 1024;
+        opts.bloom_filter_bits_per_key = 10;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(1000), get_test_value(1000)).unwrap();
+        wb.commit().unwrap();
+        engine
+            .multi_put(&[(get_test_key(1001), get_test_value(1001))])
+            .unwrap();
+        for i in 0..1002 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+        assert!(matches!(
+            engine.get(get_test_key(5000)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        // 重启后重建布隆过滤器
+        std::mem::drop(wb);
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!((0..1002).all(|i| engine.may_contain_key(&get_test_key(i))));
+        assert_eq!(
+            engine.get(get_test_key(1001)).unwrap(),
+            get_test_value(1001)
+        );
+
+        // merge后清理已删除的key
+        for i in 0..500 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        assert!((0..500).all(|i| engine.may_contain_key(&get_test_key(i))));
+        engine.merge().unwrap();
+        let false_positives = (0..500)
+            .filter(|i| engine.may_contain_key(&get_test_key(*i)))
+            .count();
+        assert!(false_positives < 50);
+        assert!((500..1002).all(|i| engine.may_contain_key(&get_test_key(i))));
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_contains_key_and_len() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-contains-key");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(engine.is_empty());
+        assert!(!engine.contains_key(&get_test_key(1)));
+        assert!(!engine.contains_key(b""));
+
+        for i in 0..100 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        engine.put(get_test_key(1), get_test_value(11)).unwrap();
+        engine.delete(get_test_key(2)).unwrap();
+        assert_eq!(engine.len(), 99);
+        assert!(!engine.is_empty());
+        assert!(engine.contains_key(&get_test_key(1)));
+        assert!(!engine.contains_key(&get_test_key(2)));
+
+        // 重启后key的数量不变
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 99);
+        assert_eq!(engine.stat().unwrap().key_num, 99);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_bptree_index() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-bptree-index");
+        opts.data_file_size = 64 * 1024;
+        opts.index_type = IndexType::BPlusTree;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..100 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(1000), get_test_value(1000)).unwrap();
+        wb.commit().unwrap();
+        engine.close().unwrap();
+        std::mem::drop(wb);
+        std::mem::drop(engine);
+
+        // 正常关闭后直接使用持久化的索引，不加载数据文件，因此不会统计无效数据
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 901);
+        assert_eq!(engine.stat().unwrap().reclaimable_size, 0);
+        assert_eq!(engine.get(get_test_key(100)).unwrap(), get_test_value(100));
+        assert_eq!(
+            engine.get(get_test_key(1000)).unwrap(),
+            get_test_value(1000)
+        );
+        assert!(matches!(
+            engine.get(get_test_key(0)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        // 从文件末尾继续写入
+        engine.put(get_test_key(0), get_test_value(0)).unwrap();
+        engine.merge().unwrap();
+        assert_eq!(engine.len(), 902);
+        std::mem::drop(engine);
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 902);
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), get_test_value(0));
+        std::mem::drop(engine);
+
+        // 索引文件不存在时从数据文件中重新加载
+        std::fs::remove_file(opts.dir_path.join(BPTREE_INDEX_FILE_NAME)).unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 902);
+        assert_eq!(engine.get(get_test_key(999)).unwrap(), get_test_value(999));
+        std::mem::drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_sync_interval() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-sync-interval");
+        opts.data_file_size = 64 * 1024 * 1024;
+        opts.sync_interval = Some(Duration::ZERO);
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::InvalidSyncInterval
+        ));
+
+        opts.sync_interval = Some(Duration::from_millis(10));
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(engine.sync_worker.lock().is_some());
+        for i in 0..100 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(50));
+
+        // 关闭时等待后台线程退出
+        engine.close().unwrap();
+        assert!(engine.sync_worker.lock().is_none());
+        std::mem::drop(engine);
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 100);
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_get_with_meta() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-get-with-meta");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let before = SystemTime::now() - Duration::from_millis(1);
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+        engine
+            .put_with_ttl(get_test_key(2), get_test_value(2), Duration::from_secs(60))
+            .unwrap();
+        let after = SystemTime::now() + Duration::from_millis(1);
+
+        let (value, meta) = engine.get_with_meta(get_test_key(1)).unwrap();
+        assert_eq!(value, get_test_value(1));
+        assert!(meta.last_modified >= before && meta.last_modified <= after);
+        assert_eq!(meta.expire_at, None);
+        assert_eq!(meta.ttl(), None);
+        let pos = engine.index.get(get_test_key(1).to_vec()).unwrap();
+        assert_eq!(
+            (meta.file_id, meta.offset, meta.size),
+            (pos.file_id, pos.offset, pos.size)
+        );
+
+        let (value, meta) = engine.get_with_meta(get_test_key(2)).unwrap();
+        assert_eq!(value, get_test_value(2));
+        assert_eq!(
+            meta.expire_at,
+            Some(meta.last_modified + Duration::from_secs(60))
+        );
+        assert!(meta.ttl().unwrap() > Duration::from_secs(50));
+        assert!(meta.offset > meta.size as u64);
+
+        // 重启后时间戳不变
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let (_, meta2) = engine.get_with_meta(get_test_key(2)).unwrap();
+        assert_eq!(meta2, meta);
+        assert!(matches!(
+            engine.get_with_meta(get_test_key(3)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        // merge后位置改变，时间戳不变
+        engine.merge().unwrap();
+        let (_, meta3) = engine.get_with_meta(get_test_key(2)).unwrap();
+        assert_ne!(meta3.file_id, meta.file_id);
+        assert_eq!(meta3.last_modified, meta.last_modified);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_reload() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-reload");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let backup_dir = PathBuf::from("/tmp/bitcask-rs-reload-backup");
+        let _ = std::fs::remove_dir_all(&backup_dir);
+        let engine = Arc::new(Engine::open(opts.clone()).expect("failed to open engine"));
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        engine
+            .backup(&backup_dir, BackupOptions::default())
+            .unwrap();
+        for i in 1000..2000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        engine.delete(get_test_key(0)).unwrap();
+        engine
+            .create_index("len", |_, value| vec![value.len().to_be_bytes().to_vec()])
+            .unwrap();
+        let seq_num = engine.seq_num.load(Ordering::SeqCst);
+
+        // 有快照时不能重新加载
+        let snapshot = engine.snapshot().unwrap();
+        assert!(matches!(engine.reload(), Err(Error::SnapshotInUse)));
+        drop(snapshot);
+
+        // 将备份恢复到数据库目录中
+        engine.sync().unwrap();
+        for entry in std::fs::read_dir(&opts.dir_path).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "data") {
+                std::fs::remove_file(path).unwrap();
+            }
+        }
+        for entry in std::fs::read_dir(&backup_dir).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_name() != FILE_LOCK_NAME {
+                std::fs::copy(entry.path(), opts.dir_path.join(entry.file_name())).unwrap();
+            }
+        }
+        engine.reload().unwrap();
+        assert_eq!(engine.len(), 1000);
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), get_test_value(0));
+        assert!(matches!(
+            engine.get(get_test_key(1000)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        assert!(engine.seq_num.load(Ordering::SeqCst) >= seq_num);
+        let index_key = get_test_value(0).len().to_be_bytes().to_vec();
+        assert_eq!(engine.get_by_index("len", &index_key).unwrap().len(), 1000);
+
+        // 重新加载后可以继续写入
+        engine
+            .put(get_test_key(2000), get_test_value(2000))
+            .unwrap();
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 1001);
+        assert_eq!(
+            engine.get(get_test_key(2000)).unwrap(),
+            get_test_value(2000)
+        );
+
+        drop(engine);
+        std::fs::remove_dir_all(backup_dir).expect("failed to remove backup dir");
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_accessors() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-accessors");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.dir_path(), opts.dir_path.as_path());
+        assert_eq!(engine.options().data_file_size, opts.data_file_size);
+        assert_eq!(engine.data_file_ids(), vec![0]);
+        assert_eq!(engine.active_file_id(), 0);
+        assert_eq!(engine.active_file_offset(), DATA_FILE_HEADER_SIZE);
+
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        let file_ids = engine.data_file_ids();
+        assert!(file_ids.len() > 1);
+        assert!(file_ids.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(engine.active_file_id(), *file_ids.last().unwrap());
+        let offset = engine.active_file_offset();
+        engine
+            .put(get_test_key(1000), get_test_value(1000))
+            .unwrap();
+        assert!(engine.active_file_offset() > offset);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_preallocate_data_files() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-preallocate");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        opts.preallocate_data_files = true;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        assert!(engine.data_file_ids().len() > 1);
+        // 预分配不改变文件大小
+        engine.sync().unwrap();
+        let active_file_path = get_data_file_full_path(&opts.dir_path, engine.active_file_id());
+        assert_eq!(
+            std::fs::metadata(active_file_path).unwrap().len(),
+            engine.active_file_offset()
+        );
+        drop(engine);
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 1000);
+        for i in 0..1000 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+        engine
+            .put(get_test_key(1000), get_test_value(1000))
+            .unwrap();
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 1001);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+    #[test]
+    fn test_engine_multiple_data_dirs() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-data-dirs");
+        opts.dir_paths = vec![
+            PathBuf::from("/tmp/bitcask-rs-data-dirs-1"),
+            PathBuf::from("/tmp/bitcask-rs-data-dirs-2"),
+        ];
+        for dir_path in opts.data_dirs() {
+            let _ = std::fs::remove_dir_all(dir_path);
+        }
+        opts.data_file_size = 64 * 1024;
+        let data_file_num = |opts: &Options| {
+            opts.data_dirs()
+                .iter()
+                .map(|dir_path| {
+                    find_data_files(std::slice::from_ref(dir_path))
+                        .unwrap()
+                        .len()
+                })
+                .collect::<Vec<_>>()
+        };
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..3000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..1000 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        // 数据文件分散在所有数据目录中
+        assert!(data_file_num(&opts).iter().all(|num| *num > 0));
+        engine.merge().unwrap();
+        assert!(data_file_num(&opts).iter().all(|num| *num > 0));
+        for i in 1000..3000 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+        drop(engine);
+
+        // 调整数据目录的顺序后仍然可以找到所有数据文件
+        opts.dir_paths.reverse();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 2000);
+        for i in 1000..3000 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+        engine.clear().unwrap();
+        assert_eq!(data_file_num(&opts).iter().sum::<usize>(), 1);
+        let file_id = engine.active_file_id();
+        drop(engine);
+
+        // 同一个数据文件出现在多个目录中
+        let data_dirs = opts.data_dirs();
+        let file_dir = find_data_file_dir(&data_dirs, file_id);
+        let other_dir = data_dirs.iter().find(|dir| **dir != file_dir).unwrap();
+        std::fs::copy(
+            get_data_file_full_path(&file_dir, file_id),
+            get_data_file_full_path(other_dir, file_id),
+        )
+        .unwrap();
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::DuplicateDataFile(id) if id == file_id
+        ));
+
+        for dir_path in opts.data_dirs() {
+            std::fs::remove_dir_all(dir_path).expect("failed to remove test dir");
+        }
+    }
+
+    #[test]
+    fn test_engine_clone() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-clone");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // 克隆的句柄共享同一个数据库
+        let handles = (0..4)
+            .map(|t| {
+                let engine = engine.clone();
+                std::thread::spawn(move || {
+                    for i in t * 100..(t + 1) * 100 {
+                        engine.put(get_test_key(i), get_test_value(i)).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(engine.len(), 400);
+
+        // 迭代器和批量写操作不借用Engine，可以移动到其他线程
+        let iter = engine.iter(IteratorOptions::default());
+        let batch = engine.new_write_batch(WriteOptions::default()).unwrap();
+        batch.put(get_test_key(400), get_test_value(400)).unwrap();
+        drop(engine);
+        let count = std::thread::spawn(move || {
+            let mut count = 0;
+            while iter.next().is_some() {
+                count += 1;
+            }
+            batch.commit().unwrap();
+            count
+        })
+        .join()
+        .unwrap();
+        assert_eq!(count, 400);
+
+        // 最后一个句柄drop后关闭数据库，可以再次打开
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 401);
+        let engine2 = engine.clone();
+        drop(engine);
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::DatabaseIsInUse
+        ));
+        assert_eq!(engine2.get(get_test_key(400)).unwrap(), get_test_value(400));
+        drop(engine2);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
 }
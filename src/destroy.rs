@@ -0,0 +1,385 @@
+//! 清空和删除数据库
+//!
+//! 清空和删除数据库前先持久化清空标记，记录需要删除的数据文件的ID上界，
+//! 中途崩溃时打开数据库会先完成删除，不会读取到只删除了一部分的数据
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+use crate::checkpoint::{remove_checkpoint, CHECKPOINT_FILE_NAME};
+use crate::data::data_file::{
+    find_data_files, get_data_file_full_path, DATA_FILE_SUFFIX, HINT_FILE_NAME, SEQ_NUM_FILE_NAME,
+};
+use crate::db::{lock_dir, read_dir, Engine, FILE_LOCK_NAME};
+use crate::dictionary::DICT_FILE_PREFIX;
+use crate::error::{Error, Result};
+use crate::fio::{self, mem_io};
+use crate::index::bptree::BPTREE_INDEX_FILE_NAME;
+use crate::merge::remove_merge_dir;
+use crate::repair::QUARANTINE_DIR_NAME;
+use crate::replication::CURSOR_FILE_NAME;
+
+/// 清空标记文件，内容为需要删除的数据文件的ID上界（不包含）
+const CLEAR_MARKER_FILE_NAME: &str = "clear-marker";
+
+impl Engine {
+    /// 清空数据库中的所有数据
+    ///
+    /// 切换新的活跃数据文件后删除所有旧的数据文件并清空索引，期间阻塞所有写操作和merge。
+    /// 仍有快照在使用的数据文件在快照全部释放后删除，订阅者收到所有key的删除通知
+    pub fn clear(&self) -> Result<()> {
+        self.check_closed()?;
+        self.check_writable()?;
+        let keys = {
+            let _merging_guard = self.merging_lock.lock();
+            let _rotate_guard = self.rotate_lock.write();
+            let mut active_file = self.active_file.write();
+            let mut older_files = self.older_files.write();
+            let dir_path = &self.options.dir_path;
+
+            // 先创建新的活跃数据文件再写入清空标记，崩溃后清空标记之前的数据文件
+            active_file.sync()?;
+            let active_file_id = active_file.get_file_id();
+            *active_file =
+                self.open_active_file(self.data_file_dir(active_file_id + 1), active_file_id + 1)?;
+            older_files.insert(
+                active_file_id,
+                self.open_data_file(self.data_file_dir(active_file_id), active_file_id)?,
+            );
+            if !self.is_in_memory() {
+                write_clear_marker(dir_path, active_file_id + 1)?;
+                remove_checkpoint(dir_path)?;
+            }
+
+            // 有二级索引或订阅者时才需要通知被删除的key
+            let keys = match self.has_secondary_indexes() || !self.watchers.lock().is_empty() {
+                true => self.index.list_keys()?,
+                false => Vec::new(),
+            };
+            self.index.clear()?;
+            if let Some(value_cache) = &self.value_cache {
+                value_cache.lock().clear();
+            }
+            if let Some(tracker) = &self.access_tracker {
+                tracker.lock().clear();
+            }
+            self.reclaim_size.store(0, Ordering::SeqCst);
+            self.file_counters.lock().clear();
+            self.rebuild_bloom_filter()?;
+
+            // 按ID从小到大删除旧的数据文件
+            let mut file_ids = older_files.keys().copied().collect::<Vec<_>>();
+            file_ids.sort();
+            for file_id in file_ids {
+                let data_file = older_files.remove(&file_id).unwrap();
+                // 仍有快照在使用时保留旧的数据文件，快照全部释放后再删除
+                if self.retire_data_file(file_id, data_file) {
+                    continue;
+                }
+                let file_path = get_data_file_full_path(self.data_file_dir(file_id), file_id);
+                if self.is_in_memory() {
+                    mem_io::remove_file(file_path);
+                } else if let Err(source) = std::fs::remove_file(&file_path) {
+                    return Err(Error::FailedToRemoveDataFile {
+                        path: file_path,
+                        source,
+                    });
+                }
+            }
+            if !self.is_in_memory() {
+                remove_file_if_exists(dir_path.join(HINT_FILE_NAME))?;
+                remove_file_if_exists(dir_path.join(CLEAR_MARKER_FILE_NAME))?;
+            }
+            keys
+        };
+        self.refresh_db_size();
+
+        let updates = keys
+            .iter()
+            .map(|key| (key.as_ref(), None))
+            .collect::<Vec<_>>();
+        self.after_commit(&updates);
+        Ok(())
+    }
+
+    /// 删除数据库目录中的所有数据库文件，数据库不能处于打开状态，目录不存在时直接返回
+    ///
+    /// 只删除数据库创建的文件，目录中没有其他文件时删除目录。
+    /// 不会删除额外的数据文件目录（Options::dir_paths）中的数据文件
+    pub fn destroy(dir_path: impl AsRef<Path>) -> Result<()> {
+        let dir_path = dir_path.as_ref();
+        if !dir_path.is_dir() {
+            return Ok(());
+        }
+        // 获取文件锁，防止删除正在使用的数据库
+        let _lock_file = lock_dir(dir_path)?;
+
+        // 先删除所有数据文件，中途崩溃时再次打开数据库会继续删除
+        write_clear_marker(dir_path, u32::MAX)?;
+        finish_clear(dir_path, &[dir_path.to_path_buf()])?;
+        for entry in read_dir(dir_path)? {
+            let Ok(file_name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let is_dict_file = file_name
+                .strip_prefix(DICT_FILE_PREFIX)
+                .is_some_and(|seq| seq.parse::<u32>().is_ok());
+            // 修复数据文件时的临时文件
+            let is_repair_file = file_name
+                .strip_suffix(".repair")
+                .and_then(|name| name.strip_suffix(DATA_FILE_SUFFIX))
+                .is_some_and(|id| id.parse::<u32>().is_ok());
+            if is_dict_file || is_repair_file {
+                remove_file_if_exists(entry.path())?;
+            }
+        }
+        for file_name in [
+            SEQ_NUM_FILE_NAME,
+            CURSOR_FILE_NAME,
+            &format!("{}.tmp", CURSOR_FILE_NAME),
+            &format!("{}.tmp", CLEAR_MARKER_FILE_NAME),
+            &format!("{}.tmp", CHECKPOINT_FILE_NAME),
+        ] {
+            remove_file_if_exists(dir_path.join(file_name))?;
+        }
+        let quarantine_dir = dir_path.join(QUARANTINE_DIR_NAME);
+        if quarantine_dir.is_dir() {
+            std::fs::remove_dir_all(&quarantine_dir).map_err(|source| {
+                Error::FailedToRemoveDatabaseFile {
+                    path: quarantine_dir,
+                    source,
+                }
+            })?;
+        }
+        remove_merge_dir(dir_path)?;
+        remove_file_if_exists(dir_path.join(FILE_LOCK_NAME))?;
+
+        if read_dir(dir_path)?.is_empty() {
+            std::fs::remove_dir(dir_path).map_err(|source| Error::FailedToRemoveDatabaseFile {
+                path: dir_path.to_path_buf(),
+                source,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// 打开数据库时调用，完成上次中途崩溃的清空操作
+pub(crate) fn finish_clear(dir_path: &Path, data_dirs: &[PathBuf]) -> Result<()> {
+    let marker_path = dir_path.join(CLEAR_MARKER_FILE_NAME);
+    let buf = match std::fs::read_to_string(&marker_path) {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(source) => {
+            return Err(Error::FailedToReadClearMarker {
+                path: marker_path,
+                source,
+            })
+        }
+    };
+    let Ok(end_file_id) = buf.trim().parse::<u32>() else {
+        return Err(Error::InvalidClearMarker { path: marker_path });
+    };
+    for (file_id, data_dir) in find_data_files(data_dirs)?
+        .into_iter()
+        .filter(|(file_id, _)| *file_id < end_file_id)
+    {
+        let file_path = get_data_file_full_path(data_dir, file_id);
+        if let Err(source) = std::fs::remove_file(&file_path) {
+            return Err(Error::FailedToRemoveDataFile {
+                path: file_path,
+                source,
+            });
+        }
+    }
+    // hint文件和持久化的索引指向被删除的数据
+    remove_file_if_exists(dir_path.join(HINT_FILE_NAME))?;
+    remove_file_if_exists(dir_path.join(BPTREE_INDEX_FILE_NAME))?;
+    remove_checkpoint(dir_path)?;
+    remove_file_if_exists(marker_path)
+}
+
+/// 持久化清空标记，先写临时文件再重命名，保证文件内容完整
+fn write_clear_marker(dir_path: &Path, end_file_id: u32) -> Result<()> {
+    let path = dir_path.join(CLEAR_MARKER_FILE_NAME);
+    let tmp_path = path.with_extension("tmp");
+    let write = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(end_file_id.to_string().as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, &path)?;
+        fio::sync_dir(dir_path)
+    };
+    write().map_err(|source| Error::FailedToWriteClearMarker { path, source })
+}
+
+fn remove_file_if_exists(path: PathBuf) -> Result<()> {
+    match std::fs::remove_file(&path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(source) => Err(Error::FailedToRemoveDatabaseFile { path, source }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::{IOType, IndexType, Options};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    fn dir_data_file_num(dir_path: &Path) -> usize {
+        read_dir(dir_path)
+            .unwrap()
+            .iter()
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(DATA_FILE_SUFFIX)
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_engine_clear() {
+        for index_type in [
+            IndexType::BTree,
+            IndexType::SkipList,
+            IndexType::ShardedBTree,
+            IndexType::Hash,
+            IndexType::BPlusTree,
+        ] {
+            let mut opts = Options::default();
+            opts.dir_path = PathBuf::from("/tmp/bitcask-rs-clear");
+            let _ = std::fs::remove_dir_all(&opts.dir_path);
+            opts.data_file_size = 64 * 1024;
+            opts.index_type = index_type;
+            opts.value_cache_size = 1024 * 1024;
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            for i in 0..1000 {
+                engine.put(get_test_key(i), get_test_value(i)).unwrap();
+                engine.get(get_test_key(i)).unwrap();
+            }
+            let snapshot = engine.snapshot().unwrap();
+            let rx = engine.watch_prefix(b"");
+
+            engine.clear().unwrap();
+            assert!(engine.is_empty());
+            assert_eq!(engine.stat().unwrap().data_file_num, 1);
+            assert_eq!(engine.stat().unwrap().reclaimable_size, 0);
+            assert!(matches!(
+                engine.get(get_test_key(0)),
+                Err(Error::KeyNotFound)
+            ));
+            // 订阅者收到所有key的删除通知，快照中的数据仍然可以读取
+            assert_eq!(rx.try_iter().count(), 1000);
+            assert_eq!(snapshot.get(get_test_key(0)).unwrap(), get_test_value(0));
+            // 快照释放后删除保留的旧数据文件
+            drop(snapshot);
+            assert_eq!(dir_data_file_num(&opts.dir_path), 1);
+
+            // 清空后可以继续写入，重新打开后数据不会复活
+            engine.put(get_test_key(1), get_test_value(2)).unwrap();
+            drop(engine);
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            assert_eq!(engine.len(), 1);
+            assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(2));
+            drop(engine);
+            std::fs::remove_dir_all(&opts.dir_path).expect("failed to remove test dir");
+        }
+
+        // 内存数据库
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-clear-memory");
+        opts.io_type = IOType::Memory;
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        engine.clear().unwrap();
+        assert!(engine.is_empty());
+        assert_eq!(engine.stat().unwrap().data_file_num, 1);
+        engine.put(get_test_key(0), get_test_value(0)).unwrap();
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), get_test_value(0));
+    }
+
+    #[test]
+    fn test_engine_finish_clear() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-finish-clear");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        let active_file_id = engine.active_file.read().get_file_id();
+        drop(engine);
+
+        // 模拟写入清空标记后崩溃
+        write_clear_marker(&opts.dir_path, active_file_id).unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(!opts.dir_path.join(CLEAR_MARKER_FILE_NAME).exists());
+        assert_eq!(engine.stat().unwrap().data_file_num, 1);
+        assert!(engine.len() < 1000);
+        assert!(matches!(
+            engine.get(get_test_key(0)),
+            Err(Error::KeyNotFound)
+        ));
+        drop(engine);
+
+        std::fs::write(opts.dir_path.join(CLEAR_MARKER_FILE_NAME), "invalid").unwrap();
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::InvalidClearMarker { .. }
+        ));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_destroy() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-destroy");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        engine.merge().unwrap();
+
+        // 数据库正在使用时不能删除
+        assert!(matches!(
+            Engine::destroy(&opts.dir_path).err().unwrap(),
+            Error::DatabaseIsInUse
+        ));
+        drop(engine);
+
+        // 目录中有其他文件时保留目录和其他文件
+        let other_file = opts.dir_path.join("other");
+        std::fs::write(&other_file, "other").unwrap();
+        Engine::destroy(&opts.dir_path).unwrap();
+        let file_names = read_dir(&opts.dir_path)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(file_names, vec!["other"]);
+
+        std::fs::remove_file(other_file).unwrap();
+        Engine::destroy(&opts.dir_path).unwrap();
+        assert!(!opts.dir_path.exists());
+        // 目录不存在时直接返回
+        Engine::destroy(&opts.dir_path).unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(engine.is_empty());
+        drop(engine);
+        Engine::destroy(&opts.dir_path).unwrap();
+        assert!(!opts.dir_path.exists());
+    }
+}
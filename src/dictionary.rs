@@ -0,0 +1,232 @@
+//! zstd字典，提高大量相似的小value的压缩率
+//!
+//! 字典保存在数据库目录中的zstd-dict-<序号>文件中，序号最大的字典用于压缩。
+//! 压缩后的数据帧中记录了字典ID，重新训练字典后旧的字典仍然保留，用于读取之前写入的数据
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use parking_lot::RwLock;
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+use crate::db::{read_dir, Engine};
+use crate::error::{Error, Result};
+
+/// 字典文件名的前缀
+pub(crate) const DICT_FILE_PREFIX: &str = "zstd-dict-";
+/// 使用字典压缩的value的最大大小，更大的value直接使用zstd压缩
+pub(crate) const DICT_MAX_VALUE_SIZE: usize = 4 * 1024;
+/// 训练的字典的最大大小
+const DICT_MAX_SIZE: usize = 16 * 1024;
+
+/// 数据库的所有zstd字典
+#[derive(Default)]
+pub(crate) struct Dictionaries {
+    /// 压缩使用的字典，即最新训练的字典，以及它的序号
+    encoder: RwLock<Option<(u32, Arc<EncoderDictionary<'static>>)>>,
+    /// 字典ID -> 解压使用的字典
+    decoders: RwLock<HashMap<u32, Arc<DecoderDictionary<'static>>>>,
+}
+
+impl Dictionaries {
+    /// 加载数据库目录中的字典
+    pub(crate) fn load(dir_path: &Path) -> Result<Self> {
+        let dictionaries = Self::default();
+        let mut dict_files = read_dir(dir_path)?
+            .iter()
+            .filter_map(|entry| {
+                let file_name = entry.file_name().into_string().ok()?;
+                let seq = file_name
+                    .strip_prefix(DICT_FILE_PREFIX)?
+                    .parse::<u32>()
+                    .ok()?;
+                Some((seq, entry.path()))
+            })
+            .collect::<Vec<_>>();
+        dict_files.sort();
+        for (seq, path) in dict_files {
+            let dict = std::fs::read(&path).map_err(|source| Error::FailedToReadDictionary {
+                path: path.clone(),
+                source,
+            })?;
+            dictionaries.insert(seq, &dict)?;
+        }
+        Ok(dictionaries)
+    }
+
+    /// 重新加载数据库目录中的字典，之前加载的字典仍然可以用于解压
+    pub(crate) fn reload(&self, dir_path: &Path) -> Result<()> {
+        let dictionaries = Self::load(dir_path)?;
+        self.decoders
+            .write()
+            .extend(dictionaries.decoders.into_inner());
+        *self.encoder.write() = dictionaries.encoder.into_inner();
+        Ok(())
+    }
+
+    /// 添加新的字典，之后使用它压缩数据，dir_path不为空时持久化到数据库目录中，返回字典ID
+    pub(crate) fn add(&self, dir_path: Option<&Path>, dict: &[u8]) -> Result<u32> {
+        let seq = self.encoder.read().as_ref().map_or(0, |(seq, _)| seq + 1);
+        if let Some(dir_path) = dir_path {
+            let path = dir_path.join(format!("{}{}", DICT_FILE_PREFIX, seq));
+            std::fs::write(&path, dict)
+                .map_err(|source| Error::FailedToWriteDictionary { path, source })?;
+        }
+        self.insert(seq, dict)
+    }
+
+    fn insert(&self, seq: u32, dict: &[u8]) -> Result<u32> {
+        let dict_id = zstd::zstd_safe::get_dict_id_from_dict(dict)
+            .ok_or(Error::InvalidDictionary)?
+            .get();
+        let encoder = EncoderDictionary::try_copy(dict, zstd::DEFAULT_COMPRESSION_LEVEL)
+            .map_err(|_| Error::InvalidDictionary)?;
+        let decoder = DecoderDictionary::try_copy(dict).map_err(|_| Error::InvalidDictionary)?;
+        self.decoders.write().insert(dict_id, Arc::new(decoder));
+        *self.encoder.write() = Some((seq, Arc::new(encoder)));
+        Ok(dict_id)
+    }
+
+    /// 是否可以使用字典压缩大小为len的value
+    pub(crate) fn can_compress(&self, len: usize) -> bool {
+        len <= DICT_MAX_VALUE_SIZE && self.encoder.read().is_some()
+    }
+
+    /// 使用最新的字典压缩数据，失败时返回None
+    pub(crate) fn compress(&self, value: &[u8]) -> Option<Vec<u8>> {
+        let (_, dict) = self.encoder.read().clone()?;
+        zstd::bulk::Compressor::with_prepared_dictionary(&dict)
+            .and_then(|mut compressor| compressor.compress(value))
+            .ok()
+    }
+
+    /// 使用数据帧中记录的字典解压数据
+    pub(crate) fn decompress(&self, value: &[u8]) -> Result<Vec<u8>> {
+        let dict_id = zstd::zstd_safe::get_dict_id_from_frame(value)
+            .ok_or(Error::FailedToDecompressValue)?
+            .get();
+        let dict = self
+            .decoders
+            .read()
+            .get(&dict_id)
+            .cloned()
+            .ok_or(Error::MissingDictionary(dict_id))?;
+        let mut buf = Vec::new();
+        zstd::stream::Decoder::with_prepared_dictionary(value, &dict)
+            .and_then(|mut decoder| decoder.read_to_end(&mut buf))
+            .map_err(|_| Error::FailedToDecompressValue)?;
+        Ok(buf)
+    }
+}
+
+impl Engine {
+    /// 使用sample_keys对应的value训练zstd字典，返回字典ID，不存在的key被忽略
+    ///
+    /// 字典保存在数据库目录中，压缩算法配置为CompressionType::ZstdDict时，
+    /// 不超过4KB的value使用最新训练的字典压缩，适合大量相似的小value
+    pub fn train_dictionary(&self, sample_keys: &[Bytes]) -> Result<u32> {
+        self.check_closed()?;
+        let mut samples = Vec::with_capacity(sample_keys.len());
+        for key in sample_keys {
+            match self.get(key.clone()) {
+                Ok(value) => samples.push(value),
+                Err(Error::KeyNotFound) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        let dict = zstd::dict::from_samples(&samples, DICT_MAX_SIZE)
+            .map_err(|e| Error::FailedToTrainDictionary(e.to_string()))?;
+        // 内存数据库的字典只保存在内存中
+        let dir_path = (!self.is_in_memory()).then_some(self.options.dir_path.as_path());
+        self.dictionaries.add(dir_path, &dict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::options::{BackupOptions, CompressionType, Options};
+    use crate::util::rand_kv::get_test_key;
+
+    use super::*;
+
+    /// 相似的小value
+    fn value(i: usize) -> Bytes {
+        Bytes::from(format!(
+            "{{\"id\":{},\"name\":\"user-{}\",\"email\":\"user-{}@example.com\",\"active\":true}}",
+            i,
+            i % 97,
+            i
+        ))
+    }
+
+    #[test]
+    fn test_engine_train_dictionary() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-dictionary");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.compression = CompressionType::ZstdDict;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), value(i)).unwrap();
+        }
+        let size_before = engine.stat().unwrap().disk_size;
+
+        // 样本太少无法训练
+        assert!(matches!(
+            engine.train_dictionary(&[get_test_key(0)]),
+            Err(Error::FailedToTrainDictionary(_))
+        ));
+        let sample_keys = (0..1000).map(get_test_key).collect::<Vec<_>>();
+        let dict_id = engine.train_dictionary(&sample_keys).unwrap();
+        assert!(opts.dir_path.join("zstd-dict-0").is_file());
+        for i in 1000..2000 {
+            engine.put(get_test_key(i), value(i)).unwrap();
+        }
+        // 使用字典后压缩率更高
+        let size_after = engine.stat().unwrap().disk_size;
+        assert!(size_after - size_before < size_before * 4 / 5);
+
+        // 重新训练字典后，旧的字典仍然可以解压之前的数据
+        let new_dict_id = engine.train_dictionary(&sample_keys[500..]).unwrap();
+        assert_ne!(new_dict_id, dict_id);
+        engine.put(get_test_key(2000), value(2000)).unwrap();
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..=2000 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), value(i));
+        }
+        // merge时使用最新的字典重写数据
+        engine.merge().unwrap();
+        for i in 0..=2000 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), value(i));
+        }
+
+        // 备份中包含字典
+        let mut backup_opts = opts.clone();
+        backup_opts.dir_path = PathBuf::from("/tmp/bitcask-rs-dictionary-backup");
+        engine
+            .backup(&backup_opts.dir_path, BackupOptions::default())
+            .unwrap();
+        let backup = Engine::open(backup_opts.clone()).expect("failed to open backup");
+        assert_eq!(backup.get(get_test_key(1500)).unwrap(), value(1500));
+        drop(backup);
+        std::fs::remove_dir_all(backup_opts.dir_path).expect("failed to remove backup dir");
+
+        // 字典丢失时无法读取使用字典压缩的数据
+        drop(engine);
+        std::fs::remove_file(opts.dir_path.join("zstd-dict-0")).unwrap();
+        std::fs::remove_file(opts.dir_path.join("zstd-dict-1")).unwrap();
+        std::fs::remove_file(opts.dir_path.join("hint-index")).unwrap();
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::MissingDictionary(_)
+        ));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
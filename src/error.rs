@@ -1,30 +1,79 @@
-use crate::data::log_record::LogRecordPos;
+use std::path::PathBuf;
+use std::time::Duration;
 
-pub(crate) type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug, PartialEq, thiserror::Error)]
+/// IO相关的错误包含原始的std::io::Error、文件路径以及读取位置，之后可能增加新的错误类型
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
-    #[error("Failed to read from data file")]
-    FailedToReadFromDataFile,
+    #[error("Failed to read from data file {path:?} at offset {offset}: {source}")]
+    FailedToReadFromDataFile {
+        path: PathBuf,
+        offset: u64,
+        source: std::io::Error,
+    },
 
-    #[error("Failed to write to data file")]
-    FailedToWriteToDataFile,
+    #[error("Failed to write to data file {path:?}: {source}")]
+    FailedToWriteToDataFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
 
-    #[error("Failed to sync data file")]
-    FailedToSyncDataFile,
+    #[error("Failed to sync data file {path:?}: {source}")]
+    FailedToSyncDataFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Disk is full when writing {path:?}: {source}")]
+    DiskFull {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Database is read-only because the disk is full, reopen it after freeing disk space")]
+    ReadOnly,
 
     #[error("Key is empty")]
     KeyIsEmpty,
 
-    #[error("Failed to open data file")]
-    FailedToOpenDataFile,
+    #[error("Key is larger than the max key size")]
+    KeyTooLarge,
+
+    #[error("Value is larger than the max value size")]
+    ValueTooLarge,
+
+    #[error("Record is larger than the data file size")]
+    RecordTooLarge,
+
+    #[error("Failed to read value from reader: {source}")]
+    FailedToReadValue { source: std::io::Error },
+
+    #[error("Failed to open data file {path:?}: {source}")]
+    FailedToOpenDataFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
 
     #[error("Failed to update index")]
     FailedToUpdateIndex,
 
+    #[error("Failed to read index")]
+    FailedToReadIndex,
+
+    #[error("Failed to open index file")]
+    FailedToOpenIndexFile,
+
+    #[error("Index memory limit exceeded")]
+    IndexMemoryLimitExceeded,
+
     #[error("Key not found")]
     KeyNotFound,
 
+    #[error("Value is not an integer or out of range")]
+    ValueIsNotInteger,
+
     #[error("Data file not found")]
     DataFileNotFound,
 
@@ -34,20 +83,83 @@ pub enum Error {
     #[error("Invalid data file size")]
     InvalidDataFileSize,
 
-    #[error("Failed to create database directory")]
-    FailedToCreateDbDir,
+    #[error("Invalid IO type, memory map can only be used at startup, memory IO can not be used with B+ tree index")]
+    InvalidIOType,
+
+    #[error("Invalid merge ratio, it must be in (0, 1]")]
+    InvalidMergeRatio,
+
+    #[error("Invalid bucket name, it must be non-empty and must not contain '\\0'")]
+    InvalidBucketName,
+
+    #[error("Secondary index not found")]
+    SecondaryIndexNotFound,
+
+    #[error("Secondary index already exists")]
+    SecondaryIndexAlreadyExists,
+
+    #[error("Log cursor expired, the data file has been merged")]
+    LogCursorExpired,
+
+    #[error("Invalid sync interval, it must be greater than 0")]
+    InvalidSyncInterval,
+
+    #[error("Invalid write rate limit, it must be greater than 0")]
+    InvalidWriteRateLimit,
+
+    #[error("Invalid merge rate limit, it must be greater than 0")]
+    InvalidMergeRateLimit,
+
+    #[error("Invalid index checkpoint interval, it must be greater than 0")]
+    InvalidCheckpointInterval,
+
+    #[error("Invalid open timeout, it must be greater than 0")]
+    InvalidOpenTimeout,
+
+    #[error("Invalid retention, it must be greater than 0")]
+    InvalidRetention,
+
+    #[error("Invalid max database size, it must be greater than 0")]
+    InvalidMaxDbSize,
 
-    #[error("Failed to read directory")]
-    FailedToReadDir,
+    #[error("Database size exceeds the quota")]
+    QuotaExceeded,
 
-    #[error("Failed to read directory entry")]
-    FailedToReadDirEntry,
+    #[error("Invalid eviction policy, it requires max_db_size or index_memory_limit")]
+    InvalidEvictionPolicy,
+
+    #[error("Open database timed out after {0:?}")]
+    OpenTimeout(Duration),
+
+    #[error("Database {path:?} does not exist")]
+    DatabaseNotFound { path: PathBuf },
+
+    #[error("Database {path:?} already exists")]
+    DatabaseAlreadyExists { path: PathBuf },
+
+    #[error("Failed to create database directory {path:?}: {source}")]
+    FailedToCreateDbDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to read directory {path:?}: {source}")]
+    FailedToReadDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to read directory entry in {path:?}: {source}")]
+    FailedToReadDirEntry {
+        path: PathBuf,
+        source: std::io::Error,
+    },
 
     #[error("Failed to parse file id")]
     FailedToParseFileId,
 
-    #[error("Failed to create data file")]
-    FailedToCreateDataFile,
+    #[error("Data file {0} exists in more than one data directory")]
+    DuplicateDataFile(u32),
 
     #[error("Read data file EOF")]
     ReadDataFileEOF,
@@ -55,6 +167,268 @@ pub enum Error {
     #[error("Invalid log record CRC")]
     InvalidLogRecordCRC,
 
+    #[error("Invalid log record, it may be partially written")]
+    InvalidLogRecord,
+
+    #[error("Failed to decompress value")]
+    FailedToDecompressValue,
+
+    #[error("Failed to encrypt value")]
+    FailedToEncryptValue,
+
+    #[error("Failed to decrypt value, the encryption key may be wrong")]
+    FailedToDecryptValue,
+
+    #[error("Data is encrypted but no encryption key is set")]
+    MissingEncryptionKey,
+
+    #[error("Invalid data file header")]
+    InvalidDataFileHeader,
+
+    #[error("Unsupported data file version: {0}")]
+    UnsupportedDataFileVersion(u16),
+
+    #[error("Unsupported checksum type: {0}")]
+    UnsupportedChecksumType(u16),
+
     #[error("Batch too large")]
     BatchTooLarge,
+
+    #[error("Cannot reload the database while snapshots are in use")]
+    SnapshotInUse,
+
+    #[error("Data file {0} cannot be compacted, it is the active file or does not exist")]
+    InvalidCompactionFile(u32),
+
+    #[error("Merge is in progress, try again later")]
+    MergeInProgress,
+
+    #[error("Merge is paused, resume it before merging")]
+    MergePaused,
+
+    #[error("Merge output exceeds the reserved data files, rewritten records are larger than the original ones")]
+    MergeOutputTooLarge,
+
+    #[error("Failed to create merge directory {path:?}: {source}")]
+    FailedToCreateMergeDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to remove merge directory {path:?}: {source}")]
+    FailedToRemoveMergeDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to move merge file {from:?} to {to:?}: {source}")]
+    FailedToMoveMergeFile {
+        from: PathBuf,
+        to: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to preallocate data file {path:?}: {source}")]
+    FailedToPreallocateDataFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to remove data file {path:?}: {source}")]
+    FailedToRemoveDataFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to truncate data file {path:?} to {offset} bytes: {source}")]
+    FailedToTruncateDataFile {
+        path: PathBuf,
+        offset: u64,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to remove seq num file {path:?}: {source}")]
+    FailedToRemoveSeqNumFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to open lock file {path:?}: {source}")]
+    FailedToOpenLockFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Database directory is used by another process")]
+    DatabaseIsInUse,
+
+    #[error("Failed to unlock database directory {path:?}: {source}")]
+    FailedToUnlockDatabase {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Database is closed")]
+    DatabaseClosed,
+
+    #[error("Failed to write index checkpoint {path:?}: {source}")]
+    FailedToWriteCheckpoint {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to read index checkpoint {path:?}: {source}")]
+    FailedToReadCheckpoint {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to write merge marker {path:?}: {source}")]
+    FailedToWriteMergeMarker {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to read merge marker {path:?}: {source}")]
+    FailedToReadMergeMarker {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid merge marker {path:?}")]
+    InvalidMergeMarker { path: PathBuf },
+
+    #[error("Failed to write clear marker {path:?}: {source}")]
+    FailedToWriteClearMarker {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to read clear marker {path:?}: {source}")]
+    FailedToReadClearMarker {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid clear marker {path:?}")]
+    InvalidClearMarker { path: PathBuf },
+
+    #[error("Failed to remove database file {path:?}: {source}")]
+    FailedToRemoveDatabaseFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Backup directory can not be the database directory")]
+    InvalidBackupDir,
+
+    #[error("In-memory database can not be backed up")]
+    BackupNotSupported,
+
+    #[error("Failed to create backup directory {path:?}: {source}")]
+    FailedToCreateBackupDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to remove backup file {path:?}: {source}")]
+    FailedToRemoveBackupFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to copy file {from:?} to {to:?}: {source}")]
+    FailedToCopyFile {
+        from: PathBuf,
+        to: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to start replication: {source}")]
+    FailedToStartReplication { source: std::io::Error },
+
+    #[error("Replication failed: {source}")]
+    ReplicationFailed { source: std::io::Error },
+
+    #[error("Invalid replication message")]
+    InvalidReplicationMessage,
+
+    #[error("Invalid replication state file {path:?}")]
+    InvalidReplicationState { path: PathBuf },
+
+    #[error("Failed to load replication state {path:?}: {source}")]
+    FailedToLoadReplicationState {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to save replication state {path:?}: {source}")]
+    FailedToSaveReplicationState {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid raft command")]
+    InvalidRaftCommand,
+
+    #[error("Transaction conflicts with a concurrent write")]
+    TransactionConflict,
+
+    #[error("Merge operator is not set")]
+    MergeOperatorNotSet,
+
+    #[error("Failed to repair data file {path:?}: {source}")]
+    FailedToRepairDataFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to quarantine file {from:?} to {to:?}: {source}")]
+    FailedToQuarantineFile {
+        from: PathBuf,
+        to: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to read dictionary {path:?}: {source}")]
+    FailedToReadDictionary {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to write dictionary {path:?}: {source}")]
+    FailedToWriteDictionary {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid zstd dictionary")]
+    InvalidDictionary,
+
+    #[error("Zstd dictionary {0} is missing")]
+    MissingDictionary(u32),
+
+    #[error("Failed to train dictionary: {0}")]
+    FailedToTrainDictionary(String),
+
+    #[error("Blocking task was cancelled")]
+    TaskCancelled,
+}
+
+impl Error {
+    /// 写入数据失败，磁盘空间不足时返回DiskFull
+    pub(crate) fn write_failed(path: PathBuf, source: std::io::Error) -> Self {
+        match source.kind() {
+            std::io::ErrorKind::StorageFull => Error::DiskFull { path, source },
+            _ => Error::FailedToWriteToDataFile { path, source },
+        }
+    }
+
+    /// 持久化数据失败，延迟分配磁盘空间的文件系统在持久化时才会发现磁盘空间不足
+    pub(crate) fn sync_failed(path: PathBuf, source: std::io::Error) -> Self {
+        match source.kind() {
+            std::io::ErrorKind::StorageFull => Error::DiskFull { path, source },
+            _ => Error::FailedToSyncDataFile { path, source },
+        }
+    }
 }
@@ -0,0 +1,210 @@
+//! 缓存模式的数据淘汰
+//!
+//! 按LRU淘汰时记录key的读写顺序，写入数据前数据库大小或者索引内存达到上限时，
+//! 写入删除记录淘汰最近最少读写的key。删除记录只是无效数据，淘汰后再merge释放磁盘空间
+
+use std::sync::atomic::Ordering;
+
+use bytes::Bytes;
+use lru::LruCache;
+
+use crate::db::Engine;
+use crate::error::Result;
+
+/// 淘汰到上限的该比例以下，避免每次写入都触发淘汰和merge
+const EVICTION_TARGET_RATIO: f64 = 0.9;
+
+/// key的读写顺序，被其他操作删除的key在淘汰时跳过
+#[derive(Debug)]
+pub(crate) struct AccessTracker {
+    keys: LruCache<Vec<u8>, ()>,
+}
+
+impl AccessTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            keys: LruCache::unbounded(),
+        }
+    }
+
+    /// key被读取或写入
+    pub(crate) fn touch(&mut self, key: &[u8]) {
+        if self.keys.get(key).is_none() {
+            self.keys.put(key.to_vec(), ());
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: &[u8]) {
+        self.keys.pop(key);
+    }
+
+    /// 取出最近最少读写的key
+    fn pop_lru(&mut self) -> Option<Vec<u8>> {
+        self.keys.pop_lru().map(|(key, _)| key)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.keys.clear();
+    }
+}
+
+impl Engine {
+    /// 记录key被读取或写入
+    pub(crate) fn record_access(&self, key: &[u8]) {
+        if let Some(tracker) = &self.access_tracker {
+            tracker.lock().touch(key);
+        }
+    }
+
+    /// 打开数据库后按索引中的key初始化读写顺序
+    pub(crate) fn reset_access_tracker(&self) -> Result<()> {
+        if let Some(tracker) = &self.access_tracker {
+            let keys = self.index.list_keys()?;
+            let mut tracker = tracker.lock();
+            tracker.clear();
+            keys.iter().for_each(|key| tracker.touch(key));
+        }
+        Ok(())
+    }
+
+    /// 按LRU淘汰时，写入数据前索引内存或者数据库大小达到上限则淘汰最近最少读写的key，
+    /// 数据库大小超出上限时随后在后台merge。不能在持有rotate_lock时调用
+    pub(crate) fn evict_if_needed(&self) -> Result<()> {
+        if self.access_tracker.is_none() {
+            return Ok(());
+        }
+        if let Some(limit) = self.options.index_memory_limit {
+            if self.index.memory_usage() >= limit {
+                let target = (limit as f64 * EVICTION_TARGET_RATIO) as usize;
+                self.evict_while(|| self.index.memory_usage() > target)?;
+            }
+        }
+        if let Some(limit) = self.options.max_db_size {
+            if self.db_size.load(Ordering::SeqCst) >= limit {
+                let target = (limit as f64 * EVICTION_TARGET_RATIO) as u64;
+                // 淘汰到有效数据低于目标大小，merge后磁盘占用接近有效数据的大小
+                self.evict_while(|| self.live_db_size() > target)?;
+                // 不阻塞写入，在后台线程中merge释放被淘汰的数据占用的磁盘空间
+                self.spawn_background_merge();
+            }
+        }
+        Ok(())
+    }
+
+    /// 满足条件时不断淘汰最近最少读写的key
+    fn evict_while(&self, over_limit: impl Fn() -> bool) -> Result<()> {
+        let Some(tracker) = &self.access_tracker else {
+            return Ok(());
+        };
+        while over_limit() {
+            let Some(key) = tracker.lock().pop_lru() else {
+                break;
+            };
+            if self.index.get(key.clone()).is_some() {
+                self.delete(Bytes::from(key))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::error::Error;
+    use crate::options::{EvictionPolicy, Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    fn data_files_size(engine: &Engine) -> u64 {
+        engine
+            .file_stats()
+            .unwrap()
+            .iter()
+            .map(|stat| stat.size)
+            .sum()
+    }
+
+    /// 等待淘汰后触发的后台merge完成。merge期间写入的数据要由下一次写入触发的merge清理
+    fn wait_background_merge(engine: &Engine) {
+        if let Some(worker) = engine.merge_worker.lock().take() {
+            worker.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_engine_evict_lru_by_db_size() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-evict-db-size");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        opts.max_db_size = Some(256 * 1024);
+        opts.eviction_policy = EvictionPolicy::Lru;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..5000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+            // 经常读取的key不会被淘汰
+            assert_eq!(engine.get(get_test_key(0)).unwrap(), get_test_value(0));
+        }
+        // 写入不等待merge，有效数据始终低于上限
+        assert!(engine.live_db_size() < 256 * 1024);
+        wait_background_merge(&engine);
+        let batch = engine.new_write_batch(WriteOptions::default()).unwrap();
+        batch.put(get_test_key(5000), get_test_value(5000)).unwrap();
+        batch.commit().unwrap();
+        wait_background_merge(&engine);
+
+        assert!(data_files_size(&engine) < 256 * 1024);
+        assert!(engine.len() < 5000);
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), get_test_value(0));
+        // 最早写入的key被淘汰，最近写入的key仍然存在
+        assert!(matches!(
+            engine.get(get_test_key(1)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+        for i in 4900..5001 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+        drop(batch);
+        drop(engine);
+
+        // 重新打开后按索引中的key继续淘汰
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 5001..6000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        wait_background_merge(&engine);
+        engine
+            .put(get_test_key(6000), get_test_value(6000))
+            .unwrap();
+        wait_background_merge(&engine);
+        assert!(data_files_size(&engine) < 256 * 1024);
+        assert_eq!(
+            engine.get(get_test_key(5999)).unwrap(),
+            get_test_value(5999)
+        );
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_evict_lru_by_index_memory() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-evict-index-memory");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.index_memory_limit = Some(4096);
+        opts.eviction_policy = EvictionPolicy::Lru;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        assert!(engine.len() < 1000);
+        assert!(engine.stat().unwrap().index_memory_usage <= 4096);
+        assert_eq!(engine.get(get_test_key(999)).unwrap(), get_test_value(999));
+        assert!(engine.get(get_test_key(0)).is_err());
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
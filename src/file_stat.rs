@@ -0,0 +1,234 @@
+//! 数据文件的统计信息
+//!
+//! 无效数据大小在数据被覆盖或删除时累加，记录数量在写入和启动加载数据文件时累加，
+//! 没有加载过的数据文件（例如从hint文件中加载索引）在第一次查询时读取一次
+
+use std::collections::HashMap;
+
+use crate::data::data_file::{DataFile, DATA_FILE_HEADER_SIZE};
+use crate::data::log_record::LogRecordPos;
+use crate::db::Engine;
+use crate::error::{Error, Result};
+
+/// 数据文件的统计信息
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "http", derive(serde::Serialize))]
+pub struct FileStat {
+    /// 数据文件ID
+    pub file_id: u32,
+    /// 数据文件的大小，包含文件头
+    pub size: u64,
+    /// 有效数据的大小（估计值）
+    pub live_size: u64,
+    /// 可以被merge清理的无效数据大小（估计值）
+    pub dead_size: u64,
+    /// 数据记录的数量，包含无效的记录
+    pub record_count: u64,
+}
+
+#[derive(Debug, Default)]
+struct FileCounter {
+    /// 记录数量和已统计到的文件末尾位置，None表示还没有统计
+    records: Option<(u64, u64)>,
+    /// 无效数据的大小
+    dead_size: u64,
+}
+
+/// 所有数据文件的统计计数
+#[derive(Debug, Default)]
+pub(crate) struct FileCounters {
+    files: HashMap<u32, FileCounter>,
+}
+
+impl FileCounters {
+    /// pos指向的数据成为无效数据
+    pub(crate) fn add_dead_size(&mut self, pos: &LogRecordPos) {
+        self.files.entry(pos.file_id).or_default().dead_size += pos.size as u64;
+    }
+
+    pub(crate) fn set_dead_size(&mut self, file_id: u32, dead_size: u64) {
+        self.files.entry(file_id).or_default().dead_size = dead_size;
+    }
+
+    /// 所有数据文件的无效数据大小
+    pub(crate) fn dead_sizes(&self) -> Vec<(u32, u64)> {
+        let mut dead_sizes = self
+            .files
+            .iter()
+            .filter(|(_, counter)| counter.dead_size > 0)
+            .map(|(file_id, counter)| (*file_id, counter.dead_size))
+            .collect::<Vec<_>>();
+        dead_sizes.sort();
+        dead_sizes
+    }
+
+    /// 向数据文件中追加写入了count条记录，写入后文件末尾位置为end_offset
+    pub(crate) fn add_records(&mut self, file_id: u32, count: u64, end_offset: u64) {
+        // 还没有统计的文件在查询时从头读取
+        if let Some(Some((records, end))) = self.files.get_mut(&file_id).map(|c| &mut c.records) {
+            *records += count;
+            *end = end_offset;
+        }
+    }
+
+    /// 从头读取了数据文件中的所有记录
+    pub(crate) fn set_records(&mut self, file_id: u32, count: u64, end_offset: u64) {
+        self.files.entry(file_id).or_default().records = Some((count, end_offset));
+    }
+
+    /// 删除数据文件的统计计数，返回其中无效数据的大小
+    pub(crate) fn remove(&mut self, file_id: u32) -> u64 {
+        self.files
+            .remove(&file_id)
+            .map_or(0, |counter| counter.dead_size)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.files.clear();
+    }
+
+    fn has_records(&self, file_id: u32) -> bool {
+        self.files
+            .get(&file_id)
+            .is_some_and(|counter| counter.records.is_some())
+    }
+}
+
+impl Engine {
+    /// 获取每个数据文件的统计信息，按数据文件ID排序，可以用于选择需要merge的数据文件
+    pub fn file_stats(&self) -> Result<Vec<FileStat>> {
+        self.check_closed()?;
+        // 旧的数据文件不会再被写入，不阻塞写操作
+        {
+            let older_files = self.older_files.read();
+            for data_file in older_files.values() {
+                self.count_records(data_file)?;
+            }
+        }
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        // 写缓冲中的数据写入文件后才能读取
+        active_file.flush()?;
+        self.count_records(&active_file)?;
+
+        let counters = self.file_counters.lock();
+        let mut stats = older_files
+            .values()
+            .chain(std::iter::once(&*active_file))
+            .filter_map(|data_file| {
+                let file_id = data_file.get_file_id();
+                let counter = counters.files.get(&file_id)?;
+                let (record_count, size) = counter.records?;
+                let data_size = size.saturating_sub(DATA_FILE_HEADER_SIZE);
+                let dead_size = counter.dead_size.min(data_size);
+                Some(FileStat {
+                    file_id,
+                    size,
+                    live_size: data_size - dead_size,
+                    dead_size,
+                    record_count,
+                })
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by_key(|stat| stat.file_id);
+        Ok(stats)
+    }
+
+    /// 还没有统计记录数量的数据文件，从头读取一次
+    fn count_records(&self, data_file: &DataFile) -> Result<()> {
+        let file_id = data_file.get_file_id();
+        if self.file_counters.lock().has_records(file_id) {
+            return Ok(());
+        }
+        let mut reader = data_file.reader(DATA_FILE_HEADER_SIZE);
+        let mut count = 0;
+        loop {
+            match reader.next_record() {
+                Ok(_) => count += 1,
+                // 活跃数据文件末尾可能有没写完整的数据
+                Err(
+                    Error::ReadDataFileEOF | Error::InvalidLogRecordCRC | Error::InvalidLogRecord,
+                ) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        self.file_counters
+            .lock()
+            .set_records(file_id, count, reader.offset());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::options::Options;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_engine_file_stats() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-file-stats");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let stats = engine.file_stats().unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].size, DATA_FILE_HEADER_SIZE);
+        assert_eq!(stats[0].record_count, 0);
+
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        // 覆盖第一个数据文件中的数据
+        for i in 0..100 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        let stats = engine.file_stats().unwrap();
+        assert_eq!(
+            stats.iter().map(|stat| stat.file_id).collect::<Vec<_>>(),
+            engine.data_file_ids()
+        );
+        assert_eq!(
+            stats.iter().map(|stat| stat.record_count).sum::<u64>(),
+            1100
+        );
+        assert_eq!(
+            stats.iter().map(|stat| stat.dead_size).sum::<u64>(),
+            engine.stat().unwrap().reclaimable_size as u64
+        );
+        assert!(stats[0].dead_size > 0);
+        assert!(stats.iter().skip(1).all(|stat| stat.dead_size == 0));
+        assert!(stats
+            .iter()
+            .all(|stat| stat.live_size + stat.dead_size + DATA_FILE_HEADER_SIZE == stat.size));
+        let active_stat = *stats.last().unwrap();
+        assert_eq!(active_stat.size, engine.active_file_offset());
+
+        // 重启后从数据文件中统计
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.file_stats().unwrap(), stats);
+
+        // merge后从hint文件中加载索引，第一次查询时读取数据文件统计记录数量
+        engine.merge().unwrap();
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.delete(get_test_key(0)).unwrap();
+        let stats = engine.file_stats().unwrap();
+        assert_eq!(
+            stats.iter().map(|stat| stat.record_count).sum::<u64>(),
+            1001
+        );
+        assert_eq!(
+            stats.iter().map(|stat| stat.dead_size).sum::<u64>(),
+            engine.stat().unwrap().reclaimable_size as u64
+        );
+        assert_eq!(stats.iter().filter(|stat| stat.dead_size > 0).count(), 2);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,221 @@
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::fio::IOManager;
+
+/// 直接IO的对齐大小，缓冲区地址、读写位置和长度都需要按该大小对齐
+const DIRECT_IO_ALIGNMENT: usize = 4096;
+
+/// 直接IO，读写绕过page cache，避免大量写入时挤占其他服务的page cache
+///
+/// 每次写入都会重写最后一个没有写满的块，按整块写入后再截断到实际大小，
+/// 因此写入的数据不会丢失，但小数据的写入放大比较明显，适合批量导入数据
+pub struct DirectIO {
+    file: File,
+    /// 文件路径，用于错误信息
+    path: PathBuf,
+    /// 写入需要互斥
+    state: Mutex<WriteState>,
+}
+
+struct WriteState {
+    /// 文件的实际大小
+    len: u64,
+    /// 最后一个没有写满的块中的数据
+    tail: Vec<u8>,
+}
+
+impl DirectIO {
+    pub fn new(file_name: impl AsRef<Path>) -> Result<Self> {
+        let path = file_name.as_ref().to_path_buf();
+        // 不能以追加模式打开，追加模式下会忽略写入的位置
+        let res = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(&path)
+            .and_then(|file| Ok((file.metadata()?.len(), file)));
+        let (len, file) = match res {
+            Ok(res) => res,
+            Err(source) => return Err(Error::FailedToOpenDataFile { path, source }),
+        };
+        let io = Self {
+            file,
+            path,
+            state: Mutex::new(WriteState {
+                len,
+                tail: Vec::new(),
+            }),
+        };
+        // 读取最后一个没有写满的块
+        let tail_len = len as usize % DIRECT_IO_ALIGNMENT;
+        let mut tail = vec![0; tail_len];
+        if io.read(&mut tail, len - tail_len as u64)? < tail_len {
+            return Err(Error::FailedToOpenDataFile {
+                path: io.path.clone(),
+                source: std::io::ErrorKind::UnexpectedEof.into(),
+            });
+        }
+        io.state.lock().tail = tail;
+        Ok(io)
+    }
+}
+
+impl IOManager for DirectIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let len = self.state.lock().len;
+        if offset >= len {
+            return Ok(0);
+        }
+        let end = (offset + buf.len() as u64).min(len);
+        let aligned_start = align_down(offset);
+        let mut aligned = AlignedBuf::new(align_up(end) as usize - aligned_start as usize);
+        let mut n_bytes = 0;
+        while n_bytes < (end - aligned_start) as usize {
+            match self.file.read_at(
+                &mut aligned.as_mut_slice()[n_bytes..],
+                aligned_start + n_bytes as u64,
+            ) {
+                Ok(0) => break,
+                Ok(n) => n_bytes += n,
+                Err(source) => {
+                    return Err(Error::FailedToReadFromDataFile {
+                        path: self.path.clone(),
+                        offset,
+                        source,
+                    });
+                }
+            }
+        }
+        let start = (offset - aligned_start) as usize;
+        let n_bytes = n_bytes
+            .min((end - aligned_start) as usize)
+            .saturating_sub(start);
+        buf[..n_bytes].copy_from_slice(&aligned.as_slice()[start..start + n_bytes]);
+        Ok(n_bytes)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        let mut state = self.state.lock();
+        // 从最后一个没有写满的块开始写入
+        let block_start = state.len - state.tail.len() as u64;
+        let total = state.tail.len() + buf.len();
+        let mut aligned = AlignedBuf::new(total);
+        aligned.as_mut_slice()[..state.tail.len()].copy_from_slice(&state.tail);
+        aligned.as_mut_slice()[state.tail.len()..total].copy_from_slice(buf);
+        let new_len = state.len + buf.len() as u64;
+        // 写入的是整块数据，需要截断补齐的部分
+        let res = self
+            .file
+            .write_all_at(aligned.as_slice(), block_start)
+            .and_then(|_| self.file.set_len(new_len));
+        if let Err(source) = res {
+            return Err(Error::write_failed(self.path.clone(), source));
+        }
+        state.len = new_len;
+        state.tail = aligned.as_slice()[align_down(total as u64) as usize..total].to_vec();
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&self, bufs: &[&[u8]]) -> Result<usize> {
+        // 每次写入都需要重写最后一个块，合并后只写入一次
+        self.write(&bufs.concat())
+    }
+
+    fn sync(&self) -> Result<()> {
+        self.file
+            .sync_data()
+            .map_err(|source| Error::sync_failed(self.path.clone(), source))
+    }
+}
+
+fn align_down(n: u64) -> u64 {
+    n / DIRECT_IO_ALIGNMENT as u64 * DIRECT_IO_ALIGNMENT as u64
+}
+
+fn align_up(n: u64) -> u64 {
+    n.div_ceil(DIRECT_IO_ALIGNMENT as u64) * DIRECT_IO_ALIGNMENT as u64
+}
+
+/// 地址和长度都按DIRECT_IO_ALIGNMENT对齐的缓冲区，初始化为0
+struct AlignedBuf {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl AlignedBuf {
+    /// 长度向上对齐，至少为一个块
+    fn new(len: usize) -> Self {
+        let len = (align_up(len as u64) as usize).max(DIRECT_IO_ALIGNMENT);
+        let layout = Layout::from_size_align(len, DIRECT_IO_ALIGNMENT).unwrap();
+        // layout的大小不为0
+        let ptr = unsafe { alloc_zeroed(layout) };
+        match NonNull::new(ptr) {
+            Some(ptr) => Self { ptr, layout },
+            None => std::alloc::handle_alloc_error(layout),
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_direct_io_read_write() {
+        let path = PathBuf::from("/tmp/direct-io-test.data");
+        let _ = std::fs::remove_file(&path);
+        let io = DirectIO::new(&path).unwrap();
+        assert_eq!(io.write(b"Hello, ").unwrap(), 7);
+        assert_eq!(io.write(b"world!").unwrap(), 6);
+        // 跨越多个块的写入
+        let large = vec![1; DIRECT_IO_ALIGNMENT * 2 + 100];
+        assert_eq!(io.write(&large).unwrap(), large.len());
+        io.sync().unwrap();
+        let len = 13 + large.len();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len as u64);
+
+        let mut buf = vec![0; 5];
+        assert_eq!(io.read(&mut buf, 7).unwrap(), 5);
+        assert_eq!(buf, b"world");
+        let mut buf = vec![0; large.len() + 10];
+        assert_eq!(io.read(&mut buf, 13).unwrap(), large.len());
+        assert_eq!(&buf[..large.len()], large.as_slice());
+        assert_eq!(io.read(&mut buf, len as u64).unwrap(), 0);
+
+        // 重新打开后继续写入
+        let io = DirectIO::new(&path).unwrap();
+        io.write(b"abc").unwrap();
+        let mut buf = vec![0; 3];
+        assert_eq!(io.read(&mut buf, len as u64).unwrap(), 3);
+        assert_eq!(buf, b"abc");
+        let mut buf = vec![0; 13];
+        io.read(&mut buf, 0).unwrap();
+        assert_eq!(buf, b"Hello, world!");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,10 +1,8 @@
 use std::fs::{File, OpenOptions};
-use std::io::Write;
-use std::os::unix::prelude::FileExt;
-use std::path::Path;
+use std::io::{IoSlice, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use log::error;
 use parking_lot::RwLock;
 
 use crate::error::{Error, Result};
@@ -12,23 +10,24 @@ use crate::fio::IOManager;
 
 pub struct FileIO {
     fd: Arc<RwLock<File>>,
+    /// 文件路径，用于错误信息
+    path: PathBuf,
 }
 
 impl FileIO {
     pub fn new(file_name: impl AsRef<Path>) -> Result<Self> {
+        let path = file_name.as_ref().to_path_buf();
         match OpenOptions::new()
             .create(true)
             .read(true)
             .append(true)
-            .open(file_name)
+            .open(&path)
         {
             Ok(file) => Ok(Self {
                 fd: Arc::new(RwLock::new(file)),
+                path,
             }),
-            Err(e) => {
-                error!("open file error: {}", e);
-                Err(Error::FailedToOpenDataFile)
-            }
+            Err(source) => Err(Error::FailedToOpenDataFile { path, source }),
         }
     }
 }
@@ -36,41 +35,77 @@ impl FileIO {
 impl IOManager for FileIO {
     fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
         let file = self.fd.read();
-        match file.read_at(buf, offset) {
-            Ok(n) => Ok(n),
-            Err(e) => {
-                error!("read file error: {}", e);
-                Err(Error::FailedToReadFromDataFile)
-            }
-        }
+        read_at(&file, buf, offset).map_err(|source| Error::FailedToReadFromDataFile {
+            path: self.path.clone(),
+            offset,
+            source,
+        })
     }
 
     fn write(&self, buf: &[u8]) -> Result<usize> {
         let mut file = self.fd.write();
-        match file.write(buf) {
-            Ok(n) => Ok(n),
-            Err(e) => {
-                error!("write file error: {}", e);
-                Err(Error::FailedToWriteToDataFile)
+        file.write(buf)
+            .map_err(|source| Error::write_failed(self.path.clone(), source))
+    }
+
+    fn write_vectored(&self, bufs: &[&[u8]]) -> Result<usize> {
+        let mut file = self.fd.write();
+        let mut slices = bufs.iter().map(|buf| IoSlice::new(buf)).collect::<Vec<_>>();
+        let mut slices = slices.as_mut_slice();
+        let mut total = 0;
+        // 一次可能只写入了部分数据
+        while !slices.is_empty() {
+            match file.write_vectored(slices) {
+                Ok(0) => break,
+                Ok(n) => {
+                    total += n;
+                    IoSlice::advance_slices(&mut slices, n);
+                }
+                Err(source) => {
+                    return Err(Error::write_failed(self.path.clone(), source));
+                }
             }
         }
+        Ok(total)
     }
 
     fn sync(&self) -> Result<()> {
         let file = self.fd.read();
-        if let Err(e) = file.sync_data() {
-            error!("sync file error: {}", e);
-            Err(Error::FailedToSyncDataFile)
-        } else {
-            Ok(())
-        }
+        file.sync_data()
+            .map_err(|source| Error::sync_failed(self.path.clone(), source))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn preallocate(&self, len: u64) -> Result<()> {
+        super::fallocate(&self.fd.read(), &self.path, len)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn advise(&self, advice: super::Advice) {
+        super::fadvise(&self.fd.read(), advice);
     }
 }
 
+/// 从指定位置读取数据，不依赖文件当前的读写位置
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.read_at(buf, offset)
+}
+
+/// windows下seek_read会移动文件指针，但文件以追加模式打开，不影响写入
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    use std::os::windows::fs::FileExt;
+    file.seek_read(buf, offset)
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
 
+    use crate::fio::Advice;
+
     use super::*;
 
     #[test]
@@ -83,6 +118,78 @@ mod tests {
         std::fs::remove_file(path).unwrap();
     }
 
+    #[test]
+    fn test_file_io_write_vectored() {
+        let path = PathBuf::from("/tmp/file-io-write-vectored.data");
+        let _ = std::fs::remove_file(&path);
+        let file_io = FileIO::new(&path).unwrap();
+        let res = file_io
+            .write_vectored(&[b"Hello", b", ", b"", b"world!"])
+            .unwrap();
+        assert_eq!(res, 13);
+        assert_eq!(std::fs::read(&path).unwrap(), b"Hello, world!");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_file_io_open_error() {
+        let path = PathBuf::from("/tmp/bitcask-rs-not-exist-dir/a.data");
+        match FileIO::new(&path) {
+            Err(Error::FailedToOpenDataFile {
+                path: err_path,
+                source,
+            }) => {
+                assert_eq!(err_path, path);
+                assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            }
+            _ => panic!("expected FailedToOpenDataFile"),
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_file_io_disk_full() {
+        // 写入/dev/full总是返回ENOSPC
+        let file_io = FileIO::new("/dev/full").unwrap();
+        assert!(matches!(
+            file_io.write(b"key-a"),
+            Err(Error::DiskFull { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_file_io_preallocate() {
+        let path = PathBuf::from("/tmp/file-io-preallocate.data");
+        let _ = std::fs::remove_file(&path);
+        let file_io = FileIO::new(&path).unwrap();
+        file_io.write(b"Hello").unwrap();
+        file_io.preallocate(1024 * 1024).unwrap();
+        // 文件大小不变，之后的数据仍然追加到文件末尾
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 5);
+        file_io.write(b", world!").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"Hello, world!");
+        let mut buf = vec![0; 16];
+        assert_eq!(file_io.read(&mut buf, 0).unwrap(), 13);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_file_io_advise() {
+        let path = PathBuf::from("/tmp/file-io-advise.data");
+        let _ = std::fs::remove_file(&path);
+        let file_io = FileIO::new(&path).unwrap();
+        file_io.write(b"Hello, world!").unwrap();
+        // 提示只影响page cache，不影响读取的数据
+        for advice in [Advice::Sequential, Advice::WillNeed, Advice::DontNeed] {
+            file_io.advise(advice);
+            let mut buf = vec![0; 13];
+            assert_eq!(file_io.read(&mut buf, 0).unwrap(), 13);
+            assert_eq!(buf, b"Hello, world!");
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[test]
     fn test_file_io_read() {
         let path = PathBuf::from("/tmp/a.data");
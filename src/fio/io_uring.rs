@@ -0,0 +1,210 @@
+use std::fs::{File, OpenOptions};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use io_uring::{opcode, types, IoUring};
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::fio::IOManager;
+
+/// 提交队列的大小，也是批量读取时一次提交的最大请求数
+const RING_ENTRIES: u32 = 32;
+
+/// 基于io_uring的文件IO，批量读取时一次系统调用提交多个请求
+pub struct IoUringIO {
+    file: File,
+    /// 文件路径，用于错误信息
+    path: PathBuf,
+    /// 提交和等待完成需要互斥
+    ring: Mutex<IoUring>,
+}
+
+impl IoUringIO {
+    pub fn new(file_name: impl AsRef<Path>) -> Result<Self> {
+        let path = file_name.as_ref().to_path_buf();
+        let res = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .and_then(|file| Ok((file, IoUring::new(RING_ENTRIES)?)));
+        match res {
+            Ok((file, ring)) => Ok(Self {
+                file,
+                path,
+                ring: Mutex::new(ring),
+            }),
+            Err(source) => Err(Error::FailedToOpenDataFile { path, source }),
+        }
+    }
+
+    /// 提交一批请求并等待全部完成，返回每个请求的结果
+    fn submit(&self, entries: &[io_uring::squeue::Entry]) -> std::io::Result<Vec<i32>> {
+        let mut ring = self.ring.lock();
+        let mut results = vec![0; entries.len()];
+        for (i, entry) in entries.iter().enumerate() {
+            // 请求使用的缓冲区在等待完成前一直有效
+            unsafe {
+                ring.submission()
+                    .push(&entry.clone().user_data(i as u64))
+                    .map_err(std::io::Error::other)?;
+            }
+        }
+        ring.submit_and_wait(entries.len())?;
+        for cqe in ring.completion() {
+            results[cqe.user_data() as usize] = cqe.result();
+        }
+        Ok(results)
+    }
+
+    /// 只有一个请求时，返回请求的结果
+    fn submit_one(&self, entry: io_uring::squeue::Entry) -> std::io::Result<usize> {
+        let res = self.submit(&[entry])?[0];
+        if res < 0 {
+            return Err(std::io::Error::from_raw_os_error(-res));
+        }
+        Ok(res as usize)
+    }
+}
+
+impl IOManager for IoUringIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        let entry = opcode::Read::new(fd, buf.as_mut_ptr(), buf.len() as u32)
+            .offset(offset)
+            .build();
+        self.submit_one(entry)
+            .map_err(|source| Error::FailedToReadFromDataFile {
+                path: self.path.clone(),
+                offset,
+                source,
+            })
+    }
+
+    fn read_batch(&self, reqs: &mut [(u64, &mut [u8])]) -> Result<Vec<usize>> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        let mut n_bytes = Vec::with_capacity(reqs.len());
+        for chunk in reqs.chunks_mut(RING_ENTRIES as usize) {
+            let entries = chunk
+                .iter_mut()
+                .map(|(offset, buf)| {
+                    opcode::Read::new(fd, buf.as_mut_ptr(), buf.len() as u32)
+                        .offset(*offset)
+                        .build()
+                })
+                .collect::<Vec<_>>();
+            let offset = chunk[0].0;
+            let results =
+                self.submit(&entries)
+                    .map_err(|source| Error::FailedToReadFromDataFile {
+                        path: self.path.clone(),
+                        offset,
+                        source,
+                    })?;
+            for (res, (offset, _)) in results.into_iter().zip(chunk.iter()) {
+                if res < 0 {
+                    return Err(Error::FailedToReadFromDataFile {
+                        path: self.path.clone(),
+                        offset: *offset,
+                        source: std::io::Error::from_raw_os_error(-res),
+                    });
+                }
+                n_bytes.push(res as usize);
+            }
+        }
+        Ok(n_bytes)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        // 文件以追加模式打开，-1表示使用文件当前的位置
+        let entry = opcode::Write::new(fd, buf.as_ptr(), buf.len() as u32)
+            .offset(u64::MAX)
+            .build();
+        self.submit_one(entry)
+            .map_err(|source| Error::write_failed(self.path.clone(), source))
+    }
+
+    fn write_vectored(&self, bufs: &[&[u8]]) -> Result<usize> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        let iovecs = bufs
+            .iter()
+            .map(|buf| libc::iovec {
+                iov_base: buf.as_ptr() as *mut libc::c_void,
+                iov_len: buf.len(),
+            })
+            .collect::<Vec<_>>();
+        let mut total = 0;
+        // 一次提交的数据段数不能超过IOV_MAX
+        for chunk in iovecs.chunks(libc::UIO_MAXIOV as usize) {
+            let entry = opcode::Writev::new(fd, chunk.as_ptr(), chunk.len() as u32)
+                .offset(u64::MAX)
+                .build();
+            let expected = chunk.iter().map(|iov| iov.iov_len).sum::<usize>();
+            match self.submit_one(entry) {
+                Ok(n) => {
+                    total += n;
+                    if n < expected {
+                        break;
+                    }
+                }
+                Err(source) => {
+                    return Err(Error::write_failed(self.path.clone(), source));
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    fn sync(&self) -> Result<()> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        let entry = opcode::Fsync::new(fd)
+            .flags(types::FsyncFlags::DATASYNC)
+            .build();
+        match self.submit_one(entry) {
+            Ok(_) => Ok(()),
+            Err(source) => Err(Error::sync_failed(self.path.clone(), source)),
+        }
+    }
+
+    fn preallocate(&self, len: u64) -> Result<()> {
+        super::fallocate(&self.file, &self.path, len)
+    }
+
+    fn advise(&self, advice: super::Advice) {
+        super::fadvise(&self.file, advice);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_io_uring_read_write() {
+        let path = PathBuf::from("/tmp/io-uring-test.data");
+        let _ = std::fs::remove_file(&path);
+        let io = IoUringIO::new(&path).unwrap();
+        assert_eq!(io.write(b"Hello, ").unwrap(), 7);
+        assert_eq!(io.write(b"world!").unwrap(), 6);
+        io.sync().unwrap();
+
+        let mut buf = vec![0; 5];
+        assert_eq!(io.read(&mut buf, 7).unwrap(), 5);
+        assert_eq!(buf, b"world");
+
+        // 批量读取，读取到文件末尾时返回实际读取的字节数
+        let (mut a, mut b, mut c) = (vec![0; 5], vec![0; 10], vec![0; 4]);
+        let n_bytes = io
+            .read_batch(&mut [(0, &mut a), (7, &mut b), (100, &mut c)])
+            .unwrap();
+        assert_eq!(n_bytes, vec![5, 6, 0]);
+        assert_eq!(a, b"Hello");
+        assert_eq!(&b[..6], b"world!");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+
+use parking_lot::{Mutex, RwLock};
+
+use crate::error::{Error, Result};
+use crate::fio::IOManager;
+
+/// 内存文件的数据
+type MemFile = Arc<RwLock<Vec<u8>>>;
+
+/// 进程内的内存文件，相同路径打开的是同一个文件
+static MEM_FILES: LazyLock<Mutex<HashMap<PathBuf, MemFile>>> = LazyLock::new(Default::default);
+
+/// 正在使用的内存数据库目录
+static MEM_DIRS: LazyLock<Mutex<HashSet<PathBuf>>> = LazyLock::new(Default::default);
+
+/// 内存IO，数据只保存在内存中，不读写文件系统，适合单元测试和临时缓存
+pub struct MemIO {
+    data: MemFile,
+}
+
+impl MemIO {
+    pub fn new(file_name: impl AsRef<Path>) -> Self {
+        let data = MEM_FILES
+            .lock()
+            .entry(file_name.as_ref().to_path_buf())
+            .or_default()
+            .clone();
+        Self { data }
+    }
+}
+
+impl IOManager for MemIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let data = self.data.read();
+        let start = (offset as usize).min(data.len());
+        let n_bytes = buf.len().min(data.len() - start);
+        buf[..n_bytes].copy_from_slice(&data[start..start + n_bytes]);
+        Ok(n_bytes)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        self.data.write().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&self, bufs: &[&[u8]]) -> Result<usize> {
+        let mut data = self.data.write();
+        bufs.iter().for_each(|buf| data.extend_from_slice(buf));
+        Ok(bufs.iter().map(|buf| buf.len()).sum())
+    }
+
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 内存数据库目录的锁，同一目录同时只能被一个数据库使用，释放时删除目录中的所有文件
+pub(crate) struct DirLock {
+    dir_path: PathBuf,
+}
+
+impl DirLock {
+    pub(crate) fn new(dir_path: impl AsRef<Path>) -> Result<Self> {
+        let dir_path = dir_path.as_ref().to_path_buf();
+        if !MEM_DIRS.lock().insert(dir_path.clone()) {
+            return Err(Error::DatabaseIsInUse);
+        }
+        Ok(Self { dir_path })
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        remove_dir(&self.dir_path);
+        MEM_DIRS.lock().remove(&self.dir_path);
+    }
+}
+
+/// 删除目录中的所有内存文件
+pub(crate) fn remove_dir(dir_path: impl AsRef<Path>) {
+    MEM_FILES
+        .lock()
+        .retain(|path, _| path.parent() != Some(dir_path.as_ref()));
+}
+
+/// 移动内存文件，目标文件存在时被覆盖
+pub(crate) fn rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
+    let mut files = MEM_FILES.lock();
+    match files.remove(from.as_ref()) {
+        Some(data) => {
+            files.insert(to.as_ref().to_path_buf(), data);
+            Ok(())
+        }
+        None => Err(Error::FailedToMoveMergeFile {
+            from: from.as_ref().to_path_buf(),
+            to: to.as_ref().to_path_buf(),
+            source: std::io::ErrorKind::NotFound.into(),
+        }),
+    }
+}
+
+/// 删除内存文件，已经打开的IO管理器仍然可以读写
+pub(crate) fn remove_file(file_name: impl AsRef<Path>) {
+    MEM_FILES.lock().remove(file_name.as_ref());
+}
+
+/// 目录中所有内存文件的大小之和
+pub(crate) fn dir_size(dir_path: impl AsRef<Path>) -> u64 {
+    MEM_FILES
+        .lock()
+        .iter()
+        .filter(|(path, _)| path.parent() == Some(dir_path.as_ref()))
+        .map(|(_, data)| data.read().len() as u64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mem_io_read_write() {
+        let dir_path = PathBuf::from("/tmp/bitcask-rs-mem-io");
+        let path = dir_path.join("a.data");
+        let io = MemIO::new(&path);
+        assert_eq!(io.write(b"Hello, ").unwrap(), 7);
+        assert_eq!(io.write_vectored(&[b"world", b"!"]).unwrap(), 6);
+        io.sync().unwrap();
+
+        let mut buf = vec![0; 5];
+        assert_eq!(io.read(&mut buf, 7).unwrap(), 5);
+        assert_eq!(buf, b"world");
+        assert_eq!(io.read(&mut buf, 10).unwrap(), 3);
+        assert_eq!(io.read(&mut buf, 100).unwrap(), 0);
+        // 文件系统中不存在该文件
+        assert!(!path.exists());
+
+        // 相同路径打开的是同一个文件
+        let io2 = MemIO::new(&path);
+        let mut buf = vec![0; 13];
+        assert_eq!(io2.read(&mut buf, 0).unwrap(), 13);
+        assert_eq!(buf, b"Hello, world!");
+        assert_eq!(dir_size(&dir_path), 13);
+
+        let new_path = dir_path.join("b.data");
+        rename(&path, &new_path).unwrap();
+        assert!(rename(&path, &new_path).is_err());
+        assert_eq!(MemIO::new(&new_path).read(&mut buf, 0).unwrap(), 13);
+        assert_eq!(MemIO::new(&path).read(&mut buf, 0).unwrap(), 0);
+
+        remove_dir(&dir_path);
+        assert_eq!(dir_size(&dir_path), 0);
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use memmap2::Mmap;
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::fio::{Advice, IOManager};
+
+/// 内存映射IO，只用于启动时加载数据文件，不支持写入
+pub struct MMapIO {
+    map: Arc<Mutex<Mmap>>,
+    /// 文件路径，用于错误信息
+    path: PathBuf,
+}
+
+impl MMapIO {
+    pub fn new(file_name: impl AsRef<Path>) -> Result<Self> {
+        let path = file_name.as_ref().to_path_buf();
+        let map = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .and_then(|file| unsafe { Mmap::map(&file) });
+        match map {
+            Ok(map) => Ok(Self {
+                map: Arc::new(Mutex::new(map)),
+                path,
+            }),
+            Err(source) => Err(Error::FailedToOpenDataFile { path, source }),
+        }
+    }
+}
+
+impl IOManager for MMapIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let map = self.map.lock();
+        let len = map.len() as u64;
+        if offset >= len {
+            return Ok(0);
+        }
+        let end = (offset + buf.len() as u64).min(len);
+        let data = &map[offset as usize..end as usize];
+        buf[..data.len()].copy_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn write(&self, _buf: &[u8]) -> Result<usize> {
+        Err(Error::FailedToWriteToDataFile {
+            path: self.path.clone(),
+            source: std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "mmap io does not support write",
+            ),
+        })
+    }
+
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn advise(&self, advice: Advice) {
+        let advice = match advice {
+            Advice::Sequential => memmap2::Advice::Sequential,
+            Advice::WillNeed => memmap2::Advice::WillNeed,
+            // 清除映射的页需要unsafe的madvise，映射只在启动时使用，不需要清除
+            Advice::DontNeed => return,
+        };
+        // 只是提示，失败时不影响读取
+        let _ = self.map.lock().advise(advice);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::fio::file_io::FileIO;
+
+    use super::*;
+
+    #[test]
+    fn test_mmap_read() {
+        let path = PathBuf::from("/tmp/mmap-test.data");
+
+        // 文件为空
+        let mmap_io = MMapIO::new(&path).unwrap();
+        let mut buf = vec![0; 10];
+        assert_eq!(mmap_io.read(&mut buf, 0).unwrap(), 0);
+
+        // 有数据
+        let file_io = FileIO::new(&path).unwrap();
+        file_io.write(b"Hello, world!").unwrap();
+        let mmap_io = MMapIO::new(&path).unwrap();
+        let mut buf = vec![0; 5];
+        assert_eq!(mmap_io.read(&mut buf, 0).unwrap(), 5);
+        assert_eq!(buf, b"Hello");
+        assert_eq!(mmap_io.read(&mut buf, 7).unwrap(), 5);
+        assert_eq!(buf, b"world");
+
+        // 读取到文件末尾
+        let mut buf = vec![0; 10];
+        assert_eq!(mmap_io.read(&mut buf, 7).unwrap(), 6);
+        assert_eq!(&buf[..6], b"world!");
+        assert_eq!(mmap_io.read(&mut buf, 100).unwrap(), 0);
+
+        // 不支持写入
+        assert!(mmap_io.write(b"abc").is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
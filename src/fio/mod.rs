@@ -1,24 +1,129 @@
+#[cfg(target_os = "linux")]
+pub mod direct_io;
 pub mod file_io;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod io_uring;
+pub mod mem_io;
+pub mod mmap;
 
 use std::path::Path;
 
 use file_io::FileIO;
+use mem_io::MemIO;
+use mmap::MMapIO;
 
+#[cfg(target_os = "linux")]
+use crate::error::Error;
 use crate::error::Result;
+use crate::options::IOType;
 
-/// IO管理接口，目前支持file IO
+/// IO管理接口，支持标准文件IO、内存映射、直接IO、io_uring和内存IO
 pub trait IOManager: Sync + Send {
     /// 从文件中读取数据
     fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize>;
 
+    /// 批量读取数据，每个请求为读取位置和缓冲区，返回每个请求读取的字节数
+    fn read_batch(&self, reqs: &mut [(u64, &mut [u8])]) -> Result<Vec<usize>> {
+        reqs.iter_mut()
+            .map(|(offset, buf)| self.read(buf, *offset))
+            .collect()
+    }
+
     /// 向文件中写入数据
     fn write(&self, buf: &[u8]) -> Result<usize>;
 
+    /// 依次写入多段数据，返回写入的总字节数
+    fn write_vectored(&self, bufs: &[&[u8]]) -> Result<usize> {
+        bufs.iter().map(|buf| self.write(buf)).sum()
+    }
+
     /// 同步数据到磁盘
     fn sync(&self) -> Result<()>;
+
+    /// 为文件预分配len字节的磁盘空间，不改变文件大小，不支持时直接返回
+    fn preallocate(&self, _len: u64) -> Result<()> {
+        Ok(())
+    }
+
+    /// 提示内核之后如何读取文件，只影响预读和page cache，不支持时直接返回
+    fn advise(&self, _advice: Advice) {}
+}
+
+/// 文件的访问方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    /// 从头到尾顺序读取，内核加大预读
+    Sequential,
+    /// 即将读取，内核提前读取到page cache
+    WillNeed,
+    /// 不再读取，从page cache中清除
+    DontNeed,
+}
+
+/// 使用posix_fadvise提示内核文件的访问方式
+#[cfg(target_os = "linux")]
+pub(crate) fn fadvise(file: &std::fs::File, advice: Advice) {
+    use std::os::unix::io::AsRawFd;
+
+    let advice = match advice {
+        Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+        Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+        Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+    };
+    // 只是提示，失败时不影响读写
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice);
+    }
+}
+
+/// 使用fallocate预分配磁盘空间，不改变文件大小，因此追加写入的位置和读取到文件末尾的判断都不受影响
+#[cfg(target_os = "linux")]
+pub(crate) fn fallocate(file: &std::fs::File, path: &Path, len: u64) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let ret = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_KEEP_SIZE,
+            0,
+            len as libc::off_t,
+        )
+    };
+    if ret == 0 {
+        return Ok(());
+    }
+    let source = std::io::Error::last_os_error();
+    // 文件系统不支持预分配
+    if source.raw_os_error() == Some(libc::EOPNOTSUPP) {
+        return Ok(());
+    }
+    Err(Error::FailedToPreallocateDataFile {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// 持久化目录，保证目录中新建和重命名的文件在崩溃后仍然存在
+#[cfg(unix)]
+pub(crate) fn sync_dir(path: &Path) -> std::io::Result<()> {
+    std::fs::File::open(path)?.sync_all()
+}
+
+/// windows打开目录需要FILE_FLAG_BACKUP_SEMANTICS，不持久化目录
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_path: &Path) -> std::io::Result<()> {
+    Ok(())
 }
 
-pub fn new_io_manager(file_name: impl AsRef<Path>) -> Result<impl IOManager> {
-    let file_io = FileIO::new(&file_name)?;
-    Ok(file_io)
+/// 根据IO类型创建IO管理器
+pub fn new_io_manager(file_name: impl AsRef<Path>, io_type: IOType) -> Result<Box<dyn IOManager>> {
+    match io_type {
+        IOType::StandardFIO => Ok(Box::new(FileIO::new(&file_name)?)),
+        IOType::MemoryMap => Ok(Box::new(MMapIO::new(&file_name)?)),
+        #[cfg(target_os = "linux")]
+        IOType::DirectIO => Ok(Box::new(direct_io::DirectIO::new(&file_name)?)),
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        IOType::IoUring => Ok(Box::new(io_uring::IoUringIO::new(&file_name)?)),
+        IOType::Memory => Ok(Box::new(MemIO::new(&file_name))),
+    }
 }
@@ -0,0 +1,200 @@
+//! HTTP接口，需要开启`http` feature
+//!
+//! - `GET/PUT/DELETE /kv/{key}`：读取、写入（可选`?ttl=秒`）、删除数据
+//! - `GET /keys?prefix=`：列出指定前缀的key
+//! - `POST /merge`：merge数据文件
+//! - `GET /stat`：数据库统计信息
+
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::db::Engine;
+use crate::error::Error;
+
+/// 创建路由，所有接口共享同一个数据库实例
+pub fn router(engine: Engine) -> Router {
+    Router::new()
+        .route(
+            "/kv/{key}",
+            get(get_value).put(put_value).delete(delete_value),
+        )
+        .route("/keys", get(list_keys))
+        .route("/merge", post(merge))
+        .route("/stat", get(stat))
+        .with_state(engine)
+}
+
+/// 在指定地址上启动HTTP服务
+pub async fn serve(engine: Engine, addr: &str) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(engine)).await
+}
+
+/// 数据库错误转换为HTTP状态码
+fn error_response(e: Error) -> Response {
+    let status = match e {
+        Error::KeyNotFound => StatusCode::NOT_FOUND,
+        Error::KeyIsEmpty => StatusCode::BAD_REQUEST,
+        Error::KeyTooLarge | Error::ValueTooLarge | Error::RecordTooLarge => {
+            StatusCode::PAYLOAD_TOO_LARGE
+        }
+        Error::MergeInProgress => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string()).into_response()
+}
+
+/// 数据库操作是同步IO，放到阻塞线程池中执行
+async fn run_blocking<T, F>(engine: Engine, f: F) -> Response
+where
+    F: FnOnce(&Engine) -> crate::error::Result<T> + Send + 'static,
+    T: IntoResponse + Send + 'static,
+{
+    match tokio::task::spawn_blocking(move || f(&engine)).await {
+        Ok(Ok(res)) => res.into_response(),
+        Ok(Err(e)) => error_response(e),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn get_value(State(engine): State<Engine>, Path(key): Path<String>) -> Response {
+    run_blocking(engine, move |engine| engine.get(key.into())).await
+}
+
+#[derive(Deserialize)]
+struct PutParams {
+    /// 过期时间，单位秒
+    ttl: Option<u64>,
+}
+
+async fn put_value(
+    State(engine): State<Engine>,
+    Path(key): Path<String>,
+    Query(params): Query<PutParams>,
+    value: Bytes,
+) -> Response {
+    run_blocking(engine, move |engine| {
+        match params.ttl {
+            Some(ttl) => engine.put_with_ttl(key.into(), value, Duration::from_secs(ttl))?,
+            None => engine.put(key.into(), value)?,
+        }
+        Ok(StatusCode::OK)
+    })
+    .await
+}
+
+async fn delete_value(State(engine): State<Engine>, Path(key): Path<String>) -> Response {
+    run_blocking(engine, move |engine| {
+        engine.delete(key.into())?;
+        Ok(StatusCode::OK)
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct KeysParams {
+    #[serde(default)]
+    prefix: String,
+}
+
+async fn list_keys(State(engine): State<Engine>, Query(params): Query<KeysParams>) -> Response {
+    run_blocking(engine, move |engine| {
+        let (keys, _) = engine.list_keys_with(params.prefix.as_bytes(), usize::MAX, None)?;
+        let keys = keys
+            .iter()
+            .map(|key| String::from_utf8_lossy(key).into_owned())
+            .collect::<Vec<_>>();
+        Ok(Json(keys))
+    })
+    .await
+}
+
+async fn merge(State(engine): State<Engine>) -> Response {
+    run_blocking(engine, |engine| {
+        engine.merge()?;
+        Ok(StatusCode::OK)
+    })
+    .await
+}
+
+async fn stat(State(engine): State<Engine>) -> Response {
+    run_blocking(engine, |engine| Ok(Json(engine.stat()?))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use crate::options::Options;
+
+    use super::*;
+
+    async fn request(
+        engine: &Engine,
+        method: Method,
+        uri: &str,
+        body: &str,
+    ) -> (StatusCode, Vec<u8>) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let res = router(engine.clone()).oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_http_api() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-http");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let (status, _) = request(&engine, Method::GET, "/kv/name", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = request(&engine, Method::PUT, "/kv/name", "bitcask").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = request(&engine, Method::GET, "/kv/name", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"bitcask");
+
+        let (status, _) = request(&engine, Method::PUT, "/kv/nick?ttl=100", "rs").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = request(&engine, Method::PUT, "/kv/age", "1").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = request(&engine, Method::GET, "/keys?prefix=n", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, br#"["name","nick"]"#);
+
+        let (status, _) = request(&engine, Method::DELETE, "/kv/name", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = request(&engine, Method::GET, "/kv/name", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = request(&engine, Method::POST, "/merge", "").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = request(&engine, Method::GET, "/stat", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let stat: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stat["key_num"], 2);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,426 @@
+// redb的错误类型较大，只在索引内部使用，出错时转换为crate的错误
+#![allow(clippy::result_large_err)]
+
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use log::error;
+use redb::{
+    Database, Durability, ReadTransaction, ReadableTable, ReadableTableMetadata, TableDefinition,
+    WriteTransaction,
+};
+
+use crate::data::log_record::{decode_log_record_pos, LogRecordPos};
+use crate::error::{Error, Result};
+use crate::options::IteratorOptions;
+
+use super::{seek_bound, start_bound, IndexInterator, Indexer, ITERATOR_BATCH_SIZE};
+
+/// B+树索引文件名
+pub(crate) const BPTREE_INDEX_FILE_NAME: &str = "bptree-index";
+
+/// key -> 编码后的位置信息
+const INDEX_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("index");
+/// 索引的元信息
+const META_TABLE: TableDefinition<&str, bool> = TableDefinition::new("meta");
+/// 上次是否正常关闭
+const CLEAN_SHUTDOWN_KEY: &str = "clean-shutdown";
+
+/// B+树索引，基于redb保存在数据库目录中
+///
+/// 每次修改不会立即持久化，关闭数据库时才持久化并标记为正常关闭，
+/// 没有正常关闭时启动时清空索引，从数据文件中重新加载
+pub struct BPlusTree {
+    db: Arc<Database>,
+}
+
+impl BPlusTree {
+    pub fn new(dir_path: impl AsRef<Path>) -> Result<Self> {
+        let db = match Database::create(dir_path.as_ref().join(BPTREE_INDEX_FILE_NAME)) {
+            Ok(db) => db,
+            Err(e) => {
+                error!("failed to open bptree index file: {}", e);
+                return Err(Error::FailedToOpenIndexFile);
+            }
+        };
+        let bpt = Self { db: Arc::new(db) };
+        // 创建表，之后的读事务可以直接打开
+        update(&bpt.db, Durability::Immediate, |txn| {
+            txn.open_table(INDEX_TABLE)?;
+            txn.open_table(META_TABLE)?;
+            Ok(())
+        })?;
+        Ok(bpt)
+    }
+
+    /// 修改单个key的位置信息，索引的修改不会失败，出错时说明索引文件已损坏
+    fn update_pos<T>(
+        &self,
+        key: &[u8],
+        f: impl FnOnce(&mut redb::Table<&[u8], &[u8]>, Option<LogRecordPos>) -> redb::Result<T>,
+    ) -> T {
+        let res = update(&self.db, Durability::Eventual, |txn| {
+            let mut table = txn.open_table(INDEX_TABLE)?;
+            let pos = table
+                .get(key)?
+                .map(|value| decode_pos(value.value()))
+                .transpose()?;
+            Ok(f(&mut table, pos)?)
+        });
+        res.unwrap_or_else(|e| panic!("failed to update bptree index: {}", e))
+    }
+}
+
+impl Indexer for BPlusTree {
+    fn put(&self, key: Vec<u8>, pos: LogRecordPos) -> Option<LogRecordPos> {
+        self.update_pos(&key, |table, old_pos| {
+            table.insert(key.as_slice(), pos.encode().as_slice())?;
+            Ok(old_pos)
+        })
+    }
+
+    fn get(&self, key: Vec<u8>) -> Option<LogRecordPos> {
+        let res = view(&self.db, |txn| {
+            let table = txn.open_table(INDEX_TABLE)?;
+            let value = table.get(key.as_slice())?;
+            value.map(|value| decode_pos(value.value())).transpose()
+        });
+        res.unwrap_or_else(|e| panic!("failed to read bptree index: {}", e))
+    }
+
+    fn delete(&self, key: Vec<u8>) -> Option<LogRecordPos> {
+        self.update_pos(&key, |table, old_pos| {
+            table.remove(key.as_slice())?;
+            Ok(old_pos)
+        })
+    }
+
+    fn relocate(&self, key: Vec<u8>, old_pos: LogRecordPos, new_pos: LogRecordPos) -> bool {
+        self.update_pos(&key, |table, pos| {
+            if pos != Some(old_pos) {
+                return Ok(false);
+            }
+            table.insert(key.as_slice(), new_pos.encode().as_slice())?;
+            Ok(true)
+        })
+    }
+
+    fn delete_if(&self, key: Vec<u8>, pos: LogRecordPos) -> bool {
+        self.update_pos(&key, |table, curr_pos| {
+            if curr_pos != Some(pos) {
+                return Ok(false);
+            }
+            table.remove(key.as_slice())?;
+            Ok(true)
+        })
+    }
+
+    fn clear(&self) -> Result<()> {
+        update(&self.db, Durability::Immediate, |txn| {
+            txn.delete_table(INDEX_TABLE)?;
+            txn.open_table(INDEX_TABLE)?;
+            Ok(())
+        })
+    }
+
+    fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexInterator> {
+        let mut iter = BPlusTreeIterator {
+            db: self.db.clone(),
+            items: Vec::new(),
+            curr_idx: 0,
+            cursor: Bound::Unbounded,
+            exhausted: false,
+            options,
+        };
+        iter.rewind();
+        Box::new(iter)
+    }
+
+    fn list_keys(&self) -> Result<Vec<Bytes>> {
+        view(&self.db, |txn| {
+            let table = txn.open_table(INDEX_TABLE)?;
+            let mut keys = Vec::new();
+            for entry in table.iter()? {
+                let (key, _) = entry?;
+                keys.push(Bytes::copy_from_slice(key.value()));
+            }
+            Ok(keys)
+        })
+    }
+
+    fn len(&self) -> usize {
+        let res = view(&self.db, |txn| Ok(txn.open_table(INDEX_TABLE)?.len()?));
+        res.unwrap_or_else(|e| panic!("failed to read bptree index: {}", e)) as usize
+    }
+
+    fn memory_usage(&self) -> usize {
+        0
+    }
+
+    fn try_reuse(&self) -> Result<bool> {
+        let clean_shutdown = view(&self.db, |txn| {
+            let table = txn.open_table(META_TABLE)?;
+            let value = table.get(CLEAN_SHUTDOWN_KEY)?;
+            Ok(value.is_some_and(|value| value.value()))
+        })?;
+        // 运行期间标记为没有正常关闭，崩溃后重新加载
+        update(&self.db, Durability::Immediate, |txn| {
+            if !clean_shutdown {
+                txn.delete_table(INDEX_TABLE)?;
+                txn.open_table(INDEX_TABLE)?;
+            }
+            txn.open_table(META_TABLE)?
+                .insert(CLEAN_SHUTDOWN_KEY, false)?;
+            Ok(())
+        })?;
+        Ok(clean_shutdown)
+    }
+
+    fn persist(&self) -> Result<()> {
+        // 持久化之前所有的修改
+        update(&self.db, Durability::Immediate, |txn| {
+            txn.open_table(META_TABLE)?
+                .insert(CLEAN_SHUTDOWN_KEY, true)?;
+            Ok(())
+        })
+    }
+}
+
+/// B+树索引的迭代器，与BTree索引的迭代器一样每次只读取一批数据
+pub struct BPlusTreeIterator {
+    db: Arc<Database>,
+
+    /// 当前批次的key + pos
+    items: Vec<(Vec<u8>, LogRecordPos)>,
+
+    /// 当前批次中的索引
+    curr_idx: usize,
+
+    /// 下一批次的起始位置，逆序迭代时为上界
+    cursor: Bound<Vec<u8>>,
+
+    /// 是否已经读取到了最后一批
+    exhausted: bool,
+
+    /// 迭代器选项
+    options: IteratorOptions,
+}
+
+impl BPlusTreeIterator {
+    /// 从cursor开始读取下一批数据，没有更多数据时返回false
+    fn load_batch(&mut self) -> bool {
+        self.items.clear();
+        self.curr_idx = 0;
+        if self.exhausted {
+            return false;
+        }
+        let cursor = self.cursor.as_ref().map(|key| key.as_slice());
+        let (range, reverse) = match self.options.reverse {
+            true => ((Bound::Unbounded, cursor), true),
+            false => ((cursor, Bound::Unbounded), false),
+        };
+        let res = view(&self.db, |txn| {
+            let table = txn.open_table(INDEX_TABLE)?;
+            let entries = table.range::<&[u8]>(range)?;
+            let entries: Box<dyn std::iter::Iterator<Item = _>> = match reverse {
+                true => Box::new(entries.rev()),
+                false => Box::new(entries),
+            };
+            let mut items = Vec::with_capacity(ITERATOR_BATCH_SIZE);
+            for entry in entries.take(ITERATOR_BATCH_SIZE) {
+                let (key, value) = entry?;
+                items.push((key.value().to_vec(), decode_pos(value.value())?));
+            }
+            Ok(items)
+        });
+        self.items = res.unwrap_or_else(|e| panic!("failed to read bptree index: {}", e));
+        self.exhausted = self.items.len() < ITERATOR_BATCH_SIZE;
+        if let Some((key, _)) = self.items.last() {
+            self.cursor = Bound::Excluded(key.clone());
+        }
+        !self.items.is_empty()
+    }
+}
+
+impl IndexInterator for BPlusTreeIterator {
+    fn rewind(&mut self) {
+        self.items.clear();
+        self.curr_idx = 0;
+        self.cursor = start_bound(&self.options);
+        self.exhausted = false;
+    }
+
+    fn seek(&mut self, key: Vec<u8>) {
+        self.items.clear();
+        self.curr_idx = 0;
+        self.cursor = seek_bound(&self.options, key);
+        self.exhausted = false;
+    }
+
+    fn next(&mut self) -> Option<(&[u8], &LogRecordPos)> {
+        let idx = loop {
+            if self.curr_idx >= self.items.len() && !self.load_batch() {
+                return None;
+            }
+            self.curr_idx += 1;
+            let (key, _) = &self.items[self.curr_idx - 1];
+            if self.options.contains(key) {
+                break self.curr_idx - 1;
+            }
+            // 从起始边界开始迭代，遇到不匹配的key说明已经超出范围
+            self.items.clear();
+            self.exhausted = true;
+        };
+        let (key, pos) = &self.items[idx];
+        Some((key, pos))
+    }
+}
+
+/// 在写事务中修改索引，出错时回滚
+fn update<T>(
+    db: &Database,
+    durability: Durability,
+    f: impl FnOnce(&WriteTransaction) -> std::result::Result<T, redb::Error>,
+) -> Result<T> {
+    let res = db
+        .begin_write()
+        .map_err(redb::Error::from)
+        .and_then(|mut txn| {
+            txn.set_durability(durability);
+            let value = f(&txn)?;
+            txn.commit()?;
+            Ok(value)
+        });
+    res.map_err(|e| {
+        error!("failed to update bptree index: {}", e);
+        Error::FailedToUpdateIndex
+    })
+}
+
+/// 在读事务中读取索引
+fn view<T>(
+    db: &Database,
+    f: impl FnOnce(&ReadTransaction) -> std::result::Result<T, redb::Error>,
+) -> Result<T> {
+    let res = db
+        .begin_read()
+        .map_err(redb::Error::from)
+        .and_then(|txn| f(&txn));
+    res.map_err(|e| {
+        error!("failed to read bptree index: {}", e);
+        Error::FailedToReadIndex
+    })
+}
+
+/// 解码索引中保存的位置信息，无法解码时说明索引文件已损坏
+fn decode_pos(value: &[u8]) -> std::result::Result<LogRecordPos, redb::Error> {
+    decode_log_record_pos(value.to_vec())
+        .map_err(|_| redb::Error::Corrupted("invalid log record pos".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn pos(file_id: u32, offset: u64) -> LogRecordPos {
+        LogRecordPos {
+            file_id,
+            offset,
+            size: 10,
+        }
+    }
+
+    #[test]
+    fn test_bptree_put_get_delete() {
+        let dir_path = PathBuf::from("/tmp/bitcask-rs-bptree-put");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let bpt = BPlusTree::new(&dir_path).unwrap();
+
+        assert!(bpt.put(b"".to_vec(), pos(1, 10)).is_none());
+        assert!(bpt.put(b"aa".to_vec(), pos(11, 22)).is_none());
+        // 覆盖写返回旧的位置信息
+        assert_eq!(bpt.put(b"aa".to_vec(), pos(12, 33)), Some(pos(11, 22)));
+        assert_eq!(bpt.get(b"".to_vec()), Some(pos(1, 10)));
+        assert_eq!(bpt.get(b"aa".to_vec()), Some(pos(12, 33)));
+        assert!(bpt.get(b"not exist".to_vec()).is_none());
+        assert_eq!(bpt.len(), 2);
+
+        assert!(!bpt.relocate(b"aa".to_vec(), pos(11, 22), pos(13, 44)));
+        assert!(bpt.relocate(b"aa".to_vec(), pos(12, 33), pos(13, 44)));
+        assert!(!bpt.delete_if(b"aa".to_vec(), pos(12, 33)));
+        assert!(bpt.delete_if(b"aa".to_vec(), pos(13, 44)));
+
+        assert_eq!(bpt.delete(b"".to_vec()), Some(pos(1, 10)));
+        assert!(bpt.delete(b"not exist".to_vec()).is_none());
+        assert_eq!(bpt.len(), 0);
+
+        std::mem::drop(bpt);
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_bptree_iterator() {
+        let dir_path = PathBuf::from("/tmp/bitcask-rs-bptree-iterator");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let bpt = BPlusTree::new(&dir_path).unwrap();
+        for i in 0..1000 {
+            bpt.put(format!("key-{:04}", i).into_bytes(), pos(1, i));
+        }
+
+        let mut iter = bpt.iterator(IteratorOptions::default());
+        for i in 0..1000 {
+            let (key, pos) = iter.next().unwrap();
+            assert_eq!(key, format!("key-{:04}", i).as_bytes());
+            assert_eq!(pos.offset, i);
+        }
+        assert!(iter.next().is_none());
+
+        let mut iter = bpt.iterator(IteratorOptions {
+            prefix: b"key-01".to_vec(),
+            reverse: true,
+            ..Default::default()
+        });
+        iter.seek(b"key-0150".to_vec());
+        for i in (100..=150).rev() {
+            assert_eq!(iter.next().unwrap().1.offset, i);
+        }
+        assert!(iter.next().is_none());
+        iter.rewind();
+        assert_eq!(iter.next().unwrap().1.offset, 199);
+
+        assert_eq!(bpt.list_keys().unwrap().len(), 1000);
+
+        std::mem::drop(iter);
+        std::mem::drop(bpt);
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_bptree_try_reuse() {
+        let dir_path = PathBuf::from("/tmp/bitcask-rs-bptree-reuse");
+        std::fs::create_dir_all(&dir_path).unwrap();
+        let bpt = BPlusTree::new(&dir_path).unwrap();
+        assert!(!bpt.try_reuse().unwrap());
+        bpt.put(b"aa".to_vec(), pos(1, 10));
+        bpt.persist().unwrap();
+        std::mem::drop(bpt);
+
+        // 正常关闭后可以直接使用
+        let bpt = BPlusTree::new(&dir_path).unwrap();
+        assert!(bpt.try_reuse().unwrap());
+        assert_eq!(bpt.get(b"aa".to_vec()), Some(pos(1, 10)));
+        std::mem::drop(bpt);
+
+        // 没有正常关闭时清空索引
+        let bpt = BPlusTree::new(&dir_path).unwrap();
+        assert!(!bpt.try_reuse().unwrap());
+        assert!(bpt.get(b"aa".to_vec()).is_none());
+
+        std::mem::drop(bpt);
+        std::fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -1,32 +1,43 @@
 use bytes::Bytes;
 use parking_lot::RwLock;
 use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use crate::data::log_record::LogRecordPos;
 use crate::error::Result;
 use crate::options::IteratorOptions;
 
-use super::{IndexInterator, Indexer};
+use super::{
+    entry_memory_usage, seek_bound, start_bound, IndexInterator, Indexer, ITERATOR_BATCH_SIZE,
+};
 
 /// BTree索引，封装了标准库的BTreeMap
 pub struct BTree {
     tree: Arc<RwLock<BTreeMap<Vec<u8>, LogRecordPos>>>,
+    /// 索引占用内存的估计值
+    memory_usage: AtomicUsize,
 }
 
 impl BTree {
     pub fn new() -> Self {
         Self {
             tree: Arc::new(RwLock::new(BTreeMap::new())),
+            memory_usage: AtomicUsize::new(0),
         }
     }
 }
 
 impl Indexer for BTree {
-    fn put(&self, key: Vec<u8>, pos: LogRecordPos) -> bool {
+    fn put(&self, key: Vec<u8>, pos: LogRecordPos) -> Option<LogRecordPos> {
         let mut write_guard = self.tree.write();
-        write_guard.insert(key, pos);
-        true
+        let usage = entry_memory_usage(&key);
+        let old_pos = write_guard.insert(key, pos);
+        if old_pos.is_none() {
+            self.memory_usage.fetch_add(usage, Ordering::Relaxed);
+        }
+        old_pos
     }
 
     fn get(&self, key: Vec<u8>) -> Option<LogRecordPos> {
@@ -34,76 +45,158 @@ impl Indexer for BTree {
         read_guard.get(&key).copied()
     }
 
-    fn delete(&self, key: Vec<u8>) -> bool {
+    fn delete(&self, key: Vec<u8>) -> Option<LogRecordPos> {
         let mut write_guard = self.tree.write();
-        let remove_res = write_guard.remove(&key);
-        remove_res.is_some()
+        let old_pos = write_guard.remove(&key);
+        if old_pos.is_some() {
+            self.memory_usage
+                .fetch_sub(entry_memory_usage(&key), Ordering::Relaxed);
+        }
+        old_pos
     }
 
-    fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexInterator> {
-        let mut items = self
-            .tree
-            .read()
-            .iter()
-            .map(|(key, pos)| (key.clone(), pos.clone()))
-            .collect::<Vec<_>>();
-        if options.reverse {
-            items.reverse()
+    fn relocate(&self, key: Vec<u8>, old_pos: LogRecordPos, new_pos: LogRecordPos) -> bool {
+        let mut write_guard = self.tree.write();
+        match write_guard.get_mut(&key) {
+            Some(pos) if *pos == old_pos => {
+                *pos = new_pos;
+                true
+            }
+            _ => false,
         }
-        Box::new(BTreeIterator {
-            items,
+    }
+
+    fn delete_if(&self, key: Vec<u8>, pos: LogRecordPos) -> bool {
+        let mut write_guard = self.tree.write();
+        if write_guard.get(&key) != Some(&pos) {
+            return false;
+        }
+        write_guard.remove(&key);
+        self.memory_usage
+            .fetch_sub(entry_memory_usage(&key), Ordering::Relaxed);
+        true
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut write_guard = self.tree.write();
+        write_guard.clear();
+        self.memory_usage.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexInterator> {
+        let mut iter = BTreeIterator {
+            tree: self.tree.clone(),
+            items: Vec::new(),
             curr_idx: 0,
+            cursor: Bound::Unbounded,
+            exhausted: false,
             options,
-        })
+        };
+        iter.rewind();
+        Box::new(iter)
     }
 
     fn list_keys(&self) -> Result<Vec<Bytes>> {
         let read_guard = self.tree.read();
         Ok(read_guard.keys().cloned().map(Bytes::from).collect())
     }
+
+    fn len(&self) -> usize {
+        self.tree.read().len()
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.memory_usage.load(Ordering::Relaxed)
+    }
 }
 
 /// BTree索引的迭代器
+///
+/// 不对整个索引做快照，每次只读取一批数据，读完后从上一批的最后一个key继续读取，
+/// 因此可以看到创建迭代器之后写入的数据。设置了前缀时只读取前缀范围内的数据
 pub struct BTreeIterator {
-    /// key + pos
+    tree: Arc<RwLock<BTreeMap<Vec<u8>, LogRecordPos>>>,
+
+    /// 当前批次的key + pos
     items: Vec<(Vec<u8>, LogRecordPos)>,
 
-    /// 当前索引
+    /// 当前批次中的索引
     curr_idx: usize,
 
+    /// 下一批次的起始位置，逆序迭代时为上界
+    cursor: Bound<Vec<u8>>,
+
+    /// 是否已经读取到了最后一批
+    exhausted: bool,
+
     /// 迭代器选项
     options: IteratorOptions,
 }
 
+impl BTreeIterator {
+    /// 从cursor开始读取下一批数据，没有更多数据时返回false
+    fn load_batch(&mut self) -> bool {
+        self.items.clear();
+        self.curr_idx = 0;
+        if self.exhausted {
+            return false;
+        }
+        let tree = self.tree.read();
+        let entries = |(key, pos): (&Vec<u8>, &LogRecordPos)| (key.clone(), *pos);
+        if self.options.reverse {
+            self.items.extend(
+                tree.range((Bound::Unbounded, self.cursor.clone()))
+                    .rev()
+                    .take(ITERATOR_BATCH_SIZE)
+                    .map(entries),
+            );
+        } else {
+            self.items.extend(
+                tree.range((self.cursor.clone(), Bound::Unbounded))
+                    .take(ITERATOR_BATCH_SIZE)
+                    .map(entries),
+            );
+        }
+        self.exhausted = self.items.len() < ITERATOR_BATCH_SIZE;
+        if let Some((key, _)) = self.items.last() {
+            self.cursor = Bound::Excluded(key.clone());
+        }
+        !self.items.is_empty()
+    }
+}
+
 impl IndexInterator for BTreeIterator {
     fn rewind(&mut self) {
+        self.items.clear();
         self.curr_idx = 0;
+        self.cursor = start_bound(&self.options);
+        self.exhausted = false;
     }
 
     fn seek(&mut self, key: Vec<u8>) {
-        self.curr_idx = match self.items.binary_search_by(|(k, _)| {
-            if self.options.reverse {
-                k.cmp(&key).reverse()
-            } else {
-                k.cmp(&key)
-            }
-        }) {
-            Ok(position) => position,
-            Err(insert_position) => insert_position,
-        };
+        self.items.clear();
+        self.curr_idx = 0;
+        self.cursor = seek_bound(&self.options, key);
+        self.exhausted = false;
     }
 
     fn next(&mut self) -> Option<(&[u8], &LogRecordPos)> {
-        if self.curr_idx >= self.items.len() {
-            return None;
-        }
-        while let Some((key, pos)) = self.items.get(self.curr_idx) {
+        let idx = loop {
+            if self.curr_idx >= self.items.len() && !self.load_batch() {
+                return None;
+            }
             self.curr_idx += 1;
-            if self.options.prefix.is_empty() || key.starts_with(&self.options.prefix) {
-                return Some((key, pos));
+            let (key, _) = &self.items[self.curr_idx - 1];
+            if self.options.contains(key) {
+                break self.curr_idx - 1;
             }
-        }
-        None
+            // 从起始边界开始迭代，遇到不匹配的key说明已经超出范围
+            self.items.clear();
+            self.exhausted = true;
+        };
+        let (key, pos) = &self.items[idx];
+        Some((key, pos))
     }
 }
 
@@ -119,18 +212,31 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 10,
+                size: 10,
             },
         );
-        assert_eq!(res1, true);
+        assert!(res1.is_none());
 
         let res2 = bt.put(
             "aa".as_bytes().to_vec(),
             LogRecordPos {
                 file_id: 11,
                 offset: 22,
+                size: 10,
+            },
+        );
+        assert!(res2.is_none());
+
+        // 覆盖写返回旧的位置信息
+        let res3 = bt.put(
+            "aa".as_bytes().to_vec(),
+            LogRecordPos {
+                file_id: 12,
+                offset: 33,
+                size: 10,
             },
         );
-        assert_eq!(res2, true);
+        assert_eq!(res3.unwrap().file_id, 11);
     }
 
     #[test]
@@ -141,17 +247,19 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 10,
+                size: 10,
             },
         );
-        assert_eq!(res1, true);
+        assert!(res1.is_none());
         let res2 = bt.put(
             "aa".as_bytes().to_vec(),
             LogRecordPos {
                 file_id: 11,
                 offset: 22,
+                size: 10,
             },
         );
-        assert_eq!(res2, true);
+        assert!(res2.is_none());
 
         let pos1 = bt.get("".as_bytes().to_vec());
         assert!(pos1.is_some());
@@ -170,26 +278,107 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 10,
+                size: 10,
             },
         );
-        assert_eq!(res1, true);
+        assert!(res1.is_none());
         let res2 = bt.put(
             "aa".as_bytes().to_vec(),
             LogRecordPos {
                 file_id: 11,
                 offset: 22,
+                size: 10,
             },
         );
-        assert_eq!(res2, true);
+        assert!(res2.is_none());
+        assert_eq!(bt.len(), 2);
 
         let del1 = bt.delete("".as_bytes().to_vec());
-        assert!(del1);
+        assert_eq!(del1.unwrap().file_id, 1);
 
         let del2 = bt.delete("aa".as_bytes().to_vec());
-        assert!(del2);
+        assert_eq!(del2.unwrap().file_id, 11);
 
         let del3 = bt.delete("not exist".as_bytes().to_vec());
-        assert!(!del3);
+        assert!(del3.is_none());
+        assert_eq!(bt.len(), 0);
+    }
+
+    #[test]
+    fn test_btree_relocate() {
+        let bt = BTree::new();
+        let old_pos = LogRecordPos {
+            file_id: 1,
+            offset: 10,
+            size: 10,
+        };
+        let new_pos = LogRecordPos {
+            file_id: 5,
+            offset: 0,
+            size: 10,
+        };
+        bt.put(b"aa".to_vec(), old_pos);
+
+        // 位置信息不匹配
+        assert!(!bt.relocate(b"aa".to_vec(), new_pos, new_pos));
+        assert_eq!(bt.get(b"aa".to_vec()), Some(old_pos));
+
+        assert!(bt.relocate(b"aa".to_vec(), old_pos, new_pos));
+        assert_eq!(bt.get(b"aa".to_vec()), Some(new_pos));
+
+        // key不存在
+        assert!(!bt.relocate(b"bb".to_vec(), old_pos, new_pos));
+        assert!(bt.get(b"bb".to_vec()).is_none());
+    }
+
+    #[test]
+    fn test_btree_delete_if() {
+        let bt = BTree::new();
+        let pos = LogRecordPos {
+            file_id: 1,
+            offset: 10,
+            size: 10,
+        };
+        let other_pos = LogRecordPos {
+            file_id: 2,
+            offset: 10,
+            size: 10,
+        };
+        bt.put(b"aa".to_vec(), pos);
+
+        assert!(!bt.delete_if(b"aa".to_vec(), other_pos));
+        assert!(bt.get(b"aa".to_vec()).is_some());
+        assert!(bt.delete_if(b"aa".to_vec(), pos));
+        assert!(bt.get(b"aa".to_vec()).is_none());
+        assert!(!bt.delete_if(b"aa".to_vec(), pos));
+    }
+
+    #[test]
+    fn test_btree_memory_usage() {
+        let bt = BTree::new();
+        let pos = LogRecordPos {
+            file_id: 1,
+            offset: 10,
+            size: 10,
+        };
+        assert_eq!(bt.memory_usage(), 0);
+        bt.put(b"aa".to_vec(), pos);
+        bt.put(b"bbb".to_vec(), pos);
+        let usage = bt.memory_usage();
+        assert_eq!(
+            usage,
+            entry_memory_usage(b"aa") + entry_memory_usage(b"bbb")
+        );
+
+        // 覆盖写不增加内存
+        bt.put(b"aa".to_vec(), pos);
+        assert_eq!(bt.memory_usage(), usage);
+
+        bt.delete(b"aa".to_vec());
+        assert!(!bt.delete_if(b"bbb".to_vec(), LogRecordPos { offset: 0, ..pos }));
+        assert_eq!(bt.memory_usage(), entry_memory_usage(b"bbb"));
+        assert!(bt.delete_if(b"bbb".to_vec(), pos));
+        assert_eq!(bt.memory_usage(), 0);
     }
 
     #[test]
@@ -208,6 +397,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 10,
+                size: 10,
             },
         );
         let mut iter = bt.iterator(IteratorOptions::default());
@@ -225,6 +415,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 10,
+                size: 10,
             },
         );
         bt.put(
@@ -232,6 +423,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 30,
+                size: 10,
             },
         );
         bt.put(
@@ -239,6 +431,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 40,
+                size: 10,
             },
         );
         let mut iter = bt.iterator(IteratorOptions::default());
@@ -265,6 +458,175 @@ mod tests {
         // }
     }
 
+    #[test]
+    fn test_btree_iterator_batch() {
+        let bt = BTree::new();
+        let pos = LogRecordPos {
+            file_id: 1,
+            offset: 10,
+            size: 10,
+        };
+        for i in 0..1000 {
+            bt.put(format!("key-{:04}", i).into_bytes(), pos);
+        }
+
+        // 跨越多个批次按顺序迭代
+        let mut iter = bt.iterator(IteratorOptions::default());
+        for i in 0..1000 {
+            assert_eq!(iter.next().unwrap().0, format!("key-{:04}", i).as_bytes());
+        }
+        assert!(iter.next().is_none());
+
+        let mut options = IteratorOptions::default();
+        options.reverse = true;
+        let mut iter = bt.iterator(options);
+        iter.seek(b"key-0500".to_vec());
+        for i in (0..=500).rev() {
+            assert_eq!(iter.next().unwrap().0, format!("key-{:04}", i).as_bytes());
+        }
+        assert!(iter.next().is_none());
+
+        // 可以看到创建迭代器之后，还没有读取到的数据的修改
+        let mut iter = bt.iterator(IteratorOptions::default());
+        iter.seek(b"key-0300".to_vec());
+        assert_eq!(iter.next().unwrap().0, b"key-0300");
+        bt.delete(b"key-0999".to_vec());
+        bt.put(b"key-1000".to_vec(), pos);
+        let mut last = vec![];
+        while let Some((key, _)) = iter.next() {
+            last = key.to_vec();
+        }
+        assert_eq!(last, b"key-1000");
+
+        iter.rewind();
+        assert_eq!(iter.next().unwrap().0, b"key-0000");
+    }
+
+    #[test]
+    fn test_btree_iterator_prefix() {
+        let bt = BTree::new();
+        let pos = LogRecordPos {
+            file_id: 1,
+            offset: 10,
+            size: 10,
+        };
+        for key in [
+            &b"a"[..],
+            b"ab",
+            b"abc",
+            b"abd",
+            b"ac",
+            b"b",
+            b"\xff",
+            b"\xff\xff\x01",
+        ] {
+            bt.put(key.to_vec(), pos);
+        }
+        let collect = |prefix: &[u8], reverse: bool, seek: Option<&[u8]>| {
+            let mut iter = bt.iterator(IteratorOptions {
+                prefix: prefix.to_vec(),
+                reverse,
+                ..Default::default()
+            });
+            if let Some(key) = seek {
+                iter.seek(key.to_vec());
+            }
+            let mut keys = vec![];
+            while let Some((key, _)) = iter.next() {
+                keys.push(key.to_vec());
+            }
+            keys
+        };
+
+        assert_eq!(collect(b"ab", false, None), [&b"ab"[..], b"abc", b"abd"]);
+        assert_eq!(collect(b"ab", true, None), [&b"abd"[..], b"abc", b"ab"]);
+        assert!(collect(b"x", false, None).is_empty());
+        assert_eq!(
+            collect(b"\xff", false, None),
+            [&b"\xff"[..], b"\xff\xff\x01"]
+        );
+        assert_eq!(
+            collect(b"\xff", true, None),
+            [&b"\xff\xff\x01"[..], b"\xff"]
+        );
+
+        // seek的位置不会越过前缀范围
+        assert_eq!(
+            collect(b"ab", false, Some(b"a")),
+            [&b"ab"[..], b"abc", b"abd"]
+        );
+        assert_eq!(collect(b"ab", false, Some(b"abc")), [&b"abc"[..], b"abd"]);
+        assert!(collect(b"ab", false, Some(b"b")).is_empty());
+        assert_eq!(
+            collect(b"ab", true, Some(b"z")),
+            [&b"abd"[..], b"abc", b"ab"]
+        );
+        assert_eq!(collect(b"ab", true, Some(b"abc")), [&b"abc"[..], b"ab"]);
+        assert!(collect(b"ab", true, Some(b"aa")).is_empty());
+
+        use crate::index::prefix_successor;
+        assert_eq!(prefix_successor(b"ab"), Some(b"ac".to_vec()));
+        assert_eq!(prefix_successor(b"a\xff"), Some(b"b".to_vec()));
+        assert_eq!(prefix_successor(b"\xff\xff"), None);
+    }
+
+    #[test]
+    fn test_btree_iterator_bounds() {
+        let bt = BTree::new();
+        let pos = LogRecordPos {
+            file_id: 1,
+            offset: 10,
+            size: 10,
+        };
+        for i in 0..1000 {
+            bt.put(format!("key-{:04}", i).into_bytes(), pos);
+        }
+        let collect = |options: IteratorOptions, seek: Option<&[u8]>| {
+            let mut iter = bt.iterator(options);
+            if let Some(key) = seek {
+                iter.seek(key.to_vec());
+            }
+            let mut keys = vec![];
+            while let Some((key, _)) = iter.next() {
+                keys.push(String::from_utf8(key.to_vec()).unwrap());
+            }
+            keys
+        };
+        let keys = |range: std::ops::Range<usize>| {
+            range.map(|i| format!("key-{:04}", i)).collect::<Vec<_>>()
+        };
+
+        let options = || IteratorOptions {
+            lower_bound: Bound::Included(b"key-0100".to_vec()),
+            upper_bound: Bound::Excluded(b"key-0500".to_vec()),
+            ..Default::default()
+        };
+        assert_eq!(collect(options(), None), keys(100..500));
+        assert_eq!(collect(options(), Some(b"key-0300")), keys(300..500));
+        assert_eq!(collect(options(), Some(b"a")), keys(100..500));
+        assert!(collect(options(), Some(b"key-0500")).is_empty());
+
+        let reverse = IteratorOptions {
+            reverse: true,
+            ..options()
+        };
+        let mut expected = keys(100..500);
+        expected.reverse();
+        assert_eq!(collect(reverse, None), expected);
+
+        // 与前缀同时使用
+        let options = IteratorOptions {
+            prefix: b"key-01".to_vec(),
+            lower_bound: Bound::Excluded(b"key-0150".to_vec()),
+            upper_bound: Bound::Included(b"key-0300".to_vec()),
+            reverse: true,
+            ..Default::default()
+        };
+        let mut expected = keys(151..200);
+        expected.reverse();
+        assert_eq!(collect(options, None), expected);
+    }
+
     #[test]
     fn test_btree_iterator_next() {
         let bt = BTree::new();
@@ -278,6 +640,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 10,
+                size: 10,
             },
         );
         let mut iter = bt.iterator(options);
@@ -290,6 +653,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 20,
+                size: 10,
             },
         );
         bt.put(
@@ -297,6 +661,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 20,
+                size: 10,
             },
         );
         let mut iter = bt.iterator(options);
@@ -0,0 +1,219 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use dashmap::DashMap;
+
+use crate::data::log_record::LogRecordPos;
+use crate::error::Result;
+use crate::options::IteratorOptions;
+
+use super::{entry_memory_usage, IndexInterator, Indexer};
+
+/// 哈希索引，封装了DashMap，读写都是O(1)，适合只有点查的场景
+///
+/// key是无序的，迭代时需要复制所有key并排序
+pub struct HashIndex {
+    map: Arc<DashMap<Vec<u8>, LogRecordPos>>,
+    /// 索引占用内存的估计值
+    memory_usage: AtomicUsize,
+}
+
+impl HashIndex {
+    pub fn new() -> Self {
+        Self {
+            map: Arc::new(DashMap::new()),
+            memory_usage: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Indexer for HashIndex {
+    fn put(&self, key: Vec<u8>, pos: LogRecordPos) -> Option<LogRecordPos> {
+        let usage = entry_memory_usage(&key);
+        let old_pos = self.map.insert(key, pos);
+        if old_pos.is_none() {
+            self.memory_usage.fetch_add(usage, Ordering::Relaxed);
+        }
+        old_pos
+    }
+
+    fn get(&self, key: Vec<u8>) -> Option<LogRecordPos> {
+        self.map.get(&key).map(|entry| *entry.value())
+    }
+
+    fn delete(&self, key: Vec<u8>) -> Option<LogRecordPos> {
+        let old_pos = self.map.remove(&key).map(|(_, pos)| pos);
+        if old_pos.is_some() {
+            self.memory_usage
+                .fetch_sub(entry_memory_usage(&key), Ordering::Relaxed);
+        }
+        old_pos
+    }
+
+    fn relocate(&self, key: Vec<u8>, old_pos: LogRecordPos, new_pos: LogRecordPos) -> bool {
+        match self.map.get_mut(&key) {
+            Some(mut entry) if *entry.value() == old_pos => {
+                *entry.value_mut() = new_pos;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn delete_if(&self, key: Vec<u8>, pos: LogRecordPos) -> bool {
+        if self
+            .map
+            .remove_if(&key, |_, curr_pos| *curr_pos == pos)
+            .is_none()
+        {
+            return false;
+        }
+        self.memory_usage
+            .fetch_sub(entry_memory_usage(&key), Ordering::Relaxed);
+        true
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.map.clear();
+        self.memory_usage.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexInterator> {
+        let mut items = self
+            .map
+            .iter()
+            .filter(|entry| options.contains(entry.key()))
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect::<Vec<_>>();
+        items.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        if options.reverse {
+            items.reverse();
+        }
+        Box::new(HashIndexIterator {
+            items,
+            curr_idx: 0,
+            reverse: options.reverse,
+        })
+    }
+
+    fn list_keys(&self) -> Result<Vec<Bytes>> {
+        let mut keys = self
+            .map
+            .iter()
+            .map(|entry| Bytes::copy_from_slice(entry.key()))
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        Ok(keys)
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.memory_usage.load(Ordering::Relaxed)
+    }
+}
+
+/// 哈希索引的迭代器，创建时对匹配的key排序
+pub struct HashIndexIterator {
+    /// 已排序的key + pos
+    items: Vec<(Vec<u8>, LogRecordPos)>,
+
+    /// 当前索引
+    curr_idx: usize,
+
+    /// 是否逆序
+    reverse: bool,
+}
+
+impl IndexInterator for HashIndexIterator {
+    fn rewind(&mut self) {
+        self.curr_idx = 0;
+    }
+
+    fn seek(&mut self, key: Vec<u8>) {
+        self.curr_idx = match self.items.binary_search_by(|(k, _)| {
+            if self.reverse {
+                k.cmp(&key).reverse()
+            } else {
+                k.cmp(&key)
+            }
+        }) {
+            Ok(position) => position,
+            Err(insert_position) => insert_position,
+        };
+    }
+
+    fn next(&mut self) -> Option<(&[u8], &LogRecordPos)> {
+        let (key, pos) = self.items.get(self.curr_idx)?;
+        self.curr_idx += 1;
+        Some((key, pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(offset: u64) -> LogRecordPos {
+        LogRecordPos {
+            file_id: 1,
+            offset,
+            size: 10,
+        }
+    }
+
+    #[test]
+    fn test_hash_index_put_get_delete() {
+        let index = HashIndex::new();
+        assert!(index.put(b"aa".to_vec(), pos(1)).is_none());
+        assert_eq!(index.put(b"aa".to_vec(), pos(2)), Some(pos(1)));
+        assert_eq!(index.get(b"aa".to_vec()), Some(pos(2)));
+        assert!(index.get(b"bb".to_vec()).is_none());
+
+        assert!(!index.relocate(b"aa".to_vec(), pos(1), pos(3)));
+        assert!(index.relocate(b"aa".to_vec(), pos(2), pos(3)));
+        assert!(!index.delete_if(b"aa".to_vec(), pos(2)));
+        assert!(index.delete_if(b"aa".to_vec(), pos(3)));
+        assert_eq!(index.len(), 0);
+        assert_eq!(index.memory_usage(), 0);
+
+        index.put(b"bb".to_vec(), pos(4));
+        assert_eq!(index.memory_usage(), entry_memory_usage(b"bb"));
+        assert_eq!(index.delete(b"bb".to_vec()), Some(pos(4)));
+        assert!(index.delete(b"bb".to_vec()).is_none());
+    }
+
+    #[test]
+    fn test_hash_index_iterator() {
+        let index = HashIndex::new();
+        for i in 0..100 {
+            index.put(format!("key-{:03}", i).into_bytes(), pos(i));
+        }
+        let keys = index.list_keys().unwrap();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+
+        // 迭代时按key排序
+        let mut iter = index.iterator(IteratorOptions::default());
+        iter.seek(b"key-050".to_vec());
+        for i in 50..100 {
+            assert_eq!(iter.next().unwrap().1.offset, i);
+        }
+        assert!(iter.next().is_none());
+
+        let mut iter = index.iterator(IteratorOptions {
+            prefix: b"key-01".to_vec(),
+            reverse: true,
+            ..Default::default()
+        });
+        for i in (10..20).rev() {
+            assert_eq!(iter.next().unwrap().1.offset, i);
+        }
+        assert!(iter.next().is_none());
+        iter.rewind();
+        assert_eq!(iter.next().unwrap().1.offset, 19);
+    }
+}
@@ -1,4 +1,12 @@
+pub mod bptree;
 pub mod btree;
+pub mod hash;
+pub mod sharded_btree;
+pub mod skiplist;
+
+use std::cmp::Ordering;
+use std::ops::Bound;
+use std::path::Path;
 
 use bytes::Bytes;
 
@@ -10,30 +18,59 @@ use crate::{
 
 /// 抽象索引接口，胡须如果想要接入其他的数据结构，就实现这个接口即可
 pub trait Indexer: Send + Sync {
-    /// 向索引中存储key对应的数据位置信息
-    fn put(&self, key: Vec<u8>, pos: LogRecordPos) -> bool;
+    /// 向索引中存储key对应的数据位置信息，返回被覆盖的旧位置信息
+    fn put(&self, key: Vec<u8>, pos: LogRecordPos) -> Option<LogRecordPos>;
 
     /// 根据key取出对应的索引位置信息
     fn get(&self, key: Vec<u8>) -> Option<LogRecordPos>;
 
-    /// 根据key删除对应的索引位置信息
-    fn delete(&self, key: Vec<u8>) -> bool;
+    /// 根据key删除对应的索引位置信息，返回被删除的位置信息
+    fn delete(&self, key: Vec<u8>) -> Option<LogRecordPos>;
+
+    /// 仅当key当前的位置信息为old_pos时，才将其更新为new_pos，用于merge后数据的迁移
+    fn relocate(&self, key: Vec<u8>, old_pos: LogRecordPos, new_pos: LogRecordPos) -> bool;
+
+    /// 仅当key当前的位置信息为pos时，才删除该key，用于merge时清理过期数据
+    fn delete_if(&self, key: Vec<u8>, pos: LogRecordPos) -> bool;
+
+    /// 清空索引，用于清空数据库
+    fn clear(&self) -> Result<()>;
 
     /// 获取索引迭代器
     fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexInterator>;
 
     /// 获取所有key
     fn list_keys(&self) -> Result<Vec<Bytes>>;
-}
 
-pub fn new_indexer(index_type: IndexType) -> impl Indexer {
-    match index_type {
-        IndexType::BTree => btree::BTree::new(),
-        IndexType::SkipList => unimplemented!(),
+    /// key的数量
+    fn len(&self) -> usize;
+
+    /// 索引占用内存的估计值，磁盘索引返回0
+    fn memory_usage(&self) -> usize;
+
+    /// 打开数据库时调用，返回持久化的索引能否直接使用，不能使用时清空索引，
+    /// 需要从数据文件中重新加载。内存索引总是需要重新加载
+    fn try_reuse(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// 关闭数据库时调用，持久化索引
+    fn persist(&self) -> Result<()> {
+        Ok(())
     }
 }
 
-pub trait IndexInterator {
+pub fn new_indexer(index_type: IndexType, dir_path: impl AsRef<Path>) -> Result<Box<dyn Indexer>> {
+    Ok(match index_type {
+        IndexType::BTree => Box::new(btree::BTree::new()),
+        IndexType::SkipList => Box::new(skiplist::SkipList::new()),
+        IndexType::ShardedBTree => Box::new(sharded_btree::ShardedBTree::new()),
+        IndexType::Hash => Box::new(hash::HashIndex::new()),
+        IndexType::BPlusTree => Box::new(bptree::BPlusTree::new(dir_path)?),
+    })
+}
+
+pub trait IndexInterator: Sync + Send {
     /// 重置迭代器
     fn rewind(&mut self);
 
@@ -43,3 +80,62 @@ pub trait IndexInterator {
     /// 获取下一个key
     fn next(&mut self) -> Option<(&[u8], &LogRecordPos)>;
 }
+
+/// 估计一条索引占用的内存，包括key的数据、Vec和LogRecordPos本身，不包括数据结构内部节点的开销
+fn entry_memory_usage(key: &[u8]) -> usize {
+    key.len() + std::mem::size_of::<Vec<u8>>() + std::mem::size_of::<LogRecordPos>()
+}
+
+/// 有序索引的迭代器每次读取的数据条数
+const ITERATOR_BATCH_SIZE: usize = 256;
+
+/// 迭代的起始位置，取前缀范围和上下界中更靠内的边界，逆序迭代时为上界
+fn start_bound(options: &IteratorOptions) -> Bound<Vec<u8>> {
+    let prefix = &options.prefix;
+    let (prefix_bound, range_bound) = if options.reverse {
+        (
+            prefix_successor(prefix).map_or(Bound::Unbounded, Bound::Excluded),
+            options.upper_bound.clone(),
+        )
+    } else {
+        (Bound::Included(prefix.clone()), options.lower_bound.clone())
+    };
+    tighter_bound(prefix_bound, range_bound, options.reverse)
+}
+
+/// seek的位置，不能越过迭代的起始边界
+fn seek_bound(options: &IteratorOptions, key: Vec<u8>) -> Bound<Vec<u8>> {
+    tighter_bound(start_bound(options), Bound::Included(key), options.reverse)
+}
+
+/// 取两个起始边界中更靠内的一个，正序时为较大的下界，逆序时为较小的上界
+fn tighter_bound(a: Bound<Vec<u8>>, b: Bound<Vec<u8>>, reverse: bool) -> Bound<Vec<u8>> {
+    let ordering = match (&a, &b) {
+        (Bound::Unbounded, _) => return b,
+        (_, Bound::Unbounded) => return a,
+        (
+            Bound::Included(key_a) | Bound::Excluded(key_a),
+            Bound::Included(key_b) | Bound::Excluded(key_b),
+        ) => key_a.cmp(key_b),
+    };
+    match ordering {
+        // key相同时不包含该key的边界更靠内
+        Ordering::Equal if matches!(a, Bound::Excluded(_)) => a,
+        Ordering::Equal => b,
+        Ordering::Greater if !reverse => a,
+        Ordering::Less if reverse => a,
+        _ => b,
+    }
+}
+
+/// 大于所有以prefix为前缀的key的最小key，prefix全为0xff时不存在
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut successor = prefix.to_vec();
+    while let Some(last) = successor.pop() {
+        if last < u8::MAX {
+            successor.push(last + 1);
+            return Some(successor);
+        }
+    }
+    None
+}
@@ -0,0 +1,216 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use bytes::Bytes;
+
+use crate::data::log_record::LogRecordPos;
+use crate::error::Result;
+use crate::options::IteratorOptions;
+
+use super::btree::BTree;
+use super::{IndexInterator, Indexer};
+
+/// 分片的数量
+const SHARD_NUM: usize = 16;
+
+/// 分片的BTree索引，按key的哈希值分到多个BTree中，
+/// 不同分片的修改互不阻塞，迭代时对所有分片做归并
+pub struct ShardedBTree {
+    shards: Vec<BTree>,
+}
+
+impl ShardedBTree {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARD_NUM).map(|_| BTree::new()).collect(),
+        }
+    }
+
+    /// key所在的分片
+    fn shard(&self, key: &[u8]) -> &BTree {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+}
+
+impl Indexer for ShardedBTree {
+    fn put(&self, key: Vec<u8>, pos: LogRecordPos) -> Option<LogRecordPos> {
+        self.shard(&key).put(key, pos)
+    }
+
+    fn get(&self, key: Vec<u8>) -> Option<LogRecordPos> {
+        self.shard(&key).get(key)
+    }
+
+    fn delete(&self, key: Vec<u8>) -> Option<LogRecordPos> {
+        self.shard(&key).delete(key)
+    }
+
+    fn relocate(&self, key: Vec<u8>, old_pos: LogRecordPos, new_pos: LogRecordPos) -> bool {
+        self.shard(&key).relocate(key, old_pos, new_pos)
+    }
+
+    fn delete_if(&self, key: Vec<u8>, pos: LogRecordPos) -> bool {
+        self.shard(&key).delete_if(key, pos)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.shards.iter().try_for_each(|shard| shard.clear())
+    }
+
+    fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexInterator> {
+        let reverse = options.reverse;
+        let mut iter = ShardedBTreeIterator {
+            iters: self
+                .shards
+                .iter()
+                .map(|shard| shard.iterator(options.clone()))
+                .collect(),
+            heads: vec![None; self.shards.len()],
+            current: None,
+            reverse,
+        };
+        iter.fill_heads();
+        Box::new(iter)
+    }
+
+    fn list_keys(&self) -> Result<Vec<Bytes>> {
+        let mut iter = self.iterator(IteratorOptions::default());
+        let mut keys = Vec::with_capacity(self.len());
+        while let Some((key, _)) = iter.next() {
+            keys.push(Bytes::copy_from_slice(key));
+        }
+        Ok(keys)
+    }
+
+    fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.shards.iter().map(|shard| shard.memory_usage()).sum()
+    }
+}
+
+/// 分片BTree索引的迭代器，每次从所有分片的下一个key中取最小（逆序时最大）的一个
+pub struct ShardedBTreeIterator {
+    /// 每个分片的迭代器
+    iters: Vec<Box<dyn IndexInterator>>,
+
+    /// 每个分片的下一个key + pos
+    heads: Vec<Option<(Vec<u8>, LogRecordPos)>>,
+
+    /// 上一次返回的key + pos
+    current: Option<(Vec<u8>, LogRecordPos)>,
+
+    /// 是否逆序
+    reverse: bool,
+}
+
+impl ShardedBTreeIterator {
+    /// 读取每个分片的下一个key
+    fn fill_heads(&mut self) {
+        for (head, iter) in self.heads.iter_mut().zip(self.iters.iter_mut()) {
+            *head = iter.next().map(|(key, pos)| (key.to_vec(), *pos));
+        }
+    }
+}
+
+impl IndexInterator for ShardedBTreeIterator {
+    fn rewind(&mut self) {
+        self.iters.iter_mut().for_each(|iter| iter.rewind());
+        self.fill_heads();
+    }
+
+    fn seek(&mut self, key: Vec<u8>) {
+        self.iters
+            .iter_mut()
+            .for_each(|iter| iter.seek(key.clone()));
+        self.fill_heads();
+    }
+
+    fn next(&mut self) -> Option<(&[u8], &LogRecordPos)> {
+        let candidates = self
+            .heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, head)| head.as_ref().map(|(key, _)| (i, key)));
+        let idx = match self.reverse {
+            true => candidates.max_by(|(_, a), (_, b)| a.cmp(b)),
+            false => candidates.min_by(|(_, a), (_, b)| a.cmp(b)),
+        }
+        .map(|(i, _)| i)?;
+        let next_head = self.iters[idx]
+            .next()
+            .map(|(key, pos)| (key.to_vec(), *pos));
+        self.current = std::mem::replace(&mut self.heads[idx], next_head);
+        self.current
+            .as_ref()
+            .map(|(key, pos)| (key.as_slice(), pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(offset: u64) -> LogRecordPos {
+        LogRecordPos {
+            file_id: 1,
+            offset,
+            size: 10,
+        }
+    }
+
+    #[test]
+    fn test_sharded_btree_put_get_delete() {
+        let sbt = ShardedBTree::new();
+        for i in 0..100 {
+            assert!(sbt
+                .put(format!("key-{:03}", i).into_bytes(), pos(i))
+                .is_none());
+        }
+        assert_eq!(sbt.put(b"key-000".to_vec(), pos(100)), Some(pos(0)));
+        assert_eq!(sbt.len(), 100);
+        // key分布在多个分片中
+        assert!(sbt.shards.iter().filter(|shard| shard.len() > 0).count() > 1);
+
+        assert_eq!(sbt.get(b"key-000".to_vec()), Some(pos(100)));
+        assert!(sbt.get(b"not exist".to_vec()).is_none());
+        assert!(sbt.relocate(b"key-001".to_vec(), pos(1), pos(101)));
+        assert!(!sbt.delete_if(b"key-001".to_vec(), pos(1)));
+        assert!(sbt.delete_if(b"key-001".to_vec(), pos(101)));
+        assert_eq!(sbt.delete(b"key-002".to_vec()), Some(pos(2)));
+        assert_eq!(sbt.len(), 98);
+    }
+
+    #[test]
+    fn test_sharded_btree_iterator() {
+        let sbt = ShardedBTree::new();
+        for i in 0..1000 {
+            sbt.put(format!("key-{:04}", i).into_bytes(), pos(i));
+        }
+        let keys = sbt.list_keys().unwrap();
+        assert_eq!(keys.len(), 1000);
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+
+        let mut iter = sbt.iterator(IteratorOptions::default());
+        iter.seek(b"key-0500".to_vec());
+        for i in 500..1000 {
+            assert_eq!(iter.next().unwrap().1.offset, i);
+        }
+        assert!(iter.next().is_none());
+        iter.rewind();
+        assert_eq!(iter.next().unwrap().1.offset, 0);
+
+        let mut iter = sbt.iterator(IteratorOptions {
+            prefix: b"key-01".to_vec(),
+            reverse: true,
+            ..Default::default()
+        });
+        for i in (100..200).rev() {
+            assert_eq!(iter.next().unwrap().1.offset, i);
+        }
+        assert!(iter.next().is_none());
+    }
+}
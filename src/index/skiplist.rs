@@ -0,0 +1,370 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use crossbeam_skiplist::SkipMap;
+use parking_lot::Mutex;
+
+use crate::data::log_record::LogRecordPos;
+use crate::error::Result;
+use crate::options::IteratorOptions;
+
+use super::{entry_memory_usage, IndexInterator, Indexer};
+
+/// SkipList索引，封装了crossbeam的无锁跳表
+pub struct SkipList {
+    skl: Arc<SkipMap<Vec<u8>, LogRecordPos>>,
+    /// 写入、删除和迁移数据需要先检查再修改，相互之间需要互斥，读取不受影响
+    relocate_lock: Mutex<()>,
+    /// 索引占用内存的估计值
+    memory_usage: AtomicUsize,
+}
+
+impl SkipList {
+    pub fn new() -> Self {
+        Self {
+            skl: Arc::new(SkipMap::new()),
+            relocate_lock: Mutex::new(()),
+            memory_usage: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Indexer for SkipList {
+    fn put(&self, key: Vec<u8>, pos: LogRecordPos) -> Option<LogRecordPos> {
+        // 与删除和迁移互斥，保证返回的旧位置信息准确
+        let _lock = self.relocate_lock.lock();
+        let old_pos = self.skl.get(&key).map(|entry| *entry.value());
+        if old_pos.is_none() {
+            self.memory_usage
+                .fetch_add(entry_memory_usage(&key), Ordering::Relaxed);
+        }
+        self.skl.insert(key, pos);
+        old_pos
+    }
+
+    fn get(&self, key: Vec<u8>) -> Option<LogRecordPos> {
+        self.skl.get(&key).map(|entry| *entry.value())
+    }
+
+    fn delete(&self, key: Vec<u8>) -> Option<LogRecordPos> {
+        let _lock = self.relocate_lock.lock();
+        let old_pos = self.skl.remove(&key).map(|entry| *entry.value());
+        if old_pos.is_some() {
+            self.memory_usage
+                .fetch_sub(entry_memory_usage(&key), Ordering::Relaxed);
+        }
+        old_pos
+    }
+
+    fn relocate(&self, key: Vec<u8>, old_pos: LogRecordPos, new_pos: LogRecordPos) -> bool {
+        let _lock = self.relocate_lock.lock();
+        match self.skl.get(&key) {
+            Some(entry) if *entry.value() == old_pos => {}
+            _ => return false,
+        }
+        // key存在时才会插入，期间被重新写入则不会覆盖
+        let entry = self.skl.compare_insert(key, new_pos, |pos| *pos == old_pos);
+        *entry.value() == new_pos
+    }
+
+    fn delete_if(&self, key: Vec<u8>, pos: LogRecordPos) -> bool {
+        let _lock = self.relocate_lock.lock();
+        match self.skl.get(&key) {
+            // 期间被重新写入时，旧的entry已被移除，不会影响新写入的数据
+            Some(entry) if *entry.value() == pos && entry.remove() => {
+                self.memory_usage
+                    .fetch_sub(entry_memory_usage(&key), Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn clear(&self) -> Result<()> {
+        let _lock = self.relocate_lock.lock();
+        self.skl.clear();
+        self.memory_usage.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexInterator> {
+        let mut items = self
+            .skl
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect::<Vec<_>>();
+        if options.reverse {
+            items.reverse()
+        }
+        Box::new(SkipListIterator {
+            items,
+            curr_idx: 0,
+            options,
+        })
+    }
+
+    fn list_keys(&self) -> Result<Vec<Bytes>> {
+        Ok(self
+            .skl
+            .iter()
+            .map(|entry| Bytes::from(entry.key().clone()))
+            .collect())
+    }
+
+    fn len(&self) -> usize {
+        self.skl.len()
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.memory_usage.load(Ordering::Relaxed)
+    }
+}
+
+/// SkipList索引的迭代器
+pub struct SkipListIterator {
+    /// key + pos
+    items: Vec<(Vec<u8>, LogRecordPos)>,
+
+    /// 当前索引
+    curr_idx: usize,
+
+    /// 迭代器选项
+    options: IteratorOptions,
+}
+
+impl IndexInterator for SkipListIterator {
+    fn rewind(&mut self) {
+        self.curr_idx = 0;
+    }
+
+    fn seek(&mut self, key: Vec<u8>) {
+        self.curr_idx = match self.items.binary_search_by(|(k, _)| {
+            if self.options.reverse {
+                k.cmp(&key).reverse()
+            } else {
+                k.cmp(&key)
+            }
+        }) {
+            Ok(position) => position,
+            Err(insert_position) => insert_position,
+        };
+    }
+
+    fn next(&mut self) -> Option<(&[u8], &LogRecordPos)> {
+        while let Some((key, pos)) = self.items.get(self.curr_idx) {
+            self.curr_idx += 1;
+            if self.options.contains(key) {
+                return Some((key, pos));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skiplist_put() {
+        let skl = SkipList::new();
+        let res1 = skl.put(
+            b"".to_vec(),
+            LogRecordPos {
+                file_id: 1,
+                offset: 10,
+                size: 10,
+            },
+        );
+        assert!(res1.is_none());
+        let res2 = skl.put(
+            b"aa".to_vec(),
+            LogRecordPos {
+                file_id: 11,
+                offset: 22,
+                size: 10,
+            },
+        );
+        assert!(res2.is_none());
+        let res3 = skl.put(
+            b"aa".to_vec(),
+            LogRecordPos {
+                file_id: 12,
+                offset: 33,
+                size: 10,
+            },
+        );
+        assert_eq!(res3.unwrap().file_id, 11);
+        assert_eq!(skl.get(b"aa".to_vec()).unwrap().file_id, 12);
+    }
+
+    #[test]
+    fn test_skiplist_get() {
+        let skl = SkipList::new();
+        skl.put(
+            b"".to_vec(),
+            LogRecordPos {
+                file_id: 1,
+                offset: 10,
+                size: 10,
+            },
+        );
+        skl.put(
+            b"aa".to_vec(),
+            LogRecordPos {
+                file_id: 11,
+                offset: 22,
+                size: 10,
+            },
+        );
+
+        let pos1 = skl.get(b"".to_vec());
+        assert!(pos1.is_some());
+        assert_eq!(pos1.unwrap().file_id, 1);
+
+        let pos2 = skl.get(b"aa".to_vec());
+        assert!(pos2.is_some());
+        assert_eq!(pos2.unwrap().offset, 22);
+
+        assert!(skl.get(b"not exist".to_vec()).is_none());
+    }
+
+    #[test]
+    fn test_skiplist_delete() {
+        let skl = SkipList::new();
+        skl.put(
+            b"".to_vec(),
+            LogRecordPos {
+                file_id: 1,
+                offset: 10,
+                size: 10,
+            },
+        );
+        skl.put(
+            b"aa".to_vec(),
+            LogRecordPos {
+                file_id: 11,
+                offset: 22,
+                size: 10,
+            },
+        );
+
+        assert_eq!(skl.len(), 2);
+        assert_eq!(skl.delete(b"".to_vec()).unwrap().file_id, 1);
+        assert_eq!(skl.delete(b"aa".to_vec()).unwrap().file_id, 11);
+        assert!(skl.delete(b"not exist".to_vec()).is_none());
+        assert!(skl.get(b"aa".to_vec()).is_none());
+        assert_eq!(skl.len(), 0);
+    }
+
+    #[test]
+    fn test_skiplist_relocate() {
+        let skl = SkipList::new();
+        let old_pos = LogRecordPos {
+            file_id: 1,
+            offset: 10,
+            size: 10,
+        };
+        let new_pos = LogRecordPos {
+            file_id: 5,
+            offset: 0,
+            size: 10,
+        };
+        skl.put(b"aa".to_vec(), old_pos);
+
+        // 位置信息不匹配
+        assert!(!skl.relocate(b"aa".to_vec(), new_pos, new_pos));
+        assert_eq!(skl.get(b"aa".to_vec()), Some(old_pos));
+
+        assert!(skl.relocate(b"aa".to_vec(), old_pos, new_pos));
+        assert_eq!(skl.get(b"aa".to_vec()), Some(new_pos));
+
+        // key不存在
+        assert!(!skl.relocate(b"bb".to_vec(), old_pos, new_pos));
+        assert!(skl.get(b"bb".to_vec()).is_none());
+    }
+
+    #[test]
+    fn test_skiplist_delete_if() {
+        let skl = SkipList::new();
+        let pos = LogRecordPos {
+            file_id: 1,
+            offset: 10,
+            size: 10,
+        };
+        let other_pos = LogRecordPos {
+            file_id: 2,
+            offset: 10,
+            size: 10,
+        };
+        skl.put(b"aa".to_vec(), pos);
+
+        assert!(!skl.delete_if(b"aa".to_vec(), other_pos));
+        assert!(skl.get(b"aa".to_vec()).is_some());
+        assert!(skl.delete_if(b"aa".to_vec(), pos));
+        assert!(skl.get(b"aa".to_vec()).is_none());
+        assert!(!skl.delete_if(b"aa".to_vec(), pos));
+    }
+
+    #[test]
+    fn test_skiplist_iterator() {
+        let skl = SkipList::new();
+        let mut iter = skl.iterator(IteratorOptions::default());
+        assert!(iter.next().is_none());
+
+        for key in [
+            b"ccde".to_vec(),
+            b"aabc".to_vec(),
+            b"bbcd".to_vec(),
+            b"ab".to_vec(),
+        ] {
+            skl.put(
+                key,
+                LogRecordPos {
+                    file_id: 1,
+                    offset: 10,
+                    size: 10,
+                },
+            );
+        }
+
+        // 正向迭代，有序
+        let mut iter = skl.iterator(IteratorOptions::default());
+        let mut keys = vec![];
+        while let Some((key, _)) = iter.next() {
+            keys.push(key.to_vec());
+        }
+        assert_eq!(
+            keys,
+            vec![
+                b"aabc".to_vec(),
+                b"ab".to_vec(),
+                b"bbcd".to_vec(),
+                b"ccde".to_vec()
+            ]
+        );
+
+        // seek
+        iter.seek(b"b".to_vec());
+        assert_eq!(iter.next().unwrap().0, b"bbcd");
+        iter.rewind();
+        assert_eq!(iter.next().unwrap().0, b"aabc");
+
+        // 反向迭代
+        let mut options = IteratorOptions::default();
+        options.reverse = true;
+        let mut iter = skl.iterator(options);
+        iter.seek(b"bz".to_vec());
+        assert_eq!(iter.next().unwrap().0, b"bbcd");
+        assert_eq!(iter.next().unwrap().0, b"ab");
+
+        // 前缀
+        let mut options = IteratorOptions::default();
+        options.prefix = b"a".to_vec();
+        let mut iter = skl.iterator(options);
+        assert_eq!(iter.next().unwrap().0, b"aabc");
+        assert_eq!(iter.next().unwrap().0, b"ab");
+        assert!(iter.next().is_none());
+    }
+}
@@ -1,37 +1,109 @@
+use std::ops::RangeBounds;
 use std::sync::Arc;
 
 use bytes::Bytes;
+use log::error;
 use parking_lot::RwLock;
 
-use crate::{db::Engine, error::Result, index::IndexInterator, options::IteratorOptions};
+use crate::{
+    db::Engine,
+    error::{Error, Result},
+    index::IndexInterator,
+    options::IteratorOptions,
+};
 
-pub struct Iterator<'a> {
+/// 用户迭代器，持有数据库的句柄，可以在线程和异步任务间传递
+pub struct Iterator {
     index_iter: Arc<RwLock<Box<dyn IndexInterator>>>,
-    engine: &'a Engine,
+    engine: Engine,
+    /// 只返回key
+    keys_only: bool,
+}
+
+/// 实现了标准库Iterator trait的迭代器，读取数据出错时返回错误
+pub struct Scan {
+    iter: Iterator,
 }
 
 impl Engine {
     /// 用户迭代器
     pub fn iter(&self, options: IteratorOptions) -> Iterator {
-        Iterator {
-            index_iter: Arc::new(RwLock::new(self.index.iterator(options))),
-            engine: self,
-        }
+        let keys_only = options.keys_only;
+        Iterator::new(self.clone(), self.index.iterator(options), keys_only)
+    }
+
+    /// 按顺序遍历数据，可以用于for循环和标准库的迭代器适配器
+    pub fn scan(&self, options: IteratorOptions) -> Scan {
+        Scan::new(self.iter(options))
+    }
+
+    /// 按顺序遍历key在range范围内的数据，reverse为true时逆序
+    pub fn range(&self, range: impl RangeBounds<Bytes>, reverse: bool) -> Scan {
+        self.scan(IteratorOptions {
+            reverse,
+            lower_bound: range.start_bound().map(|key| key.to_vec()),
+            upper_bound: range.end_bound().map(|key| key.to_vec()),
+            ..Default::default()
+        })
     }
 
     /// 所有key
     pub fn list_keys(&self) -> Result<Vec<Bytes>> {
+        self.check_closed()?;
         self.index.list_keys()
     }
 
+    /// 分页列出指定前缀的key，从start_after之后开始，最多返回limit个
+    ///
+    /// 同时返回下一页的起始位置，即本页最后一个key，没有更多key时为None
+    pub fn list_keys_with(
+        &self,
+        prefix: &[u8],
+        limit: usize,
+        start_after: Option<&[u8]>,
+    ) -> Result<(Vec<Bytes>, Option<Bytes>)> {
+        self.check_closed()?;
+        let mut index_iter = self.index.iterator(IteratorOptions::default());
+        match start_after {
+            Some(start_after) if start_after > prefix => index_iter.seek(start_after.to_vec()),
+            _ => index_iter.seek(prefix.to_vec()),
+        }
+
+        let mut keys = Vec::new();
+        while let Some((key, _)) = index_iter.next() {
+            // key是有序的，前缀不匹配说明已经超出范围
+            if !key.starts_with(prefix) {
+                return Ok((keys, None));
+            }
+            if start_after.is_some_and(|start_after| key <= start_after) {
+                continue;
+            }
+            if keys.len() == limit {
+                // 还有更多的key
+                let next = keys.last().cloned();
+                return Ok((keys, next));
+            }
+            keys.push(Bytes::copy_from_slice(key));
+        }
+        Ok((keys, None))
+    }
+
+    /// 遍历所有数据，回调返回false时停止，跳过已过期的数据
     ///
-    pub fn fold<F>(&self, f: F) -> Result<()>
+    /// 直接使用索引迭代器，不需要构造用户迭代器
+    pub fn fold<F>(&self, mut f: F) -> Result<()>
     where
-        F: Fn(Bytes, Bytes) -> bool,
+        F: FnMut(Bytes, Bytes) -> bool,
     {
-        let iter = self.iter(IteratorOptions::default());
-        while let Some((key, value)) = iter.next() {
-            if !f(key, value) {
+        self.check_closed()?;
+        let mut index_iter = self.index.iterator(IteratorOptions::default());
+        while let Some((key, pos)) = index_iter.next() {
+            let value = match self.get_value_by_position(pos) {
+                Ok(value) => value,
+                Err(Error::KeyNotFound) => continue,
+                Err(e) => return Err(e),
+            };
+            if !f(Bytes::copy_from_slice(key), value) {
                 break;
             }
         }
@@ -39,7 +111,20 @@ impl Engine {
     }
 }
 
-impl<'a> Iterator<'a> {
+impl Iterator {
+    /// 使用索引迭代器构造用户迭代器
+    pub(crate) fn new(
+        engine: Engine,
+        index_iter: Box<dyn IndexInterator>,
+        keys_only: bool,
+    ) -> Self {
+        Self {
+            index_iter: Arc::new(RwLock::new(index_iter)),
+            engine,
+            keys_only,
+        }
+    }
+
     /// 重置迭代器
     pub fn rewind(&self) {
         self.index_iter.write().rewind();
@@ -50,28 +135,71 @@ impl<'a> Iterator<'a> {
         self.index_iter.write().seek(key);
     }
 
-    /// 获取下一个(key, value)
+    /// 获取下一个(key, value)，跳过已过期或者已被删除的数据
+    ///
+    /// 读取数据出错时结束迭代，需要获取错误时使用Engine::scan
     pub fn next(&self) -> Option<(Bytes, Bytes)> {
+        match self.next_entry()? {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                error!("failed to read value while iterating: {}", e);
+                None
+            }
+        }
+    }
+
+    /// 获取下一个(key, value)，跳过已过期或者已被删除的数据，读取数据出错时返回错误
+    fn next_entry(&self) -> Option<Result<(Bytes, Bytes)>> {
         let mut index_iter = self.index_iter.write();
-        match index_iter.next() {
-            Some((key, pos)) => {
-                let value = self.engine.get_value_by_position(&pos).expect(&format!(
-                    "failed to get value by position, key is {:?}, pos is {:?}",
-                    key, pos
-                ));
-                Some((key.to_vec().into(), value))
+        if self.keys_only {
+            if let Err(e) = self.engine.check_closed() {
+                return Some(Err(e));
             }
-            None => None,
+            return index_iter
+                .next()
+                .map(|(key, _)| Ok((Bytes::copy_from_slice(key), Bytes::new())));
+        }
+        while let Some((key, pos)) = index_iter.next() {
+            let value = match self.engine.get_value_by_position(pos) {
+                Ok(value) => value,
+                Err(Error::KeyNotFound) => continue,
+                // 创建迭代器后数据文件被merge清理，重新查询索引，key已被删除时跳过
+                Err(Error::DataFileNotFound) => {
+                    match self.engine.get(Bytes::copy_from_slice(key)) {
+                        Ok(value) => value,
+                        Err(Error::KeyNotFound) => continue,
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            };
+            return Some(Ok((key.to_vec().into(), value)));
         }
+        None
+    }
+}
+
+impl Scan {
+    pub(crate) fn new(iter: Iterator) -> Self {
+        Self { iter }
+    }
+}
+
+impl std::iter::Iterator for Scan {
+    type Item = Result<(Bytes, Bytes)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next_entry()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    use std::time::Duration;
 
     use crate::{
-        options::Options,
+        options::{IndexType, Options},
         util::rand_kv::{get_test_key, get_test_value},
     };
 
@@ -99,6 +227,49 @@ mod tests {
         std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove dir");
     }
 
+    #[test]
+    fn test_list_keys_with() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-iterator-list-keys-with");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let (keys, next) = engine.list_keys_with(b"", 10, None).unwrap();
+        assert!(keys.is_empty());
+        assert!(next.is_none());
+
+        for key in ["aaabcd", "ababcd", "acabcd", "baabcd", "bbabcd", "zzzzz"] {
+            engine.put(key.into(), "value".into()).unwrap();
+        }
+        // 分页
+        let (keys, next) = engine.list_keys_with(b"", 4, None).unwrap();
+        assert_eq!(keys, ["aaabcd", "ababcd", "acabcd", "baabcd"]);
+        assert_eq!(next.as_deref(), Some(b"baabcd".as_slice()));
+        let (keys, next) = engine.list_keys_with(b"", 4, next.as_deref()).unwrap();
+        assert_eq!(keys, ["bbabcd", "zzzzz"]);
+        assert!(next.is_none());
+
+        // 前缀
+        let (keys, next) = engine.list_keys_with(b"a", 2, None).unwrap();
+        assert_eq!(keys, ["aaabcd", "ababcd"]);
+        let (keys, next) = engine.list_keys_with(b"a", 2, next.as_deref()).unwrap();
+        assert_eq!(keys, ["acabcd"]);
+        assert!(next.is_none());
+        let (keys, next) = engine.list_keys_with(b"a", 3, None).unwrap();
+        assert_eq!(keys.len(), 3);
+        assert!(next.is_none());
+
+        // start_after不存在或者在前缀之前
+        let (keys, _) = engine.list_keys_with(b"b", 10, Some(b"ab")).unwrap();
+        assert_eq!(keys, ["baabcd", "bbabcd"]);
+        let (keys, _) = engine.list_keys_with(b"", 10, Some(b"bb")).unwrap();
+        assert_eq!(keys, ["bbabcd", "zzzzz"]);
+        let (keys, _) = engine.list_keys_with(b"c", 10, None).unwrap();
+        assert!(keys.is_empty());
+
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove dir");
+    }
+
     #[test]
     fn test_fold() {
         let mut opts = Options::default();
@@ -113,15 +284,39 @@ mod tests {
         engine.put("baabcd".into(), "value4".into()).unwrap();
         engine.put("bbabcd".into(), "value5".into()).unwrap();
 
+        let mut pairs = vec![];
         engine
             .fold(|k, v| {
                 if k.starts_with(b"b") {
                     return false;
                 }
-                println!("{:?} {:?}", k, v);
+                pairs.push((k, v));
                 true
             })
             .expect("fold ok");
+        assert_eq!(
+            pairs,
+            [
+                ("aaabcd", "value1"),
+                ("ababcd", "value2"),
+                ("acabcd", "value3")
+            ]
+            .map(|(k, v)| (Bytes::from(k), Bytes::from(v)))
+        );
+
+        // 跳过已过期的数据
+        engine
+            .put_with_ttl("aaabcd".into(), "value1".into(), Duration::from_millis(1))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        let mut count = 0;
+        engine
+            .fold(|_, _| {
+                count += 1;
+                true
+            })
+            .expect("fold ok");
+        assert_eq!(count, 5);
 
         std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove dir");
     }
@@ -145,11 +340,11 @@ mod tests {
         iter.seek(get_test_key(11).to_vec());
         assert!(iter.next().is_some());
 
-        let put_res = engine.put("aaabcd".into(), "value1".into());
-        let put_res = engine.put("ababcd".into(), "value2".into());
-        let put_res = engine.put("acabcd".into(), "value3".into());
-        let put_res = engine.put("baabcd".into(), "value4".into());
-        let put_res = engine.put("bbabcd".into(), "value5".into());
+        engine.put("aaabcd".into(), "value1".into()).unwrap();
+        engine.put("ababcd".into(), "value2".into()).unwrap();
+        engine.put("acabcd".into(), "value3".into()).unwrap();
+        engine.put("baabcd".into(), "value4".into()).unwrap();
+        engine.put("bbabcd".into(), "value5".into()).unwrap();
         let iter = engine.iter(IteratorOptions::default());
         iter.seek("ac".into());
         assert_eq!(iter.next().unwrap().1, "value3");
@@ -169,11 +364,11 @@ mod tests {
         opts.data_file_size = 64 * 1024 * 1024;
         let engine = Engine::open(opts.clone()).expect("failed to open engine");
 
-        let put_res = engine.put("aaabcd".into(), "value1".into());
-        let put_res = engine.put("ababcd".into(), "value2".into());
-        let put_res = engine.put("acabcd".into(), "value3".into());
-        let put_res = engine.put("baabcd".into(), "value4".into());
-        let put_res = engine.put("bbabcd".into(), "value5".into());
+        engine.put("aaabcd".into(), "value1".into()).unwrap();
+        engine.put("ababcd".into(), "value2".into()).unwrap();
+        engine.put("acabcd".into(), "value3".into()).unwrap();
+        engine.put("baabcd".into(), "value4".into()).unwrap();
+        engine.put("bbabcd".into(), "value5".into()).unwrap();
         let iter = engine.iter(IteratorOptions::default());
         assert_eq!(iter.next().unwrap().1, "value1");
         assert_eq!(iter.next().unwrap().1, "value2");
@@ -184,6 +379,162 @@ mod tests {
         std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove dir");
     }
 
+    #[test]
+    fn test_scan() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-iterator-scan");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        assert_eq!(engine.scan(IteratorOptions::default()).count(), 0);
+        for i in 0..10 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        engine
+            .put_with_ttl(get_test_key(10), get_test_value(10), Duration::ZERO)
+            .unwrap();
+
+        // 可以使用for循环和标准库的迭代器适配器，跳过已过期的数据
+        let mut i = 0;
+        for res in engine.scan(IteratorOptions::default()) {
+            let (key, value) = res.unwrap();
+            assert_eq!(key, get_test_key(i));
+            assert_eq!(value, get_test_value(i));
+            i += 1;
+        }
+        assert_eq!(i, 10);
+        let values = engine
+            .scan(IteratorOptions {
+                reverse: true,
+                ..Default::default()
+            })
+            .map(|res| res.map(|(_, value)| value))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(values.len(), 10);
+        assert_eq!(values[0], get_test_value(9));
+
+        // 读取数据出错时返回错误
+        engine.close().unwrap();
+        let mut scan = engine.scan(IteratorOptions::default());
+        assert!(matches!(
+            scan.next().unwrap().err().unwrap(),
+            Error::DatabaseClosed
+        ));
+
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove dir");
+    }
+
+    #[test]
+    fn test_iterator_delete_and_merge() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-iterator-delete-and-merge");
+        opts.data_file_size = 32 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        // 开始迭代后删除一半的数据并merge，迭代器已读取的位置信息指向已被清理的数据文件
+        let iter = engine.iter(IteratorOptions::default());
+        let mut scan = engine.scan(IteratorOptions::default());
+        assert!(iter.next().is_some());
+        assert!(scan.next().unwrap().is_ok());
+        for i in (1..1000).step_by(2) {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        engine.merge().unwrap();
+
+        let mut count = 1;
+        while let Some((key, value)) = iter.next() {
+            assert_eq!(engine.get(key).unwrap(), value);
+            count += 1;
+        }
+        assert_eq!(count, 500);
+        assert_eq!(scan.by_ref().filter(|res| res.is_ok()).count(), 499);
+
+        std::mem::drop(scan);
+        std::mem::drop(iter);
+        std::mem::drop(engine);
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove dir");
+    }
+
+    #[test]
+    fn test_range() {
+        for index_type in [
+            IndexType::BTree,
+            IndexType::SkipList,
+            IndexType::ShardedBTree,
+            IndexType::Hash,
+        ] {
+            let mut opts = Options::default();
+            opts.dir_path = PathBuf::from("/tmp/bitcask-rs-iterator-range");
+            opts.data_file_size = 64 * 1024 * 1024;
+            opts.index_type = index_type;
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+            for i in 0..100 {
+                engine.put(get_test_key(i), get_test_value(i)).unwrap();
+            }
+            let keys = |range, reverse| {
+                engine
+                    .range(range, reverse)
+                    .map(|res| res.unwrap().0)
+                    .collect::<Vec<_>>()
+            };
+            let expected = (10..20).map(get_test_key).collect::<Vec<_>>();
+            assert_eq!(keys(get_test_key(10)..get_test_key(20), false), expected);
+            assert_eq!(
+                keys(get_test_key(10)..get_test_key(20), true),
+                expected.into_iter().rev().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                engine
+                    .range(get_test_key(95)..=get_test_key(99), false)
+                    .count(),
+                5
+            );
+            assert_eq!(engine.range(get_test_key(95).., true).count(), 5);
+            assert!(keys(get_test_key(20)..get_test_key(10), false).is_empty());
+
+            std::mem::drop(engine);
+            std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove dir");
+        }
+    }
+
+    #[test]
+    fn test_iterator_keys_only() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-iterator-keys-only");
+        opts.data_file_size = 64 * 1024 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..10 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        let iter = engine.iter(IteratorOptions {
+            keys_only: true,
+            ..Default::default()
+        });
+        for i in 0..10 {
+            assert_eq!(iter.next().unwrap(), (get_test_key(i), Bytes::new()));
+        }
+        assert!(iter.next().is_none());
+
+        // 默认跳过已过期的数据，只返回key时无法判断是否过期
+        engine
+            .put_with_ttl(get_test_key(10), get_test_value(10), Duration::ZERO)
+            .unwrap();
+        assert_eq!(engine.scan(IteratorOptions::default()).count(), 10);
+        let keys_only = IteratorOptions {
+            keys_only: true,
+            ..Default::default()
+        };
+        assert_eq!(engine.scan(keys_only).count(), 11);
+
+        std::fs::remove_dir_all(opts.clone().dir_path).expect("failed to remove dir");
+    }
+
     #[test]
     fn test_iterator_rewind() {
         let mut opts = Options::default();
@@ -191,11 +542,11 @@ mod tests {
         opts.data_file_size = 64 * 1024 * 1024;
         let engine = Engine::open(opts.clone()).expect("failed to open engine");
 
-        let put_res = engine.put("aaabcd".into(), "value1".into());
-        let put_res = engine.put("ababcd".into(), "value2".into());
-        let put_res = engine.put("acabcd".into(), "value3".into());
-        let put_res = engine.put("baabcd".into(), "value4".into());
-        let put_res = engine.put("bbabcd".into(), "value5".into());
+        engine.put("aaabcd".into(), "value1".into()).unwrap();
+        engine.put("ababcd".into(), "value2".into()).unwrap();
+        engine.put("acabcd".into(), "value3".into()).unwrap();
+        engine.put("baabcd".into(), "value4".into()).unwrap();
+        engine.put("bbabcd".into(), "value5".into()).unwrap();
         let iter = engine.iter(IteratorOptions::default());
         assert_eq!(iter.next().unwrap().1, "value1");
         assert_eq!(iter.next().unwrap().1, "value2");
@@ -221,12 +572,12 @@ mod tests {
         opts.data_file_size = 64 * 1024 * 1024;
         let engine = Engine::open(opts.clone()).expect("failed to open engine");
 
-        let put_res = engine.put("aaabcd".into(), "value1".into());
-        let put_res = engine.put("ababcd".into(), "value2".into());
-        let put_res = engine.put("acabcd".into(), "value3".into());
-        let put_res = engine.put("baabcd".into(), "value4".into());
-        let put_res = engine.put("bbabcd".into(), "value5".into());
-        let put_res = engine.put("abbbcd".into(), "value6".into());
+        engine.put("aaabcd".into(), "value1".into()).unwrap();
+        engine.put("ababcd".into(), "value2".into()).unwrap();
+        engine.put("acabcd".into(), "value3".into()).unwrap();
+        engine.put("baabcd".into(), "value4".into()).unwrap();
+        engine.put("bbabcd".into(), "value5".into()).unwrap();
+        engine.put("abbbcd".into(), "value6".into()).unwrap();
         let mut iter_opts = IteratorOptions::default();
         iter_opts.prefix = "ab".into();
         let iter = engine.iter(iter_opts);
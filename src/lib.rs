@@ -1,9 +1,46 @@
+#![cfg_attr(test, allow(clippy::field_reassign_with_default))]
+
+#[cfg(feature = "tokio")]
+pub mod async_engine;
+mod backup;
 pub mod batch;
+mod bloom;
+pub mod bucket;
+mod cache;
+pub mod cdc;
+mod checkpoint;
+mod chunk;
+mod compact;
 pub mod data;
 pub mod db;
-mod error;
+mod destroy;
+mod dictionary;
+pub mod error;
+mod eviction;
+pub mod file_stat;
 mod fio;
+#[cfg(feature = "http")]
+pub mod http;
 mod index;
 pub mod iterator;
-mod options;
+mod merge;
+pub mod merge_control;
+pub mod merge_operator;
+pub mod open_progress;
+pub mod options;
+mod quota;
+#[cfg(feature = "raft")]
+pub mod raft;
+mod rate_limiter;
+pub mod repair;
+pub mod replication;
+mod retention;
+pub mod secondary_index;
+pub mod slow_op;
+pub mod snapshot;
+mod telemetry;
+pub mod transaction;
+#[cfg(test)]
 mod util;
+pub mod verify;
+pub mod watch;
@@ -0,0 +1,1098 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use log::warn;
+
+use crate::batch::{log_record_key_with_seq_num, parse_log_record_key, NON_TRANSACTION_SEQ_NUM};
+use crate::checkpoint::remove_checkpoint;
+use crate::chunk::read_chunks;
+use crate::data::data_file::{
+    find_data_file_dir, find_data_files, get_data_file_full_path, DataFile, DATA_FILE_HEADER_SIZE,
+    DATA_FILE_SUFFIX, HINT_FILE_NAME,
+};
+use crate::data::log_record::{
+    decode_chunk_positions, decode_log_record_pos, encode_chunk_positions, LogRecordPos,
+    LogRecordType,
+};
+use crate::db::{read_dir, Engine};
+use crate::error::{Error, Result};
+use crate::fio::{self, mem_io, Advice};
+use crate::open_progress::OpenProgressTracker;
+use crate::options::Options;
+
+use crate::telemetry;
+const MERGE_DIR_SUFFIX: &str = "-merge";
+/// merge完成标记文件，位于merge临时目录中，内容为第一个没有被merge的数据文件ID，
+/// 小于该ID的数据文件都已被merge
+const MERGE_FINISHED_FILE_NAME: &str = "merge-finished";
+
+impl Engine {
+    /// merge数据文件，只保留内存索引中仍然有效的数据，清理无效数据释放磁盘空间
+    ///
+    /// 有效数据先重写到临时目录的新数据文件中，同时生成hint索引文件，
+    /// 再移动到数据库目录，最后删除旧的数据文件
+    pub fn merge(&self) -> Result<()> {
+        let start = Instant::now();
+        let res = telemetry::timed("merge", || self.merge_files());
+        // merge后磁盘占用和无效数据大小发生变化
+        if res.is_ok() {
+            self.refresh_db_size();
+            telemetry::refresh_stat(self);
+            self.check_slow_op("merge", start, 0, 0, None);
+        }
+        res
+    }
+
+    /// 以离线merge模式打开已存在的数据库，用于在其他进程没有使用数据库时merge数据库目录
+    ///
+    /// 数据库正在被其他进程使用时返回DatabaseIsInUse，merge期间持有文件锁，其他进程无法打开数据库。
+    /// 不自动merge，也不启动后台持久化和索引检查点线程
+    pub fn open_for_merge(opts: Options) -> Result<Self> {
+        if !opts.dir_path.is_dir() {
+            return Err(Error::DatabaseNotFound {
+                path: opts.dir_path,
+            });
+        }
+        Self::open(Options {
+            create_if_missing: false,
+            error_if_exists: false,
+            auto_merge: false,
+            sync_interval: None,
+            index_checkpoint: false,
+            index_checkpoint_interval: None,
+            ..opts
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(files, merge_start_id))
+    )]
+    fn merge_files(&self) -> Result<()> {
+        self.check_closed()?;
+        self.check_merge_paused()?;
+        // 同一时刻只允许一个merge
+        let _merging_guard = match self.merging_lock.try_lock() {
+            Some(guard) => guard,
+            None => return Err(Error::MergeInProgress),
+        };
+
+        // 获取需要merge的数据文件
+        let (merge_file_ids, merge_start_id) = self.rotate_merge_files()?;
+        if merge_file_ids.is_empty() {
+            return Ok(());
+        }
+        telemetry::record_field("files", merge_file_ids.len() as u64);
+        // merge会替换旧的数据文件，索引检查点不再可用
+        if !self.is_in_memory() {
+            remove_checkpoint(&self.options.dir_path)?;
+        }
+        telemetry::record_field("merge_start_id", merge_start_id as u64);
+        self.track_merge(merge_file_ids.len(), || {
+            self.merge_rotated_files(&merge_file_ids, merge_start_id)
+        })
+    }
+
+    /// 重写需要merge的数据文件中的有效数据，并替换旧的数据文件
+    fn merge_rotated_files(&self, merge_file_ids: &[u32], merge_start_id: u32) -> Result<()> {
+        let dir_path = self.options.dir_path.clone();
+        let merge_path = get_merge_path(&dir_path);
+        let (merged_file_ids, reclaimed_size) =
+            self.write_merge_dir(merge_file_ids, merge_start_id)?;
+        self.merge_control.set_reclaimed(reclaimed_size as u64);
+
+        // 将merge后的数据文件移动到各个数据目录
+        for file_id in merged_file_ids.iter() {
+            let src = get_data_file_full_path(&merge_path, *file_id);
+            let dst = get_data_file_full_path(self.data_file_dir(*file_id), *file_id);
+            if self.is_in_memory() {
+                mem_io::rename(src, dst)?;
+            } else {
+                move_file(src, dst)?;
+            }
+        }
+        // 旧的数据文件删除前替换hint文件，保证hint文件始终与数据文件一致
+        if !self.is_in_memory() {
+            move_file(
+                merge_path.join(HINT_FILE_NAME),
+                dir_path.join(HINT_FILE_NAME),
+            )?;
+        }
+
+        {
+            let mut older_files = self.older_files.write();
+            // 使用数据库目录中的文件替换临时目录中的文件
+            for file_id in merged_file_ids.iter() {
+                older_files.insert(
+                    *file_id,
+                    self.open_data_file(self.data_file_dir(*file_id), *file_id)?,
+                );
+            }
+            // 按ID从小到大删除旧的数据文件，保证中途崩溃时不会因为丢失较新的删除记录而导致数据复活
+            for file_id in merge_file_ids.iter() {
+                // 仍有快照在使用时保留旧的数据文件，快照全部释放后再删除
+                if let Some(data_file) = older_files.remove(file_id) {
+                    // 旧的数据文件不再被读取，从page cache中清除，快照仍在使用时按需重新读取
+                    data_file.advise(Advice::DontNeed);
+                    if self.retire_data_file(*file_id, data_file) {
+                        continue;
+                    }
+                }
+                let file_path = get_data_file_full_path(self.data_file_dir(*file_id), *file_id);
+                if self.is_in_memory() {
+                    mem_io::remove_file(file_path);
+                } else if let Err(source) = std::fs::remove_file(&file_path) {
+                    return Err(Error::FailedToRemoveDataFile {
+                        path: file_path,
+                        source,
+                    });
+                }
+            }
+        }
+
+        // 被清理的数据文件中的数据不会再被读取
+        if let Some(value_cache) = &self.value_cache {
+            value_cache.lock().remove_files(merge_file_ids);
+        }
+        {
+            let mut file_counters = self.file_counters.lock();
+            for file_id in merge_file_ids.iter() {
+                file_counters.remove(*file_id);
+            }
+        }
+        // 旧的数据文件中的无效数据已被清理
+        let _ = self
+            .reclaim_size
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |size| {
+                Some(size.saturating_sub(reclaimed_size))
+            });
+        // 布隆过滤器中清理已删除和已过期的key
+        {
+            let _rotate_guard = self.rotate_lock.write();
+            self.rebuild_bloom_filter()?;
+        }
+        // merge后的数据文件中不再保留事务编号
+        self.save_seq_num(&dir_path)?;
+
+        self.remove_merge_dir()
+    }
+
+    /// 在临时目录中重写有效数据并写入merge完成标记，返回写入的文件ID，以及被清理的无效数据大小
+    ///
+    /// 写入完成标记前崩溃时，打开数据库会删除临时目录；写入之后崩溃时，打开数据库会完成merge
+    fn write_merge_dir(
+        &self,
+        merge_file_ids: &[u32],
+        merge_start_id: u32,
+    ) -> Result<(Vec<u32>, usize)> {
+        let merge_path = get_merge_path(&self.options.dir_path);
+        self.remove_merge_dir()?;
+        if !self.is_in_memory() {
+            if let Err(source) = std::fs::create_dir_all(&merge_path) {
+                return Err(Error::FailedToCreateMergeDir {
+                    path: merge_path,
+                    source,
+                });
+            }
+        }
+        let res = self.rewrite_valid_records(merge_file_ids, merge_start_id)?;
+        // 小于merge_start_id的数据文件都已被merge
+        if !self.is_in_memory() {
+            write_merge_finished(&merge_path, merge_start_id)?;
+        }
+        Ok(res)
+    }
+
+    /// 开启自动merge时，无效数据占磁盘空间的比例达到阈值后，在后台线程中merge
+    pub(crate) fn try_auto_merge(&self) {
+        if !self.options.auto_merge {
+            return;
+        }
+        let reclaim_size = self.reclaim_size.load(Ordering::SeqCst);
+        let disk_size = match self.disk_size() {
+            Ok(size) => size,
+            Err(e) => {
+                warn!("failed to get disk size for auto merge: {}", e);
+                return;
+            }
+        };
+        if disk_size == 0 || (reclaim_size as f32) < disk_size as f32 * self.options.merge_ratio {
+            return;
+        }
+        self.spawn_background_merge();
+    }
+
+    /// 在后台线程中merge，正在merge或者merge被暂停时直接返回
+    pub(crate) fn spawn_background_merge(&self) {
+        if self.merging_lock.is_locked() || self.merge_control.is_paused() {
+            return;
+        }
+        let mut merge_worker = self.merge_worker.lock();
+        if merge_worker
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+        {
+            return;
+        }
+        let engine = self.background_handle();
+        *merge_worker = Some(std::thread::spawn(move || {
+            if let Err(e) = engine.merge() {
+                warn!("background merge failed: {}", e);
+            }
+        }));
+    }
+
+    /// 将当前活跃文件转换为旧的数据文件，返回需要merge的文件ID，以及merge后数据文件的起始ID
+    ///
+    /// merge后的数据文件数量通常不会超过被merge的文件数量，因此为其预留同样数量的ID，
+    /// 新的活跃文件ID排在预留的ID之后，保证启动时仍按写入顺序加载数据。
+    /// 重新编码后的数据变大导致预留的ID不够用时，merge失败并被撤销
+    fn rotate_merge_files(&self) -> Result<(Vec<u32>, u32)> {
+        // 等待正在进行的写操作更新完内存索引
+        let _rotate_guard = self.rotate_lock.write();
+        let mut active_file = self.active_file.write();
+        let mut older_files = self.older_files.write();
+        // 没有任何数据
+        if older_files.is_empty() && active_file.get_write_offset() == DATA_FILE_HEADER_SIZE {
+            return Ok((vec![], 0));
+        }
+
+        active_file.sync()?;
+        let active_file_id = active_file.get_file_id();
+        older_files.insert(
+            active_file_id,
+            self.open_data_file(self.data_file_dir(active_file_id), active_file_id)?,
+        );
+
+        let mut merge_file_ids = older_files.keys().copied().collect::<Vec<_>>();
+        merge_file_ids.sort();
+
+        let merge_start_id = active_file_id + 1;
+        let new_file_id = merge_start_id + merge_file_ids.len() as u32;
+        *active_file = self.open_active_file(self.data_file_dir(new_file_id), new_file_id)?;
+        Ok((merge_file_ids, merge_start_id))
+    }
+
+    /// 将需要merge的文件中的有效数据写入临时目录，并写入hint文件、更新内存索引，
+    /// 返回写入的文件ID，以及被清理的无效数据大小。失败时撤销已迁移的索引并删除临时目录
+    fn rewrite_valid_records(
+        &self,
+        merge_file_ids: &[u32],
+        start_id: u32,
+    ) -> Result<(Vec<u32>, usize)> {
+        let mut merged_file_ids = vec![start_id];
+        let mut relocated = Vec::new();
+        match self.rewrite_records(
+            merge_file_ids,
+            start_id,
+            &mut merged_file_ids,
+            &mut relocated,
+        ) {
+            Ok(reclaimed_size) => Ok((merged_file_ids, reclaimed_size)),
+            Err(e) => {
+                self.abort_merge(&merged_file_ids, relocated);
+                Err(e)
+            }
+        }
+    }
+
+    /// 重写有效数据，记录写入的merge文件ID和迁移的key
+    fn rewrite_records(
+        &self,
+        merge_file_ids: &[u32],
+        start_id: u32,
+        merged_file_ids: &mut Vec<u32>,
+        relocated: &mut Vec<(Vec<u8>, LogRecordPos, LogRecordPos)>,
+    ) -> Result<usize> {
+        let merge_path = get_merge_path(&self.options.dir_path);
+        // 内存数据库启动时不加载数据，不需要hint文件
+        let hint_file = match self.is_in_memory() {
+            true => None,
+            false => Some(DataFile::new_hint_file(&merge_path)?),
+        };
+
+        // 预留的文件ID之后是新的活跃文件
+        let end_id = start_id + merge_file_ids.len() as u32;
+        let mut reclaimed_size = 0;
+        // 仍然有效的分块的大小
+        let mut live_chunk_size = 0;
+        let mut merge_file = self.open_merge_file(&merge_path, start_id)?;
+        for file_id in merge_file_ids.iter() {
+            let data_file = self.open_data_file(self.data_file_dir(*file_id), *file_id)?;
+            data_file.advise(Advice::Sequential);
+            let mut reader = data_file.reader(DATA_FILE_HEADER_SIZE);
+            loop {
+                // 暂停时在记录之间等待恢复
+                self.wait_merge_resumed()?;
+                let offset = reader.offset();
+                let (mut log_record, size) = match reader.next_record() {
+                    Ok(rc) => (rc.record, rc.size),
+                    Err(Error::ReadDataFileEOF) => break,
+                    Err(e) => return Err(e),
+                };
+                self.merge_control.add_processed(size as u64);
+                self.throttle_merge(size);
+                let pos = LogRecordPos {
+                    file_id: *file_id,
+                    offset,
+                    size: size as u32,
+                };
+
+                // 分块随记录分块位置的记录一起迁移，先计入无效数据
+                if !matches!(
+                    log_record.record_type,
+                    LogRecordType::NORMAL | LogRecordType::CHUNKED | LogRecordType::OPERAND
+                ) {
+                    reclaimed_size += size;
+                    continue;
+                }
+                // 只保留内存索引中仍然指向该位置的数据
+                let (key, _) = parse_log_record_key(&log_record.key)?;
+                if self.index.get(key.clone()) != Some(pos) {
+                    reclaimed_size += size;
+                    continue;
+                }
+                // 清理已过期的数据，与被覆盖的数据一样计入无效数据
+                if log_record.is_expired() {
+                    if self.index.delete_if(key.clone(), pos) {
+                        self.add_reclaim_size(&pos);
+                        self.update_secondary_indexes(&[(&key, None)]);
+                    }
+                    reclaimed_size += size;
+                    continue;
+                }
+
+                // 合并操作数，写入完整的value，链上其余的记录已计入无效数据
+                if log_record.record_type == LogRecordType::OPERAND {
+                    let active_file = self.active_file.read();
+                    let older_files = self.older_files.read();
+                    log_record = self.resolve_operands(&active_file, &older_files, log_record)?;
+                }
+                // 分块存储的数据先迁移各个分块，再写入指向新位置的记录
+                let chunks = match log_record.record_type {
+                    LogRecordType::CHUNKED => {
+                        live_chunk_size += decode_chunk_positions(&log_record.value)?
+                            .iter()
+                            .map(|pos| pos.size as usize)
+                            .sum::<usize>();
+                        let active_file = self.active_file.read();
+                        let older_files = self.older_files.read();
+                        read_chunks(&active_file, &older_files, &log_record)?
+                    }
+                    _ => vec![],
+                };
+                // 已提交的事务数据不再需要事务编号
+                log_record.key = log_record_key_with_seq_num(&key, NON_TRANSACTION_SEQ_NUM);
+                if log_record.record_type == LogRecordType::CHUNKED {
+                    let positions = chunks
+                        .into_iter()
+                        .map(|mut chunk| {
+                            chunk.key = log_record.key.clone();
+                            let encoded_chunk = self.encode_log_record(&chunk)?;
+                            self.write_merge_record(
+                                &merge_path,
+                                &mut merge_file,
+                                merged_file_ids,
+                                end_id,
+                                &encoded_chunk,
+                            )
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    log_record.value = encode_chunk_positions(&positions);
+                }
+                let encoded_record = self.encode_log_record(&log_record)?;
+                let new_pos = self.write_merge_record(
+                    &merge_path,
+                    &mut merge_file,
+                    merged_file_ids,
+                    end_id,
+                    &encoded_record,
+                )?;
+                if let Some(hint_file) = &hint_file {
+                    hint_file.write_hint_record(key.clone(), new_pos)?;
+                }
+                // 期间key可能被重新写入或删除，此时不更新索引，
+                // 旧数据已计入无效数据，迁移后的数据同样是无效数据
+                if self.index.relocate(key.clone(), pos, new_pos) {
+                    relocated.push((key, pos, new_pos));
+                }
+            }
+            self.merge_control.finish_file();
+            self.merge_control
+                .set_reclaimed(reclaimed_size.saturating_sub(live_chunk_size) as u64);
+        }
+        merge_file.sync()?;
+        if let Some(hint_file) = &hint_file {
+            hint_file.sync()?;
+        }
+        Ok(reclaimed_size.saturating_sub(live_chunk_size))
+    }
+
+    /// 撤销失败的merge：仍指向merge文件的key恢复为原来的位置，再删除merge文件和临时目录
+    fn abort_merge(
+        &self,
+        merged_file_ids: &[u32],
+        relocated: Vec<(Vec<u8>, LogRecordPos, LogRecordPos)>,
+    ) {
+        for (key, old_pos, new_pos) in relocated {
+            self.index.relocate(key, new_pos, old_pos);
+        }
+        {
+            let mut older_files = self.older_files.write();
+            for file_id in merged_file_ids.iter() {
+                older_files.remove(file_id);
+            }
+        }
+        if let Some(value_cache) = &self.value_cache {
+            value_cache.lock().remove_files(merged_file_ids);
+        }
+        if let Err(e) = self.remove_merge_dir() {
+            warn!("failed to remove merge directory: {}", e);
+        }
+    }
+
+    /// 写入merge文件，当前merge文件写不下时切换新的merge文件，文件ID不能达到end_id
+    fn write_merge_record(
+        &self,
+        merge_path: &Path,
+        merge_file: &mut DataFile,
+        merged_file_ids: &mut Vec<u32>,
+        end_id: u32,
+        encoded_record: &[u8],
+    ) -> Result<LogRecordPos> {
+        self.throttle_write(encoded_record.len());
+        self.throttle_merge(encoded_record.len());
+        if merge_file.get_write_offset() > DATA_FILE_HEADER_SIZE
+            && merge_file.get_write_offset() + encoded_record.len() as u64
+                > self.options.data_file_size
+        {
+            merge_file.sync()?;
+            let next_file_id = merge_file.get_file_id() + 1;
+            // 重新编码后的数据比原来的大，预留的ID用完后继续写入会覆盖新的活跃文件
+            if next_file_id >= end_id {
+                return Err(Error::MergeOutputTooLarge);
+            }
+            *merge_file = self.open_merge_file(merge_path, next_file_id)?;
+            merged_file_ids.push(next_file_id);
+        }
+        let pos = LogRecordPos {
+            file_id: merge_file.get_file_id(),
+            offset: merge_file.get_write_offset(),
+            size: encoded_record.len() as u32,
+        };
+        merge_file.write(encoded_record)?;
+        Ok(pos)
+    }
+
+    /// 从hint文件中加载merge后数据文件的索引，返回hint文件覆盖的最大数据文件ID，
+    /// 不大于该ID的数据文件无需再加载
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub(crate) fn load_index_from_hint_file(
+        &self,
+        progress: &mut OpenProgressTracker,
+    ) -> Result<Option<u32>> {
+        let hint_file_path = self.options.dir_path.join(HINT_FILE_NAME);
+        if !hint_file_path.is_file() {
+            return Ok(None);
+        }
+
+        let hint_file = DataFile::new_hint_file(&self.options.dir_path)?;
+        // merge后的数据文件可能已被compact_files压缩，其中的有效数据在之后的数据文件中
+        let file_ids = self
+            .older_files
+            .read()
+            .keys()
+            .copied()
+            .chain(std::iter::once(self.active_file.read().get_file_id()))
+            .collect::<HashSet<_>>();
+        let mut merged_file_id = None;
+        let mut reader = hint_file.reader(0);
+        loop {
+            let log_record = match reader.next_record() {
+                Ok(rc) => {
+                    progress.add_record(rc.size as u64)?;
+                    rc.record
+                }
+                Err(Error::ReadDataFileEOF) => break,
+                Err(e) => return Err(e),
+            };
+            let Ok(pos) = decode_log_record_pos(log_record.value) else {
+                // 丢弃已加载的索引，从全部数据文件中加载
+                warn!("ignore invalid hint file {:?}", hint_file_path);
+                self.index.clear()?;
+                return Ok(None);
+            };
+            if merged_file_id.is_none_or(|id| pos.file_id > id) {
+                merged_file_id = Some(pos.file_id);
+            }
+            if file_ids.contains(&pos.file_id) {
+                self.index.put(log_record.key, pos);
+            }
+        }
+        Ok(merged_file_id)
+    }
+
+    /// 删除merge临时目录
+    fn remove_merge_dir(&self) -> Result<()> {
+        match self.is_in_memory() {
+            true => {
+                mem_io::remove_dir(get_merge_path(&self.options.dir_path));
+                Ok(())
+            }
+            false => remove_merge_dir(&self.options.dir_path),
+        }
+    }
+
+    /// 在临时目录中创建merge文件，同时将其加入旧数据文件，使迁移后的数据可以被读取
+    fn open_merge_file(&self, merge_path: &Path, file_id: u32) -> Result<DataFile> {
+        let reader = self.open_data_file(merge_path, file_id)?;
+        self.older_files.write().insert(file_id, reader);
+        self.open_data_file(merge_path, file_id)
+    }
+}
+
+/// merge临时目录，与数据库目录位于同一父目录下
+fn get_merge_path(dir_path: impl AsRef<Path>) -> PathBuf {
+    let dir_path = dir_path.as_ref();
+    let file_name = dir_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let parent = dir_path.parent().unwrap_or(dir_path);
+    parent.join(format!("{}{}", file_name, MERGE_DIR_SUFFIX))
+}
+
+/// 打开数据库时调用，merge临时目录中有完成标记时完成上次中途崩溃的merge，否则删除临时目录
+pub(crate) fn finish_merge(dir_path: &Path, data_dirs: &[PathBuf]) -> Result<()> {
+    let merge_path = get_merge_path(dir_path);
+    let marker_path = merge_path.join(MERGE_FINISHED_FILE_NAME);
+    let buf = match std::fs::read_to_string(&marker_path) {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return remove_merge_dir(dir_path),
+        Err(source) => {
+            return Err(Error::FailedToReadMergeMarker {
+                path: marker_path,
+                source,
+            })
+        }
+    };
+    let Ok(merge_start_id) = buf.trim().parse::<u32>() else {
+        return Err(Error::InvalidMergeMarker { path: marker_path });
+    };
+
+    // 与merge相同，先移动merge后的数据文件和hint文件，再按ID从小到大删除旧的数据文件
+    let mut merged_file_ids = read_dir(&merge_path)?
+        .iter()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            file_name
+                .strip_suffix(DATA_FILE_SUFFIX)?
+                .parse::<u32>()
+                .ok()
+        })
+        .collect::<Vec<_>>();
+    merged_file_ids.sort();
+    for file_id in merged_file_ids {
+        move_file(
+            get_data_file_full_path(&merge_path, file_id),
+            get_data_file_full_path(find_data_file_dir(data_dirs, file_id), file_id),
+        )?;
+    }
+    let hint_path = merge_path.join(HINT_FILE_NAME);
+    if hint_path.is_file() {
+        move_file(hint_path, dir_path.join(HINT_FILE_NAME))?;
+    }
+    for (file_id, data_dir) in find_data_files(data_dirs)?
+        .into_iter()
+        .filter(|(file_id, _)| *file_id < merge_start_id)
+    {
+        let file_path = get_data_file_full_path(data_dir, file_id);
+        if let Err(source) = std::fs::remove_file(&file_path) {
+            return Err(Error::FailedToRemoveDataFile {
+                path: file_path,
+                source,
+            });
+        }
+    }
+    remove_checkpoint(dir_path)?;
+    remove_merge_dir(dir_path)
+}
+
+/// 持久化merge完成标记，先写临时文件再重命名，保证文件内容完整
+fn write_merge_finished(merge_path: &Path, merge_start_id: u32) -> Result<()> {
+    let path = merge_path.join(MERGE_FINISHED_FILE_NAME);
+    let tmp_path = path.with_extension("tmp");
+    let write = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(merge_start_id.to_string().as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, &path)?;
+        fio::sync_dir(merge_path)
+    };
+    write().map_err(|source| Error::FailedToWriteMergeMarker { path, source })
+}
+
+/// 删除merge临时目录
+pub(crate) fn remove_merge_dir(dir_path: impl AsRef<Path>) -> Result<()> {
+    let merge_path = get_merge_path(dir_path);
+    if !merge_path.exists() {
+        return Ok(());
+    }
+    std::fs::remove_dir_all(&merge_path).map_err(|source| Error::FailedToRemoveMergeDir {
+        path: merge_path,
+        source,
+    })
+}
+
+/// 将merge临时目录中的文件移动到数据目录，数据目录位于其他磁盘时拷贝后删除原文件
+fn move_file(from: PathBuf, to: PathBuf) -> Result<()> {
+    let res = std::fs::rename(&from, &to).or_else(|e| match e.kind() {
+        std::io::ErrorKind::CrossesDevices => std::fs::copy(&from, &to)
+            .and_then(|_| File::open(&to)?.sync_all())
+            .and_then(|_| std::fs::remove_file(&from)),
+        _ => Err(e),
+    });
+    match res {
+        Ok(_) => Ok(()),
+        Err(source) => Err(Error::FailedToMoveMergeFile { from, to, source }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use crate::{
+        data::log_record::LogRecord,
+        options::{CompressionType, Options},
+        util::rand_kv::{get_test_key, get_test_value},
+    };
+
+    use super::*;
+
+    fn dir_size(dir_path: impl AsRef<Path>) -> u64 {
+        std::fs::read_dir(dir_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap().len())
+            .sum()
+    }
+
+    #[test]
+    fn test_merge_empty() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-empty");
+        opts.data_file_size = 32 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        let merge_res = engine.merge();
+        assert!(merge_res.is_ok());
+        assert!(!get_merge_path(&opts.dir_path).exists());
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_merge_valid_records() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-valid");
+        opts.data_file_size = 32 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..10000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        // 覆盖写一部分数据，删除一部分数据
+        for i in 0..5000 {
+            engine
+                .put(get_test_key(i), Bytes::from("new value"))
+                .unwrap();
+        }
+        for i in 5000..8000 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        let size_before_merge = dir_size(&opts.dir_path);
+
+        let merge_res = engine.merge();
+        assert!(merge_res.is_ok());
+        assert!(dir_size(&opts.dir_path) < size_before_merge);
+        assert!(!get_merge_path(&opts.dir_path).exists());
+
+        // merge后可以正常读写
+        let check = |engine: &Engine| {
+            assert_eq!(engine.list_keys().unwrap().len(), 7000);
+            for i in 0..5000 {
+                assert_eq!(
+                    engine.get(get_test_key(i)).unwrap(),
+                    Bytes::from("new value")
+                );
+            }
+            for i in 5000..8000 {
+                assert!(matches!(
+                    engine.get(get_test_key(i)).err().unwrap(),
+                    Error::KeyNotFound
+                ));
+            }
+            for i in 8000..10000 {
+                assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+            }
+        };
+        check(&engine);
+        engine
+            .put(get_test_key(10000), get_test_value(10000))
+            .unwrap();
+        assert_eq!(
+            engine.get(get_test_key(10000)).unwrap(),
+            get_test_value(10000)
+        );
+        engine.delete(get_test_key(10000)).unwrap();
+
+        // 重启后数据仍然有效
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        check(&engine);
+
+        // 再次merge
+        let merge_res = engine.merge();
+        assert!(merge_res.is_ok());
+        check(&engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_merge_all_deleted() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-all-deleted");
+        opts.data_file_size = 32 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..2000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..2000 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+
+        let merge_res = engine.merge();
+        assert!(merge_res.is_ok());
+        assert!(engine.list_keys().unwrap().is_empty());
+
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(engine.list_keys().unwrap().is_empty());
+        assert!(matches!(
+            engine.get(get_test_key(1)).err().unwrap(),
+            Error::KeyNotFound
+        ));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_merge_with_concurrent_writes() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-concurrent");
+        opts.data_file_size = 32 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..10000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..5000 {
+                    engine.delete(get_test_key(i)).unwrap();
+                }
+                for i in 10000..12000 {
+                    engine.put(get_test_key(i), get_test_value(i)).unwrap();
+                }
+            });
+            s.spawn(|| {
+                engine.merge().unwrap();
+            });
+        });
+
+        let check = |engine: &Engine| {
+            assert_eq!(engine.list_keys().unwrap().len(), 7000);
+            for i in 5000..12000 {
+                assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+            }
+        };
+        check(&engine);
+
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        check(&engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_merge_hint_file() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-hint");
+        opts.data_file_size = 32 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..5000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..1000 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        engine.merge().unwrap();
+        assert!(opts.dir_path.join(HINT_FILE_NAME).is_file());
+
+        // merge后继续写入的数据需要从数据文件中加载
+        for i in 1000..2000 {
+            engine
+                .put(get_test_key(i), Bytes::from("new value"))
+                .unwrap();
+        }
+        engine.delete(get_test_key(4999)).unwrap();
+
+        let check = |engine: &Engine| {
+            assert_eq!(engine.list_keys().unwrap().len(), 3999);
+            assert!(matches!(
+                engine.get(get_test_key(999)).err().unwrap(),
+                Error::KeyNotFound
+            ));
+            for i in 1000..2000 {
+                assert_eq!(
+                    engine.get(get_test_key(i)).unwrap(),
+                    Bytes::from("new value")
+                );
+            }
+            for i in 2000..4999 {
+                assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+            }
+            assert!(matches!(
+                engine.get(get_test_key(4999)).err().unwrap(),
+                Error::KeyNotFound
+            ));
+        };
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        check(&engine);
+
+        // hint文件中的位置信息无法解码时，从全部数据文件中加载
+        std::mem::drop(engine);
+        let invalid_hint = LogRecord {
+            key: get_test_key(2000).to_vec(),
+            value: vec![0xff],
+            record_type: LogRecordType::NORMAL,
+            timestamp: 0,
+            expire_at: 0,
+        };
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(opts.dir_path.join(HINT_FILE_NAME))
+            .unwrap()
+            .write_all(&invalid_hint.encode())
+            .unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        check(&engine);
+
+        // 没有hint文件时，从全部数据文件中加载
+        std::mem::drop(engine);
+        std::fs::remove_file(opts.dir_path.join(HINT_FILE_NAME)).unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        check(&engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_merge_expired_records() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-expired");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 32 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        for i in 0..1000 {
+            engine
+                .put_with_ttl(get_test_key(i), get_test_value(i), Duration::ZERO)
+                .unwrap();
+        }
+        engine.merge().unwrap();
+        // 过期的数据全部被清理
+        let status = engine.merge_status();
+        assert!(status.bytes_processed > 0);
+        assert_eq!(status.bytes_reclaimed, status.bytes_processed);
+        assert_eq!(engine.stat().unwrap().reclaimable_size, 0);
+        assert_eq!(engine.len(), 0);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_merge_output_too_large() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-output-too-large");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        opts.compression = CompressionType::Zstd;
+        let value = Bytes::from(vec![b'v'; 4000]);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..2000 {
+            engine.put(get_test_key(i), value.clone()).unwrap();
+        }
+        drop(engine);
+
+        // 不压缩时merge后的数据需要更多的数据文件，预留的ID不够用时merge失败并撤销
+        opts.compression = CompressionType::None;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.put(get_test_key(2000), value.clone()).unwrap();
+        let file_ids = engine.data_file_ids();
+        assert!(matches!(
+            engine.merge().err().unwrap(),
+            Error::MergeOutputTooLarge
+        ));
+        assert!(!get_merge_path(&opts.dir_path).exists());
+        // merge转换了活跃文件，之前的数据文件保持不变
+        assert_eq!(engine.data_file_ids()[..file_ids.len()], file_ids[..]);
+        engine.put(get_test_key(2001), value.clone()).unwrap();
+        for i in 0..2002 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), value);
+        }
+        drop(engine);
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 2002);
+        for i in 0..2002 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), value);
+        }
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_merge_crash_recovery() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-crash");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..3000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..1000 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        // 写入merge完成标记后、替换数据文件前崩溃
+        let (merge_file_ids, merge_start_id) = engine.rotate_merge_files().unwrap();
+        engine
+            .write_merge_dir(&merge_file_ids, merge_start_id)
+            .unwrap();
+        let merge_path = get_merge_path(&opts.dir_path);
+        assert!(merge_path.join(MERGE_FINISHED_FILE_NAME).is_file());
+
+        let copy_dir = |from: &Path, to: &Path| {
+            let _ = std::fs::remove_dir_all(to);
+            std::fs::create_dir_all(to).unwrap();
+            for entry in std::fs::read_dir(from).unwrap() {
+                let entry = entry.unwrap();
+                std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+            }
+        };
+        let mut crash_opts = opts.clone();
+        crash_opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-crashed");
+        let crash_merge_path = get_merge_path(&crash_opts.dir_path);
+        let check = |engine: &Engine| {
+            assert_eq!(engine.len(), 2000);
+            assert!(engine.get(get_test_key(0)).is_err());
+            for i in 1000..3000 {
+                assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+            }
+        };
+
+        // 有完成标记时打开数据库完成merge，删除旧的数据文件
+        copy_dir(&opts.dir_path, &crash_opts.dir_path);
+        copy_dir(&merge_path, &crash_merge_path);
+        let crashed = Engine::open(crash_opts.clone()).expect("failed to open engine");
+        check(&crashed);
+        assert!(!crash_merge_path.exists());
+        assert!(crashed.data_file_ids()[0] >= merge_start_id);
+        assert!(crash_opts.dir_path.join(HINT_FILE_NAME).is_file());
+        drop(crashed);
+        let crashed = Engine::open(crash_opts.clone()).expect("failed to open engine");
+        check(&crashed);
+        drop(crashed);
+
+        // 没有完成标记时删除merge临时目录，保留旧的数据文件
+        copy_dir(&opts.dir_path, &crash_opts.dir_path);
+        copy_dir(&merge_path, &crash_merge_path);
+        std::fs::remove_file(crash_merge_path.join(MERGE_FINISHED_FILE_NAME)).unwrap();
+        let crashed = Engine::open(crash_opts.clone()).expect("failed to open engine");
+        check(&crashed);
+        assert!(!crash_merge_path.exists());
+        assert_eq!(crashed.data_file_ids()[0], merge_file_ids[0]);
+        drop(crashed);
+
+        drop(engine);
+        std::fs::remove_dir_all(crash_opts.dir_path).expect("failed to remove test dir");
+        let _ = std::fs::remove_dir_all(merge_path);
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_auto_merge() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-auto");
+        opts.data_file_size = 32 * 1024;
+        opts.auto_merge = true;
+        opts.merge_ratio = 0.5;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // 反复覆盖写同一批数据，产生大量无效数据
+        for _ in 0..20 {
+            for i in 0..500 {
+                engine.put(get_test_key(i), get_test_value(i)).unwrap();
+            }
+        }
+        // 等待后台merge完成
+        let merge_worker = engine.merge_worker.lock().take();
+        assert!(merge_worker.is_some());
+        merge_worker.unwrap().join().unwrap();
+
+        assert!(opts.dir_path.join(HINT_FILE_NAME).is_file());
+        assert_eq!(engine.stat().unwrap().key_num, 500);
+        for i in 0..500 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+
+        // 重启后数据仍然有效
+        std::mem::drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.list_keys().unwrap().len(), 500);
+        for i in 0..500 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_auto_merge_invalid_ratio() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-invalid-ratio");
+        opts.merge_ratio = 0.0;
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::InvalidMergeRatio
+        ));
+        opts.merge_ratio = 1.5;
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::InvalidMergeRatio
+        ));
+    }
+}
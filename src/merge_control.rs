@@ -0,0 +1,214 @@
+//! 查询merge进度，暂停和恢复merge
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::db::Engine;
+use crate::error::{Error, Result};
+
+/// merge的状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "http", derive(serde::Serialize))]
+pub struct MergeStatus {
+    /// 是否正在merge或者压缩数据文件
+    pub running: bool,
+    /// 是否已暂停
+    pub paused: bool,
+    /// 本次merge已处理的数据文件数量
+    pub files_processed: usize,
+    /// 本次merge需要处理的数据文件数量
+    pub total_files: usize,
+    /// 本次merge已读取的数据大小
+    pub bytes_processed: u64,
+    /// 最近一次merge清理的无效数据大小，正在merge时为目前已清理的大小
+    pub bytes_reclaimed: u64,
+}
+
+/// merge的状态，以及暂停时等待恢复的条件变量
+#[derive(Default)]
+pub(crate) struct MergeControl {
+    status: Mutex<MergeStatus>,
+    resumed: Condvar,
+}
+
+impl MergeControl {
+    /// 开始merge，需要处理total_files个数据文件
+    fn start(&self, total_files: usize) {
+        let mut status = self.status.lock();
+        *status = MergeStatus {
+            running: true,
+            paused: status.paused,
+            total_files,
+            ..Default::default()
+        };
+    }
+
+    /// 结束merge
+    fn finish(&self) {
+        self.status.lock().running = false;
+    }
+
+    /// 需要处理的数据文件增加了count个
+    pub(crate) fn add_total_files(&self, count: usize) {
+        self.status.lock().total_files += count;
+    }
+
+    /// 读取了大小为size的数据
+    pub(crate) fn add_processed(&self, size: u64) {
+        self.status.lock().bytes_processed += size;
+    }
+
+    /// 处理完一个数据文件
+    pub(crate) fn finish_file(&self) {
+        self.status.lock().files_processed += 1;
+    }
+
+    /// 更新目前已清理的无效数据大小
+    pub(crate) fn set_reclaimed(&self, size: u64) {
+        self.status.lock().bytes_reclaimed = size;
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.status.lock().paused
+    }
+
+    /// 唤醒等待恢复的merge，关闭数据库时使用
+    pub(crate) fn wake(&self) {
+        let _status = self.status.lock();
+        self.resumed.notify_all();
+    }
+}
+
+impl Engine {
+    /// 获取merge的状态
+    pub fn merge_status(&self) -> MergeStatus {
+        *self.merge_control.status.lock()
+    }
+
+    /// 暂停merge，正在进行的merge和压缩在处理完当前记录后等待恢复，之后不再自动merge，
+    /// 手动调用merge和compact_files返回MergePaused
+    ///
+    /// 暂停期间merge仍然持有merge锁，快照、备份和清空数据库等操作会等待恢复
+    pub fn pause_merge(&self) {
+        self.merge_control.status.lock().paused = true;
+    }
+
+    /// 恢复merge
+    pub fn resume_merge(&self) {
+        let mut status = self.merge_control.status.lock();
+        status.paused = false;
+        self.merge_control.resumed.notify_all();
+    }
+
+    /// 执行merge或者压缩，期间记录merge状态
+    pub(crate) fn track_merge<T>(
+        &self,
+        total_files: usize,
+        f: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        self.merge_control.start(total_files);
+        let res = f();
+        self.merge_control.finish();
+        res
+    }
+
+    /// 开始merge前检查是否已暂停
+    pub(crate) fn check_merge_paused(&self) -> Result<()> {
+        match self.merge_control.is_paused() {
+            true => Err(Error::MergePaused),
+            false => Ok(()),
+        }
+    }
+
+    /// 暂停时等待恢复，期间关闭数据库返回DatabaseClosed
+    pub(crate) fn wait_merge_resumed(&self) -> Result<()> {
+        let mut status = self.merge_control.status.lock();
+        while status.paused && self.check_closed().is_ok() {
+            self.merge_control.resumed.wait(&mut status);
+        }
+        drop(status);
+        self.check_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::options::Options;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_engine_pause_merge() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-control");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Arc::new(Engine::open(opts.clone()).expect("failed to open engine"));
+        for i in 0..2000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..1000 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        assert_eq!(engine.merge_status(), MergeStatus::default());
+
+        // 暂停时不能手动merge
+        engine.pause_merge();
+        assert!(engine.merge_status().paused);
+        assert!(matches!(engine.merge(), Err(Error::MergePaused)));
+        assert!(matches!(
+            engine.compact_files(&engine.data_file_ids()[..1]),
+            Err(Error::MergePaused)
+        ));
+        engine.resume_merge();
+
+        // 正在进行的merge在暂停后等待恢复，先阻塞merge转换活跃文件
+        let rotate_guard = engine.rotate_lock.write();
+        let handle = {
+            let engine = engine.clone();
+            std::thread::spawn(move || engine.merge())
+        };
+        while !engine.merging_lock.is_locked() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        engine.pause_merge();
+        drop(rotate_guard);
+        std::thread::sleep(Duration::from_millis(100));
+        let status = engine.merge_status();
+        assert!(status.running && status.paused);
+        assert!(status.total_files > 0);
+        assert_eq!(status.files_processed, 0);
+        assert!(!handle.is_finished());
+        engine.resume_merge();
+        handle.join().unwrap().unwrap();
+        let status = engine.merge_status();
+        assert!(!status.running && !status.paused);
+        assert_eq!(status.files_processed, status.total_files);
+        assert!(status.bytes_processed > 0);
+        assert!(status.bytes_reclaimed > 0);
+        assert_eq!(engine.len(), 1000);
+
+        // 关闭数据库时唤醒暂停中的merge
+        for i in 0..1000 {
+            engine.delete(get_test_key(i + 1000)).unwrap();
+        }
+        let rotate_guard = engine.rotate_lock.write();
+        let handle = {
+            let engine = engine.clone();
+            std::thread::spawn(move || engine.merge())
+        };
+        while !engine.merging_lock.is_locked() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        engine.pause_merge();
+        drop(rotate_guard);
+        engine.close().unwrap();
+        assert!(matches!(handle.join().unwrap(), Err(Error::DatabaseClosed)));
+
+        std::fs::remove_dir_all(&opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use prost::{decode_length_delimiter, encode_length_delimiter};
+
+use crate::batch::{log_record_key_with_seq_num, parse_log_record_key, NON_TRANSACTION_SEQ_NUM};
+use crate::chunk::read_chunked_record;
+use crate::data::data_file::DataFile;
+use crate::data::log_record::{now_millis, LogRecord, LogRecordPos, LogRecordType};
+use crate::db::{read_log_record_from_files, Engine};
+use crate::error::{Error, Result};
+
+/// 一个key上连续的操作数超过该数量后，写入合并后的完整value，避免读取时遍历过长的链
+const MAX_SUCCESSIVE_OPERANDS: usize = 64;
+
+/// 将操作数合并到已有的value上，参数为(key, 已有的value, 操作数)，key不存在时已有的value为None
+///
+/// 合并时持有数据文件的读锁，不能在其中访问数据库
+pub type MergeOperator = dyn Fn(&[u8], Option<&[u8]>, &[u8]) -> Vec<u8> + Send + Sync;
+
+impl Engine {
+    /// 设置合并操作数的merge operator，替换已有的merge operator
+    ///
+    /// merge operator不会持久化，使用过merge_value的数据库每次打开后需要先设置，
+    /// 否则读取和merge这些key时返回MergeOperatorNotSet
+    pub fn set_merge_operator<F>(&self, merge_operator: F)
+    where
+        F: Fn(&[u8], Option<&[u8]>, &[u8]) -> Vec<u8> + Send + Sync + 'static,
+    {
+        *self.merge_operator.write() = Some(Arc::new(merge_operator));
+        // 缓存中合并后的value可能与新的merge operator不一致
+        if let Some(value_cache) = &self.value_cache {
+            value_cache.lock().clear();
+        }
+    }
+
+    /// 向key追加一个操作数，不读取已有的value，读取和merge时再由merge operator合并
+    ///
+    /// 适合计数器、集合等需要读-改-写的场景，并发调用时不会丢失操作数。
+    /// 合并的结果没有过期时间，已有的value过期后视为不存在
+    pub fn merge_value(&self, key: Bytes, operand: Bytes) -> Result<()> {
+        self.check_closed()?;
+        if key.is_empty() {
+            return Err(Error::KeyIsEmpty);
+        }
+        self.check_kv_size(&key, operand.len())?;
+        let merge_operator = self.get_merge_operator()?;
+        self.evict_if_needed()?;
+        // 持有写锁，读取链头到更新索引期间key不会被其他写操作修改
+        let _rotate_guard = self.rotate_lock.write();
+        self.check_db_size()?;
+        self.check_index_memory(&key)?;
+        let mut retried = false;
+        let (prev, count) = loop {
+            let Some(pos) = self.index.get(key.to_vec()) else {
+                break (None, 0);
+            };
+            let head = {
+                let active_file = self.active_file.read();
+                let older_files = self.older_files.read();
+                read_log_record_from_files(&active_file, &older_files, &pos)
+            };
+            match head {
+                Ok(head) if head.record_type == LogRecordType::OPERAND => {
+                    break (Some(pos), decode_operand(&head.value)?.1)
+                }
+                Ok(head) if head.is_expired() => break (None, 0),
+                Ok(_) => break (Some(pos), 0),
+                // 数据文件可能刚被merge清理，重新查询索引后重试一次
+                Err(Error::DataFileNotFound) if !retried => retried = true,
+                Err(e) => return Err(e),
+            }
+        };
+
+        let mut log_record = LogRecord {
+            key: log_record_key_with_seq_num(&key, NON_TRANSACTION_SEQ_NUM),
+            value: encode_operand(prev, count + 1, &operand),
+            record_type: LogRecordType::OPERAND,
+            timestamp: now_millis(),
+            expire_at: 0,
+        };
+        // 链过长时直接写入完整的value；merge期间链上的旧数据文件会被删除，也写入完整的value
+        let resolved =
+            prev.is_some() && (count >= MAX_SUCCESSIVE_OPERANDS || self.merging_lock.is_locked());
+        let value = match resolved || self.has_subscribers() {
+            true => {
+                let current = match self.get_log_record(&key) {
+                    Ok(current) => Some(current.value),
+                    Err(Error::KeyNotFound) => None,
+                    Err(e) => return Err(e),
+                };
+                Some(merge_operator(&key, current.as_deref(), &operand))
+            }
+            false => None,
+        };
+        if resolved {
+            let value = value.clone().unwrap();
+            self.check_kv_size(&key, value.len())?;
+            log_record.value = value;
+            log_record.record_type = LogRecordType::NORMAL;
+        }
+        self.add_to_bloom_filter(&key);
+        let pos = self.append_log_record(&log_record)?;
+
+        // 写入完整的value时整条链都成为无效数据，这里只计入链头
+        let old_pos = self.index.put(key.to_vec(), pos);
+        if let Some(old_pos) = old_pos.filter(|old_pos| resolved || Some(*old_pos) != prev) {
+            self.add_reclaim_size(&old_pos);
+        }
+        self.after_commit(&[(&key, Some(value.as_deref().unwrap_or_default()))]);
+        Ok(())
+    }
+
+    /// 沿着操作数记录中上一条记录的位置读取整条链，合并为完整的value
+    pub(crate) fn resolve_operands(
+        &self,
+        active_file: &DataFile,
+        older_files: &HashMap<u32, DataFile>,
+        log_record: LogRecord,
+    ) -> Result<LogRecord> {
+        let merge_operator = self.get_merge_operator()?;
+        let mut operands = Vec::new();
+        let mut record = log_record.clone();
+        let base = loop {
+            let (prev, _, operand) = decode_operand(&record.value)?;
+            operands.push(operand.to_vec());
+            let Some(prev) = prev else {
+                break None;
+            };
+            // 更早的数据文件已超过数据保留期限被删除
+            if older_files
+                .keys()
+                .chain(std::iter::once(&active_file.get_file_id()))
+                .all(|file_id| prev.file_id < *file_id)
+            {
+                break None;
+            }
+            record = read_log_record_from_files(active_file, older_files, &prev)?;
+            match record.record_type {
+                LogRecordType::OPERAND => continue,
+                _ if record.is_expired() => break None,
+                LogRecordType::NORMAL => break Some(record.value),
+                LogRecordType::CHUNKED => {
+                    break Some(read_chunked_record(active_file, older_files, record)?.value)
+                }
+                _ => return Err(Error::InvalidLogRecord),
+            }
+        };
+        let (key, _) = parse_log_record_key(&log_record.key)?;
+        let value = operands.iter().rev().fold(base, |value, operand| {
+            Some(merge_operator(&key, value.as_deref(), operand))
+        });
+        Ok(LogRecord {
+            value: value.unwrap_or_default(),
+            record_type: LogRecordType::NORMAL,
+            ..log_record
+        })
+    }
+
+    fn get_merge_operator(&self) -> Result<Arc<MergeOperator>> {
+        self.merge_operator
+            .read()
+            .clone()
+            .ok_or(Error::MergeOperatorNotSet)
+    }
+
+    /// 是否有二级索引或订阅者需要写入后的value
+    fn has_subscribers(&self) -> bool {
+        self.has_secondary_indexes() || !self.watchers.lock().is_empty()
+    }
+}
+
+/// 编码操作数记录的value
+/// ```text
+///  +--------------------------------------------+
+///  | has_prev | prev      | count     | operand |
+///  +--------------------------------------------+
+///  | 1B       | var       | var(max:5)| var     |
+///  +--------------------------------------------+
+/// ```
+/// count为链上连续的操作数数量，包括这一个
+fn encode_operand(prev: Option<LogRecordPos>, count: usize, operand: &[u8]) -> Vec<u8> {
+    let mut buf = BytesMut::new();
+    buf.put_u8(prev.is_some() as u8);
+    if let Some(prev) = prev {
+        buf.put_slice(&prev.encode());
+    }
+    encode_length_delimiter(count, &mut buf).unwrap();
+    buf.put_slice(operand);
+    buf.to_vec()
+}
+
+/// 解码操作数记录的value，返回上一条记录的位置、连续的操作数数量以及操作数
+fn decode_operand(value: &[u8]) -> Result<(Option<LogRecordPos>, usize, &[u8])> {
+    let (has_prev, mut buf) = value.split_first().ok_or(Error::InvalidLogRecord)?;
+    let mut decode = || decode_length_delimiter(&mut buf).map_err(|_| Error::InvalidLogRecord);
+    let prev = match has_prev {
+        0 => None,
+        _ => Some(LogRecordPos {
+            file_id: decode()? as u32,
+            offset: decode()? as u64,
+            size: decode()? as u32,
+        }),
+    };
+    let count = decode()?;
+    Ok((prev, count, buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::cdc::{LogCursor, LogOp};
+    use crate::options::{IteratorOptions, Options};
+    use crate::util::rand_kv::get_test_key;
+
+    use super::*;
+
+    /// value为十进制整数，操作数为增量
+    fn add_operator(_key: &[u8], value: Option<&[u8]>, operand: &[u8]) -> Vec<u8> {
+        let parse = |buf: &[u8]| std::str::from_utf8(buf).unwrap().parse::<i64>().unwrap();
+        (value.map_or(0, parse) + parse(operand))
+            .to_string()
+            .into_bytes()
+    }
+
+    /// 操作数追加到value末尾，使用逗号分隔
+    fn append_operator(_key: &[u8], value: Option<&[u8]>, operand: &[u8]) -> Vec<u8> {
+        match value {
+            Some(value) => [value, b",", operand].concat(),
+            None => operand.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_merge_value() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-value");
+        opts.data_file_size = 4096;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(matches!(
+            engine.merge_value(get_test_key(0), Bytes::from("a")),
+            Err(Error::MergeOperatorNotSet)
+        ));
+        engine.set_merge_operator(append_operator);
+
+        // 合并到已有的value上，key不存在时从None开始合并
+        engine.put(get_test_key(0), Bytes::from("a")).unwrap();
+        for value in ["b", "c"] {
+            engine
+                .merge_value(get_test_key(0), Bytes::from(value))
+                .unwrap();
+            engine
+                .merge_value(get_test_key(1), Bytes::from(value))
+                .unwrap();
+        }
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), Bytes::from("a,b,c"));
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), Bytes::from("b,c"));
+        let values = engine
+            .multi_get(&[get_test_key(0), get_test_key(1)])
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(values, vec![Bytes::from("a,b,c"), Bytes::from("b,c")]);
+        let entries = engine
+            .scan(IteratorOptions::default())
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(entries[1], (get_test_key(1), Bytes::from("b,c")));
+
+        // 删除或覆盖后重新开始合并
+        engine.delete(get_test_key(1)).unwrap();
+        engine
+            .merge_value(get_test_key(1), Bytes::from("d"))
+            .unwrap();
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), Bytes::from("d"));
+
+        // 变更日志中返回合并后的value
+        let (entries, _) = engine.read_log_since(LogCursor::default(), 100).unwrap();
+        assert_eq!(
+            entries[2].ops,
+            vec![LogOp::Put {
+                key: get_test_key(1),
+                value: Bytes::from("b"),
+                expire_at: 0,
+            }]
+        );
+        assert_eq!(
+            entries[3].ops,
+            vec![LogOp::Put {
+                key: get_test_key(0),
+                value: Bytes::from("a,b,c"),
+                expire_at: 0,
+            }]
+        );
+
+        // 重启后需要重新设置merge operator
+        engine.close().unwrap();
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(matches!(
+            engine.get(get_test_key(0)),
+            Err(Error::MergeOperatorNotSet)
+        ));
+        engine.set_merge_operator(append_operator);
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), Bytes::from("a,b,c"));
+
+        // merge后操作数合并为完整的value
+        engine.merge().unwrap();
+        let head = engine.get_head_record(&get_test_key(0)).unwrap();
+        assert_eq!(head.record_type, LogRecordType::NORMAL);
+        engine
+            .merge_value(get_test_key(0), Bytes::from("d"))
+            .unwrap();
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), Bytes::from("a,b,c,d"));
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), Bytes::from("d"));
+
+        // 索引指向不存在的数据文件时只重试一次
+        let stale_pos = LogRecordPos {
+            file_id: u32::MAX,
+            offset: 0,
+            size: 0,
+        };
+        engine.index.put(get_test_key(2).to_vec(), stale_pos);
+        assert!(matches!(
+            engine.merge_value(get_test_key(2), Bytes::from("e")),
+            Err(Error::DataFileNotFound)
+        ));
+        drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_merge_value_concurrent() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-value-concurrent");
+        opts.data_file_size = 64 * 1024;
+        let engine = Arc::new(Engine::open(opts.clone()).expect("failed to open engine"));
+        engine.set_merge_operator(add_operator);
+        let key = Bytes::from("counter");
+
+        // 并发追加操作数，期间merge，所有的自增都不会丢失
+        let handles = (0..4)
+            .map(|_| {
+                let engine = engine.clone();
+                let key = key.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..200 {
+                        engine.merge_value(key.clone(), Bytes::from("1")).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..5 {
+            match engine.merge() {
+                Ok(_) | Err(Error::MergeInProgress) => {}
+                Err(e) => panic!("failed to merge: {}", e),
+            }
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(engine.get(key.clone()).unwrap(), Bytes::from("800"));
+        engine.merge().unwrap();
+        assert_eq!(engine.get(key).unwrap(), Bytes::from("800"));
+
+        std::fs::remove_dir_all(opts.dir_path.clone()).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::options::Options;
+
+/// 每读取多少条记录检查一次是否超时
+const TIMEOUT_CHECK_INTERVAL: usize = 4096;
+
+/// 打开数据库时加载索引的进度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpenProgress {
+    /// 已处理的数据文件数量，从hint文件中加载索引的数据文件同样计入
+    pub files_processed: usize,
+    /// 需要处理的数据文件数量
+    pub total_files: usize,
+    /// 已读取的数据文件和hint文件中记录的总大小
+    pub bytes_replayed: u64,
+    /// 已加载到索引中的key的数量
+    pub keys_loaded: usize,
+    /// 开始打开数据库后经过的时间
+    pub elapsed: Duration,
+}
+
+/// 报告打开进度的回调，每处理完一个文件在打开数据库的线程中调用
+#[derive(Clone)]
+pub struct OpenProgressCallback(Arc<dyn Fn(&OpenProgress) + Send + Sync>);
+
+impl OpenProgressCallback {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&OpenProgress) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for OpenProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OpenProgressCallback")
+    }
+}
+
+/// 统计打开进度，超过Options::open_timeout时返回OpenTimeout
+pub(crate) struct OpenProgressTracker {
+    start: Instant,
+    timeout: Option<Duration>,
+    callback: Option<OpenProgressCallback>,
+    progress: OpenProgress,
+    records: usize,
+}
+
+impl OpenProgressTracker {
+    pub(crate) fn new(opts: &Options) -> Self {
+        Self {
+            start: Instant::now(),
+            timeout: opts.open_timeout,
+            callback: opts.on_open_progress.clone(),
+            progress: OpenProgress::default(),
+            records: 0,
+        }
+    }
+
+    /// 不检查是否超时
+    pub(crate) fn without_timeout(mut self) -> Self {
+        self.timeout = None;
+        self
+    }
+
+    pub(crate) fn set_total_files(&mut self, total_files: usize) {
+        self.progress.total_files = total_files;
+    }
+
+    /// 读取了一条记录
+    pub(crate) fn add_record(&mut self, size: u64) -> Result<()> {
+        self.progress.bytes_replayed += size;
+        self.records += 1;
+        match self.records % TIMEOUT_CHECK_INTERVAL {
+            0 => self.check_timeout(),
+            _ => Ok(()),
+        }
+    }
+
+    /// 在其他线程中读取了bytes字节的记录
+    pub(crate) fn add_replayed(&mut self, bytes: u64) {
+        self.progress.bytes_replayed += bytes;
+    }
+
+    /// 处理完files个数据文件，报告进度
+    pub(crate) fn finish_files(&mut self, files: usize, keys_loaded: usize) -> Result<()> {
+        self.progress.files_processed += files;
+        self.progress.keys_loaded = keys_loaded;
+        self.progress.elapsed = self.start.elapsed();
+        if let Some(callback) = &self.callback {
+            (callback.0)(&self.progress);
+        }
+        self.check_timeout()
+    }
+
+    /// 检查打开数据库是否超时
+    pub(crate) fn check_timeout(&self) -> Result<()> {
+        match self.timeout {
+            Some(timeout) if self.start.elapsed() > timeout => Err(Error::OpenTimeout(timeout)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use parking_lot::Mutex;
+
+    use crate::db::Engine;
+    use crate::options::IndexType;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_open_progress() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-open-progress");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        let data_file_num = engine.stat().unwrap().data_file_num;
+        drop(engine);
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let p = progress.clone();
+        opts.on_open_progress = Some(OpenProgressCallback::new(move |progress| {
+            p.lock().push(*progress)
+        }));
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let progress = std::mem::take(&mut *progress.lock());
+        assert_eq!(progress.len(), data_file_num);
+        let last = progress.last().unwrap();
+        assert_eq!(last.files_processed, data_file_num);
+        assert_eq!(last.total_files, data_file_num);
+        assert_eq!(last.keys_loaded, 1000);
+        assert!(last.bytes_replayed > 0);
+        assert!(progress
+            .windows(2)
+            .all(|w| w[0].bytes_replayed < w[1].bytes_replayed && w[0].elapsed <= w[1].elapsed));
+
+        // merge后从hint文件中加载索引
+        engine.merge().unwrap();
+        drop(engine);
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let p = progress.clone();
+        opts.on_open_progress = Some(OpenProgressCallback::new(move |progress| {
+            p.lock().push(*progress)
+        }));
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let last = *progress.lock().last().unwrap();
+        assert_eq!(last.files_processed, last.total_files);
+        assert_eq!(last.keys_loaded, 1000);
+        drop(engine);
+
+        // 超时后打开失败，持久化的索引不会被标记为可用
+        opts.index_type = IndexType::BPlusTree;
+        opts.open_timeout = Some(Duration::from_nanos(1));
+        assert!(matches!(
+            Engine::open(opts.clone()).err().unwrap(),
+            Error::OpenTimeout(_)
+        ));
+        opts.open_timeout = Some(Duration::from_secs(60));
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 1000);
+        drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -1,15 +1,91 @@
+use std::ops::Bound;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::open_progress::{OpenProgress, OpenProgressCallback};
 
 #[derive(Debug, Clone)]
 pub struct Options {
     /// 数据库目录
-    pub(crate) dir_path: PathBuf,
+    pub dir_path: PathBuf,
+    /// 额外的数据文件目录，新的数据文件按ID轮流放在数据库目录和这些目录中，将读写分散到多块磁盘上。
+    /// hint文件、索引检查点等其他文件只保存在数据库目录中，打开数据库时在所有目录中查找数据文件
+    pub dir_paths: Vec<PathBuf>,
+    /// 数据库不存在时是否创建，为false时打开不存在的数据库返回DatabaseNotFound
+    pub create_if_missing: bool,
+    /// 数据库已存在时是否返回DatabaseAlreadyExists，用于保证初始化的是新的数据库。
+    /// 目录中没有数据文件时视为数据库不存在
+    pub error_if_exists: bool,
     /// 数据文件大小
-    pub(crate) data_file_size: u64,
+    pub data_file_size: u64,
     /// 是否持久化
-    pub(crate) sync_write: bool,
+    pub sync_write: bool,
     /// 索引类型
-    pub(crate) index_type: IndexType,
+    pub index_type: IndexType,
+    /// 启动时加载数据文件使用的IO类型
+    pub startup_io_type: IOType,
+    /// 启动时并行读取旧的数据文件的线程数，0表示使用CPU核数
+    pub startup_threads: usize,
+    /// 启动后读写数据文件使用的IO类型，不能使用内存映射
+    pub io_type: IOType,
+    /// 活跃数据文件的写缓冲区大小，0表示不使用写缓冲区。小数据的写入先合并到缓冲区中，
+    /// 缓冲区满了、sync、切换活跃文件和关闭数据库时再写入文件，进程崩溃时缓冲区中的数据会丢失
+    pub write_buffer_size: usize,
+    /// 是否为新的活跃数据文件预分配data_file_size大小的磁盘空间，减少文件系统碎片和元数据更新。
+    /// 只在Linux上使用标准文件IO或io_uring时生效，预分配不改变文件大小
+    pub preallocate_data_files: bool,
+    /// 是否在无效数据过多时自动merge
+    pub auto_merge: bool,
+    /// 无效数据占磁盘空间的比例达到该值时自动merge，取值范围(0, 1]
+    pub merge_ratio: f32,
+    /// value的压缩算法
+    pub compression: CompressionType,
+    /// 数据记录的校验算法，记录在数据文件头中，修改配置后旧的数据文件仍然使用原来的算法
+    pub checksum: ChecksumType,
+    /// 后台定时持久化活跃数据文件的间隔，None表示不在后台持久化
+    pub sync_interval: Option<Duration>,
+    /// 布隆过滤器中每个key占用的位数，0表示不使用布隆过滤器，每个key占10位时误判率约为1%
+    pub bloom_filter_bits_per_key: usize,
+    /// 读缓存的容量，单位为字节，缓存最近读取的value，0表示不使用读缓存
+    pub value_cache_size: usize,
+    /// 索引占用内存的上限（估计值），超出后写入新的key会返回错误，覆盖已有的key不受影响。
+    /// None表示不限制，磁盘索引不占用内存
+    pub index_memory_limit: Option<usize>,
+    /// key的最大长度，None表示不限制
+    pub max_key_size: Option<usize>,
+    /// value的最大长度，None表示不限制。编码后的数据不能超过数据文件的大小
+    pub max_value_size: Option<usize>,
+    /// 磁盘空间不足导致写入失败后是否切换为只读模式，只读模式下读操作不受影响，
+    /// 写操作返回ReadOnly，释放磁盘空间后重新打开数据库恢复写入
+    pub read_only_on_disk_full: bool,
+    /// 每秒最多写入的字节数，写入数据和merge共用同一个限额，超出后写操作阻塞等待。
+    /// None表示不限制
+    pub write_rate_limit_bytes_per_sec: Option<u64>,
+    /// merge和压缩数据文件时每秒最多读写的字节数，与写入限速同时生效，避免merge占满磁盘带宽。
+    /// None表示不限制
+    pub merge_rate_limit_bytes_per_sec: Option<u64>,
+    /// put、get、批量写入和merge耗时达到该值时记录慢操作，None表示不记录
+    pub slow_op_threshold: Option<Duration>,
+    /// 是否在关闭数据库时保存索引检查点，再次打开时加载检查点后只需要加载之后写入的数据。
+    /// merge后检查点失效，内存数据库和B+树索引不使用检查点
+    pub index_checkpoint: bool,
+    /// 开启索引检查点时在后台定时保存检查点的间隔，None表示只在关闭数据库时保存
+    pub index_checkpoint_interval: Option<Duration>,
+    /// 打开数据库时报告加载索引进度的回调，每处理完一个数据文件调用一次
+    pub on_open_progress: Option<OpenProgressCallback>,
+    /// 打开数据库的最长时间，超时后放弃打开并返回OpenTimeout，None表示不限制
+    pub open_timeout: Option<Duration>,
+    /// 数据保留期限，调用apply_retention时删除最新的数据也超过该期限的旧数据文件，None表示永久保留
+    pub retention: Option<Duration>,
+    /// 所有数据文件总大小的上限（估计值），达到上限后写入数据返回QuotaExceeded，
+    /// 删除数据不受影响，merge释放磁盘空间后可以继续写入。None表示不限制
+    pub max_db_size: Option<u64>,
+    /// 数据库大小或者索引内存达到上限时的淘汰策略，需要同时设置max_db_size或index_memory_limit
+    pub eviction_policy: EvictionPolicy,
+    /// value加密使用的密钥，None表示不加密
+    #[cfg(feature = "encryption")]
+    pub encryption_key: Option<[u8; 32]>,
 }
 
 /// 索引类型
@@ -19,39 +95,441 @@ pub enum IndexType {
     BTree,
     /// SkipList
     SkipList,
+    /// 按key的哈希值分片的BTree，多线程写入时减少锁竞争
+    ShardedBTree,
+    /// 哈希表，读写都是O(1)，key是无序的，迭代时需要对所有key排序
+    Hash,
+    /// 保存在磁盘上的B+树，key的数量不受内存大小限制，正常关闭后再次启动时不需要加载数据文件
+    BPlusTree,
+}
+
+/// IO类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IOType {
+    /// 标准文件IO
+    StandardFIO,
+    /// 内存映射，只能用于读取
+    MemoryMap,
+    /// 直接IO（O_DIRECT），读写绕过page cache，只支持Linux，适合批量导入数据
+    #[cfg(target_os = "linux")]
+    DirectIO,
+    /// io_uring，只支持Linux，需要开启`io-uring` feature
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    IoUring,
+    /// 内存IO，数据只保存在内存中，关闭数据库后丢失。不读写文件系统，
+    /// 启动时不加载数据文件，也不能使用B+树索引
+    Memory,
+}
+
+/// 压缩算法，每条数据单独记录使用的压缩算法，修改配置后旧的数据仍然可以读取
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionType {
+    /// 不压缩
+    None = 0,
+    /// LZ4，压缩和解压速度快
+    Lz4 = 1,
+    /// Zstd，压缩率高
+    Zstd = 2,
+    /// 使用Engine::train_dictionary训练的字典压缩不超过4KB的value，
+    /// 没有训练字典或者value较大时使用Zstd
+    ZstdDict = 3,
+}
+
+/// 淘汰策略，用于将数据库作为有容量上限的持久化缓存
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// 不淘汰，达到上限后写入返回错误
+    #[default]
+    None,
+    /// 写入删除记录淘汰最近最少读写的key，直到低于上限的90%。
+    /// 数据库大小超出上限时随后在后台merge释放磁盘空间
+    Lru,
+}
+
+/// 数据记录的校验算法，校验值都占4个字节
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChecksumType {
+    /// CRC32
+    #[default]
+    Crc32 = 0,
+    /// XXH3，取64位哈希值的低32位，写入频繁时计算更快
+    Xxh3 = 1,
+    /// 不校验，适合在更上层校验数据的场景，无法发现数据损坏
+    None = 2,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
             dir_path: std::env::temp_dir().join("bitcast-rs"),
+            dir_paths: Vec::new(),
+            create_if_missing: true,
+            error_if_exists: false,
             data_file_size: 1024 * 1024,
             sync_write: false,
             index_type: IndexType::BTree,
+            startup_io_type: IOType::StandardFIO,
+            startup_threads: 0,
+            io_type: IOType::StandardFIO,
+            write_buffer_size: 0,
+            preallocate_data_files: false,
+            auto_merge: false,
+            merge_ratio: 0.5,
+            compression: CompressionType::None,
+            checksum: ChecksumType::Crc32,
+            sync_interval: None,
+            bloom_filter_bits_per_key: 0,
+            value_cache_size: 0,
+            index_memory_limit: None,
+            max_key_size: None,
+            max_value_size: None,
+            read_only_on_disk_full: false,
+            write_rate_limit_bytes_per_sec: None,
+            merge_rate_limit_bytes_per_sec: None,
+            slow_op_threshold: None,
+            index_checkpoint: false,
+            index_checkpoint_interval: None,
+            on_open_progress: None,
+            open_timeout: None,
+            retention: None,
+            max_db_size: None,
+            eviction_policy: EvictionPolicy::None,
+            #[cfg(feature = "encryption")]
+            encryption_key: None,
         }
     }
 }
 
+impl Options {
+    /// 从默认配置开始构造配置项
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder::default()
+    }
+
+    /// 所有存放数据文件的目录，第一个为数据库目录
+    pub(crate) fn data_dirs(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.dir_path)
+            .chain(self.dir_paths.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+/// 配置项构造器，build时校验配置项
+#[derive(Debug, Clone, Default)]
+pub struct OptionsBuilder {
+    opts: Options,
+}
+
+impl OptionsBuilder {
+    pub fn dir_path(mut self, dir_path: impl Into<PathBuf>) -> Self {
+        self.opts.dir_path = dir_path.into();
+        self
+    }
+
+    pub fn dir_paths(mut self, dir_paths: Vec<PathBuf>) -> Self {
+        self.opts.dir_paths = dir_paths;
+        self
+    }
+
+    pub fn create_if_missing(mut self, create_if_missing: bool) -> Self {
+        self.opts.create_if_missing = create_if_missing;
+        self
+    }
+
+    pub fn error_if_exists(mut self, error_if_exists: bool) -> Self {
+        self.opts.error_if_exists = error_if_exists;
+        self
+    }
+
+    pub fn data_file_size(mut self, data_file_size: u64) -> Self {
+        self.opts.data_file_size = data_file_size;
+        self
+    }
+
+    pub fn sync_write(mut self, sync_write: bool) -> Self {
+        self.opts.sync_write = sync_write;
+        self
+    }
+
+    pub fn index_type(mut self, index_type: IndexType) -> Self {
+        self.opts.index_type = index_type;
+        self
+    }
+
+    pub fn startup_io_type(mut self, startup_io_type: IOType) -> Self {
+        self.opts.startup_io_type = startup_io_type;
+        self
+    }
+
+    pub fn startup_threads(mut self, startup_threads: usize) -> Self {
+        self.opts.startup_threads = startup_threads;
+        self
+    }
+
+    pub fn io_type(mut self, io_type: IOType) -> Self {
+        self.opts.io_type = io_type;
+        self
+    }
+
+    pub fn write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.opts.write_buffer_size = write_buffer_size;
+        self
+    }
+
+    pub fn preallocate_data_files(mut self, preallocate_data_files: bool) -> Self {
+        self.opts.preallocate_data_files = preallocate_data_files;
+        self
+    }
+
+    pub fn auto_merge(mut self, auto_merge: bool) -> Self {
+        self.opts.auto_merge = auto_merge;
+        self
+    }
+
+    pub fn merge_ratio(mut self, merge_ratio: f32) -> Self {
+        self.opts.merge_ratio = merge_ratio;
+        self
+    }
+
+    pub fn compression(mut self, compression: CompressionType) -> Self {
+        self.opts.compression = compression;
+        self
+    }
+
+    pub fn checksum(mut self, checksum: ChecksumType) -> Self {
+        self.opts.checksum = checksum;
+        self
+    }
+
+    pub fn sync_interval(mut self, sync_interval: Option<Duration>) -> Self {
+        self.opts.sync_interval = sync_interval;
+        self
+    }
+
+    pub fn bloom_filter_bits_per_key(mut self, bloom_filter_bits_per_key: usize) -> Self {
+        self.opts.bloom_filter_bits_per_key = bloom_filter_bits_per_key;
+        self
+    }
+
+    pub fn value_cache_size(mut self, value_cache_size: usize) -> Self {
+        self.opts.value_cache_size = value_cache_size;
+        self
+    }
+
+    pub fn index_memory_limit(mut self, index_memory_limit: Option<usize>) -> Self {
+        self.opts.index_memory_limit = index_memory_limit;
+        self
+    }
+
+    pub fn max_key_size(mut self, max_key_size: Option<usize>) -> Self {
+        self.opts.max_key_size = max_key_size;
+        self
+    }
+
+    pub fn max_value_size(mut self, max_value_size: Option<usize>) -> Self {
+        self.opts.max_value_size = max_value_size;
+        self
+    }
+
+    pub fn read_only_on_disk_full(mut self, read_only_on_disk_full: bool) -> Self {
+        self.opts.read_only_on_disk_full = read_only_on_disk_full;
+        self
+    }
+
+    pub fn write_rate_limit_bytes_per_sec(
+        mut self,
+        write_rate_limit_bytes_per_sec: Option<u64>,
+    ) -> Self {
+        self.opts.write_rate_limit_bytes_per_sec = write_rate_limit_bytes_per_sec;
+        self
+    }
+
+    pub fn merge_rate_limit_bytes_per_sec(
+        mut self,
+        merge_rate_limit_bytes_per_sec: Option<u64>,
+    ) -> Self {
+        self.opts.merge_rate_limit_bytes_per_sec = merge_rate_limit_bytes_per_sec;
+        self
+    }
+
+    pub fn slow_op_threshold(mut self, slow_op_threshold: Option<Duration>) -> Self {
+        self.opts.slow_op_threshold = slow_op_threshold;
+        self
+    }
+
+    pub fn index_checkpoint(mut self, index_checkpoint: bool) -> Self {
+        self.opts.index_checkpoint = index_checkpoint;
+        self
+    }
+
+    pub fn index_checkpoint_interval(
+        mut self,
+        index_checkpoint_interval: Option<Duration>,
+    ) -> Self {
+        self.opts.index_checkpoint_interval = index_checkpoint_interval;
+        self
+    }
+
+    pub fn on_open_progress<F>(mut self, on_open_progress: F) -> Self
+    where
+        F: Fn(&OpenProgress) + Send + Sync + 'static,
+    {
+        self.opts.on_open_progress = Some(OpenProgressCallback::new(on_open_progress));
+        self
+    }
+
+    pub fn open_timeout(mut self, open_timeout: Option<Duration>) -> Self {
+        self.opts.open_timeout = open_timeout;
+        self
+    }
+
+    pub fn retention(mut self, retention: Option<Duration>) -> Self {
+        self.opts.retention = retention;
+        self
+    }
+
+    pub fn max_db_size(mut self, max_db_size: Option<u64>) -> Self {
+        self.opts.max_db_size = max_db_size;
+        self
+    }
+
+    pub fn eviction_policy(mut self, eviction_policy: EvictionPolicy) -> Self {
+        self.opts.eviction_policy = eviction_policy;
+        self
+    }
+
+    #[cfg(feature = "encryption")]
+    pub fn encryption_key(mut self, encryption_key: Option<[u8; 32]>) -> Self {
+        self.opts.encryption_key = encryption_key;
+        self
+    }
+
+    /// 校验并返回配置项
+    pub fn build(self) -> Result<Options> {
+        check_options(&self.opts)?;
+        Ok(self.opts)
+    }
+}
+
+/// 校验配置项
+pub(crate) fn check_options(opts: &Options) -> Result<()> {
+    if opts.dir_path.to_str().is_none() || opts.dir_path.to_str().unwrap().is_empty() {
+        return Err(Error::InvalidDbDir);
+    }
+    // 数据文件目录不能为空，也不能重复
+    let data_dirs = opts.data_dirs();
+    if data_dirs
+        .iter()
+        .any(|dir_path| dir_path.as_os_str().is_empty())
+        || data_dirs
+            .iter()
+            .enumerate()
+            .any(|(i, dir_path)| data_dirs[..i].contains(dir_path))
+    {
+        return Err(Error::InvalidDbDir);
+    }
+    if opts.data_file_size == 0 {
+        return Err(Error::InvalidDataFileSize);
+    }
+    if opts.io_type == IOType::MemoryMap {
+        return Err(Error::InvalidIOType);
+    }
+    if opts.io_type == IOType::Memory && matches!(opts.index_type, IndexType::BPlusTree) {
+        return Err(Error::InvalidIOType);
+    }
+    if opts.merge_ratio <= 0.0 || opts.merge_ratio > 1.0 {
+        return Err(Error::InvalidMergeRatio);
+    }
+    if opts
+        .sync_interval
+        .is_some_and(|interval| interval.is_zero())
+    {
+        return Err(Error::InvalidSyncInterval);
+    }
+    if opts.write_rate_limit_bytes_per_sec == Some(0) {
+        return Err(Error::InvalidWriteRateLimit);
+    }
+    if opts.merge_rate_limit_bytes_per_sec == Some(0) {
+        return Err(Error::InvalidMergeRateLimit);
+    }
+    if opts
+        .index_checkpoint_interval
+        .is_some_and(|interval| interval.is_zero())
+    {
+        return Err(Error::InvalidCheckpointInterval);
+    }
+    if opts.open_timeout.is_some_and(|timeout| timeout.is_zero()) {
+        return Err(Error::InvalidOpenTimeout);
+    }
+    if opts.retention.is_some_and(|retention| retention.is_zero()) {
+        return Err(Error::InvalidRetention);
+    }
+    if opts.max_db_size == Some(0) {
+        return Err(Error::InvalidMaxDbSize);
+    }
+    if opts.eviction_policy != EvictionPolicy::None
+        && opts.max_db_size.is_none()
+        && opts.index_memory_limit.is_none()
+    {
+        return Err(Error::InvalidEvictionPolicy);
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
 pub struct IteratorOptions {
     /// key前缀
-    pub(crate) prefix: Vec<u8>,
+    pub prefix: Vec<u8>,
 
     /// 是否逆序
-    pub(crate) reverse: bool,
+    pub reverse: bool,
+
+    /// key的下界，与逆序无关
+    pub lower_bound: Bound<Vec<u8>>,
+
+    /// key的上界，与逆序无关
+    pub upper_bound: Bound<Vec<u8>>,
+
+    /// 只返回key，不从磁盘读取value，返回的value为空。
+    /// 此时无法判断数据是否过期，已过期但还没有被merge清理的key也会返回
+    pub keys_only: bool,
 }
 
 impl Default for IteratorOptions {
     fn default() -> Self {
         Self {
-            prefix: vec![],
+            prefix: Vec::new(),
             reverse: false,
+            lower_bound: Bound::Unbounded,
+            upper_bound: Bound::Unbounded,
+            keys_only: false,
         }
     }
 }
 
+impl IteratorOptions {
+    /// key是否匹配前缀并且在上下界范围内
+    pub(crate) fn contains(&self, key: &[u8]) -> bool {
+        let above_lower = match &self.lower_bound {
+            Bound::Included(lower) => key >= lower.as_slice(),
+            Bound::Excluded(lower) => key > lower.as_slice(),
+            Bound::Unbounded => true,
+        };
+        let below_upper = match &self.upper_bound {
+            Bound::Included(upper) => key <= upper.as_slice(),
+            Bound::Excluded(upper) => key < upper.as_slice(),
+            Bound::Unbounded => true,
+        };
+        key.starts_with(&self.prefix) && above_lower && below_upper
+    }
+}
+
 pub struct WriteOptions {
     pub max_batch_size: usize,
     pub sync_writes: bool,
+    /// batch中的数据编码后的最大字节数，超出后写入batch返回BatchTooLarge
+    pub max_batch_bytes: usize,
 }
 
 impl Default for WriteOptions {
@@ -59,6 +537,186 @@ impl Default for WriteOptions {
         Self {
             max_batch_size: 10000,
             sync_writes: true,
+            max_batch_bytes: 256 * 1024 * 1024,
         }
     }
 }
+
+/// 备份选项
+#[derive(Default)]
+pub struct BackupOptions {
+    /// 旧的数据文件不会再被修改，使用硬链接代替拷贝
+    pub hard_link: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_builder() {
+        let opts = Options::builder()
+            .dir_path("/tmp/bitcask-rs-options")
+            .dir_paths(vec![PathBuf::from("/tmp/bitcask-rs-options-1")])
+            .create_if_missing(false)
+            .error_if_exists(true)
+            .data_file_size(64 * 1024 * 1024)
+            .sync_write(true)
+            .index_type(IndexType::SkipList)
+            .startup_threads(4)
+            .auto_merge(true)
+            .merge_ratio(0.3)
+            .compression(CompressionType::Lz4)
+            .checksum(ChecksumType::Xxh3)
+            .sync_interval(Some(Duration::from_secs(1)))
+            .bloom_filter_bits_per_key(10)
+            .value_cache_size(1024 * 1024)
+            .max_key_size(Some(64))
+            .max_value_size(Some(1024))
+            .read_only_on_disk_full(true)
+            .preallocate_data_files(true)
+            .write_rate_limit_bytes_per_sec(Some(1024))
+            .merge_rate_limit_bytes_per_sec(Some(2048))
+            .slow_op_threshold(Some(Duration::from_millis(100)))
+            .index_checkpoint(true)
+            .index_checkpoint_interval(Some(Duration::from_secs(60)))
+            .on_open_progress(|_| {})
+            .open_timeout(Some(Duration::from_secs(10)))
+            .retention(Some(Duration::from_secs(86400)))
+            .max_db_size(Some(1024 * 1024 * 1024))
+            .eviction_policy(EvictionPolicy::Lru)
+            .build()
+            .unwrap();
+        assert_eq!(opts.dir_path, PathBuf::from("/tmp/bitcask-rs-options"));
+        assert_eq!(
+            opts.dir_paths,
+            vec![PathBuf::from("/tmp/bitcask-rs-options-1")]
+        );
+        assert!(!opts.create_if_missing);
+        assert!(opts.error_if_exists);
+        assert_eq!(opts.data_file_size, 64 * 1024 * 1024);
+        assert!(opts.sync_write);
+        assert!(matches!(opts.index_type, IndexType::SkipList));
+        assert_eq!(opts.startup_threads, 4);
+        assert!(opts.auto_merge);
+        assert_eq!(opts.merge_ratio, 0.3);
+        assert_eq!(opts.compression, CompressionType::Lz4);
+        assert_eq!(opts.checksum, ChecksumType::Xxh3);
+        assert_eq!(opts.sync_interval, Some(Duration::from_secs(1)));
+        assert_eq!(opts.bloom_filter_bits_per_key, 10);
+        assert_eq!(opts.value_cache_size, 1024 * 1024);
+        assert_eq!(opts.max_key_size, Some(64));
+        assert_eq!(opts.max_value_size, Some(1024));
+        assert!(opts.read_only_on_disk_full);
+        assert!(opts.preallocate_data_files);
+        assert_eq!(opts.write_rate_limit_bytes_per_sec, Some(1024));
+        assert_eq!(opts.merge_rate_limit_bytes_per_sec, Some(2048));
+        assert_eq!(opts.slow_op_threshold, Some(Duration::from_millis(100)));
+        assert!(opts.index_checkpoint);
+        assert_eq!(
+            opts.index_checkpoint_interval,
+            Some(Duration::from_secs(60))
+        );
+        assert!(opts.on_open_progress.is_some());
+        assert_eq!(opts.open_timeout, Some(Duration::from_secs(10)));
+        assert_eq!(opts.retention, Some(Duration::from_secs(86400)));
+        assert_eq!(opts.max_db_size, Some(1024 * 1024 * 1024));
+        assert_eq!(opts.eviction_policy, EvictionPolicy::Lru);
+
+        // 非法的配置项
+        assert!(matches!(
+            Options::builder().dir_path("").build().err().unwrap(),
+            Error::InvalidDbDir
+        ));
+        assert!(matches!(
+            Options::builder()
+                .dir_path("/tmp/bitcask-rs-options")
+                .dir_paths(vec![PathBuf::from("/tmp/bitcask-rs-options")])
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidDbDir
+        ));
+        assert!(matches!(
+            Options::builder()
+                .dir_paths(vec![PathBuf::new()])
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidDbDir
+        ));
+        assert!(matches!(
+            Options::builder().data_file_size(0).build().err().unwrap(),
+            Error::InvalidDataFileSize
+        ));
+        assert!(matches!(
+            Options::builder().merge_ratio(1.5).build().err().unwrap(),
+            Error::InvalidMergeRatio
+        ));
+        assert!(matches!(
+            Options::builder()
+                .sync_interval(Some(Duration::ZERO))
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidSyncInterval
+        ));
+        assert!(matches!(
+            Options::builder()
+                .index_checkpoint_interval(Some(Duration::ZERO))
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidCheckpointInterval
+        ));
+        assert!(matches!(
+            Options::builder()
+                .open_timeout(Some(Duration::ZERO))
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidOpenTimeout
+        ));
+        assert!(matches!(
+            Options::builder()
+                .retention(Some(Duration::ZERO))
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidRetention
+        ));
+        assert!(matches!(
+            Options::builder()
+                .max_db_size(Some(0))
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidMaxDbSize
+        ));
+        assert!(matches!(
+            Options::builder()
+                .eviction_policy(EvictionPolicy::Lru)
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidEvictionPolicy
+        ));
+        assert!(matches!(
+            Options::builder()
+                .io_type(IOType::MemoryMap)
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidIOType
+        ));
+        assert!(matches!(
+            Options::builder()
+                .io_type(IOType::Memory)
+                .index_type(IndexType::BPlusTree)
+                .build()
+                .err()
+                .unwrap(),
+            Error::InvalidIOType
+        ));
+    }
+}
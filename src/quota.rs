@@ -0,0 +1,142 @@
+//! 数据库大小配额
+//!
+//! 打开数据库、merge、压缩、清空等操作后重新统计数据文件的大小，之后累加写入活跃数据文件的数据大小，
+//! 写入时不需要读取目录
+
+use std::sync::atomic::Ordering;
+
+use log::warn;
+
+use crate::data::data_file::{find_data_files, get_data_file_full_path};
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::fio::mem_io;
+use crate::options::EvictionPolicy;
+
+impl Engine {
+    /// 数据文件的总大小达到上限时，拒绝写入新的数据
+    pub(crate) fn check_db_size(&self) -> Result<()> {
+        match self.options.max_db_size {
+            Some(limit) if self.live_db_size() >= limit => Err(Error::QuotaExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    /// 按配额判断的数据库大小。按LRU淘汰时被淘汰的数据由后台merge清理，只计算有效数据
+    pub(crate) fn live_db_size(&self) -> u64 {
+        let db_size = self.db_size.load(Ordering::SeqCst);
+        match self.options.eviction_policy {
+            EvictionPolicy::Lru => {
+                db_size.saturating_sub(self.reclaim_size.load(Ordering::SeqCst) as u64)
+            }
+            EvictionPolicy::None => db_size,
+        }
+    }
+
+    /// 写入了len字节的数据
+    pub(crate) fn add_db_size(&self, len: u64) {
+        self.db_size.fetch_add(len, Ordering::SeqCst);
+    }
+
+    /// 删除数据文件等操作后重新统计数据文件的大小，没有设置配额时不统计
+    pub(crate) fn refresh_db_size(&self) {
+        if self.options.max_db_size.is_none() {
+            return;
+        }
+        match self.data_files_size() {
+            Ok(size) => self.db_size.store(size, Ordering::SeqCst),
+            Err(e) => warn!("failed to refresh database size: {}", e),
+        }
+    }
+
+    /// 所有数据文件的大小之和，内存数据库为所有内存文件的大小
+    fn data_files_size(&self) -> Result<u64> {
+        if self.is_in_memory() {
+            return Ok(mem_io::dir_size(&self.options.dir_path));
+        }
+        let mut size = 0;
+        for (file_id, dir_path) in find_data_files(&self.options.data_dirs())? {
+            let path = get_data_file_full_path(dir_path, file_id);
+            size += std::fs::metadata(&path)
+                .map_err(|source| Error::FailedToReadFromDataFile {
+                    path,
+                    offset: 0,
+                    source,
+                })?
+                .len();
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_engine_max_db_size() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-quota");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        opts.max_db_size = Some(256 * 1024);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let mut key_num = 0;
+        loop {
+            match engine.put(get_test_key(key_num), get_test_value(key_num)) {
+                Ok(()) => key_num += 1,
+                Err(e) => {
+                    assert!(matches!(e, Error::QuotaExceeded));
+                    break;
+                }
+            }
+        }
+        assert!(engine.disk_size().unwrap() >= 256 * 1024);
+        assert!(matches!(
+            engine
+                .multi_put(&[(get_test_key(key_num), get_test_value(key_num))])
+                .err()
+                .unwrap(),
+            Error::QuotaExceeded
+        ));
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(0), get_test_value(0)).unwrap();
+        assert!(matches!(wb.commit().err().unwrap(), Error::QuotaExceeded));
+        drop(wb);
+
+        // 删除数据不受影响，merge释放磁盘空间后可以继续写入
+        for i in 0..key_num / 2 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.delete(get_test_key(key_num / 2)).unwrap();
+        wb.commit().unwrap();
+        engine.merge().unwrap();
+        engine
+            .put(Bytes::from("new"), Bytes::from("value"))
+            .unwrap();
+        drop(wb);
+        drop(engine);
+
+        // 重新打开后仍然按磁盘占用判断
+        opts.max_db_size = Some(1024);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(matches!(
+            engine
+                .put(Bytes::from("new"), Bytes::from("value"))
+                .err()
+                .unwrap(),
+            Error::QuotaExceeded
+        ));
+        assert_eq!(engine.get(Bytes::from("new")).unwrap(), "value");
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,300 @@
+use std::path::Path;
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+
+use crate::cdc::LogOp;
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::options::{BackupOptions, WriteOptions};
+use crate::replication::{apply_snapshot, read_ops, stage_ops, write_ops};
+
+/// 状态机元数据的key的前缀，bucket名称不能为空，不会与bucket中的key冲突
+const RESERVED_KEY_PREFIX: &[u8] = b"\0raft:";
+/// 最后应用的日志位置
+const LAST_APPLIED_KEY: &[u8] = b"\0raft:last-applied";
+/// 最后应用的集群成员配置
+const MEMBERSHIP_KEY: &[u8] = b"\0raft:membership";
+
+/// Raft日志的位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogId {
+    pub term: u64,
+    pub index: u64,
+}
+
+/// 状态机的元数据，随数据一起写入，也包含在快照中
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateMachineMeta {
+    /// 最后应用的日志位置，None表示还没有应用过日志
+    pub last_applied: Option<LogId>,
+    /// 最后应用的集群成员配置，由Raft实现自行编码
+    pub membership: Option<Bytes>,
+}
+
+/// 基于Engine的Raft状态机，可以接入openraft、raft-rs等Raft实现
+///
+/// 日志中的写命令使用encode_command编码，应用时与最后应用的日志位置在同一个batch中写入，
+/// 重启后从last_applied之后继续应用。元数据保存在以"\0raft:"开头的key中，写命令不能修改这些key
+pub struct StateMachine {
+    engine: Engine,
+    /// 日志按顺序应用，生成快照期间不能应用日志
+    apply_lock: Mutex<()>,
+}
+
+impl StateMachine {
+    pub fn new(engine: Engine) -> Self {
+        Self {
+            engine,
+            apply_lock: Mutex::new(()),
+        }
+    }
+
+    /// 状态机使用的数据库，只应通过它读取数据
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// 读取状态机的元数据
+    pub fn meta(&self) -> Result<StateMachineMeta> {
+        let last_applied = match self.engine.get(Bytes::from_static(LAST_APPLIED_KEY)) {
+            Ok(value) => Some(decode_log_id(&value)?),
+            Err(Error::KeyNotFound) => None,
+            Err(e) => return Err(e),
+        };
+        let membership = match self.engine.get(Bytes::from_static(MEMBERSHIP_KEY)) {
+            Ok(value) => Some(value),
+            Err(Error::KeyNotFound) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(StateMachineMeta {
+            last_applied,
+            membership,
+        })
+    }
+
+    /// 应用一条写命令，已经应用过的日志直接跳过
+    pub fn apply(&self, log_id: LogId, command: &[u8]) -> Result<()> {
+        let ops = decode_command(command)?;
+        self.apply_ops(log_id, &ops, None)
+    }
+
+    /// 应用一条不修改数据的日志，如新leader提交的空日志
+    pub fn apply_blank(&self, log_id: LogId) -> Result<()> {
+        self.apply_ops(log_id, &[], None)
+    }
+
+    /// 应用一条集群成员变更日志
+    pub fn apply_membership(&self, log_id: LogId, membership: Bytes) -> Result<()> {
+        self.apply_ops(log_id, &[], Some(membership))
+    }
+
+    /// 生成快照到dir，返回快照对应的元数据，dir中的文件可以打包后发送给其他节点
+    pub fn snapshot(&self, dir: impl AsRef<Path>) -> Result<StateMachineMeta> {
+        let _guard = self.apply_lock.lock();
+        let meta = self.meta()?;
+        self.engine.backup(dir, BackupOptions { hard_link: true })?;
+        Ok(meta)
+    }
+
+    /// 安装其他节点通过snapshot生成的快照，替换状态机中的所有数据，返回快照对应的元数据
+    ///
+    /// 元数据在其他数据全部写入后才写入，中途失败时last_applied为None，需要重新安装
+    pub fn install_snapshot(&self, dir: impl AsRef<Path>) -> Result<StateMachineMeta> {
+        let _guard = self.apply_lock.lock();
+        apply_snapshot(
+            &self.engine,
+            dir.as_ref(),
+            &[MEMBERSHIP_KEY, LAST_APPLIED_KEY],
+        )?;
+        self.meta()
+    }
+
+    fn apply_ops(&self, log_id: LogId, ops: &[LogOp], membership: Option<Bytes>) -> Result<()> {
+        let _guard = self.apply_lock.lock();
+        let applied = self
+            .meta()?
+            .last_applied
+            .is_some_and(|last_applied| log_id.index <= last_applied.index);
+        if applied {
+            return Ok(());
+        }
+        let modifies_meta = ops.iter().any(|op| match op {
+            LogOp::Put { key, .. } | LogOp::Delete { key } => key.starts_with(RESERVED_KEY_PREFIX),
+        });
+        if modifies_meta {
+            return Err(Error::InvalidRaftCommand);
+        }
+        // 崩溃后丢失的数据可以从Raft日志重新应用，不需要每次持久化
+        let wb = self.engine.new_write_batch(WriteOptions {
+            max_batch_size: usize::MAX,
+            sync_writes: false,
+            max_batch_bytes: usize::MAX,
+        })?;
+        stage_ops(&wb, ops)?;
+        wb.put(Bytes::from_static(LAST_APPLIED_KEY), encode_log_id(log_id))?;
+        if let Some(membership) = membership {
+            wb.put(Bytes::from_static(MEMBERSHIP_KEY), membership)?;
+        }
+        wb.commit()?;
+        Ok(())
+    }
+}
+
+/// 将一组写操作编码为Raft日志中的写命令
+pub fn encode_command(ops: &[LogOp]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_ops(&mut buf, ops).expect("failed to write to vec");
+    buf
+}
+
+/// 解码encode_command编码的写命令
+pub fn decode_command(command: &[u8]) -> Result<Vec<LogOp>> {
+    let mut reader = command;
+    match read_ops(&mut reader) {
+        Ok(ops) if reader.is_empty() => Ok(ops),
+        _ => Err(Error::InvalidRaftCommand),
+    }
+}
+
+fn encode_log_id(log_id: LogId) -> Bytes {
+    [log_id.term.to_be_bytes(), log_id.index.to_be_bytes()]
+        .concat()
+        .into()
+}
+
+fn decode_log_id(buf: &[u8]) -> Result<LogId> {
+    if buf.len() != 16 {
+        return Err(Error::InvalidRaftCommand);
+    }
+    Ok(LogId {
+        term: u64::from_be_bytes(buf[..8].try_into().unwrap()),
+        index: u64::from_be_bytes(buf[8..].try_into().unwrap()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::options::Options;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    fn open_state_machine(dir_path: &str) -> StateMachine {
+        let _ = std::fs::remove_dir_all(dir_path);
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from(dir_path);
+        StateMachine::new(Engine::open(opts).expect("failed to open engine"))
+    }
+
+    fn put(i: usize) -> LogOp {
+        LogOp::Put {
+            key: get_test_key(i),
+            value: get_test_value(i),
+            expire_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_raft_state_machine() {
+        let sm = open_state_machine("/tmp/bitcask-rs-raft-leader");
+        assert_eq!(sm.meta().unwrap(), StateMachineMeta::default());
+        sm.apply_blank(LogId { term: 1, index: 1 }).unwrap();
+        sm.apply_membership(LogId { term: 1, index: 2 }, Bytes::from("n1,n2"))
+            .unwrap();
+        for i in 0..100 {
+            let command = encode_command(&[put(i)]);
+            assert_eq!(decode_command(&command).unwrap(), vec![put(i)]);
+            sm.apply(
+                LogId {
+                    term: 1,
+                    index: 3 + i as u64,
+                },
+                &command,
+            )
+            .unwrap();
+        }
+        let command = encode_command(&[
+            put(100),
+            LogOp::Delete {
+                key: get_test_key(0),
+            },
+        ]);
+        sm.apply(
+            LogId {
+                term: 2,
+                index: 103,
+            },
+            &command,
+        )
+        .unwrap();
+        assert!(!sm.engine().contains_key(&get_test_key(0)));
+
+        // 已应用的日志直接跳过
+        let command = encode_command(&[LogOp::Delete {
+            key: get_test_key(1),
+        }]);
+        sm.apply(LogId { term: 1, index: 50 }, &command).unwrap();
+        assert!(sm.engine().contains_key(&get_test_key(1)));
+        let meta = sm.meta().unwrap();
+        assert_eq!(
+            meta.last_applied,
+            Some(LogId {
+                term: 2,
+                index: 103
+            })
+        );
+        assert_eq!(meta.membership, Some(Bytes::from("n1,n2")));
+
+        // 写命令不能修改元数据
+        let command = encode_command(&[LogOp::Delete {
+            key: Bytes::from_static(LAST_APPLIED_KEY),
+        }]);
+        assert!(matches!(
+            sm.apply(
+                LogId {
+                    term: 2,
+                    index: 104
+                },
+                &command
+            ),
+            Err(Error::InvalidRaftCommand)
+        ));
+        assert!(matches!(
+            decode_command(&command[1..]),
+            Err(Error::InvalidRaftCommand)
+        ));
+
+        // 通过快照同步到其他节点
+        let snapshot_dir = "/tmp/bitcask-rs-raft-snapshot";
+        let _ = std::fs::remove_dir_all(snapshot_dir);
+        assert_eq!(sm.snapshot(snapshot_dir).unwrap(), meta);
+        let follower = open_state_machine("/tmp/bitcask-rs-raft-follower");
+        follower
+            .apply(LogId { term: 1, index: 1 }, &encode_command(&[put(1000)]))
+            .unwrap();
+        assert_eq!(follower.install_snapshot(snapshot_dir).unwrap(), meta);
+        assert_eq!(follower.engine().len(), sm.engine().len());
+        assert!(!follower.engine().contains_key(&get_test_key(1000)));
+        follower
+            .apply(
+                LogId {
+                    term: 2,
+                    index: 104,
+                },
+                &encode_command(&[put(1000)]),
+            )
+            .unwrap();
+        assert!(follower.engine().contains_key(&get_test_key(1000)));
+
+        for dir in [
+            "/tmp/bitcask-rs-raft-leader",
+            "/tmp/bitcask-rs-raft-snapshot",
+            "/tmp/bitcask-rs-raft-follower",
+        ] {
+            std::fs::remove_dir_all(dir).expect("failed to remove test dir");
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// 令牌桶限速器，限制每秒写入的字节数，最多积累一秒的令牌
+///
+/// 令牌不足时先预支，调用方在锁外等待到令牌补足，单次写入大于每秒限额时也不会一直阻塞
+pub(crate) struct RateLimiter {
+    bytes_per_sec: f64,
+    /// 剩余的令牌，预支后为负数；上次补充令牌的时间
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec as f64,
+            state: Mutex::new((bytes_per_sec as f64, Instant::now())),
+        }
+    }
+
+    /// 获取写入bytes字节的令牌，令牌不足时阻塞等待
+    pub(crate) fn acquire(&self, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock();
+            let (tokens, last) = &mut *state;
+            let now = Instant::now();
+            let refill = now.duration_since(*last).as_secs_f64() * self.bytes_per_sec;
+            *tokens = (*tokens + refill).min(self.bytes_per_sec) - bytes as f64;
+            *last = now;
+            if *tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-*tokens / self.bytes_per_sec)
+        };
+        std::thread::sleep(wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+
+    use crate::db::Engine;
+    use crate::options::Options;
+    use crate::util::rand_kv::get_test_key;
+
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(1000);
+        // 积累的令牌可以直接使用
+        let start = Instant::now();
+        limiter.acquire(1000);
+        assert!(start.elapsed() < Duration::from_millis(100));
+        // 令牌不足时等待补充
+        limiter.acquire(200);
+        limiter.acquire(200);
+        assert!(start.elapsed() >= Duration::from_millis(350));
+    }
+
+    #[test]
+    fn test_engine_write_rate_limit() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-write-rate-limit");
+        opts.write_rate_limit_bytes_per_sec = Some(64 * 1024);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // 写入约两秒的限额，第一秒的令牌已积累
+        let start = Instant::now();
+        let value = Bytes::from(vec![b'a'; 1024]);
+        for i in 0..128 {
+            engine.put(get_test_key(i), value.clone()).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(900));
+        // merge的写入同样受限制
+        let start = Instant::now();
+        engine.merge().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(900));
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), value);
+
+        drop(engine);
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_engine_merge_rate_limit() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-merge-rate-limit");
+        opts.merge_rate_limit_bytes_per_sec = Some(64 * 1024);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+
+        // 写操作不受merge限速影响
+        let start = Instant::now();
+        let value = Bytes::from(vec![b'a'; 1024]);
+        for i in 0..64 {
+            engine.put(get_test_key(i), value.clone()).unwrap();
+        }
+        assert!(start.elapsed() < Duration::from_millis(900));
+        // merge读取和写入约两秒的限额，第一秒的令牌已积累
+        let start = Instant::now();
+        engine.merge().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(900));
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), value);
+
+        drop(engine);
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,313 @@
+use std::fs::File;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::batch::{log_record_key_with_seq_num, NON_TRANSACTION_SEQ_NUM};
+use crate::checkpoint::CHECKPOINT_FILE_NAME;
+use crate::data::data_file::{
+    check_data_file_header, check_record, encode_data_file_header, DATA_FILE_HEADER_SIZE,
+    DATA_FILE_SUFFIX, HINT_FILE_NAME,
+};
+use crate::data::log_record::{now_millis, LogRecord, LogRecordType};
+use crate::db::{lock_dir, read_dir, Engine};
+use crate::error::{Error, Result};
+use crate::index::bptree::BPTREE_INDEX_FILE_NAME;
+use crate::options::{ChecksumType, CompressionType};
+
+/// 存放被修复的原始文件的目录
+pub const QUARANTINE_DIR_NAME: &str = "quarantine";
+/// 填充损坏区域的记录的key，启动时分块记录不会建立索引，merge时被清理
+const FILLER_KEY: &[u8] = b"\0repair";
+
+/// 修复数据库的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// 被修复的数据文件ID
+    pub repaired_files: Vec<u32>,
+    /// 被修复的数据文件中保留下来的记录数量
+    pub salvaged_records: usize,
+    /// 无法读取而被丢弃的字节数
+    pub discarded_bytes: u64,
+    /// 原始文件被移动到的目录，没有修复任何文件时为None
+    pub quarantine_dir: Option<PathBuf>,
+}
+
+impl Engine {
+    /// 修复数据库目录中损坏的数据文件，数据库不能处于打开状态
+    ///
+    /// 逐字节查找下一条crc正确的记录，跳过无法解码的区域，可以读取的记录保持原来的位置写入新的文件，
+    /// 损坏的区域替换为不会被索引的填充记录，文件末尾损坏的数据直接截断。
+    /// 原始文件移动到quarantine目录中，hint文件和持久化的索引同样被移走，下次打开时重新加载索引
+    pub fn repair(dir_path: impl AsRef<Path>) -> Result<RepairReport> {
+        let dir_path = dir_path.as_ref();
+        // 获取文件锁，防止修复正在使用的数据库
+        let _lock_file = lock_dir(dir_path)?;
+
+        let mut file_ids = read_dir(dir_path)?
+            .iter()
+            .filter_map(|entry| {
+                let file_name = entry.file_name().into_string().ok()?;
+                file_name
+                    .strip_suffix(DATA_FILE_SUFFIX)?
+                    .parse::<u32>()
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+        file_ids.sort();
+
+        let mut report = RepairReport::default();
+        let quarantine_dir = dir_path
+            .join(QUARANTINE_DIR_NAME)
+            .join(now_millis().to_string());
+        for file_id in file_ids {
+            let file_name = format!("{:09}{}", file_id, DATA_FILE_SUFFIX);
+            let path = dir_path.join(&file_name);
+            let buf = std::fs::read(&path).map_err(|source| Error::FailedToReadFromDataFile {
+                path: path.clone(),
+                offset: 0,
+                source,
+            })?;
+            let Some(repaired) = repair_data_file(&buf, &mut report)? else {
+                continue;
+            };
+            warn!("repair data file {:?}", path);
+            // 先写入临时文件，再替换原始文件
+            let tmp_path = dir_path.join(format!("{}.repair", file_name));
+            let repair_err = |source| Error::FailedToRepairDataFile {
+                path: path.clone(),
+                source,
+            };
+            let mut tmp_file = File::create(&tmp_path).map_err(repair_err)?;
+            tmp_file.write_all(&repaired).map_err(repair_err)?;
+            tmp_file.sync_all().map_err(repair_err)?;
+            quarantine(&path, &quarantine_dir)?;
+            std::fs::rename(&tmp_path, &path).map_err(repair_err)?;
+            report.repaired_files.push(file_id);
+        }
+        if report.repaired_files.is_empty() {
+            return Ok(report);
+        }
+        // hint文件、持久化的索引和索引检查点可能指向被丢弃的记录
+        for file_name in [HINT_FILE_NAME, BPTREE_INDEX_FILE_NAME, CHECKPOINT_FILE_NAME] {
+            let path = dir_path.join(file_name);
+            if path.is_file() {
+                quarantine(&path, &quarantine_dir)?;
+            }
+        }
+        report.quarantine_dir = Some(quarantine_dir);
+        Ok(report)
+    }
+}
+
+/// 修复一个数据文件的内容，没有损坏时返回None
+fn repair_data_file(buf: &[u8], report: &mut RepairReport) -> Result<Option<Vec<u8>>> {
+    let header_size = DATA_FILE_HEADER_SIZE as usize;
+    // 文件头损坏时重新写入文件头，不支持的版本不能修复
+    let checksum = match buf.len() < header_size {
+        true => None,
+        false => match check_data_file_header(&buf[..header_size]) {
+            Ok(checksum) => Some(checksum),
+            Err(Error::InvalidDataFileHeader) => None,
+            Err(e) => return Err(e),
+        },
+    };
+    let header_damaged = checksum.is_none();
+    // 不知道校验算法时，使用可以读取更多记录的校验算法
+    let (checksum, (records, damaged, end)) = match checksum {
+        Some(checksum) => (checksum, scan_records(buf, checksum)),
+        None => [ChecksumType::Crc32, ChecksumType::Xxh3]
+            .into_iter()
+            .map(|checksum| (checksum, scan_records(buf, checksum)))
+            .max_by_key(|(_, (records, _, _))| *records)
+            .unwrap(),
+    };
+    // 有效数据之后的0是写入数据前预留的空间，不算作损坏
+    let tail = buf.get(end..).unwrap_or_default();
+    let tail_len = tail.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    if !header_damaged && damaged.is_empty() && tail_len == 0 {
+        return Ok(None);
+    }
+    report.salvaged_records += records;
+    report.discarded_bytes += (damaged.iter().map(|r| r.len()).sum::<usize>() + tail_len) as u64;
+
+    let mut repaired = encode_data_file_header(checksum);
+    let mut offset = header_size;
+    for range in damaged {
+        repaired.extend_from_slice(&buf[offset..range.start]);
+        repaired.extend_from_slice(&filler_record(range.len(), checksum).unwrap());
+        offset = range.end;
+    }
+    repaired.extend_from_slice(buf.get(offset..end).unwrap_or_default());
+    Ok(Some(repaired))
+}
+
+/// 扫描数据文件中的记录，返回可以读取的记录数量、中间损坏的区域以及有效数据的结束位置
+///
+/// 损坏的区域之后一定有可以读取的记录，区域的大小足够写入填充记录
+fn scan_records(buf: &[u8], checksum: ChecksumType) -> (usize, Vec<Range<usize>>, usize) {
+    let header_size = DATA_FILE_HEADER_SIZE as usize;
+    // 最后一个非0字节之后没有数据
+    let data_end = buf.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    let mut records = 0;
+    let mut damaged = vec![];
+    let mut damaged_start = None;
+    let mut offset = header_size;
+    while offset < data_end {
+        let Some(size) = check_record(&buf[offset..], checksum) else {
+            damaged_start.get_or_insert(offset);
+            offset += 1;
+            continue;
+        };
+        match damaged_start {
+            // 损坏的区域太小，无法写入填充记录时，连同这条记录一起丢弃
+            Some(start) if filler_record(offset - start, checksum).is_none() => {}
+            Some(start) => {
+                damaged.push(start..offset);
+                damaged_start = None;
+                records += 1;
+            }
+            None => records += 1,
+        }
+        offset += size;
+    }
+    let end = damaged_start.unwrap_or(offset.max(header_size));
+    (records, damaged, end)
+}
+
+/// 构造编码后恰好占据len字节的填充记录，len太小时返回None
+fn filler_record(len: usize, checksum: ChecksumType) -> Option<Vec<u8>> {
+    let encode = |timestamp, value_len| {
+        LogRecord {
+            key: log_record_key_with_seq_num(FILLER_KEY, NON_TRANSACTION_SEQ_NUM),
+            value: vec![0; value_len],
+            record_type: LogRecordType::CHUNK,
+            timestamp,
+            expire_at: 0,
+        }
+        .encode_with(CompressionType::None, None, checksum, None)
+        .unwrap()
+    };
+    let min_len = encode(0, 0).len();
+    // value长度的编码大小变化时可能凑不出len，用时间戳的编码大小补齐
+    for timestamp in [0, 128] {
+        for extra in 0..=5 {
+            let Some(value_len) = len.checked_sub(min_len + extra) else {
+                continue;
+            };
+            let record = encode(timestamp, value_len);
+            if record.len() == len {
+                return Some(record);
+            }
+        }
+    }
+    None
+}
+
+/// 将文件移动到quarantine目录中
+fn quarantine(path: &Path, quarantine_dir: &Path) -> Result<()> {
+    let to = quarantine_dir.join(path.file_name().unwrap());
+    let quarantine_err = |source| Error::FailedToQuarantineFile {
+        from: path.to_path_buf(),
+        to: to.clone(),
+        source,
+    };
+    std::fs::create_dir_all(quarantine_dir).map_err(quarantine_err)?;
+    std::fs::rename(path, &to).map_err(quarantine_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Seek, SeekFrom};
+    use std::path::PathBuf;
+
+    use crate::data::data_file::get_data_file_full_path;
+    use crate::options::Options;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_filler_record() {
+        for checksum in [ChecksumType::Crc32, ChecksumType::Xxh3] {
+            for len in 0..1000 {
+                if let Some(record) = filler_record(len, checksum) {
+                    assert_eq!(record.len(), len);
+                    assert_eq!(check_record(&record, checksum), Some(len));
+                }
+            }
+            // 足够大的区域都可以填充
+            assert!((64..4096)
+                .chain([1 << 20])
+                .all(|len| filler_record(len, checksum).is_some()));
+            assert!(filler_record(0, checksum).is_none());
+        }
+    }
+
+    #[test]
+    fn test_engine_repair() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-repair");
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        // 数据库打开时不能修复
+        assert!(matches!(
+            Engine::repair(&opts.dir_path),
+            Err(Error::DatabaseIsInUse)
+        ));
+        drop(engine);
+
+        // 没有损坏时不修改任何文件
+        let report = Engine::repair(&opts.dir_path).unwrap();
+        assert_eq!(report, RepairReport::default());
+        assert!(!opts.dir_path.join(QUARANTINE_DIR_NAME).exists());
+
+        // 损坏第一个数据文件中间的数据，并在末尾写入无法解码的数据
+        let path = get_data_file_full_path(&opts.dir_path, 0);
+        let len = std::fs::metadata(&path).unwrap().len();
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(32 * 1024)).unwrap();
+        file.write_all(&[0xff; 16]).unwrap();
+        file.seek(SeekFrom::Start(len)).unwrap();
+        file.write_all(&[0xff; 10]).unwrap();
+        drop(file);
+
+        let report = Engine::repair(&opts.dir_path).unwrap();
+        assert_eq!(report.repaired_files, vec![0]);
+        assert!(report.salvaged_records > 0);
+        assert!(report.discarded_bytes >= 26);
+        let quarantine_dir = report.quarantine_dir.unwrap();
+        assert_eq!(
+            std::fs::read(quarantine_dir.join("000000000.data"))
+                .unwrap()
+                .len() as u64,
+            len + 10
+        );
+        assert!(!opts.dir_path.join("000000000.data.repair").exists());
+        // 可以读取的记录位置不变
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert!(engine.verify().unwrap().is_ok());
+        let missing = (0..1000)
+            .filter(|i| engine.get(get_test_key(*i)).is_err())
+            .count();
+        assert!(missing > 0 && missing < 5, "missing {} keys", missing);
+        // 修复后可以继续写入和merge
+        engine
+            .put(get_test_key(1000), get_test_value(1000))
+            .unwrap();
+        engine.merge().unwrap();
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), 1001 - missing);
+
+        drop(engine);
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,814 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use log::warn;
+use parking_lot::Mutex;
+
+use crate::batch::WriteBatch;
+use crate::cdc::{LogCursor, LogEntry, LogOp};
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::options::{BackupOptions, Options, WriteOptions};
+
+/// 快照中的一个文件：文件名，文件长度，文件内容
+const MSG_SNAPSHOT_FILE: u8 = 1;
+/// 快照发送完成：快照对应的读取位置
+const MSG_SNAPSHOT_END: u8 = 2;
+/// 一次提交的数据
+const MSG_ENTRY: u8 = 3;
+
+const OP_PUT: u8 = 1;
+const OP_DELETE: u8 = 2;
+
+/// follower保存同步位置的文件
+pub const CURSOR_FILE_NAME: &str = "replication-cursor";
+/// 快照临时目录的后缀
+const SNAPSHOT_DIR_SUFFIX: &str = "-replication-snapshot";
+/// leader每次最多读取的提交数
+const MAX_ENTRIES_PER_READ: usize = 1000;
+/// 应用快照时每个batch写入的数据条数
+const SNAPSHOT_BATCH_SIZE: usize = 1000;
+/// 检查新的提交和停止信号的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// follower断开连接后重连的间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 复制的leader，将已提交的数据异步发送给连接的follower
+///
+/// follower没有同步位置或同步位置所在的文件已被merge清理时，先通过backup发送全量快照，
+/// 再从快照的结束位置继续发送之后提交的数据
+pub struct Leader {
+    local_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Leader {
+    /// 在addr上监听follower的连接，内存数据库不能作为leader
+    pub fn start(engine: Engine, addr: impl ToSocketAddrs) -> Result<Self> {
+        engine.check_closed()?;
+        if engine.is_in_memory() {
+            return Err(Error::BackupNotSupported);
+        }
+        let start = || -> std::io::Result<(TcpListener, SocketAddr)> {
+            let listener = TcpListener::bind(addr)?;
+            // 非阻塞地接受连接，以便及时检查停止信号
+            listener.set_nonblocking(true)?;
+            let local_addr = listener.local_addr()?;
+            Ok((listener, local_addr))
+        };
+        let (listener, local_addr) =
+            start().map_err(|source| Error::FailedToStartReplication { source })?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let stop = stop.clone();
+            std::thread::spawn(move || accept_followers(engine, listener, stop))
+        };
+        Ok(Self {
+            local_addr,
+            stop,
+            worker: Some(worker),
+        })
+    }
+
+    /// 实际监听的地址
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// 停止监听并断开所有follower
+    pub fn shutdown(mut self) {
+        self.stop_worker();
+    }
+
+    fn stop_worker(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                warn!("replication leader thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        self.stop_worker();
+    }
+}
+
+/// 复制的follower，从leader接收数据写入本地数据库，断开连接后自动重连
+///
+/// 同步位置保存在数据库目录中，重新启动后从上次的位置继续同步。
+/// follower的数据库不应被直接写入，否则数据会和leader不一致
+pub struct Follower {
+    cursor: Arc<Mutex<Option<LogCursor>>>,
+    stop: Arc<AtomicBool>,
+    /// 当前的连接，停止时关闭连接以唤醒阻塞的读操作
+    stream: Arc<Mutex<Option<TcpStream>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Follower {
+    /// 开始从leader_addr同步数据到engine
+    pub fn start(engine: Engine, leader_addr: impl ToSocketAddrs) -> Result<Self> {
+        engine.check_closed()?;
+        let addrs = leader_addr
+            .to_socket_addrs()
+            .map_err(|source| Error::FailedToStartReplication { source })?
+            .collect::<Vec<_>>();
+        let cursor = Arc::new(Mutex::new(load_cursor(&engine)?));
+        let stop = Arc::new(AtomicBool::new(false));
+        let stream = Arc::new(Mutex::new(None));
+        let worker = {
+            let cursor = cursor.clone();
+            let stop = stop.clone();
+            let stream = stream.clone();
+            std::thread::spawn(move || follow_leader(engine, addrs, cursor, stop, stream))
+        };
+        Ok(Self {
+            cursor,
+            stop,
+            stream,
+            worker: Some(worker),
+        })
+    }
+
+    /// 已同步到的leader的位置，None表示还没有同步过
+    pub fn cursor(&self) -> Option<LogCursor> {
+        *self.cursor.lock()
+    }
+
+    /// 停止同步，正在应用的数据写入完成后返回
+    pub fn shutdown(mut self) {
+        self.stop_worker();
+    }
+
+    fn stop_worker(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(stream) = self.stream.lock().as_ref() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                warn!("replication follower thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for Follower {
+    fn drop(&mut self) {
+        self.stop_worker();
+    }
+}
+
+/// leader接受follower的连接，每个follower使用一个线程发送数据
+fn accept_followers(engine: Engine, listener: TcpListener, stop: Arc<AtomicBool>) {
+    let mut session_id = 0;
+    // 每个会话的线程和连接，停止时关闭连接以唤醒阻塞的读写操作
+    let mut sessions: Vec<(JoinHandle<()>, TcpStream)> = Vec::new();
+    while !stop.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                let conn = match stream.try_clone() {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        warn!("failed to accept follower {}: {}", addr, e);
+                        continue;
+                    }
+                };
+                session_id += 1;
+                let engine = engine.clone();
+                let stop = stop.clone();
+                let session = std::thread::spawn(move || {
+                    if let Err(e) = serve_follower(&engine, stream, &stop, session_id) {
+                        if !stop.load(Ordering::SeqCst) {
+                            warn!("replication to follower {} stopped: {}", addr, e);
+                        }
+                    }
+                });
+                sessions.push((session, conn));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL)
+            }
+            Err(e) => {
+                warn!("failed to accept follower: {}", e);
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+        sessions.retain(|(session, _)| !session.is_finished());
+    }
+    for (_, conn) in sessions.iter() {
+        let _ = conn.shutdown(Shutdown::Both);
+    }
+    for (session, _) in sessions {
+        let _ = session.join();
+    }
+}
+
+/// 向一个follower发送数据，直到连接断开或leader停止
+fn serve_follower(
+    engine: &Engine,
+    stream: TcpStream,
+    stop: &AtomicBool,
+    session_id: u64,
+) -> Result<()> {
+    stream.set_nonblocking(false).map_err(replication_failed)?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(replication_failed)?);
+    let mut writer = BufWriter::new(stream);
+    let mut cursor = read_handshake(&mut reader)?;
+    let snapshot_dir = sibling_dir(
+        &engine.options.dir_path,
+        &format!("{}-{}", SNAPSHOT_DIR_SUFFIX, session_id),
+    );
+    while !stop.load(Ordering::SeqCst) {
+        let from = match cursor {
+            Some(cursor) => cursor,
+            None => send_snapshot(engine, &mut writer, &snapshot_dir)?,
+        };
+        // 先记录提交次数再读取，读取期间的新提交不会被遗漏
+        let commit_seq = engine.commit_seq.load(Ordering::SeqCst);
+        match engine.read_log_since(from, MAX_ENTRIES_PER_READ) {
+            Ok((entries, next)) => {
+                for entry in entries.iter() {
+                    write_entry(&mut writer, entry)?;
+                }
+                writer.flush().map_err(replication_failed)?;
+                cursor = Some(next);
+                if entries.len() < MAX_ENTRIES_PER_READ {
+                    // 等待新的提交
+                    while !stop.load(Ordering::SeqCst)
+                        && engine.commit_seq.load(Ordering::SeqCst) == commit_seq
+                    {
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            }
+            // 同步位置已被merge清理，重新发送快照
+            Err(Error::LogCursorExpired) => cursor = None,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// 通过backup生成快照并发送给follower，返回快照的结束位置
+fn send_snapshot(
+    engine: &Engine,
+    writer: &mut impl Write,
+    snapshot_dir: &Path,
+) -> Result<LogCursor> {
+    let _ = std::fs::remove_dir_all(snapshot_dir);
+    let result = engine
+        .backup_with_cursor(snapshot_dir, BackupOptions { hard_link: true })
+        .and_then(|cursor| {
+            write_snapshot(writer, snapshot_dir, cursor).map_err(replication_failed)?;
+            Ok(cursor)
+        });
+    let _ = std::fs::remove_dir_all(snapshot_dir);
+    result
+}
+
+/// 发送快照目录中的所有文件
+fn write_snapshot(
+    writer: &mut impl Write,
+    snapshot_dir: &Path,
+    cursor: LogCursor,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(snapshot_dir)? {
+        let entry = entry?;
+        let file = File::open(entry.path())?;
+        let len = file.metadata()?.len();
+        writer.write_all(&[MSG_SNAPSHOT_FILE])?;
+        write_bytes(writer, entry.file_name().to_string_lossy().as_bytes())?;
+        writer.write_all(&len.to_be_bytes())?;
+        std::io::copy(&mut file.take(len), writer)?;
+    }
+    writer.write_all(&[MSG_SNAPSHOT_END])?;
+    write_cursor(writer, cursor)?;
+    writer.flush()
+}
+
+/// follower连接leader并应用收到的数据，断开后等待一段时间重连
+fn follow_leader(
+    engine: Engine,
+    addrs: Vec<SocketAddr>,
+    cursor: Arc<Mutex<Option<LogCursor>>>,
+    stop: Arc<AtomicBool>,
+    stream: Arc<Mutex<Option<TcpStream>>>,
+) {
+    while !stop.load(Ordering::SeqCst) {
+        if let Err(e) = sync_from_leader(&engine, &addrs, &cursor, &stop, &stream) {
+            if !stop.load(Ordering::SeqCst) {
+                warn!("replication from leader stopped: {}", e);
+            }
+        }
+        *stream.lock() = None;
+        if matches!(engine.check_closed(), Err(Error::DatabaseClosed)) {
+            return;
+        }
+        let deadline = Instant::now() + RECONNECT_INTERVAL;
+        while !stop.load(Ordering::SeqCst) && Instant::now() < deadline {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// 通过一个连接从leader同步数据，直到连接断开或follower停止
+fn sync_from_leader(
+    engine: &Engine,
+    addrs: &[SocketAddr],
+    cursor: &Mutex<Option<LogCursor>>,
+    stop: &AtomicBool,
+    stream: &Mutex<Option<TcpStream>>,
+) -> Result<()> {
+    let conn = TcpStream::connect(addrs).map_err(replication_failed)?;
+    *stream.lock() = Some(conn.try_clone().map_err(replication_failed)?);
+    // 保存连接后再检查停止信号，保证停止时能关闭该连接
+    if stop.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    let mut reader = BufReader::new(conn.try_clone().map_err(replication_failed)?);
+    let mut writer = BufWriter::new(conn);
+    write_handshake(&mut writer, *cursor.lock()).map_err(replication_failed)?;
+
+    let snapshot_dir = sibling_dir(&engine.options.dir_path, SNAPSHOT_DIR_SUFFIX);
+    let mut receiving_snapshot = false;
+    loop {
+        match read_u8(&mut reader)? {
+            MSG_SNAPSHOT_FILE => {
+                if !receiving_snapshot {
+                    let _ = std::fs::remove_dir_all(&snapshot_dir);
+                    std::fs::create_dir_all(&snapshot_dir).map_err(replication_failed)?;
+                    receiving_snapshot = true;
+                }
+                let name = String::from_utf8(read_bytes(&mut reader)?)
+                    .map_err(|_| Error::InvalidReplicationMessage)?;
+                // 文件名不能包含路径
+                if Path::new(&name).file_name() != Some(name.as_ref()) {
+                    return Err(Error::InvalidReplicationMessage);
+                }
+                let len = read_u64(&mut reader)?;
+                let mut file =
+                    File::create(snapshot_dir.join(&name)).map_err(replication_failed)?;
+                let copied = std::io::copy(&mut (&mut reader).take(len), &mut file)
+                    .map_err(replication_failed)?;
+                if copied != len {
+                    return Err(replication_failed(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+            }
+            MSG_SNAPSHOT_END => {
+                let snapshot_cursor = read_cursor(&mut reader)?;
+                if !receiving_snapshot {
+                    return Err(Error::InvalidReplicationMessage);
+                }
+                receiving_snapshot = false;
+                // 快照完整应用之前没有同步位置，中途失败后重新同步快照
+                clear_cursor(engine, cursor)?;
+                let result = apply_snapshot(engine, &snapshot_dir, &[]);
+                let _ = std::fs::remove_dir_all(&snapshot_dir);
+                result?;
+                save_cursor(engine, cursor, snapshot_cursor)?;
+            }
+            MSG_ENTRY => {
+                let entry = read_entry(&mut reader)?;
+                apply_entry(engine, &entry)?;
+                save_cursor(engine, cursor, entry.next)?;
+            }
+            _ => return Err(Error::InvalidReplicationMessage),
+        }
+    }
+}
+
+/// 一次提交的数据作为一个batch写入，保证原子性
+fn apply_entry(engine: &Engine, entry: &LogEntry) -> Result<()> {
+    let wb = engine.new_write_batch(apply_write_options())?;
+    stage_ops(&wb, &entry.ops)?;
+    wb.commit()?;
+    Ok(())
+}
+
+/// 将写操作暂存到batch中
+pub(crate) fn stage_ops(wb: &WriteBatch, ops: &[LogOp]) -> Result<()> {
+    for op in ops.iter() {
+        match op {
+            LogOp::Put {
+                key,
+                value,
+                expire_at,
+            } => wb.put_with_expire_at(key.clone(), value.clone(), *expire_at)?,
+            LogOp::Delete { key } => wb.delete(key.clone())?,
+        }
+    }
+    Ok(())
+}
+
+/// 用快照替换数据库中的数据：清空数据库后分批写入快照中的数据
+///
+/// 中途失败时数据库中只有部分数据，last_keys中的key在其他数据全部写入后才写入，
+/// 调用方据此判断快照是否已完整应用
+pub(crate) fn apply_snapshot(
+    engine: &Engine,
+    snapshot_dir: &Path,
+    last_keys: &[&[u8]],
+) -> Result<()> {
+    // 只沿用解码数据需要的配置，不使用follower的数据目录、配额、淘汰等配置
+    #[allow(unused_mut)]
+    let mut opts = Options {
+        dir_path: snapshot_dir.to_path_buf(),
+        create_if_missing: false,
+        checksum: engine.options.checksum,
+        ..Default::default()
+    };
+    #[cfg(feature = "encryption")]
+    {
+        opts.encryption_key = engine.options.encryption_key;
+    }
+    let snapshot = Engine::open(opts)?;
+    // 读取快照中merge_value写入的数据
+    *snapshot.merge_operator.write() = engine.merge_operator.read().clone();
+
+    engine.clear()?;
+    let wb = engine.new_write_batch(apply_write_options())?;
+    let stage = |wb: &WriteBatch, key: Bytes| match snapshot.get_log_record(&key) {
+        Ok(log_record) => wb.put_with_expire_at(key, log_record.value.into(), log_record.expire_at),
+        // 快照中已过期的数据
+        Err(Error::KeyNotFound) => Ok(()),
+        Err(e) => Err(e),
+    };
+    for key in snapshot.list_keys()? {
+        if last_keys.contains(&key.as_ref()) {
+            continue;
+        }
+        stage(&wb, key)?;
+        if wb.len() >= SNAPSHOT_BATCH_SIZE {
+            wb.commit()?;
+        }
+    }
+    wb.commit()?;
+    for key in last_keys.iter() {
+        if snapshot.contains_key(key) {
+            stage(&wb, Bytes::copy_from_slice(key))?;
+        }
+    }
+    wb.commit()?;
+    snapshot.close()
+}
+
+fn apply_write_options() -> WriteOptions {
+    WriteOptions {
+        max_batch_size: usize::MAX,
+        sync_writes: true,
+        max_batch_bytes: usize::MAX,
+    }
+}
+
+/// 读取follower保存的同步位置
+fn load_cursor(engine: &Engine) -> Result<Option<LogCursor>> {
+    if engine.is_in_memory() {
+        return Ok(None);
+    }
+    let path = engine.options.dir_path.join(CURSOR_FILE_NAME);
+    let buf = match std::fs::read(&path) {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => return Err(Error::FailedToLoadReplicationState { path, source }),
+    };
+    match read_cursor(&mut buf.as_slice()) {
+        Ok(cursor) => Ok(Some(cursor)),
+        Err(_) => Err(Error::InvalidReplicationState { path }),
+    }
+}
+
+/// 数据写入后保存同步位置，先写临时文件再重命名，保证文件内容完整
+fn save_cursor(engine: &Engine, cursor: &Mutex<Option<LogCursor>>, value: LogCursor) -> Result<()> {
+    if !engine.is_in_memory() {
+        let path = engine.options.dir_path.join(CURSOR_FILE_NAME);
+        let tmp_path = path.with_extension("tmp");
+        let save = || -> std::io::Result<()> {
+            let mut file = File::create(&tmp_path)?;
+            write_cursor(&mut file, value)?;
+            file.sync_all()?;
+            std::fs::rename(&tmp_path, &path)
+        };
+        save().map_err(|source| Error::FailedToSaveReplicationState {
+            path: path.clone(),
+            source,
+        })?;
+    }
+    *cursor.lock() = Some(value);
+    Ok(())
+}
+
+/// 删除follower保存的同步位置
+fn clear_cursor(engine: &Engine, cursor: &Mutex<Option<LogCursor>>) -> Result<()> {
+    if !engine.is_in_memory() {
+        let path = engine.options.dir_path.join(CURSOR_FILE_NAME);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(source) => return Err(Error::FailedToSaveReplicationState { path, source }),
+        }
+    }
+    *cursor.lock() = None;
+    Ok(())
+}
+
+/// 与数据库目录同级的目录
+fn sibling_dir(dir_path: &Path, suffix: &str) -> PathBuf {
+    let file_name = dir_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let parent = dir_path.parent().unwrap_or(dir_path);
+    parent.join(format!("{}{}", file_name, suffix))
+}
+
+fn replication_failed(source: std::io::Error) -> Error {
+    Error::ReplicationFailed { source }
+}
+
+/// follower连接后发送的同步位置
+fn write_handshake(writer: &mut impl Write, cursor: Option<LogCursor>) -> std::io::Result<()> {
+    match cursor {
+        Some(cursor) => {
+            writer.write_all(&[1])?;
+            write_cursor(writer, cursor)?;
+        }
+        None => writer.write_all(&[0])?,
+    }
+    writer.flush()
+}
+
+fn read_handshake(reader: &mut impl Read) -> Result<Option<LogCursor>> {
+    match read_u8(reader)? {
+        0 => Ok(None),
+        1 => Ok(Some(read_cursor(reader)?)),
+        _ => Err(Error::InvalidReplicationMessage),
+    }
+}
+
+fn write_entry(writer: &mut impl Write, entry: &LogEntry) -> Result<()> {
+    let write = |writer: &mut dyn Write| -> std::io::Result<()> {
+        writer.write_all(&[MSG_ENTRY])?;
+        writer.write_all(&entry.timestamp.to_be_bytes())?;
+        write_cursor(writer, entry.next)?;
+        write_ops(writer, &entry.ops)
+    };
+    write(writer).map_err(replication_failed)
+}
+
+fn read_entry(reader: &mut impl Read) -> Result<LogEntry> {
+    let timestamp = read_u64(reader)?;
+    let next = read_cursor(reader)?;
+    let ops = read_ops(reader)?;
+    Ok(LogEntry {
+        ops,
+        timestamp,
+        next,
+    })
+}
+
+/// 编码一组写操作：写操作数量，每个写操作的类型、key、value和过期时间
+pub(crate) fn write_ops(writer: &mut (impl Write + ?Sized), ops: &[LogOp]) -> std::io::Result<()> {
+    writer.write_all(&(ops.len() as u32).to_be_bytes())?;
+    for op in ops.iter() {
+        match op {
+            LogOp::Put {
+                key,
+                value,
+                expire_at,
+            } => {
+                writer.write_all(&[OP_PUT])?;
+                write_bytes(writer, key)?;
+                write_bytes(writer, value)?;
+                writer.write_all(&expire_at.to_be_bytes())?;
+            }
+            LogOp::Delete { key } => {
+                writer.write_all(&[OP_DELETE])?;
+                write_bytes(writer, key)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn read_ops(reader: &mut impl Read) -> Result<Vec<LogOp>> {
+    let op_num = read_u32(reader)?;
+    let mut ops = Vec::new();
+    for _ in 0..op_num {
+        let op = match read_u8(reader)? {
+            OP_PUT => LogOp::Put {
+                key: Bytes::from(read_bytes(reader)?),
+                value: Bytes::from(read_bytes(reader)?),
+                expire_at: read_u64(reader)?,
+            },
+            OP_DELETE => LogOp::Delete {
+                key: Bytes::from(read_bytes(reader)?),
+            },
+            _ => return Err(Error::InvalidReplicationMessage),
+        };
+        ops.push(op);
+    }
+    Ok(ops)
+}
+
+fn write_cursor(writer: &mut (impl Write + ?Sized), cursor: LogCursor) -> std::io::Result<()> {
+    writer.write_all(&cursor.file_id.to_be_bytes())?;
+    writer.write_all(&cursor.offset.to_be_bytes())
+}
+
+fn read_cursor(reader: &mut impl Read) -> Result<LogCursor> {
+    Ok(LogCursor {
+        file_id: read_u32(reader)?,
+        offset: read_u64(reader)?,
+    })
+}
+
+fn write_bytes(writer: &mut (impl Write + ?Sized), buf: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(buf.len() as u32).to_be_bytes())?;
+    writer.write_all(buf)
+}
+
+fn read_bytes(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let len = read_u32(reader)? as usize;
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf).map_err(replication_failed)?;
+    Ok(buf)
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf).map_err(replication_failed)?;
+    Ok(buf[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf).map_err(replication_failed)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf).map_err(replication_failed)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    /// 等待follower同步到满足条件
+    fn wait_until(cond: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !cond() {
+            assert!(Instant::now() < deadline, "replication timed out");
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn open_engine(dir_path: &str) -> Engine {
+        let _ = std::fs::remove_dir_all(dir_path);
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from(dir_path);
+        opts.data_file_size = 64 * 1024;
+        Engine::open(opts).expect("failed to open engine")
+    }
+
+    #[test]
+    fn test_replication() {
+        let leader_engine = open_engine("/tmp/bitcask-rs-replication-leader");
+        let follower_engine = open_engine("/tmp/bitcask-rs-replication-follower");
+        for i in 0..1000 {
+            leader_engine
+                .put(get_test_key(i), get_test_value(i))
+                .unwrap();
+        }
+        leader_engine.delete(get_test_key(0)).unwrap();
+        // 只存在于follower中的数据在应用快照时被删除
+        follower_engine
+            .put(Bytes::from("stale"), Bytes::from("value"))
+            .unwrap();
+
+        // 新的follower先同步快照
+        let leader = Leader::start(leader_engine.clone(), "127.0.0.1:0").unwrap();
+        let follower = Follower::start(follower_engine.clone(), leader.local_addr()).unwrap();
+        wait_until(|| follower.cursor().is_some());
+        assert_eq!(follower_engine.len(), 999);
+        assert!(!follower_engine.contains_key(b"stale"));
+
+        // 之后提交的数据增量同步
+        let wb = leader_engine
+            .new_write_batch(WriteOptions::default())
+            .unwrap();
+        wb.put(get_test_key(1000), get_test_value(1000)).unwrap();
+        wb.delete(get_test_key(1)).unwrap();
+        wb.commit().unwrap();
+        leader_engine
+            .put_with_ttl(
+                get_test_key(1001),
+                get_test_value(1001),
+                Duration::from_secs(3600),
+            )
+            .unwrap();
+        wait_until(|| follower_engine.contains_key(&get_test_key(1001)));
+        assert_eq!(
+            follower_engine.get(get_test_key(1000)).unwrap(),
+            get_test_value(1000)
+        );
+        assert!(!follower_engine.contains_key(&get_test_key(1)));
+        assert!(follower_engine.ttl(get_test_key(1001)).unwrap().is_some());
+
+        // 重新启动后从保存的位置继续同步
+        follower.shutdown();
+        leader_engine
+            .put(get_test_key(2), get_test_value(3))
+            .unwrap();
+        let follower = Follower::start(follower_engine.clone(), leader.local_addr()).unwrap();
+        assert!(follower.cursor().is_some());
+        wait_until(|| follower_engine.get(get_test_key(2)).unwrap() == get_test_value(3));
+
+        // 同步位置被merge清理后重新同步快照
+        follower.shutdown();
+        leader_engine.delete(get_test_key(3)).unwrap();
+        leader_engine.merge().unwrap();
+        let follower = Follower::start(follower_engine.clone(), leader.local_addr()).unwrap();
+        wait_until(|| !follower_engine.contains_key(&get_test_key(3)));
+        leader_engine
+            .put(get_test_key(4), get_test_value(5))
+            .unwrap();
+        // 应用快照期间数据库中只有部分数据
+        wait_until(|| follower_engine.get(get_test_key(4)).ok() == Some(get_test_value(5)));
+        assert_eq!(
+            follower_engine.list_keys().unwrap(),
+            leader_engine.list_keys().unwrap()
+        );
+
+        follower.shutdown();
+        // 没有发送握手的连接不会阻塞leader停止
+        let idle = TcpStream::connect(leader.local_addr()).unwrap();
+        std::thread::sleep(POLL_INTERVAL * 5);
+        leader.shutdown();
+        drop(idle);
+        std::fs::remove_dir_all("/tmp/bitcask-rs-replication-leader")
+            .expect("failed to remove test dir");
+        std::fs::remove_dir_all("/tmp/bitcask-rs-replication-follower")
+            .expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_replication_follower_options() {
+        let leader_engine = open_engine("/tmp/bitcask-rs-replication-options-leader");
+        for i in 0..100 {
+            leader_engine
+                .put(get_test_key(i), get_test_value(i))
+                .unwrap();
+        }
+
+        // 应用快照不受follower的目录和error_if_exists配置影响
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-replication-options-follower");
+        opts.dir_paths = vec![PathBuf::from(
+            "/tmp/bitcask-rs-replication-options-follower-extra",
+        )];
+        opts.error_if_exists = true;
+        opts.data_file_size = 64 * 1024;
+        for dir_path in opts.data_dirs() {
+            let _ = std::fs::remove_dir_all(dir_path);
+        }
+        let follower_engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 100..200 {
+            follower_engine
+                .put(get_test_key(i), get_test_value(i))
+                .unwrap();
+        }
+        let leader = Leader::start(leader_engine.clone(), "127.0.0.1:0").unwrap();
+        let follower = Follower::start(follower_engine.clone(), leader.local_addr()).unwrap();
+        wait_until(|| follower.cursor().is_some());
+        assert_eq!(
+            follower_engine.list_keys().unwrap(),
+            leader_engine.list_keys().unwrap()
+        );
+
+        follower.shutdown();
+        leader.shutdown();
+        std::fs::remove_dir_all("/tmp/bitcask-rs-replication-options-leader")
+            .expect("failed to remove test dir");
+        for dir_path in opts.data_dirs() {
+            std::fs::remove_dir_all(dir_path).expect("failed to remove test dir");
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! 数据保留期限
+//!
+//! 按ID从小到大删除最新的数据也超过保留期限的旧数据文件，只删除连续的最旧的数据文件，
+//! 保证被删除的数据不会因为丢失更早的删除记录而复活
+
+use std::sync::atomic::Ordering;
+
+use crate::batch::{parse_log_record_key, NON_TRANSACTION_SEQ_NUM};
+use crate::checkpoint::remove_checkpoint;
+use crate::data::data_file::{get_data_file_full_path, DataFile, DATA_FILE_HEADER_SIZE};
+use crate::data::log_record::{now_millis, LogRecordPos, LogRecordType};
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::fio::mem_io;
+use crate::telemetry;
+
+/// 读取数据文件得到的信息
+struct ExpiredFile {
+    file_id: u32,
+    /// 第一条记录属于事务，事务可能从上一个数据文件开始
+    starts_in_transaction: bool,
+    /// 文件中的数据记录的key和位置
+    records: Vec<(Vec<u8>, LogRecordPos)>,
+}
+
+impl Engine {
+    /// 删除最新的数据也超过保留期限的旧数据文件，同时从索引中删除其中的key，返回被删除的数据文件ID
+    ///
+    /// 只删除从最旧的数据文件开始连续的数据文件，不删除活跃数据文件。事务或者分块存储的数据
+    /// 跨越保留的数据文件时，所在的数据文件同样被保留。不能与merge同时进行，没有设置保留期限时直接返回
+    pub fn apply_retention(&self) -> Result<Vec<u32>> {
+        self.check_closed()?;
+        self.check_writable()?;
+        let Some(retention) = self.options.retention else {
+            return Ok(vec![]);
+        };
+        let _merging_guard = match self.merging_lock.try_lock() {
+            Some(guard) => guard,
+            None => return Err(Error::MergeInProgress),
+        };
+        let deadline = now_millis().saturating_sub(retention.as_millis() as u64);
+
+        let mut file_ids = self.older_files.read().keys().copied().collect::<Vec<_>>();
+        file_ids.sort();
+        let mut expired_files = Vec::new();
+        // 第一个保留的数据文件，None表示活跃数据文件
+        let mut retained_file_id = None;
+        for file_id in file_ids {
+            match self.read_expired_file(file_id, deadline)? {
+                Some(expired_file) => expired_files.push(expired_file),
+                None => {
+                    retained_file_id = Some(file_id);
+                    break;
+                }
+            }
+        }
+        // 保留的数据文件从事务中间开始时，事务开始的数据文件同样保留
+        let mut in_transaction = match retained_file_id {
+            Some(file_id) => {
+                let data_file = self.open_data_file(self.data_file_dir(file_id), file_id)?;
+                starts_in_transaction(&data_file)?
+            }
+            None => {
+                let active_file = self.active_file.read();
+                active_file.flush()?;
+                starts_in_transaction(&active_file)?
+            }
+        };
+        while in_transaction {
+            match expired_files.pop() {
+                Some(expired_file) => in_transaction = expired_file.starts_in_transaction,
+                None => break,
+            }
+        }
+        if expired_files.is_empty() {
+            return Ok(vec![]);
+        }
+        // 被删除的数据文件中的数据不再可用，索引检查点不再可用
+        if !self.is_in_memory() {
+            remove_checkpoint(&self.options.dir_path)?;
+        }
+
+        // 先删除索引中仍然指向这些数据文件的key，再删除数据文件
+        let mut removed_keys = Vec::new();
+        for expired_file in expired_files.iter_mut() {
+            for (key, pos) in expired_file.records.drain(..) {
+                if self.index.delete_if(key.clone(), pos) {
+                    removed_keys.push(key);
+                }
+            }
+        }
+        let removed_file_ids = expired_files
+            .iter()
+            .map(|expired_file| expired_file.file_id)
+            .collect::<Vec<_>>();
+        {
+            let mut older_files = self.older_files.write();
+            for file_id in removed_file_ids.iter() {
+                // 仍有快照在使用时保留旧的数据文件，快照全部释放后再删除
+                if let Some(data_file) = older_files.remove(file_id) {
+                    if self.retire_data_file(*file_id, data_file) {
+                        continue;
+                    }
+                }
+                let file_path = get_data_file_full_path(self.data_file_dir(*file_id), *file_id);
+                if self.is_in_memory() {
+                    mem_io::remove_file(file_path);
+                } else if let Err(source) = std::fs::remove_file(&file_path) {
+                    return Err(Error::FailedToRemoveDataFile {
+                        path: file_path,
+                        source,
+                    });
+                }
+            }
+        }
+        if let Some(value_cache) = &self.value_cache {
+            value_cache.lock().remove_files(&removed_file_ids);
+        }
+        // 被删除的数据文件中的无效数据已被清理
+        let reclaimed_size = {
+            let mut file_counters = self.file_counters.lock();
+            removed_file_ids
+                .iter()
+                .map(|file_id| file_counters.remove(*file_id))
+                .sum::<u64>()
+        };
+        let _ = self
+            .reclaim_size
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |size| {
+                Some(size.saturating_sub(reclaimed_size as usize))
+            });
+
+        let updates = removed_keys
+            .iter()
+            .map(|key| (key.as_slice(), None))
+            .collect::<Vec<_>>();
+        self.after_commit(&updates);
+        self.refresh_db_size();
+        telemetry::refresh_stat(self);
+        Ok(removed_file_ids)
+    }
+
+    /// 读取旧的数据文件，最新的数据早于deadline时返回其中的数据记录，否则返回None。
+    /// 最后一条记录是分块时，记录分块位置的记录在下一个数据文件中，同样返回None
+    fn read_expired_file(&self, file_id: u32, deadline: u64) -> Result<Option<ExpiredFile>> {
+        let data_file = self.open_data_file(self.data_file_dir(file_id), file_id)?;
+        let mut reader = data_file.reader(DATA_FILE_HEADER_SIZE);
+        let mut expired_file = ExpiredFile {
+            file_id,
+            starts_in_transaction: false,
+            records: Vec::new(),
+        };
+        let mut first = true;
+        let mut ends_with_chunk = false;
+        loop {
+            let offset = reader.offset();
+            let (log_record, size) = match reader.next_record() {
+                Ok(rc) => (rc.record, rc.size),
+                Err(Error::ReadDataFileEOF) => break,
+                Err(e) => return Err(e),
+            };
+            if log_record.timestamp > deadline {
+                return Ok(None);
+            }
+            let (key, seq_num) = parse_log_record_key(&log_record.key)?;
+            if first {
+                expired_file.starts_in_transaction = seq_num != NON_TRANSACTION_SEQ_NUM;
+                first = false;
+            }
+            ends_with_chunk = log_record.record_type == LogRecordType::CHUNK;
+            if matches!(
+                log_record.record_type,
+                LogRecordType::NORMAL | LogRecordType::CHUNKED | LogRecordType::OPERAND
+            ) {
+                let pos = LogRecordPos {
+                    file_id,
+                    offset,
+                    size: size as u32,
+                };
+                expired_file.records.push((key, pos));
+            }
+        }
+        Ok((!ends_with_chunk).then_some(expired_file))
+    }
+}
+
+/// 数据文件的第一条记录是否属于事务
+fn starts_in_transaction(data_file: &DataFile) -> Result<bool> {
+    let mut reader = data_file.reader(DATA_FILE_HEADER_SIZE);
+    match reader.next_record() {
+        Ok(rc) => {
+            let (_, seq_num) = parse_log_record_key(&rc.record.key)?;
+            Ok(seq_num != NON_TRANSACTION_SEQ_NUM)
+        }
+        Err(Error::ReadDataFileEOF) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_engine_apply_retention() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-retention");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        // 没有设置保留期限
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        assert!(engine.apply_retention().unwrap().is_empty());
+        drop(engine);
+
+        opts.retention = Some(Duration::from_millis(500));
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        // 事务跨越多个数据文件
+        let batch = engine.new_write_batch(WriteOptions::default()).unwrap();
+        for i in 1000..2500 {
+            batch.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        batch.commit().unwrap();
+        let file_ids = engine.data_file_ids();
+        assert!(file_ids.len() > 3);
+        std::thread::sleep(Duration::from_millis(600));
+        // 新写入的数据覆盖过期的数据
+        engine.put(get_test_key(1), get_test_value(2)).unwrap();
+        engine
+            .put(Bytes::from("new"), Bytes::from("value"))
+            .unwrap();
+        // 合并操作数时基础value所在的数据文件被删除，只合并保留的操作数
+        engine.set_merge_operator(|_, value, operand| {
+            let mut value = value.unwrap_or_default().to_vec();
+            value.extend_from_slice(operand);
+            value
+        });
+        engine
+            .merge_value(get_test_key(2), Bytes::from("x"))
+            .unwrap();
+
+        // 活跃数据文件从事务中间开始，事务开始的数据文件及之后的数据文件被保留
+        let removed = engine.apply_retention().unwrap();
+        assert_eq!(removed, vec![file_ids[0]]);
+        assert_eq!(engine.data_file_ids(), file_ids[1..].to_vec());
+        assert!(engine.get(get_test_key(0)).is_err());
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(2));
+        for i in 999..2500 {
+            assert_eq!(engine.get(get_test_key(i)).unwrap(), get_test_value(i));
+        }
+        assert_eq!(engine.get(Bytes::from("new")).unwrap(), "value");
+        assert_eq!(engine.get(get_test_key(2)).unwrap(), "x");
+        let len = engine.len();
+        assert!(len < 2501);
+        assert!(engine.apply_retention().unwrap().is_empty());
+
+        // 重启后被删除的数据不会复活
+        drop(batch);
+        drop(engine);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        assert_eq!(engine.len(), len);
+        assert!(engine.get(get_test_key(0)).is_err());
+        drop(engine);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ops::{Bound, RangeBounds};
+
+use bytes::Bytes;
+
+use crate::db::Engine;
+use crate::error::{Error, Result};
+
+/// 二级索引的key
+pub type IndexKey = Vec<u8>;
+
+/// 从(key, value)中提取二级索引的key，一条数据可以对应多个索引key
+pub type Extractor = Box<dyn Fn(&[u8], &[u8]) -> Vec<IndexKey> + Send + Sync>;
+
+/// 内存中的二级索引，保存索引key到主键的映射，不记录数据位置，merge后不需要更新
+pub(crate) struct SecondaryIndex {
+    extractor: Extractor,
+    /// (索引key, 主键)
+    entries: BTreeSet<(IndexKey, Vec<u8>)>,
+    /// 主键 -> 索引key，更新或删除数据时用于清理旧的索引项
+    index_keys: HashMap<Vec<u8>, Vec<IndexKey>>,
+}
+
+impl SecondaryIndex {
+    /// value为None表示数据被删除
+    fn update(&mut self, key: &[u8], value: Option<&[u8]>) {
+        if let Some(index_keys) = self.index_keys.remove(key) {
+            for index_key in index_keys {
+                self.entries.remove(&(index_key, key.to_vec()));
+            }
+        }
+        let Some(value) = value else {
+            return;
+        };
+        let mut index_keys = (self.extractor)(key, value);
+        index_keys.sort();
+        index_keys.dedup();
+        if index_keys.is_empty() {
+            return;
+        }
+        for index_key in index_keys.iter() {
+            self.entries.insert((index_key.clone(), key.to_vec()));
+        }
+        self.index_keys.insert(key.to_vec(), index_keys);
+    }
+
+    /// 索引key在range范围内的(索引key, 主键)
+    fn range<'a>(
+        &'a self,
+        range: &'a impl RangeBounds<IndexKey>,
+    ) -> impl Iterator<Item = &'a (IndexKey, Vec<u8>)> + 'a {
+        let start = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) => {
+                Bound::Included((start.clone(), vec![]))
+            }
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        self.entries
+            .range((start, Bound::Unbounded))
+            .skip_while(|(index_key, _)| !range.contains(index_key))
+            .take_while(|(index_key, _)| range.contains(index_key))
+    }
+}
+
+impl Engine {
+    /// 创建名称为name的二级索引，创建时为已有的数据建立索引
+    ///
+    /// 二级索引只保存在内存中，每次打开数据库后需要重新创建
+    pub fn create_index<F>(&self, name: &str, extractor: F) -> Result<()>
+    where
+        F: Fn(&[u8], &[u8]) -> Vec<IndexKey> + Send + Sync + 'static,
+    {
+        self.check_closed()?;
+        // 建立索引期间持有写锁，并发写入的数据在索引建立完成后再更新索引
+        let mut secondary_indexes = self.secondary_indexes.write();
+        if secondary_indexes.contains_key(name) {
+            return Err(Error::SecondaryIndexAlreadyExists);
+        }
+        let mut index = SecondaryIndex {
+            extractor: Box::new(extractor),
+            entries: BTreeSet::new(),
+            index_keys: HashMap::new(),
+        };
+        self.fold(|key, value| {
+            index.update(&key, Some(&value));
+            true
+        })?;
+        secondary_indexes.insert(name.to_string(), index);
+        Ok(())
+    }
+
+    /// 删除二级索引
+    pub fn drop_index(&self, name: &str) -> Result<()> {
+        match self.secondary_indexes.write().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(Error::SecondaryIndexNotFound),
+        }
+    }
+
+    /// 根据二级索引的key读取所有匹配的(主键, value)，按主键排序，跳过已过期的数据
+    pub fn get_by_index(&self, name: &str, index_key: &[u8]) -> Result<Vec<(Bytes, Bytes)>> {
+        let keys = self
+            .scan_index(name, index_key.to_vec()..=index_key.to_vec())?
+            .into_iter()
+            .map(|(_, key)| key)
+            .collect::<Vec<_>>();
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys {
+            match self.get(key.clone()) {
+                Ok(value) => entries.push((key, value)),
+                Err(Error::KeyNotFound) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// 按索引key的顺序遍历range范围内的(索引key, 主键)，只读取内存中的索引，
+    /// 已过期但还没有被merge清理的数据也会返回
+    pub fn scan_index(
+        &self,
+        name: &str,
+        range: impl RangeBounds<IndexKey>,
+    ) -> Result<Vec<(Bytes, Bytes)>> {
+        self.check_closed()?;
+        let secondary_indexes = self.secondary_indexes.read();
+        let index = secondary_indexes
+            .get(name)
+            .ok_or(Error::SecondaryIndexNotFound)?;
+        Ok(index
+            .range(&range)
+            .map(|(index_key, key)| (index_key.clone().into(), key.clone().into()))
+            .collect())
+    }
+
+    /// 是否创建了二级索引
+    pub(crate) fn has_secondary_indexes(&self) -> bool {
+        !self.secondary_indexes.read().is_empty()
+    }
+
+    /// 写入数据后更新所有二级索引，value为None表示数据被删除
+    ///
+    /// 同一批写入的数据一起更新，不能在持有活跃数据文件的锁时调用
+    pub(crate) fn update_secondary_indexes(&self, updates: &[(&[u8], Option<&[u8]>)]) {
+        if !self.has_secondary_indexes() {
+            return;
+        }
+        let mut secondary_indexes = self.secondary_indexes.write();
+        for index in secondary_indexes.values_mut() {
+            for (key, value) in updates {
+                index.update(key, *value);
+            }
+        }
+    }
+
+    /// 根据当前的数据重新建立所有二级索引
+    pub(crate) fn rebuild_secondary_indexes(&self) -> Result<()> {
+        if !self.has_secondary_indexes() {
+            return Ok(());
+        }
+        let mut secondary_indexes = self.secondary_indexes.write();
+        for index in secondary_indexes.values_mut() {
+            index.entries.clear();
+            index.index_keys.clear();
+        }
+        self.fold(|key, value| {
+            for index in secondary_indexes.values_mut() {
+                index.update(&key, Some(&value));
+            }
+            true
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::options::{Options, WriteOptions};
+
+    use super::*;
+
+    /// value格式为 "城市,年龄"，按城市建立索引
+    fn city_extractor(_key: &[u8], value: &[u8]) -> Vec<IndexKey> {
+        value
+            .split(|b| *b == b',')
+            .next()
+            .map(|city| vec![city.to_vec()])
+            .unwrap_or_default()
+    }
+
+    fn keys(entries: Vec<(Bytes, Bytes)>) -> Vec<Bytes> {
+        entries.into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn test_secondary_index() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-secondary-index");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine
+            .put(Bytes::from("alice"), Bytes::from("beijing,20"))
+            .unwrap();
+        engine
+            .put(Bytes::from("bob"), Bytes::from("shanghai,30"))
+            .unwrap();
+
+        // 创建索引时为已有的数据建立索引
+        engine.create_index("city", city_extractor).unwrap();
+        assert!(matches!(
+            engine.create_index("city", city_extractor),
+            Err(Error::SecondaryIndexAlreadyExists)
+        ));
+        assert_eq!(
+            engine.get_by_index("city", b"beijing").unwrap(),
+            vec![(Bytes::from("alice"), Bytes::from("beijing,20"))]
+        );
+
+        // 写入、覆盖和删除时更新索引
+        engine
+            .put(Bytes::from("carol"), Bytes::from("beijing,25"))
+            .unwrap();
+        engine
+            .put(Bytes::from("bob"), Bytes::from("beijing,31"))
+            .unwrap();
+        assert_eq!(
+            keys(engine.get_by_index("city", b"beijing").unwrap()),
+            vec!["alice", "bob", "carol"]
+        );
+        assert!(engine.get_by_index("city", b"shanghai").unwrap().is_empty());
+        engine.delete(Bytes::from("alice")).unwrap();
+        engine
+            .multi_put(&[(Bytes::from("dave"), Bytes::from("shenzhen,40"))])
+            .unwrap();
+
+        // 批量写入一起更新索引
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(Bytes::from("erin"), Bytes::from("shanghai,22"))
+            .unwrap();
+        wb.delete(Bytes::from("carol")).unwrap();
+        wb.commit().unwrap();
+        assert_eq!(
+            keys(engine.get_by_index("city", b"beijing").unwrap()),
+            vec!["bob"]
+        );
+
+        // 按索引key的范围遍历
+        assert_eq!(
+            engine.scan_index("city", b"s".to_vec()..).unwrap(),
+            vec![
+                (Bytes::from("shanghai"), Bytes::from("erin")),
+                (Bytes::from("shenzhen"), Bytes::from("dave")),
+            ]
+        );
+        assert_eq!(
+            engine
+                .scan_index(
+                    "city",
+                    (Bound::Excluded(b"beijing".to_vec()), Bound::Unbounded)
+                )
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // 已过期的数据不会返回，merge后从索引中清理
+        engine
+            .put_with_ttl(
+                Bytes::from("frank"),
+                Bytes::from("shenzhen,50"),
+                Duration::from_millis(10),
+            )
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(
+            keys(engine.get_by_index("city", b"shenzhen").unwrap()),
+            vec!["dave"]
+        );
+        assert_eq!(
+            engine
+                .scan_index("city", b"shenzhen".to_vec()..)
+                .unwrap()
+                .len(),
+            2
+        );
+        engine.merge().unwrap();
+        assert_eq!(
+            engine
+                .scan_index("city", b"shenzhen".to_vec()..)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        engine.drop_index("city").unwrap();
+        assert!(matches!(
+            engine.get_by_index("city", b"beijing"),
+            Err(Error::SecondaryIndexNotFound)
+        ));
+        assert!(matches!(
+            engine.drop_index("city"),
+            Err(Error::SecondaryIndexNotFound)
+        ));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use crate::data::log_record::LogRecordPos;
+use crate::db::Engine;
+
+/// 耗时超过Options::slow_op_threshold的操作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowOp {
+    /// 操作类型：put、get、commit或merge
+    pub op: &'static str,
+    /// 操作耗时
+    pub elapsed: Duration,
+    /// key的大小，批量写入时为所有key的大小之和
+    pub key_size: usize,
+    /// value的大小，批量写入时为所有value的大小之和
+    pub value_size: usize,
+    /// 数据所在的数据文件ID，批量写入时为第一条数据，merge时为None
+    pub file_id: Option<u32>,
+    /// 数据在数据文件中的偏移
+    pub offset: Option<u64>,
+}
+
+/// 处理慢操作的回调，在执行操作的线程中调用
+pub type SlowOpListener = dyn Fn(&SlowOp) + Send + Sync;
+
+impl Engine {
+    /// 设置处理慢操作的回调，替换已有的回调，未设置时慢操作输出到warn日志
+    pub fn set_slow_op_listener<F>(&self, listener: F)
+    where
+        F: Fn(&SlowOp) + Send + Sync + 'static,
+    {
+        *self.slow_op_listener.write() = Some(Arc::new(listener));
+    }
+
+    /// 操作耗时超过阈值时记录慢操作，未设置阈值时不做任何处理
+    pub(crate) fn check_slow_op(
+        &self,
+        op: &'static str,
+        start: Instant,
+        key_size: usize,
+        value_size: usize,
+        pos: Option<LogRecordPos>,
+    ) {
+        let Some(threshold) = self.options.slow_op_threshold else {
+            return;
+        };
+        let elapsed = start.elapsed();
+        if elapsed < threshold {
+            return;
+        }
+        let slow_op = SlowOp {
+            op,
+            elapsed,
+            key_size,
+            value_size,
+            file_id: pos.map(|pos| pos.file_id),
+            offset: pos.map(|pos| pos.offset),
+        };
+        // 回调可能较慢，不在持有锁时调用
+        let listener = self.slow_op_listener.read().clone();
+        match listener {
+            Some(listener) => listener(&slow_op),
+            None => warn!("slow operation: {:?}", slow_op),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use parking_lot::Mutex;
+
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_slow_op_listener() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-slow-op");
+        opts.slow_op_threshold = Some(Duration::ZERO);
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let slow_ops = Arc::new(Mutex::new(Vec::new()));
+        let ops = slow_ops.clone();
+        engine.set_slow_op_listener(move |slow_op| ops.lock().push(slow_op.clone()));
+
+        engine.put(get_test_key(0), get_test_value(0)).unwrap();
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), get_test_value(0));
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(1), get_test_value(1)).unwrap();
+        wb.put(get_test_key(2), get_test_value(2)).unwrap();
+        wb.commit().unwrap();
+        engine.merge().unwrap();
+
+        let slow_ops = slow_ops.lock();
+        let ops = slow_ops
+            .iter()
+            .map(|slow_op| slow_op.op)
+            .collect::<Vec<_>>();
+        assert_eq!(ops, vec!["put", "get", "commit", "merge"]);
+        // 写入和读取的是同一条数据
+        assert_eq!(slow_ops[0].key_size, get_test_key(0).len());
+        assert_eq!(slow_ops[0].value_size, get_test_value(0).len());
+        assert_eq!(slow_ops[0].file_id, Some(0));
+        assert!(slow_ops[0].offset.is_some());
+        assert_eq!(slow_ops[1].file_id, slow_ops[0].file_id);
+        assert_eq!(slow_ops[1].offset, slow_ops[0].offset);
+        assert_eq!(
+            slow_ops[2].key_size,
+            get_test_key(1).len() + get_test_key(2).len()
+        );
+        assert_eq!(
+            slow_ops[2].value_size,
+            get_test_value(1).len() + get_test_value(2).len()
+        );
+        assert!(slow_ops[2].offset > slow_ops[0].offset);
+        assert_eq!(slow_ops[3].file_id, None);
+        drop(slow_ops);
+
+        // 未超过阈值的操作不记录
+        drop(wb);
+        drop(engine);
+        opts.slow_op_threshold = Some(Duration::from_secs(60));
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let count = Arc::new(Mutex::new(0));
+        let c = count.clone();
+        engine.set_slow_op_listener(move |_| *c.lock() += 1);
+        engine.put(get_test_key(0), get_test_value(0)).unwrap();
+        engine.get(get_test_key(0)).unwrap();
+        assert_eq!(*count.lock(), 0);
+
+        drop(engine);
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
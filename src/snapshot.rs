@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use log::warn;
+
+use crate::data::data_file::{get_data_file_full_path, DataFile};
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::fio::mem_io;
+use crate::index::btree::BTree;
+use crate::index::Indexer;
+use crate::iterator::{Iterator, Scan};
+use crate::options::IteratorOptions;
+
+/// 数据库在某一时刻的只读视图，读取和迭代不受之后的写入和merge影响
+///
+/// 快照保存了创建时的内存索引，快照存在期间merge不会删除旧的数据文件，
+/// 长时间持有快照会占用额外的内存和磁盘空间
+pub struct Snapshot {
+    engine: Engine,
+    /// 创建快照时的索引
+    index: BTree,
+}
+
+/// 使用中的快照数量，以及merge后仍被快照使用的旧数据文件
+#[derive(Default)]
+pub(crate) struct SnapshotFiles {
+    pub(crate) count: usize,
+    pub(crate) retired_files: HashMap<u32, DataFile>,
+}
+
+impl Engine {
+    /// 创建快照，复制索引期间会短暂阻塞写操作
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        self.check_closed()?;
+        // 先登记快照再复制索引，保证复制的数据位置所在的文件不会被merge删除
+        self.snapshots.lock().count += 1;
+        let snapshot = Snapshot {
+            engine: self.clone(),
+            index: BTree::new(),
+        };
+        // 批量写入的数据在持有读锁时更新索引，持有写锁时复制的索引包含完整的批次
+        let _rotate_guard = self.rotate_lock.write();
+        let mut index_iter = self.index.iterator(IteratorOptions::default());
+        while let Some((key, pos)) = index_iter.next() {
+            snapshot.index.put(key.to_vec(), *pos);
+        }
+        Ok(snapshot)
+    }
+
+    /// merge删除旧的数据文件前调用，仍有快照时保留该文件并返回true
+    pub(crate) fn retire_data_file(&self, file_id: u32, data_file: DataFile) -> bool {
+        let mut snapshots = self.snapshots.lock();
+        if snapshots.count == 0 {
+            return false;
+        }
+        snapshots.retired_files.insert(file_id, data_file);
+        true
+    }
+
+    /// 释放快照，最后一个快照释放后删除保留的旧数据文件
+    fn release_snapshot(&self) {
+        let retired_files = {
+            let mut snapshots = self.snapshots.lock();
+            snapshots.count -= 1;
+            if snapshots.count > 0 {
+                return;
+            }
+            std::mem::take(&mut snapshots.retired_files)
+        };
+        for (file_id, data_file) in retired_files {
+            drop(data_file);
+            let file_path = get_data_file_full_path(self.data_file_dir(file_id), file_id);
+            if self.is_in_memory() {
+                mem_io::remove_file(file_path);
+            } else if let Err(e) = std::fs::remove_file(&file_path) {
+                warn!("failed to remove merged data file {:?}: {}", file_path, e);
+            }
+        }
+    }
+}
+
+impl Snapshot {
+    /// 读取创建快照时的数据
+    pub fn get(&self, key: Bytes) -> Result<Bytes> {
+        if key.is_empty() {
+            return Err(Error::KeyIsEmpty);
+        }
+        match self.index.get(key.to_vec()) {
+            Some(pos) => self.engine.get_value_by_position(&pos),
+            None => Err(Error::KeyNotFound),
+        }
+    }
+
+    /// 创建快照时key是否存在，只查询索引，已过期但还没有被merge清理的key也返回true
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.index.get(key.to_vec()).is_some()
+    }
+
+    /// 创建快照时key的数量
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 创建快照时的所有key
+    pub fn list_keys(&self) -> Result<Vec<Bytes>> {
+        self.index.list_keys()
+    }
+
+    /// 快照的迭代器
+    pub fn iter(&self, options: IteratorOptions) -> Iterator {
+        let keys_only = options.keys_only;
+        Iterator::new(self.engine.clone(), self.index.iterator(options), keys_only)
+    }
+
+    /// 按顺序遍历快照中的数据
+    pub fn scan(&self, options: IteratorOptions) -> Scan {
+        Scan::new(self.iter(options))
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.engine.release_snapshot();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-snapshot");
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+
+        let snapshot = engine.snapshot().unwrap();
+        // 快照创建后的写入、删除和批量写入不影响快照
+        engine.put(get_test_key(0), get_test_value(1)).unwrap();
+        engine.delete(get_test_key(1)).unwrap();
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(1000), get_test_value(1000)).unwrap();
+        wb.delete(get_test_key(2)).unwrap();
+        wb.commit().unwrap();
+        assert_eq!(snapshot.get(get_test_key(0)).unwrap(), get_test_value(0));
+        assert_eq!(snapshot.get(get_test_key(1)).unwrap(), get_test_value(1));
+        assert!(matches!(
+            snapshot.get(get_test_key(1000)),
+            Err(Error::KeyNotFound)
+        ));
+        assert_eq!(snapshot.len(), 1000);
+        assert!(snapshot.contains_key(&get_test_key(2)));
+
+        // merge后快照中的数据仍然可以读取
+        engine.merge().unwrap();
+        let retired_file_ids = engine
+            .snapshots
+            .lock()
+            .retired_files
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        assert!(!retired_file_ids.is_empty());
+        assert_eq!(snapshot.get(get_test_key(1)).unwrap(), get_test_value(1));
+        let entries = snapshot
+            .scan(IteratorOptions::default())
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            (0..1000)
+                .map(|i| (get_test_key(i), get_test_value(i)))
+                .collect::<Vec<_>>()
+        );
+        let iter = snapshot.iter(IteratorOptions {
+            reverse: true,
+            ..Default::default()
+        });
+        assert_eq!(iter.next().unwrap().0, get_test_key(999));
+        assert_eq!(engine.get(get_test_key(0)).unwrap(), get_test_value(1));
+
+        // 快照全部释放后删除旧的数据文件
+        let other = engine.snapshot().unwrap();
+        assert_eq!(other.len(), 999);
+        drop(iter);
+        drop(snapshot);
+        assert!(!engine.snapshots.lock().retired_files.is_empty());
+        drop(other);
+        assert!(engine.snapshots.lock().retired_files.is_empty());
+        for file_id in retired_file_ids {
+            assert!(!get_data_file_full_path(&opts.dir_path, file_id).exists());
+        }
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
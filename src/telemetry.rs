@@ -0,0 +1,263 @@
+use std::time::Instant;
+
+use crate::data::log_record::LogRecordPos;
+use crate::db::{Engine, Stat};
+use crate::error::{Error, Result};
+
+/// 执行一次操作，开启`metrics` feature时记录操作次数、出错次数以及耗时
+///
+/// 指标名称：
+/// - bitcask_operations_total：操作次数，op标签为操作类型
+/// - bitcask_operation_errors_total：出错次数，key不存在不视为出错
+/// - bitcask_operation_duration_seconds：操作耗时的直方图
+pub(crate) fn timed<T>(op: &'static str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let start = Instant::now();
+    let res = f();
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!("bitcask_operations_total", "op" => op).increment(1);
+        if matches!(&res, Err(e) if !matches!(e, Error::KeyNotFound)) {
+            metrics::counter!("bitcask_operation_errors_total", "op" => op).increment(1);
+        }
+        metrics::histogram!("bitcask_operation_duration_seconds", "op" => op)
+            .record(start.elapsed().as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (op, start, matches!(&res, Err(Error::KeyNotFound)));
+    res
+}
+
+/// 开启`metrics` feature时更新数据库状态的gauge
+///
+/// 指标名称：bitcask_keys、bitcask_data_files、bitcask_disk_size_bytes、
+/// bitcask_reclaimable_bytes、bitcask_index_memory_bytes
+pub(crate) fn record_stat(stat: &Stat) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::gauge!("bitcask_keys").set(stat.key_num as f64);
+        metrics::gauge!("bitcask_data_files").set(stat.data_file_num as f64);
+        metrics::gauge!("bitcask_disk_size_bytes").set(stat.disk_size as f64);
+        metrics::gauge!("bitcask_reclaimable_bytes").set(stat.reclaimable_size as f64);
+        metrics::gauge!("bitcask_index_memory_bytes").set(stat.index_memory_usage as f64);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = stat;
+}
+
+/// 开启`metrics` feature时重新统计数据库状态并更新gauge
+pub(crate) fn refresh_stat(engine: &Engine) {
+    #[cfg(feature = "metrics")]
+    if let Err(e) = engine.stat() {
+        log::warn!("failed to refresh metrics: {}", e);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = engine;
+}
+
+/// 开启`tracing` feature时在当前span中记录字段的值，span需要预先声明该字段
+pub(crate) fn record_field(name: &'static str, value: u64) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record(name, value);
+    #[cfg(not(feature = "tracing"))]
+    let _ = (name, value);
+}
+
+/// 在当前span中记录数据写入的位置
+pub(crate) fn record_pos(pos: &LogRecordPos) {
+    record_field("file_id", pos.file_id as u64);
+    record_field("offset", pos.offset);
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::path::PathBuf;
+
+    use metrics::Key;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use metrics_util::{CompositeKey, MetricKind};
+
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    fn counter(op: &'static str) -> CompositeKey {
+        CompositeKey::new(
+            MetricKind::Counter,
+            Key::from_parts("bitcask_operations_total", &[("op", op)]),
+        )
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-metrics");
+        let engine = crate::db::Engine::open(opts.clone()).expect("failed to open engine");
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            for i in 0..10 {
+                engine.put(get_test_key(i), get_test_value(i)).unwrap();
+                engine.get(get_test_key(i)).unwrap();
+            }
+            engine.get(get_test_key(10)).unwrap_err();
+            engine.delete(get_test_key(0)).unwrap();
+            let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+            wb.put(get_test_key(0), get_test_value(0)).unwrap();
+            wb.commit().unwrap();
+            engine.merge().unwrap();
+        });
+
+        let metrics = snapshotter.snapshot().into_hashmap();
+        let value = |key: &CompositeKey| metrics.get(key).map(|(_, _, value)| value);
+        assert_eq!(value(&counter("put")), Some(&DebugValue::Counter(10)));
+        assert_eq!(value(&counter("get")), Some(&DebugValue::Counter(11)));
+        assert_eq!(value(&counter("delete")), Some(&DebugValue::Counter(1)));
+        assert_eq!(
+            value(&counter("batch_commit")),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(value(&counter("merge")), Some(&DebugValue::Counter(1)));
+        // key不存在不视为出错
+        let errors = CompositeKey::new(
+            MetricKind::Counter,
+            Key::from_parts("bitcask_operation_errors_total", &[("op", "get")]),
+        );
+        assert_eq!(value(&errors), None);
+        let histogram = CompositeKey::new(
+            MetricKind::Histogram,
+            Key::from_parts("bitcask_operation_duration_seconds", &[("op", "put")]),
+        );
+        assert!(matches!(value(&histogram), Some(DebugValue::Histogram(h)) if h.len() == 10));
+        // merge后更新gauge
+        let keys = CompositeKey::new(MetricKind::Gauge, Key::from_name("bitcask_keys"));
+        assert!(matches!(value(&keys), Some(DebugValue::Gauge(n)) if n.into_inner() == 10.0));
+
+        drop(engine);
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tracing_tests {
+    use std::fmt::Debug;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+    use tracing_core::span::Current;
+
+    use crate::db::Engine;
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    type SpanFields = Vec<(String, u64)>;
+
+    /// 记录创建的span以及span中记录的字段
+    #[derive(Clone, Default)]
+    struct Collector {
+        spans: Arc<Mutex<Vec<(&'static Metadata<'static>, SpanFields)>>>,
+        /// 当前线程进入的span
+        stack: Arc<Mutex<Vec<Id>>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut SpanFields);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.push((field.name().to_string(), value));
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+    }
+
+    impl Subscriber for Collector {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.spans.lock();
+            let mut fields = Vec::new();
+            span.record(&mut FieldVisitor(&mut fields));
+            spans.push((span.metadata(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock();
+            let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+            values.record(&mut FieldVisitor(fields));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, span: &Id) {
+            self.stack.lock().push(span.clone());
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.stack.lock().pop();
+        }
+
+        fn current_span(&self) -> Current {
+            match self.stack.lock().last() {
+                Some(id) => {
+                    let metadata = self.spans.lock()[id.into_u64() as usize - 1].0;
+                    Current::new(id.clone(), metadata)
+                }
+                None => Current::none(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_tracing_spans() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-tracing");
+        let _ = std::fs::remove_dir_all(&opts.dir_path);
+        opts.data_file_size = 64 * 1024;
+        let collector = Collector::default();
+        tracing::subscriber::with_default(collector.clone(), || {
+            let engine = Engine::open(opts.clone()).expect("failed to open engine");
+            for i in 0..1000 {
+                engine.put(get_test_key(i), get_test_value(i)).unwrap();
+            }
+            let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+            wb.put(get_test_key(0), get_test_value(0)).unwrap();
+            wb.commit().unwrap();
+            engine.merge().unwrap();
+            drop(wb);
+            drop(engine);
+            Engine::open(opts.clone()).expect("failed to open engine");
+        });
+
+        let spans = collector.spans.lock();
+        let find = |name: &'static str| spans.iter().filter(move |(m, _)| m.name() == name);
+        let field = |fields: &SpanFields, name: &str| {
+            fields.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+        };
+        assert_eq!(find("open").count(), 2);
+        assert_eq!(find("append_log_record").count(), 1000);
+        // 写入位置记录在span中
+        let (_, fields) = find("append_log_record").next_back().unwrap();
+        assert!(field(fields, "file_id").unwrap() > 0);
+        assert!(field(fields, "offset").is_some());
+        assert!(find("rotate_active_file").count() > 0);
+        let (_, fields) = find("commit_with_check").next().unwrap();
+        assert_eq!(field(fields, "records"), Some(1));
+        assert!(field(fields, "seq_num").is_some());
+        let (_, fields) = find("merge_files").next().unwrap();
+        assert!(field(fields, "files").unwrap() > 1);
+        // 第一次打开时没有数据文件，不需要读取hint文件
+        assert_eq!(find("load_index_from_hint_file").count(), 1);
+        assert_eq!(find("load_index_from_data_files").count(), 2);
+        drop(spans);
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+
+use crate::batch::{CommitInfo, WriteBatch};
+use crate::data::log_record::LogRecordPos;
+use crate::db::Engine;
+use crate::error::{Error, Result};
+use crate::options::WriteOptions;
+
+/// 乐观事务，在WriteBatch的基础上记录读取过的key
+///
+/// 提交时检查读取过的key在读取之后是否被修改，被修改时返回TransactionConflict，不写入任何数据。
+/// merge迁移数据也会被视为修改，发生冲突时回滚后重试即可
+pub struct Transaction {
+    batch: WriteBatch,
+    engine: Engine,
+    /// 读取过的key -> 第一次读取时的数据位置，None表示key不存在
+    read_set: Mutex<HashMap<Vec<u8>, Option<LogRecordPos>>>,
+}
+
+impl Engine {
+    /// 开始一个乐观事务
+    pub fn begin_transaction(&self, opts: WriteOptions) -> Result<Transaction> {
+        Ok(Transaction {
+            batch: self.new_write_batch(opts)?,
+            engine: self.clone(),
+            read_set: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+impl Transaction {
+    /// 读取数据，优先读取事务中未提交的写入和删除，从数据库中读取的key在提交时检查冲突
+    pub fn get(&self, key: Bytes) -> Result<Bytes> {
+        if key.is_empty() {
+            return Err(Error::KeyIsEmpty);
+        }
+        if let Some(res) = self.batch.get_pending(&key) {
+            return res;
+        }
+        self.engine.check_closed()?;
+        let read = |pos: Option<LogRecordPos>| match pos {
+            Some(pos) => self.engine.get_value_by_position(&pos),
+            None => Err(Error::KeyNotFound),
+        };
+        let mut pos = self.engine.index.get(key.to_vec());
+        let mut value = read(pos);
+        // 数据文件可能刚被merge清理，数据已迁移到新的位置，重新查询索引后重试一次
+        if matches!(value, Err(Error::DataFileNotFound)) {
+            let new_pos = self.engine.index.get(key.to_vec());
+            if new_pos != pos {
+                pos = new_pos;
+                value = read(pos);
+            }
+        }
+        self.read_set.lock().entry(key.to_vec()).or_insert(pos);
+        value
+    }
+
+    pub fn put(&self, key: Bytes, value: Bytes) -> Result<()> {
+        self.batch.put(key, value)
+    }
+
+    pub fn delete(&self, key: Bytes) -> Result<()> {
+        self.batch.delete(key)
+    }
+
+    /// 丢弃所有未提交的数据和读取记录，事务可以继续使用
+    pub fn rollback(&self) {
+        self.batch.rollback();
+        self.read_set.lock().clear();
+    }
+
+    /// 提交事务，读取过的key被其他写操作修改时返回TransactionConflict
+    pub fn commit(&self) -> Result<CommitInfo> {
+        let read_set = self.read_set.lock();
+        let check = || {
+            let modified = read_set
+                .iter()
+                .any(|(key, pos)| self.engine.index.get(key.clone()) != *pos);
+            match modified {
+                true => Err(Error::TransactionConflict),
+                false => Ok(()),
+            }
+        };
+        let commit_info = self.batch.commit_with_check(Some(&check))?;
+        drop(read_set);
+        self.read_set.lock().clear();
+        Ok(commit_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use crate::options::Options;
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_transaction() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-transaction");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+
+        // 没有冲突时正常提交
+        let txn = engine.begin_transaction(WriteOptions::default()).unwrap();
+        assert_eq!(txn.get(get_test_key(1)).unwrap(), get_test_value(1));
+        assert!(matches!(txn.get(get_test_key(2)), Err(Error::KeyNotFound)));
+        txn.put(get_test_key(2), get_test_value(2)).unwrap();
+        assert_eq!(txn.get(get_test_key(2)).unwrap(), get_test_value(2));
+        txn.commit().unwrap();
+        assert_eq!(engine.get(get_test_key(2)).unwrap(), get_test_value(2));
+
+        // 读取的key被修改
+        let txn = engine.begin_transaction(WriteOptions::default()).unwrap();
+        txn.get(get_test_key(1)).unwrap();
+        txn.put(get_test_key(3), get_test_value(3)).unwrap();
+        engine.put(get_test_key(1), get_test_value(10)).unwrap();
+        assert!(matches!(txn.commit(), Err(Error::TransactionConflict)));
+        assert!(!engine.contains_key(&get_test_key(3)));
+
+        // 读取时不存在的key被写入
+        txn.rollback();
+        assert!(matches!(txn.get(get_test_key(4)), Err(Error::KeyNotFound)));
+        txn.put(get_test_key(3), get_test_value(3)).unwrap();
+        engine.put(get_test_key(4), get_test_value(4)).unwrap();
+        assert!(matches!(txn.commit(), Err(Error::TransactionConflict)));
+
+        // 只写入没有读取的key不会冲突
+        txn.rollback();
+        txn.put(get_test_key(1), get_test_value(11)).unwrap();
+        engine.put(get_test_key(1), get_test_value(12)).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(engine.get(get_test_key(1)).unwrap(), get_test_value(11));
+
+        // 索引指向不存在的数据文件时只重试一次
+        let stale_pos = LogRecordPos {
+            file_id: u32::MAX,
+            offset: 0,
+            size: 0,
+        };
+        engine.index.put(get_test_key(5).to_vec(), stale_pos);
+        assert!(matches!(
+            txn.get(get_test_key(5)),
+            Err(Error::DataFileNotFound)
+        ));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn test_transaction_concurrent_increment() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-transaction-concurrent");
+        let engine = Arc::new(Engine::open(opts.clone()).expect("failed to open engine"));
+        let key = Bytes::from("counter");
+        engine.put(key.clone(), Bytes::from("0")).unwrap();
+
+        // 冲突时重试，所有的自增都不会丢失
+        let handles = (0..4)
+            .map(|_| {
+                let engine = engine.clone();
+                let key = key.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        loop {
+                            let txn = engine.begin_transaction(WriteOptions::default()).unwrap();
+                            let value = txn.get(key.clone()).unwrap();
+                            let n = std::str::from_utf8(&value).unwrap().parse::<u64>().unwrap();
+                            txn.put(key.clone(), Bytes::from((n + 1).to_string()))
+                                .unwrap();
+                            match txn.commit() {
+                                Ok(_) => break,
+                                Err(Error::TransactionConflict) => continue,
+                                Err(e) => panic!("failed to commit: {}", e),
+                            }
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(engine.get(key).unwrap(), Bytes::from("200"));
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+
+use prost::decode_length_delimiter;
+
+use crate::chunk::read_chunks;
+use crate::data::data_file::{DataFile, DATA_FILE_HEADER_SIZE};
+use crate::data::log_record::LogRecordType;
+use crate::db::{read_log_record_from_files, Engine};
+use crate::error::{Error, Result};
+use crate::options::IteratorOptions;
+
+/// 一致性检查的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// 检查的数据文件数量
+    pub files: usize,
+    /// 检查的记录数量
+    pub records: usize,
+    /// 检查的索引项数量
+    pub index_entries: usize,
+    /// 发现的损坏，按发现的顺序排列
+    pub corruptions: Vec<Corruption>,
+}
+
+impl VerifyReport {
+    /// 是否没有发现任何损坏
+    pub fn is_ok(&self) -> bool {
+        self.corruptions.is_empty()
+    }
+}
+
+/// 一处损坏，位置为损坏的记录或索引项指向的记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corruption {
+    pub file_id: u32,
+    pub offset: u64,
+    pub kind: CorruptionKind,
+}
+
+/// 损坏的类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CorruptionKind {
+    /// 数据文件头的魔数或版本不正确
+    InvalidFileHeader(String),
+    /// 记录无法解码，该数据文件中之后的记录无法继续读取
+    InvalidRecord(String),
+    /// 记录的key中没有合法的事务编号，或者数据记录的key为空
+    InvalidRecordKey,
+    /// 索引指向的记录无法读取，包括数据文件不存在、分块缺失等
+    UnreadableIndexEntry { key: Vec<u8>, error: String },
+    /// 索引指向的记录的key与索引中的key不一致
+    KeyMismatch { key: Vec<u8>, record_key: Vec<u8> },
+    /// 索引指向的记录不是数据记录
+    UnexpectedRecordType {
+        key: Vec<u8>,
+        record_type: LogRecordType,
+    },
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "file {} offset {}: ", self.file_id, self.offset)?;
+        match &self.kind {
+            CorruptionKind::InvalidFileHeader(e) => write!(f, "invalid file header: {}", e),
+            CorruptionKind::InvalidRecord(e) => write!(f, "invalid record: {}", e),
+            CorruptionKind::InvalidRecordKey => write!(f, "invalid record key"),
+            CorruptionKind::UnreadableIndexEntry { key, error } => write!(
+                f,
+                "unreadable index entry {}: {}",
+                String::from_utf8_lossy(key),
+                error
+            ),
+            CorruptionKind::KeyMismatch { key, record_key } => write!(
+                f,
+                "index entry {} points to record of key {}",
+                String::from_utf8_lossy(key),
+                String::from_utf8_lossy(record_key)
+            ),
+            CorruptionKind::UnexpectedRecordType { key, record_type } => write!(
+                f,
+                "index entry {} points to {:?} record",
+                String::from_utf8_lossy(key),
+                record_type
+            ),
+        }
+    }
+}
+
+impl Engine {
+    /// 检查数据库的一致性，遍历所有数据文件校验文件头和每条记录的crc，
+    /// 再检查每个索引项都指向key一致的有效记录，返回发现的所有损坏
+    ///
+    /// 检查期间持有数据文件的读锁，写操作会被阻塞，不允许merge
+    pub fn verify(&self) -> Result<VerifyReport> {
+        self.check_closed()?;
+        let _merging_guard = self.merging_lock.lock();
+        let active_file = self.active_file.read();
+        let older_files = self.older_files.read();
+        let mut report = VerifyReport::default();
+
+        let mut file_ids = older_files.keys().copied().collect::<Vec<_>>();
+        file_ids.sort();
+        file_ids.push(active_file.get_file_id());
+        for file_id in file_ids {
+            let data_file = match older_files.get(&file_id) {
+                Some(data_file) => data_file,
+                None => &*active_file,
+            };
+            verify_data_file(data_file, &mut report);
+        }
+
+        let mut iter = self.index.iterator(IteratorOptions::default());
+        while let Some((key, pos)) = iter.next() {
+            report.index_entries += 1;
+            let corruption = |kind| Corruption {
+                file_id: pos.file_id,
+                offset: pos.offset,
+                kind,
+            };
+            let unreadable = |e: Error| {
+                corruption(CorruptionKind::UnreadableIndexEntry {
+                    key: key.to_vec(),
+                    error: e.to_string(),
+                })
+            };
+            let log_record = match read_log_record_from_files(&active_file, &older_files, pos) {
+                Ok(log_record) => log_record,
+                Err(e) => {
+                    report.corruptions.push(unreadable(e));
+                    continue;
+                }
+            };
+            let record_key = match strip_seq_num(&log_record.key) {
+                Some(record_key) => record_key,
+                None => {
+                    report
+                        .corruptions
+                        .push(corruption(CorruptionKind::InvalidRecordKey));
+                    continue;
+                }
+            };
+            if record_key != key {
+                report
+                    .corruptions
+                    .push(corruption(CorruptionKind::KeyMismatch {
+                        key: key.to_vec(),
+                        record_key: record_key.to_vec(),
+                    }));
+                continue;
+            }
+            match log_record.record_type {
+                LogRecordType::NORMAL | LogRecordType::OPERAND => {}
+                // 分块存储的数据需要所有分块都能读取
+                LogRecordType::CHUNKED => {
+                    if let Err(e) = read_chunks(&active_file, &older_files, &log_record) {
+                        report.corruptions.push(unreadable(e));
+                    }
+                }
+                record_type => {
+                    report
+                        .corruptions
+                        .push(corruption(CorruptionKind::UnexpectedRecordType {
+                            key: key.to_vec(),
+                            record_type,
+                        }))
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// 校验数据文件头和其中的所有记录，记录无法解码时停止读取该文件
+fn verify_data_file(data_file: &DataFile, report: &mut VerifyReport) {
+    let file_id = data_file.get_file_id();
+    report.files += 1;
+    if let Err(e) = data_file.check_header() {
+        report.corruptions.push(Corruption {
+            file_id,
+            offset: 0,
+            kind: CorruptionKind::InvalidFileHeader(e.to_string()),
+        });
+        return;
+    }
+    let mut reader = data_file.reader(DATA_FILE_HEADER_SIZE);
+    loop {
+        let offset = reader.offset();
+        let log_record = match reader.next_record() {
+            Ok(rc) => rc.record,
+            Err(Error::ReadDataFileEOF) => break,
+            Err(e) => {
+                report.corruptions.push(Corruption {
+                    file_id,
+                    offset,
+                    kind: CorruptionKind::InvalidRecord(e.to_string()),
+                });
+                break;
+            }
+        };
+        report.records += 1;
+        let valid_key = strip_seq_num(&log_record.key).is_some_and(|key| {
+            !key.is_empty() || log_record.record_type == LogRecordType::TXNFINISHED
+        });
+        if !valid_key {
+            report.corruptions.push(Corruption {
+                file_id,
+                offset,
+                kind: CorruptionKind::InvalidRecordKey,
+            });
+        }
+    }
+}
+
+/// 去掉记录的key中的事务编号，事务编号无法解码时返回None
+fn strip_seq_num(key: &[u8]) -> Option<&[u8]> {
+    let mut buf = key;
+    decode_length_delimiter(&mut buf).ok()?;
+    Some(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Seek, SeekFrom, Write};
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+
+    use crate::data::data_file::get_data_file_full_path;
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_engine_verify() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-verify");
+        opts.data_file_size = 64 * 1024;
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        for i in 0..1000 {
+            engine.put(get_test_key(i), get_test_value(i)).unwrap();
+        }
+        for i in 0..100 {
+            engine.delete(get_test_key(i)).unwrap();
+        }
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(get_test_key(0), get_test_value(0)).unwrap();
+        wb.commit().unwrap();
+        // 分块存储的数据
+        engine
+            .put(Bytes::from("large"), Bytes::from(vec![b'a'; 100 * 1024]))
+            .unwrap();
+
+        let report = engine.verify().unwrap();
+        assert!(report.is_ok(), "{:?}", report.corruptions);
+        assert!(report.files > 1);
+        assert!(report.records > 1100);
+        assert_eq!(report.index_entries, engine.len());
+
+        // 损坏第一个数据文件中间的一个字节
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(get_data_file_full_path(&opts.dir_path, 0))
+            .unwrap();
+        file.seek(SeekFrom::Start(32 * 1024)).unwrap();
+        file.write_all(&[0xff; 4]).unwrap();
+        drop(file);
+
+        let report = engine.verify().unwrap();
+        assert!(!report.is_ok());
+        let corruption = &report.corruptions[0];
+        assert_eq!(corruption.file_id, 0);
+        assert!(corruption.offset <= 32 * 1024);
+        assert!(matches!(corruption.kind, CorruptionKind::InvalidRecord(_)));
+        // 指向损坏记录的索引项也无法读取
+        assert!(report
+            .corruptions
+            .iter()
+            .any(|c| matches!(c.kind, CorruptionKind::UnreadableIndexEntry { .. })));
+
+        drop(engine);
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}
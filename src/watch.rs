@@ -0,0 +1,130 @@
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use bytes::Bytes;
+
+use crate::db::Engine;
+
+/// 数据变更事件，同一次提交中的事件使用相同的seq
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Put { key: Bytes, value: Bytes, seq: u64 },
+    Delete { key: Bytes, seq: u64 },
+}
+
+/// 订阅指定前缀的key的变更
+pub(crate) struct Watcher {
+    prefix: Vec<u8>,
+    sender: Sender<Event>,
+}
+
+impl Engine {
+    /// 订阅以prefix开头的key的写入和删除，数据提交后发送事件，
+    /// drop Receiver后自动取消订阅
+    pub fn watch_prefix(&self, prefix: &[u8]) -> Receiver<Event> {
+        let (sender, receiver) = channel();
+        self.watchers.lock().push(Watcher {
+            prefix: prefix.to_vec(),
+            sender,
+        });
+        receiver
+    }
+
+    /// 是否有订阅者
+    pub(crate) fn has_watchers(&self) -> bool {
+        !self.watchers.lock().is_empty()
+    }
+
+    /// 向订阅者发送一次提交中的所有变更，value为None表示数据被删除
+    pub(crate) fn notify_watchers(&self, updates: &[(&[u8], Option<&[u8]>)]) {
+        // 持有锁时分配seq，保证订阅者按seq的顺序收到事件
+        let mut watchers = self.watchers.lock();
+        let seq = self.commit_seq.fetch_add(1, Ordering::SeqCst) + 1;
+        if watchers.is_empty() {
+            return;
+        }
+        // 发送失败说明Receiver已被drop，移除该订阅者
+        watchers.retain(|watcher| {
+            updates
+                .iter()
+                .filter(|(key, _)| key.starts_with(&watcher.prefix))
+                .all(|(key, value)| {
+                    let key = Bytes::copy_from_slice(key);
+                    let event = match value {
+                        Some(value) => Event::Put {
+                            key,
+                            value: Bytes::copy_from_slice(value),
+                            seq,
+                        },
+                        None => Event::Delete { key, seq },
+                    };
+                    watcher.sender.send(event).is_ok()
+                })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::options::{Options, WriteOptions};
+    use crate::util::rand_kv::{get_test_key, get_test_value};
+
+    use super::*;
+
+    #[test]
+    fn test_watch_prefix() {
+        let mut opts = Options::default();
+        opts.dir_path = PathBuf::from("/tmp/bitcask-rs-watch-prefix");
+        let engine = Engine::open(opts.clone()).expect("failed to open engine");
+        let all = engine.watch_prefix(b"");
+        let users = engine.watch_prefix(b"user:");
+
+        engine
+            .put(Bytes::from("user:1"), Bytes::from("alice"))
+            .unwrap();
+        engine.put(get_test_key(1), get_test_value(1)).unwrap();
+        engine.delete(Bytes::from("user:1")).unwrap();
+        // 删除不存在的key不产生事件
+        engine.delete(Bytes::from("user:2")).unwrap();
+        let wb = engine.new_write_batch(WriteOptions::default()).unwrap();
+        wb.put(Bytes::from("user:2"), Bytes::from("bob")).unwrap();
+        wb.put(Bytes::from("user:3"), Bytes::from("carol")).unwrap();
+        wb.commit().unwrap();
+
+        assert_eq!(all.try_iter().count(), 5);
+        let events = users.try_iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            Event::Put {
+                key: Bytes::from("user:1"),
+                value: Bytes::from("alice"),
+                seq: 1,
+            }
+        );
+        assert_eq!(
+            events[1],
+            Event::Delete {
+                key: Bytes::from("user:1"),
+                seq: 3,
+            }
+        );
+        // 同一批次的事件使用相同的seq
+        assert!(matches!(events[2], Event::Put { seq: 4, .. }));
+        assert!(matches!(events[3], Event::Put { seq: 4, .. }));
+
+        // drop Receiver后，下一次发送事件时取消订阅
+        std::mem::drop(all);
+        std::mem::drop(users);
+        engine.put(get_test_key(2), get_test_value(2)).unwrap();
+        assert!(engine.has_watchers());
+        engine
+            .put(Bytes::from("user:4"), Bytes::from("dave"))
+            .unwrap();
+        assert!(!engine.has_watchers());
+
+        std::fs::remove_dir_all(opts.dir_path).expect("failed to remove test dir");
+    }
+}